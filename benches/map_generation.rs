@@ -0,0 +1,71 @@
+//! How long it takes to generate a level with each of the map builders.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rltk::RandomNumberGenerator;
+use roguelike_tut::map_builders::{
+    build_valid_level, BspDungeonBuilder, DrunkardsWalkBuilder, MapBuilder, SimpleMapBuilder,
+    WaveformCollapseBuilder,
+};
+use roguelike_tut::{LevelId, MapSize};
+
+const DEPTH: i32 = 5;
+
+const SIZES: [MapSize; 2] = [MapSize::STANDARD, MapSize::SPRAWLING];
+
+/// Build a level with whatever builder `new_builder` makes, from the same seed
+/// every time.
+fn build(new_builder: impl Fn() -> Box<dyn MapBuilder>) {
+    let mut rng = RandomNumberGenerator::seeded(1);
+    let mut builder = new_builder();
+    builder.build_map(&mut rng);
+}
+
+fn room_builders(c: &mut Criterion) {
+    let mut group = c.benchmark_group("room_builders");
+    for size in SIZES {
+        let label = format!("{}x{}", size.width, size.height);
+        group.bench_with_input(BenchmarkId::new("simple_map", &label), &size, |b, size| {
+            b.iter(|| build(|| Box::new(SimpleMapBuilder::new(DEPTH, *size))));
+        });
+        group.bench_with_input(BenchmarkId::new("bsp_dungeon", &label), &size, |b, size| {
+            b.iter(|| build(|| Box::new(BspDungeonBuilder::new(DEPTH, *size))));
+        });
+        group.bench_with_input(
+            BenchmarkId::new("waveform_collapse", &label),
+            &size,
+            |b, size| {
+                b.iter(|| {
+                    build(|| {
+                        let source = Box::new(BspDungeonBuilder::new(DEPTH, *size));
+                        Box::new(WaveformCollapseBuilder::new(DEPTH, source))
+                    })
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn cave_builders(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cave_builders");
+    group.bench_function("open_area", |b| {
+        b.iter(|| build(|| Box::new(DrunkardsWalkBuilder::open_area(DEPTH))));
+    });
+    group.bench_function("open_halls", |b| {
+        b.iter(|| build(|| Box::new(DrunkardsWalkBuilder::open_halls(DEPTH))));
+    });
+    group.bench_function("winding_passages", |b| {
+        b.iter(|| build(|| Box::new(DrunkardsWalkBuilder::winding_passages(DEPTH))));
+    });
+    group.finish();
+}
+
+fn finished_levels(c: &mut Criterion) {
+    c.bench_function("build_valid_level", |b| {
+        let mut rng = RandomNumberGenerator::seeded(1);
+        b.iter(|| build_valid_level(LevelId::main(DEPTH), &mut rng));
+    });
+}
+
+criterion_group!(benches, room_builders, cave_builders, finished_levels);
+criterion_main!(benches);
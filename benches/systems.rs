@@ -0,0 +1,109 @@
+//! How long the systems that run every turn take on a crowded level, with
+//! hundreds of monsters on it.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rltk::RandomNumberGenerator;
+use roguelike_tut::map_builders::{MapBuilder, SimpleMapBuilder};
+use roguelike_tut::{
+    spawner, MapIndexingSystem, MapSize, MonsterAI, NewRunOptions, PlayerEntity, PlayerPos,
+    Position, RunState, State, TileType, Viewshed, VisibilitySystem,
+};
+use specs::prelude::*;
+
+/// The levels to benchmark on, and how many monsters to fill each one with.
+const WORLDS: [(MapSize, usize); 2] = [(MapSize::STANDARD, 200), (MapSize::SPRAWLING, 400)];
+
+/// Set up a run on a level of `size`, with `monsters` goblins spread out across it.
+fn crowded_world(size: MapSize, monsters: usize) -> State {
+    let mut gs = State::new().expect("Unable to set up game state");
+    gs.ecs.insert(NewRunOptions {
+        fixed_seed: Some(1),
+        ..Default::default()
+    });
+    gs.start_new_run();
+
+    // Clear out the town, and swap in a level to fill with monsters instead
+    let player = *gs.ecs.fetch::<PlayerEntity>();
+    let everything_else: Vec<Entity> = gs
+        .ecs
+        .entities()
+        .join()
+        .filter(|entity| *entity != *player)
+        .collect();
+    gs.ecs
+        .delete_entities(&everything_else)
+        .expect("Unable to clear out the town");
+    gs.ecs.maintain();
+
+    let mut builder = SimpleMapBuilder::new(5, size);
+    builder.build_map(&mut RandomNumberGenerator::seeded(1));
+    let start = builder.get_starting_position();
+    let map = builder.get_map();
+    let start_idx = map.xy_idx(start.x, start.y);
+    let floors: Vec<usize> = (0..map.tiles.len())
+        .filter(|idx| map.tiles[*idx] == TileType::Floor && *idx != start_idx)
+        .collect();
+    gs.ecs.insert(map);
+
+    gs.ecs.insert(PlayerPos::new(start.x, start.y));
+    gs.ecs
+        .write_storage::<Position>()
+        .insert(*player, start)
+        .expect("Unable to move the player");
+
+    let spacing = (floors.len() / monsters).max(1);
+    for idx in floors.into_iter().step_by(spacing).take(monsters) {
+        spawner::spawn_entity(&mut gs.ecs, idx, "Goblin");
+    }
+
+    MapIndexingSystem.run_now(&gs.ecs);
+    VisibilitySystem.run_now(&gs.ecs);
+    gs.ecs.maintain();
+    gs
+}
+
+fn label(size: MapSize, monsters: usize) -> String {
+    format!("{}x{} with {monsters} monsters", size.width, size.height)
+}
+
+fn visibility(c: &mut Criterion) {
+    let mut group = c.benchmark_group("visibility");
+    for (size, monsters) in WORLDS {
+        let gs = crowded_world(size, monsters);
+        group.bench_function(BenchmarkId::from_parameter(label(size, monsters)), |b| {
+            b.iter(|| {
+                for viewshed in (&mut gs.ecs.write_storage::<Viewshed>()).join() {
+                    viewshed.dirty = true;
+                }
+                VisibilitySystem.run_now(&gs.ecs);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn monster_ai(c: &mut Criterion) {
+    let mut group = c.benchmark_group("monster_ai");
+    for (size, monsters) in WORLDS {
+        let mut gs = crowded_world(size, monsters);
+        gs.ecs.insert(RunState::MonsterTurn);
+        group.bench_function(BenchmarkId::from_parameter(label(size, monsters)), |b| {
+            b.iter(|| MonsterAI.run_now(&gs.ecs));
+        });
+    }
+    group.finish();
+}
+
+fn map_indexing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("map_indexing");
+    for (size, monsters) in WORLDS {
+        let gs = crowded_world(size, monsters);
+        group.bench_function(BenchmarkId::from_parameter(label(size, monsters)), |b| {
+            b.iter(|| MapIndexingSystem.run_now(&gs.ecs));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, visibility, monster_ai, map_indexing);
+criterion_main!(benches);
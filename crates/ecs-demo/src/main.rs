@@ -1,5 +1,3 @@
-use std::cmp::{max, min};
-
 use rltk::VirtualKeyCode;
 use rltk::{GameState, Rltk, RltkBuilder, RGB};
 use specs::prelude::*;
@@ -90,8 +88,8 @@ fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     let mut players = ecs.write_storage::<Player>();
 
     for (_player, pos) in (&mut players, &mut positions).join() {
-        pos.x = min(79, max(0, pos.x + delta_x));
-        pos.y = min(49, max(0, pos.y + delta_y));
+        pos.x = (pos.x + delta_x).clamp(0, 79);
+        pos.y = (pos.y + delta_y).clamp(0, 49);
     }
 }
 
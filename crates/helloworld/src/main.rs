@@ -55,14 +55,14 @@ impl GameState for State {
             1,
             RGB::named(rltk::YELLOW),
             RGB::named(rltk::BLACK),
-            &format!("FPS: {}", ctx.fps),
+            format!("FPS: {}", ctx.fps),
         );
         ctx.print_color(
             40,
             2,
             RGB::named(rltk::CYAN),
             RGB::named(rltk::BLACK),
-            &format!("Frame Time: {} ms", ctx.frame_time_ms),
+            format!("Frame Time: {} ms", ctx.frame_time_ms),
         );
     }
 }
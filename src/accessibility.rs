@@ -0,0 +1,215 @@
+//! An optional screen-reader friendly mirror of what's shown on screen.
+//!
+//! When enabled, new game log entries, menus, examine descriptions, and whatever's
+//! around the player at the start of each turn are written out as plain lines
+//! tagged with what they are, e.g. `[log] The Goblin hits you, for 3 hp.` or
+//! `[menu] Inventory: a) Dagger; b) Shield`. Natively they go to stdout. On the
+//! web they're added to the page's `screen-reader` live region instead, which
+//! screen readers read out as it changes.
+
+use rustc_hash::FxHashMap;
+use specs::prelude::*;
+
+use crate::l10n::tr;
+use crate::{
+    identification, CombatStats, GameLog, Hidden, Map, PlayerEntity, PlayerPos, RunState, TileType,
+};
+
+/// Each of the eight tiles around the player, clockwise from north, and the ID of
+/// the message that names that direction.
+const DIRECTIONS: [(i32, i32, &str); 8] = [
+    (0, -1, "direction-north"),
+    (1, -1, "direction-north-east"),
+    (1, 0, "direction-east"),
+    (1, 1, "direction-south-east"),
+    (0, 1, "direction-south"),
+    (-1, 1, "direction-south-west"),
+    (-1, 0, "direction-west"),
+    (-1, -1, "direction-north-west"),
+];
+
+/// How many lines the web page's live region keeps before dropping the oldest.
+#[cfg(target_arch = "wasm32")]
+const MAX_WEB_LINES: u32 = 100;
+
+/// Mirrors text to stdout for screen readers, if [`Self::enabled`].
+#[derive(Debug, Default)]
+pub struct ScreenReader {
+    pub enabled: bool,
+    /// How many game log entries have already been read out.
+    log_entries_read: usize,
+    /// The last thing announced on each channel, so that screens redrawn every
+    /// frame are only read out when they change.
+    last_announced: FxHashMap<&'static str, String>,
+    /// The run state as of the end of the last tick.
+    last_runstate: Option<RunState>,
+    /// The last description of the player's surroundings read out. Unlike the
+    /// other channels, it's only read out again once something's changed.
+    last_surroundings: String,
+}
+
+impl ScreenReader {
+    /// Read out `text` on `channel` (e.g. `"menu"`), unless it's exactly what was
+    /// last read out on that channel.
+    pub fn announce<S: ToString>(&mut self, channel: &'static str, text: S) {
+        if !self.enabled {
+            return;
+        }
+
+        let text = text.to_string();
+        if self.last_announced.get(channel) != Some(&text) {
+            speak(channel, &text);
+            self.last_announced.insert(channel, text);
+        }
+    }
+}
+
+/// Write out a line tagged with `channel`, for the screen reader to pick up.
+#[cfg(not(target_arch = "wasm32"))]
+fn speak(channel: &str, text: &str) {
+    println!("[{channel}] {text}");
+}
+
+/// Add a line tagged with `channel` to the page's live region, for the screen
+/// reader to pick up.
+#[cfg(target_arch = "wasm32")]
+fn speak(channel: &str, text: &str) {
+    let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+        return;
+    };
+    let Some(region) = document.get_element_by_id("screen-reader") else {
+        return;
+    };
+    let Ok(line) = document.create_element("p") else {
+        return;
+    };
+    line.set_text_content(Some(&format!("[{channel}] {text}")));
+    let _ = region.append_with_node_1(&line);
+    while region.child_element_count() > MAX_WEB_LINES {
+        match region.first_element_child() {
+            Some(oldest) => oldest.remove(),
+            None => break,
+        }
+    }
+}
+
+/// Read `text` out on `channel` using the [`ScreenReader`] in `ecs`.
+pub fn announce<S: ToString>(ecs: &World, channel: &'static str, text: S) {
+    ecs.fetch_mut::<ScreenReader>().announce(channel, text);
+}
+
+/// Read out any new game log entries, and get ready to re-announce screens if
+/// the run state changed this tick. Call once at the end of every tick.
+pub fn update_screen_reader(ecs: &World) {
+    let mut reader = ecs.fetch_mut::<ScreenReader>();
+    let log = ecs.fetch::<GameLog>();
+    let runstate = *ecs.fetch::<RunState>();
+
+    // The log starts over when a new run does
+    if log.entries.len() < reader.log_entries_read {
+        reader.log_entries_read = 0;
+    }
+    if reader.enabled {
+        for entry in log.entries.iter().skip(reader.log_entries_read) {
+            speak("log", &entry.to_string());
+        }
+    }
+    reader.log_entries_read = log.entries.len();
+
+    // Describe where the player's got to once it's their turn again
+    if reader.enabled && runstate == RunState::AwaitingInput {
+        let surroundings = describe_surroundings(ecs);
+        if surroundings != reader.last_surroundings {
+            speak("here", &surroundings);
+            reader.last_surroundings = surroundings;
+        }
+    }
+
+    if reader.last_runstate != Some(runstate) {
+        reader.last_announced.clear();
+        reader.last_runstate = Some(runstate);
+    }
+}
+
+/// What the player can see from where they're standing: their depth and health,
+/// what they're standing on, and anything on the tiles around them. For example,
+/// `Depth 2, HP 25/30. On floor, with Dagger. North: Goblin. South-east: down stairs.`
+pub fn describe_surroundings(ecs: &World) -> String {
+    let map = ecs.fetch::<Map>();
+    let player_pos = **ecs.fetch::<PlayerPos>();
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let hidden = ecs.read_storage::<Hidden>();
+
+    // The names of whatever the player can see on a tile, besides themselves
+    let things_at = |idx: usize| -> Vec<String> {
+        if !map.visible_tiles[idx] {
+            return Vec::new();
+        }
+        map.tile_content[idx]
+            .iter()
+            .filter(|entity| **entity != player_entity && !hidden.contains(**entity))
+            .filter_map(|entity| identification::display_name(ecs, *entity))
+            .collect()
+    };
+
+    let mut text = tr("surroundings-depth").arg("depth", map.depth).to_string();
+    if let Some(stats) = ecs.read_storage::<CombatStats>().get(player_entity) {
+        let hp = tr("surroundings-hp")
+            .arg("hp", stats.hp)
+            .arg("max_hp", stats.max_hp);
+        text.push_str(&hp.to_string());
+    }
+
+    let here_idx = map.xy_idx(player_pos.x, player_pos.y);
+    let here = things_at(here_idx);
+    let tile = tile_name(map.tiles[here_idx]);
+    let standing_on = if here.is_empty() {
+        tr("surroundings-on").arg("tile", tile)
+    } else {
+        tr("surroundings-on-with")
+            .arg("tile", tile)
+            .arg("things", here.join(", "))
+    };
+    text.push_str(&standing_on.to_string());
+
+    let mut nearby = false;
+    for (dx, dy, direction) in DIRECTIONS {
+        let (x, y) = (player_pos.x + dx, player_pos.y + dy);
+        if x < 0 || y < 0 || x >= map.width || y >= map.height {
+            continue;
+        }
+        // Mention the tile itself too, unless it's just plain floor or wall
+        let idx = map.xy_idx(x, y);
+        let tile = match map.tiles[idx] {
+            TileType::Floor | TileType::Wall => None,
+            tile if map.revealed_tiles[idx] => Some(tile_name(tile)),
+            _ => None,
+        };
+        let there: Vec<String> = tile.into_iter().chain(things_at(idx)).collect();
+        if !there.is_empty() {
+            let there = tr("surroundings-direction")
+                .arg("direction", tr(direction))
+                .arg("things", there.join(", "));
+            text.push_str(&there.to_string());
+            nearby = true;
+        }
+    }
+    if !nearby {
+        text.push_str(&tr("surroundings-nothing-nearby").to_string());
+    }
+
+    text
+}
+
+/// What to call a tile when reading it out.
+fn tile_name(tile: TileType) -> String {
+    match tile {
+        TileType::Wall => tr("tile-wall"),
+        TileType::Floor => tr("tile-floor"),
+        TileType::DownStairs => tr("tile-down-stairs"),
+        TileType::UpStairs => tr("tile-up-stairs"),
+        TileType::ShallowWater => tr("tile-shallow-water"),
+        TileType::DeepWater => tr("tile-deep-water"),
+    }
+    .to_string()
+}
@@ -0,0 +1,186 @@
+//! Achievements, unlocked by doing something notable during a run. They're kept in
+//! their own profile file rather than in any savegame, so they stay unlocked after
+//! the character that earned them is long dead. The [`AchievementListener`] keeps
+//! an eye on [`GameEvent`]s for anything worth an achievement.
+
+use rltk::RGB;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::game_events::GameEventListener;
+use crate::l10n::{self, tr};
+use crate::{storage, GameEvent, GameLog, PlayerEntity};
+
+/// Where unlocked achievements are kept, relative to the working directory.
+pub const ACHIEVEMENTS_PATH: &str = "./achievements.ron";
+
+/// How deep the player has to get for [`Achievement::DeepDelver`].
+const DEEP_DELVER_DEPTH: i32 = 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AchievementsError {
+    #[error("Unable to read achievements from `{path}`")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("The achievements at `{path}` are corrupted")]
+    Parse {
+        path: String,
+        source: ron::error::SpannedError,
+    },
+
+    #[error("Failed to serialize achievements")]
+    Serialization { source: ron::Error },
+
+    #[error("Unable to write achievements to `{path}`")]
+    Write {
+        path: String,
+        source: std::io::Error,
+    },
+}
+
+/// Something notable that can be done during a run.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize, strum::EnumIter)]
+pub enum Achievement {
+    FirstBlood,
+    Regicide,
+    DeepDelver,
+    HoistByOwnPetard,
+}
+
+impl Achievement {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::FirstBlood => "First Blood",
+            Self::Regicide => "Regicide",
+            Self::DeepDelver => "Deep Delver",
+            Self::HoistByOwnPetard => "Hoist by Your Own Petard",
+        }
+    }
+
+    /// What has to be done to unlock the achievement.
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::FirstBlood => "Kill a monster",
+            Self::Regicide => "Kill the leader of a pack",
+            Self::DeepDelver => "Reach depth 5",
+            Self::HoistByOwnPetard => "Die in your own blast",
+        }
+    }
+}
+
+/// Every [`Achievement`] that's been unlocked, in any run.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Achievements {
+    unlocked: Vec<Achievement>,
+}
+
+impl Achievements {
+    /// Load achievements from [`ACHIEVEMENTS_PATH`]. Nothing's unlocked if nothing's
+    /// been saved there yet.
+    pub fn load() -> Result<Self, AchievementsError> {
+        let data = match storage::read(ACHIEVEMENTS_PATH) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(source) => {
+                return Err(AchievementsError::Read {
+                    path: ACHIEVEMENTS_PATH.to_string(),
+                    source,
+                })
+            }
+        };
+
+        ron::de::from_bytes(&data).map_err(|source| AchievementsError::Parse {
+            path: ACHIEVEMENTS_PATH.to_string(),
+            source,
+        })
+    }
+
+    /// Write achievements to [`ACHIEVEMENTS_PATH`].
+    pub fn save(&self) -> Result<(), AchievementsError> {
+        let data = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|source| AchievementsError::Serialization { source })?;
+        storage::write(ACHIEVEMENTS_PATH, data.as_bytes()).map_err(|source| {
+            AchievementsError::Write {
+                path: ACHIEVEMENTS_PATH.to_string(),
+                source,
+            }
+        })
+    }
+
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    /// Unlock `achievement`. Returns `true` if it wasn't already.
+    fn unlock(&mut self, achievement: Achievement) -> bool {
+        if self.is_unlocked(achievement) {
+            return false;
+        }
+        self.unlocked.push(achievement);
+        true
+    }
+}
+
+/// Unlocks [`Achievements`] as the player earns them, announces them in the game
+/// log, and saves them straight away.
+#[derive(Default)]
+pub struct AchievementListener {
+    /// The turn that the player was last caught in their own blast, if ever.
+    caught_in_own_blast: Option<u32>,
+}
+
+impl GameEventListener for AchievementListener {
+    fn on_event(&mut self, ecs: &World, event: &GameEvent) {
+        let player = **ecs.fetch::<PlayerEntity>();
+        let turn = ecs.fetch::<GameLog>().turn;
+
+        let achievement = match event {
+            GameEvent::MonsterKilled { killer, .. } if *killer != Some(player) => return,
+            GameEvent::MonsterKilled { leader: true, .. } => Achievement::Regicide,
+            GameEvent::MonsterKilled { .. } => Achievement::FirstBlood,
+            GameEvent::LevelEntered { depth } if *depth >= DEEP_DELVER_DEPTH => {
+                Achievement::DeepDelver
+            }
+            GameEvent::CaughtInOwnBlast { user, .. } if *user == player => {
+                self.caught_in_own_blast = Some(turn);
+                return;
+            }
+            GameEvent::PlayerKilled if self.caught_in_own_blast == Some(turn) => {
+                Achievement::HoistByOwnPetard
+            }
+            _ => return,
+        };
+
+        let mut achievements = ecs.fetch_mut::<Achievements>();
+        let mut log = ecs.fetch_mut::<GameLog>();
+        // Killing a leader is a kill like any other
+        if achievement == Achievement::Regicide {
+            unlock(&mut achievements, &mut log, Achievement::FirstBlood);
+        }
+        unlock(&mut achievements, &mut log, achievement);
+    }
+}
+
+/// Unlock `achievement`, and let the player know about it if it's new.
+fn unlock(achievements: &mut Achievements, log: &mut GameLog, achievement: Achievement) {
+    if !achievements.unlock(achievement) {
+        return;
+    }
+
+    let msg = tr("achievement-unlocked").colored_arg(
+        "achievement",
+        l10n::name(achievement.name()),
+        RGB::named(rltk::YELLOW),
+    );
+    log.entry()
+        .colored_message(&msg, RGB::named(rltk::GOLD))
+        .log();
+    if let Err(e) = achievements.save() {
+        log.entry()
+            .colored(format!("{e}."), RGB::named(rltk::RED))
+            .log();
+    }
+}
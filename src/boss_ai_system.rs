@@ -0,0 +1,198 @@
+use rltk::{Point, RandomNumberGenerator, RGB};
+use specs::prelude::*;
+
+use crate::effects::{EffectQueue, EffectType, Targets, PARTICLE_LIFETIME_MS};
+use crate::l10n::{self, tr};
+use crate::{
+    blast_area, spawner, Ability, AiState, CombatStats, GameLog, Map, Name, PlayerPos, Position,
+    RunState, SpecialAbilities, StatusEffects, TileType, Viewshed,
+};
+
+/// How far from a monster the allies it summons turn up.
+const SUMMON_RADIUS: i32 = 2;
+
+/// A system that runs the scripted side of monsters with [`SpecialAbilities`],
+/// like [`crate::Boss`]es.
+///
+/// Once a monster like that has spotted the player, it uses whichever of its
+/// abilities are ready and would do some good instead of fighting that turn. It
+/// also flies into a rage the first time it drops below half health.
+///
+/// Summoned monsters can't be spawned from inside a system, so they wait in
+/// [`PendingSummons`] until [`spawn_summons()`] is run.
+pub struct BossAI;
+
+/// Monsters that have been summoned by [`BossAI`], but not spawned yet.
+#[derive(Debug, Default)]
+pub struct PendingSummons {
+    /// The name of every monster summoned, and where it should appear.
+    summons: Vec<(String, Point)>,
+}
+
+/// Spawn every monster waiting in [`PendingSummons`].
+pub fn spawn_summons(ecs: &mut World) {
+    let summons = std::mem::take(&mut ecs.write_resource::<PendingSummons>().summons);
+    for (name, pos) in summons {
+        spawner::spawn_named(ecs, pos.x, pos.y, &name);
+    }
+}
+
+impl<'a> System<'a> for BossAI {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, PlayerPos>,
+        ReadExpect<'a, RunState>,
+        Entities<'a>,
+        WriteStorage<'a, SpecialAbilities>,
+        WriteStorage<'a, CombatStats>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Viewshed>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, StatusEffects>,
+        ReadStorage<'a, AiState>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, PendingSummons>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            map,
+            player_pos,
+            runstate,
+            entities,
+            mut special_abilities,
+            mut combat_stats,
+            positions,
+            viewsheds,
+            names,
+            status_effects,
+            ai_states,
+            mut effects,
+            mut rng,
+            mut log,
+            mut pending_summons,
+        ): Self::SystemData,
+    ) {
+        // Only run when it's the monsters' turn!
+        if *runstate != RunState::MonsterTurn {
+            return;
+        }
+
+        for (entity, abilities, stats, pos, viewshed) in (
+            &entities,
+            &mut special_abilities,
+            &mut combat_stats,
+            &positions,
+            &viewsheds,
+        )
+            .join()
+        {
+            abilities.used_this_turn = false;
+            for special in abilities.abilities.iter_mut() {
+                special.ready_in = (special.ready_in - 1).max(0);
+            }
+
+            let pos = Point::new(pos.x, pos.y);
+            let seen = map.visible_tiles[map.xy_idx(pos.x, pos.y)];
+            let name = names.get(entity).map_or_else(
+                || tr("nameless-monster").to_string(),
+                |name| l10n::name(&name.name),
+            );
+
+            // Getting badly hurt only makes it angrier
+            if !abilities.enraged && abilities.enrage_power > 0 && stats.hp * 2 < stats.max_hp {
+                abilities.enraged = true;
+                stats.power += abilities.enrage_power;
+                if seen {
+                    log.entry()
+                        .colored_message(
+                            &tr("boss-enraged").arg("boss", &name),
+                            RGB::named(rltk::ORANGE),
+                        )
+                        .log();
+                }
+            }
+
+            // Abilities are only for fighting the player, when the monster is able to
+            let can_act = status_effects
+                .get(entity)
+                .and_then(StatusEffects::skips_turn)
+                .is_none();
+            let fighting = ai_states.get(entity).copied().unwrap_or_default() == AiState::Fighting;
+            if !can_act || !fighting || !viewshed.visible_tiles.contains(&*player_pos) {
+                continue;
+            }
+
+            let distance = rltk::DistanceAlg::Chebyshev.distance2d(pos, **player_pos);
+            let Some(special) = abilities.abilities.iter_mut().find(|special| {
+                special.ready_in == 0
+                    && match special.ability {
+                        Ability::Summon { .. } => true,
+                        Ability::Slam { radius, .. } => distance <= radius as f32,
+                    }
+            }) else {
+                continue;
+            };
+            special.ready_in = special.cooldown;
+            abilities.used_this_turn = true;
+
+            match &special.ability {
+                Ability::Summon { monster, count } => {
+                    let mut spots: Vec<Point> = blast_area(&map, pos, SUMMON_RADIUS)
+                        .into_iter()
+                        .filter(|spot| {
+                            let idx = map.xy_idx(spot.x, spot.y);
+                            map.tiles[idx] == TileType::Floor && !map.blocked[idx]
+                        })
+                        .collect();
+                    for _ in 0..*count {
+                        if spots.is_empty() {
+                            break;
+                        }
+                        let spot = spots.swap_remove(rng.range(0, spots.len()));
+                        pending_summons.summons.push((monster.clone(), spot));
+                    }
+                    if seen {
+                        log.log(tr("boss-summons").arg("boss", &name));
+                    }
+                }
+
+                Ability::Slam { radius, damage } => {
+                    let tiles: Vec<usize> = blast_area(&map, pos, *radius)
+                        .into_iter()
+                        .filter(|tile| *tile != pos)
+                        .map(|tile| map.xy_idx(tile.x, tile.y))
+                        .collect();
+                    effects.add_effect(
+                        EffectType::Particle {
+                            glyph: rltk::to_cp437('▒'),
+                            fg: RGB::named(rltk::BURLYWOOD),
+                            lifetime_ms: PARTICLE_LIFETIME_MS,
+                        },
+                        Targets::Tiles {
+                            tiles: tiles.clone(),
+                        },
+                    );
+                    effects.add_effect(
+                        EffectType::Damage {
+                            amount: *damage,
+                            attacker: Some(entity),
+                        },
+                        Targets::Tiles { tiles },
+                    );
+                    if seen {
+                        log.entry()
+                            .colored_message(
+                                &tr("boss-slams").arg("boss", &name),
+                                RGB::named(rltk::ORANGE),
+                            )
+                            .log();
+                    }
+                }
+            }
+        }
+    }
+}
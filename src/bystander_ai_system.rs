@@ -0,0 +1,69 @@
+use rltk::{Algorithm2D, Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+use crate::{Bystander, HomeLocation, Map, Position, RunState, StatusEffects};
+
+/// How far a [`Bystander`] will wander from their [`HomeLocation`].
+const WANDER_RADIUS: f32 = 6.0;
+
+/// A system that makes [`Bystander`]s amble around aimlessly. They never pick a
+/// fight, and never stray far from home.
+pub struct BystanderAI;
+
+impl<'a> System<'a> for BystanderAI {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, RunState>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        Entities<'a>,
+        ReadStorage<'a, Bystander>,
+        ReadStorage<'a, HomeLocation>,
+        ReadStorage<'a, StatusEffects>,
+        WriteStorage<'a, Position>,
+    );
+
+    fn run(
+        &mut self,
+        (mut map, runstate, mut rng, entities, bystanders, homes, status_effects, mut positions): Self::SystemData,
+    ) {
+        // Only run when it's the monsters' turn!
+        if *runstate != RunState::MonsterTurn {
+            return;
+        }
+
+        for (entity, _bystander, pos) in (&entities, &bystanders, &mut positions).join() {
+            if status_effects
+                .get(entity)
+                .and_then(StatusEffects::skips_turn)
+                .is_some()
+            {
+                continue;
+            }
+
+            // Stand around about half of the time
+            if rng.roll_dice(1, 2) == 1 {
+                continue;
+            }
+
+            let (x, y) = (pos.x + rng.range(-1, 2), pos.y + rng.range(-1, 2));
+            if !map.in_bounds(Point::new(x, y)) {
+                continue;
+            }
+            let strays = homes.get(entity).is_some_and(|home| {
+                rltk::DistanceAlg::Pythagoras
+                    .distance2d(Point::new(home.x, home.y), Point::new(x, y))
+                    > WANDER_RADIUS
+            });
+            let destination_idx = map.xy_idx(x, y);
+            if strays || map.blocked[destination_idx] {
+                continue;
+            }
+
+            // Bystanders don't get an `EntityMoved`, so that they never set off traps
+            let idx = map.xy_idx(pos.x, pos.y);
+            map.blocked.set(idx, false);
+            map.blocked.set(destination_idx, true);
+            (pos.x, pos.y) = (x, y);
+        }
+    }
+}
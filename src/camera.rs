@@ -0,0 +1,95 @@
+//! Which part of the map is on screen. Maps can be bigger than the space the HUD
+//! leaves for them, so the map is drawn as a window onto the level that follows the
+//! player around, and everything drawn over the map has to translate between map
+//! and screen coordinates through a [`Camera`]. Maps smaller than the window are
+//! drawn in the middle of it instead.
+
+use rltk::{Point, Rltk, RGB};
+use specs::prelude::*;
+
+use crate::{Map, PlayerPos};
+
+/// How many tiles wide the map's window on screen is.
+pub const VIEW_WIDTH: i32 = 80;
+
+/// How many tiles tall the map's window on screen is.
+pub const VIEW_HEIGHT: i32 = 43;
+
+/// A [`VIEW_WIDTH`] × [`VIEW_HEIGHT`] window onto the map, drawn in the top-left
+/// corner of the screen.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Camera {
+    /// The map tile shown in the very top-left corner of the screen. Negative if
+    /// the map is smaller than the window, and so is drawn away from the corner.
+    pub origin: Point,
+    map_width: i32,
+    map_height: i32,
+}
+
+impl Camera {
+    /// The camera for the current frame, centred on the player.
+    pub fn new(ecs: &World) -> Self {
+        let map = ecs.fetch::<Map>();
+        let player_pos = ecs.fetch::<PlayerPos>();
+        Self::centered_on(&map, **player_pos)
+    }
+
+    /// A window onto `map` centred on `center`, as far as it can be without
+    /// showing anything past the edges of the map.
+    pub fn centered_on(map: &Map, center: Point) -> Self {
+        Self {
+            origin: Point::new(
+                view_origin(center.x, map.width, VIEW_WIDTH),
+                view_origin(center.y, map.height, VIEW_HEIGHT),
+            ),
+            map_width: map.width,
+            map_height: map.height,
+        }
+    }
+
+    /// Where the map tile at `pos` is drawn on screen, if it's in view.
+    pub fn map_to_screen(&self, pos: Point) -> Option<Point> {
+        let screen = pos - self.origin;
+        (self.on_map(pos) && on_screen(screen)).then_some(screen)
+    }
+
+    /// Which map tile is drawn at `pos` on screen, if the map is drawn there at all.
+    pub fn screen_to_map(&self, pos: Point) -> Option<Point> {
+        let map_pos = pos + self.origin;
+        (on_screen(pos) && self.on_map(map_pos)).then_some(map_pos)
+    }
+
+    /// Every map tile in view, along with where it's drawn on screen.
+    pub fn tiles(self) -> impl Iterator<Item = (Point, Point)> {
+        (0..VIEW_HEIGHT)
+            .flat_map(|y| (0..VIEW_WIDTH).map(move |x| Point::new(x, y)))
+            .filter_map(move |screen| Some((self.screen_to_map(screen)?, screen)))
+    }
+
+    /// Set the background colour of the map tile at `pos`, if it's in view.
+    pub fn set_bg(&self, ctx: &mut Rltk, pos: Point, bg: RGB) {
+        if let Some(screen) = self.map_to_screen(pos) {
+            ctx.set_bg(screen.x, screen.y, bg);
+        }
+    }
+
+    fn on_map(&self, pos: Point) -> bool {
+        pos.x >= 0 && pos.x < self.map_width && pos.y >= 0 && pos.y < self.map_height
+    }
+}
+
+/// Returns `true` if `screen` is inside the map's window.
+fn on_screen(screen: Point) -> bool {
+    screen.x >= 0 && screen.x < VIEW_WIDTH && screen.y >= 0 && screen.y < VIEW_HEIGHT
+}
+
+/// Where along one axis the window should start to be centred on `center`,
+/// without going past either end of a map `map_len` tiles long. Maps shorter
+/// than the window are centred in it instead.
+fn view_origin(center: i32, map_len: i32, view_len: i32) -> i32 {
+    if map_len <= view_len {
+        -(view_len - map_len) / 2
+    } else {
+        (center - view_len / 2).clamp(0, map_len - view_len)
+    }
+}
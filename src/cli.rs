@@ -0,0 +1,70 @@
+//! The game's command-line flags.
+
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::run_options;
+use crate::saveload_system::SAVE_SLOTS;
+
+/// My implementation of the roguelike game described in Herbert Wolverson's
+/// "Roguelike Tutorial - In Rust".
+#[derive(PartialEq, Eq, Clone, Debug, Default, Parser)]
+#[command(version)]
+pub struct Args {
+    /// Start every new run with this seed. Anything that isn't a number is hashed
+    /// into one.
+    #[arg(long, value_parser = parse_seed)]
+    pub seed: Option<u64>,
+
+    /// Load the game in this save slot (counting from 1) instead of starting a new
+    /// run.
+    #[arg(
+        long,
+        value_name = "SLOT",
+        value_parser = parse_slot,
+        conflicts_with_all = ["seed", "replay"],
+    )]
+    pub load: Option<usize>,
+
+    /// Play back a recorded run, starting it with the same options and seed that
+    /// it was recorded with.
+    #[arg(long, value_name = "FILE", conflicts_with = "seed")]
+    pub replay: Option<PathBuf>,
+
+    /// Take up the whole screen.
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Mirror what's on screen as plain text on stdout, for screen readers.
+    #[arg(long)]
+    pub screen_reader: bool,
+
+    /// Show the entire map and everything in it, whether the player can see it or
+    /// not.
+    #[arg(long)]
+    pub debug_map: bool,
+
+    /// Set up the run without opening a window, print its map, and quit. Handy
+    /// for checking what a seed generates.
+    #[arg(long, conflicts_with = "replay")]
+    pub headless: bool,
+
+    /// Read settings from this file instead of `config.toml`.
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+}
+
+/// Parse a `--seed` the same way as one typed into the options menu.
+fn parse_seed(text: &str) -> Result<u64, String> {
+    run_options::parse_seed(text).ok_or_else(|| "the seed can't be blank".to_string())
+}
+
+/// Parse a `--load` slot, counting from 1 like the load menu does, into one
+/// counting from zero.
+fn parse_slot(text: &str) -> Result<usize, String> {
+    match text.parse::<usize>() {
+        Ok(slot @ 1..=SAVE_SLOTS) => Ok(slot - 1),
+        _ => Err(format!("there are only save slots 1 to {SAVE_SLOTS}")),
+    }
+}
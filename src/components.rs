@@ -19,11 +19,19 @@ pub fn register_all_components(ecs: &mut World) {
     ecs.register::<Monster>();
     ecs.register::<Item>();
     ecs.register::<Consumable>();
+    ecs.register::<Shatters>();
+    ecs.register::<ProvidesMapping>();
+    ecs.register::<ProvidesTownPortal>();
+    ecs.register::<Portal>();
+    ecs.register::<HungerClock>();
+    ecs.register::<ProvidesFood>();
+    ecs.register::<Enchants>();
     ecs.register::<ProvidesHealing>();
     ecs.register::<Ranged>();
     ecs.register::<InflictsDamage>();
     ecs.register::<AreaOfEffect>();
-    ecs.register::<Confusion>();
+    ecs.register::<InflictsStatusEffect>();
+    ecs.register::<StatusEffects>();
     ecs.register::<InBackpack>();
     ecs.register::<WantsToPickupItem>();
     ecs.register::<WantsToDropItem>();
@@ -33,7 +41,69 @@ pub fn register_all_components(ecs: &mut World) {
     ecs.register::<BlocksTile>();
     ecs.register::<CombatStats>();
     ecs.register::<WantsToMelee>();
-    ecs.register::<SufferDamage>();
+    ecs.register::<Equippable>();
+    ecs.register::<Equipped>();
+    ecs.register::<MeleePowerBonus>();
+    ecs.register::<MeleeAttack>();
+    ecs.register::<CritRangeBonus>();
+    ecs.register::<Rarity>();
+    ecs.register::<Affixed>();
+    ecs.register::<DefenseBonus>();
+    ecs.register::<BlockChance>();
+    ecs.register::<HomeLocation>();
+    ecs.register::<Hoarder>();
+    ecs.register::<RangedAttack>();
+    ecs.register::<MakesNoise>();
+    ecs.register::<WantsToMakeNoise>();
+    ecs.register::<HeardNoise>();
+    ecs.register::<Purse>();
+    ecs.register::<Gold>();
+    ecs.register::<Value>();
+    ecs.register::<Weight>();
+    ecs.register::<Vendor>();
+    ecs.register::<Restocks>();
+    ecs.register::<Enchanted>();
+    ecs.register::<ParticleLifetime>();
+    ecs.register::<LastSeenPlayer>();
+    ecs.register::<Guard>();
+    ecs.register::<ServiceNpc>();
+    ecs.register::<Hidden>();
+    ecs.register::<EntryTrigger>();
+    ecs.register::<SingleActivation>();
+    ecs.register::<EntityMoved>();
+    ecs.register::<Stash>();
+    ecs.register::<InStash>();
+    ecs.register::<Container>();
+    ecs.register::<WantsToTransferItem>();
+    ecs.register::<WantsToThrowItem>();
+    ecs.register::<OtherLevelPosition>();
+    ecs.register::<WinsGame>();
+    ecs.register::<Mana>();
+    ecs.register::<Spell>();
+    ecs.register::<KnownSpells>();
+    ecs.register::<Experience>();
+    ecs.register::<Attributes>();
+    ecs.register::<Skills>();
+    ecs.register::<Perks>();
+    ecs.register::<Morale>();
+    ecs.register::<Leader>();
+    ecs.register::<AiState>();
+    ecs.register::<FloatingText>();
+    ecs.register::<CachedPath>();
+    ecs.register::<LastAttacker>();
+    ecs.register::<Description>();
+    ecs.register::<Bystander>();
+    ecs.register::<Quips>();
+    ecs.register::<QuestGiver>();
+    ecs.register::<ExtraSerializationHelper>();
+    ecs.register::<LevelExit>();
+    ecs.register::<Swimmer>();
+    ecs.register::<LightSource>();
+    ecs.register::<Faction>();
+    ecs.register::<GroupLeader>();
+    ecs.register::<PackMember>();
+    ecs.register::<Boss>();
+    ecs.register::<SpecialAbilities>();
 }
 
 /// Indicates that an entity should be serialized when the game is saved.
@@ -51,6 +121,32 @@ pub struct Serializable;
 #[derive(Component, ConvertSaveload, Default, Debug, Clone)]
 pub struct SerializationHelper {
     pub map: crate::Map,
+    pub run_stats: crate::RunStats,
+    pub dungeon_master: crate::MasterDungeonMap,
+    pub item_names: crate::MasterItemNames,
+    pub level_events: crate::LevelEventScheduler,
+    pub reputation: crate::Reputation,
+}
+
+/// Resources added to saves since version 5, which can't go in the
+/// [`SerializationHelper`] without breaking older saves. Any fields missing from
+/// a save are left at their defaults.
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct ExtraSerializationHelper {
+    pub quest_journal: crate::QuestJournal,
+    pub respawns: crate::respawns::RespawnTimer,
+}
+
+/// Tracks where an entity is on a level other than the current one. See
+/// [`crate::MasterDungeonMap`].
+#[derive(Component, Serialize, Deserialize, Default, Debug, Copy, Clone)]
+pub struct OtherLevelPosition {
+    pub x: i32,
+    pub y: i32,
+    pub depth: i32,
+    #[serde(default)]
+    pub branch: crate::Branch,
 }
 
 /// Tracks the location of an entity.
@@ -96,6 +192,137 @@ pub struct Player;
 #[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
 pub struct Monster;
 
+/// Where a monster lives. [`Hoarder`]s haul the items they find back here.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct HomeLocation {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A monster that collects any items it sees and stashes them at its
+/// [`HomeLocation`].
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Hoarder {
+    /// How many items the monster can carry before it has to head home and drop them off.
+    pub capacity: usize,
+}
+
+/// How much gold an entity is carrying.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Purse {
+    pub gold: i32,
+}
+
+/// A pile of gold lying on the floor. Anything with a [`Purse`] that walks over it
+/// picks it up automatically.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Gold {
+    pub amount: i32,
+}
+
+/// How much an item is worth. [`Vendor`]s sell it for this much, and buy it back
+/// for less.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Value {
+    pub gold: i32,
+}
+
+impl Value {
+    /// The most a [`Vendor`] will pay for an item, out of every 100 gold it's worth.
+    const MAX_SELL_PERCENT: i32 = 75;
+
+    /// How much a [`Vendor`] will pay a seller with `attributes` for the item: half
+    /// what it's worth, and 5% more for every point of [`Attribute::Intellect`] they
+    /// have to drive a harder bargain with.
+    pub fn sell_price(&self, attributes: &Attributes) -> i32 {
+        let percent = (50 + 5 * attributes.intellect).min(Self::MAX_SELL_PERCENT);
+        self.gold * percent / 100
+    }
+}
+
+/// How heavy an item is. Nobody can carry more than their
+/// [`Attributes::carry_capacity()`].
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Weight {
+    pub pounds: i32,
+}
+
+/// A friendly NPC that buys and sells items. Whatever it's carrying in its
+/// [`InBackpack`] is up for sale.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Vendor;
+
+/// A [`Vendor`] that gets new wares in every so often while the player's away,
+/// better ones the deeper the player has been. See [`crate::restocking`].
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Restocks {
+    /// The [`crate::GameLog::turn`] that the vendor last restocked on.
+    pub last_restock: u32,
+}
+
+/// A member of the town watch. Guards keep to themselves until the player commits
+/// a [`crate::crime::Crime`], after which they hunt them down like any [`Monster`].
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Guard;
+
+/// The kinds of services that a [`ServiceNpc`] can sell.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Service {
+    /// Restores health and cures ailments.
+    Healer,
+    /// Identifies unknown items.
+    Sage,
+}
+
+/// A friendly NPC that the player can bump into to buy a [`Service`] from.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ServiceNpc {
+    pub service: Service,
+}
+
+/// A monster that can shoot at the player from a distance.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct RangedAttack {
+    /// How far away the monster can shoot from.
+    pub range: i32,
+    /// How much damage each shot does, ignoring the target's defense.
+    pub damage: i32,
+}
+
+/// A monster that has heard a noise, and will go and investigate it as long as it
+/// can't see the player.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct HeardNoise {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Where a monster last saw the player. Once it loses sight of them, it heads there
+/// and searches the area for a while before giving up.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct LastSeenPlayer {
+    pub x: i32,
+    pub y: i32,
+    /// How many more spots the monster will check before forgetting the player.
+    pub search_turns: i32,
+}
+
+/// An item that makes a loud noise wherever it's used, distracting nearby monsters.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct MakesNoise {
+    /// How far away the noise can be heard from.
+    pub volume: i32,
+}
+
+/// Entities tagged with this component intend to make a noise at (`x`, `y`) this ECS tick.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct WantsToMakeNoise {
+    pub x: i32,
+    pub y: i32,
+    /// How far away the noise can be heard from.
+    pub volume: i32,
+}
+
 /// An item that can be picked up and used.
 #[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
 pub struct Item;
@@ -104,6 +331,60 @@ pub struct Item;
 #[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
 pub struct Consumable;
 
+/// Indicates that an item breaks when it's thrown, like a potion, applying its
+/// effects to whatever's where it lands.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Shatters;
+
+/// Indicates that an item reveals the layout of the whole level, though not
+/// what's in it.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct ProvidesMapping;
+
+/// Indicates that an item opens a [`Portal`] back to town, and takes the player
+/// through it.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct ProvidesTownPortal;
+
+/// Indicates that an item can be eaten, winding its eater's [`HungerClock`] back
+/// to [`HungerState::WellFed`].
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct ProvidesFood;
+
+/// Which of its reader's equipped items an [`Enchants`] scroll can be used on.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum EnchantTarget {
+    /// Whatever's in the [`EquipmentSlot::Melee`] slot, which gets more power.
+    Weapon,
+    /// Anything worn in any other slot, which gets more defense.
+    Armor,
+}
+
+impl EnchantTarget {
+    /// Whether an item equipped in `slot` can be enchanted.
+    pub fn fits(self, slot: EquipmentSlot) -> bool {
+        match self {
+            Self::Weapon => slot == EquipmentSlot::Melee,
+            Self::Armor => slot != EquipmentSlot::Melee,
+        }
+    }
+}
+
+/// Indicates that an item permanently improves one of its reader's equipped
+/// items, which they pick once they've read it. Every enchantment risks
+/// destroying the item instead.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Enchants {
+    pub target: EnchantTarget,
+}
+
+/// Indicates that an item has been improved by [`Enchants`] scrolls, and how many
+/// times. The more it has been, the riskier the next one is.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Enchanted {
+    pub times: i32,
+}
+
 /// Indicates that an item heals the user.
 #[derive(Component, Debug, Default, ConvertSaveload, Clone)]
 pub struct ProvidesHealing {
@@ -132,10 +413,205 @@ pub struct AreaOfEffect {
     pub radius: i32,
 }
 
-/// This entity can apply confusion, which makes other entities "Confused" for
-/// a few turns - during which time they'll do nothing.
-#[derive(Component, Debug, Default, Copy, Clone, ConvertSaveload)]
-pub struct Confusion {
+/// Lingering conditions that can affect an entity for a few turns. See [`StatusEffects`].
+#[derive(
+    PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize, strum::Display, strum::EnumIter,
+)]
+pub enum StatusEffect {
+    /// Can't do anything at all.
+    Confused,
+    /// Takes a little damage every turn.
+    Poisoned,
+    /// Takes a lot of damage every turn.
+    Burning,
+    /// Loses every other turn.
+    Slowed,
+    /// Gets an extra turn every other turn.
+    Hasted,
+    /// Runs away instead of fighting, whatever its [`Morale`].
+    Frightened,
+    /// Hits harder in melee.
+    Strengthened,
+    /// Shrugs off more damage in melee.
+    Fortified,
+    /// Loses its turns while it makes its way through water. See
+    /// [`crate::TileType::movement_cost()`].
+    Wading,
+}
+
+impl StatusEffect {
+    /// How much damage the effect does each turn.
+    pub const fn damage_per_turn(self) -> i32 {
+        match self {
+            Self::Poisoned => 1,
+            Self::Burning => 2,
+            _ => 0,
+        }
+    }
+
+    /// How much the effect adds to its bearer's melee power.
+    pub const fn power_bonus(self) -> i32 {
+        match self {
+            Self::Strengthened => 3,
+            _ => 0,
+        }
+    }
+
+    /// How much the effect adds to its bearer's defense.
+    pub const fn defense_bonus(self) -> i32 {
+        match self {
+            Self::Fortified => 3,
+            _ => 0,
+        }
+    }
+
+    /// Whether the effect is bad for whoever has it.
+    pub const fn is_harmful(self) -> bool {
+        !matches!(self, Self::Hasted | Self::Strengthened | Self::Fortified)
+    }
+}
+
+/// A [`StatusEffect`] that's affecting an entity, and how much longer it'll last.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct ActiveStatusEffect {
+    pub effect: StatusEffect,
+    pub turns: i32,
+}
+
+/// The [`StatusEffect`]s currently affecting an entity. Durations are ticked down
+/// once per turn by [`crate::StatusEffectSystem`].
+#[derive(Component, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StatusEffects {
+    pub active: Vec<ActiveStatusEffect>,
+}
+
+impl StatusEffects {
+    /// Inflict `effect` on `victim` for `turns` turns. If it's already affected, the
+    /// effect lasts for whichever is longer.
+    pub fn inflict(
+        store: &mut WriteStorage<Self>,
+        victim: Entity,
+        effect: StatusEffect,
+        turns: i32,
+    ) {
+        if let Some(effects) = store.get_mut(victim) {
+            match effects
+                .active
+                .iter_mut()
+                .find(|active| active.effect == effect)
+            {
+                Some(active) => active.turns = active.turns.max(turns),
+                None => effects.active.push(ActiveStatusEffect { effect, turns }),
+            }
+        } else {
+            let effects = Self {
+                active: vec![ActiveStatusEffect { effect, turns }],
+            };
+            store
+                .insert(victim, effects)
+                .expect("Unable to insert brand-new status effects for victim entity");
+        }
+    }
+
+    /// Whether `effect` is currently active.
+    pub fn has(&self, effect: StatusEffect) -> bool {
+        self.active.iter().any(|active| active.effect == effect)
+    }
+
+    /// The effect that stops the entity from acting this turn, if any.
+    pub fn skips_turn(&self) -> Option<StatusEffect> {
+        self.active
+            .iter()
+            .find(|active| match active.effect {
+                StatusEffect::Confused | StatusEffect::Wading => true,
+                StatusEffect::Slowed => active.turns % 2 == 1,
+                _ => false,
+            })
+            .map(|active| active.effect)
+    }
+
+    /// How much every active effect adds to the entity's melee power, on top of its
+    /// [`CombatStats`].
+    pub fn power_bonus(&self) -> i32 {
+        self.active
+            .iter()
+            .map(|active| active.effect.power_bonus())
+            .sum()
+    }
+
+    /// How much every active effect adds to the entity's defense, on top of its
+    /// [`CombatStats`].
+    pub fn defense_bonus(&self) -> i32 {
+        self.active
+            .iter()
+            .map(|active| active.effect.defense_bonus())
+            .sum()
+    }
+
+    /// Whether the entity gets to act again before anyone else this turn.
+    pub fn gets_extra_turn(&self) -> bool {
+        self.active
+            .iter()
+            .any(|active| active.effect == StatusEffect::Hasted && active.turns % 2 == 0)
+    }
+}
+
+/// How hungry an entity is. See [`HungerClock`].
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize, strum::Display)]
+pub enum HungerState {
+    #[strum(to_string = "Well fed")]
+    WellFed,
+    Normal,
+    Hungry,
+    /// Takes a little damage every turn, until it eats.
+    Starving,
+}
+
+impl HungerState {
+    /// How many turns it takes to get hungrier than this.
+    pub const fn turns(self) -> i32 {
+        match self {
+            Self::WellFed => 150,
+            Self::Normal => 300,
+            Self::Hungry => 150,
+            Self::Starving => 0,
+        }
+    }
+
+    /// What comes after this, once its [`Self::turns()`] are up.
+    pub const fn next(self) -> Self {
+        match self {
+            Self::WellFed => Self::Normal,
+            Self::Normal => Self::Hungry,
+            Self::Hungry | Self::Starving => Self::Starving,
+        }
+    }
+}
+
+/// Counts down to an entity getting hungrier. Wound down once per turn by
+/// [`crate::HungerSystem`], and back up by eating anything that [`ProvidesFood`].
+#[derive(Component, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct HungerClock {
+    pub state: HungerState,
+    /// How long until the next [`HungerState`].
+    pub turns: i32,
+}
+
+impl HungerClock {
+    /// A clock that's just started counting down from `state`.
+    pub const fn new(state: HungerState) -> Self {
+        Self {
+            state,
+            turns: state.turns(),
+        }
+    }
+}
+
+/// An item that inflicts a [`StatusEffect`] on whatever it's used on (or steps on it,
+/// for traps) for a few turns.
+#[derive(Component, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct InflictsStatusEffect {
+    pub effect: StatusEffect,
     pub turns: i32,
 }
 
@@ -145,6 +621,144 @@ pub struct InBackpack {
     pub owner: Entity,
 }
 
+/// The slots that an [`Equippable`] item can be equipped into.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Melee,
+    Shield,
+    Head,
+    Torso,
+    Feet,
+}
+
+/// An item that can be equipped into an [`EquipmentSlot`].
+#[derive(Component, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Equippable {
+    pub slot: EquipmentSlot,
+}
+
+/// An item that is currently equipped by its `owner`.
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct Equipped {
+    pub owner: Entity,
+    pub slot: EquipmentSlot,
+}
+
+/// An equippable item that adds to its wearer's melee attack power.
+#[derive(Component, Debug, Default, Clone, ConvertSaveload)]
+pub struct MeleePowerBonus {
+    pub power: i32,
+}
+
+/// A roll of `n_dice` dice with `die_type` sides each, plus a flat `bonus`. Written
+/// like "1d8+1".
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Dice {
+    pub n_dice: i32,
+    pub die_type: i32,
+    pub bonus: i32,
+}
+
+impl Dice {
+    pub const fn new(n_dice: i32, die_type: i32, bonus: i32) -> Self {
+        Self {
+            n_dice,
+            die_type,
+            bonus,
+        }
+    }
+
+    /// Roll the dice and add up the result.
+    pub fn roll(&self, rng: &mut rltk::RandomNumberGenerator) -> i32 {
+        rng.roll_dice(self.n_dice, self.die_type) + self.bonus
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d{}", self.n_dice, self.die_type)?;
+        match self.bonus {
+            0 => Ok(()),
+            bonus if bonus > 0 => write!(f, "+{bonus}"),
+            bonus => write!(f, "{bonus}"),
+        }
+    }
+}
+
+/// The [`Dice`] rolled for damage when hitting something in melee, on top of the
+/// attacker's power. On a creature this is its natural attack (fists, claws, teeth);
+/// on an equipped weapon it's used instead.
+#[derive(Component, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct MeleeAttack {
+    pub damage: Dice,
+}
+
+/// An equippable item that widens the range of natural rolls that its wearer
+/// scores a critical hit on in melee, by `range`.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct CritRangeBonus {
+    pub range: i32,
+}
+
+/// How hard an item is to come by. Rarer items turn up more often the deeper the
+/// player goes, and their names are shown in their rarity's colour. Items without
+/// one are common.
+#[derive(
+    Component,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    strum::Display,
+)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Uncommon,
+    Rare,
+    Legendary,
+}
+
+impl Rarity {
+    /// How likely items of this rarity are to turn up at `depth`, as a percentage
+    /// of how likely they are on the first level.
+    pub fn spawn_bias(self, depth: i32) -> i32 {
+        let levels_down = depth - 1;
+        match self {
+            Self::Common => (100 - 5 * levels_down).max(50),
+            Self::Uncommon => 100,
+            Self::Rare => 100 + 10 * levels_down,
+            Self::Legendary => 100 + 25 * levels_down,
+        }
+    }
+}
+
+/// An item that had magic affixes rolled onto it when it was spawned, which have
+/// been added to its [`Name`] and stats. `base` is what it's called without them.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Affixed {
+    pub base: String,
+}
+
+/// An equippable item that adds to its wearer's defense.
+#[derive(Component, Debug, Default, Clone, ConvertSaveload)]
+pub struct DefenseBonus {
+    pub defense: i32,
+}
+
+/// An equippable item (usually a shield) that gives its wearer a `percent` chance
+/// to block a melee hit outright. Chances from everything worn add up.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct BlockChance {
+    pub percent: i32,
+}
+
 /// Entities tagged with this component are attempting to pick up an [`Item`]
 /// and put it into their own backpack this ECS tick.
 #[derive(Component, Debug, Clone, ConvertSaveload)]
@@ -153,6 +767,23 @@ pub struct WantsToPickupItem {
     pub item: Entity,
 }
 
+/// Entities tagged with this component intend to move `item` out of `from`'s
+/// backpack and into `to`'s this ECS tick, like when looting a [`Container`].
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToTransferItem {
+    pub item: Entity,
+    pub from: Entity,
+    pub to: Entity,
+}
+
+/// Entities tagged with this component intend to throw an item from their
+/// backpack towards `target` this ECS tick.
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToThrowItem {
+    pub item: Entity,
+    pub target: rltk::Point,
+}
+
 /// Entities tagged with this component intend to drop an item from their backpack.
 #[derive(Component, Debug, Clone, ConvertSaveload)]
 pub struct WantsToDropItem {
@@ -192,6 +823,85 @@ impl<'a> From<&'a str> for Name {
     }
 }
 
+/// A line or two of flavour text about an entity, shown in its tooltip.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Description {
+    pub text: String,
+}
+
+impl<'a> From<&'a str> for Description {
+    fn from(text: &'a str) -> Self {
+        Self {
+            text: text.to_string(),
+        }
+    }
+}
+
+/// A harmless townsperson who wanders around their [`HomeLocation`], and has a
+/// word for the player when bumped into. See [`crate::BystanderAI`].
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Bystander;
+
+/// Things that an NPC says when the player talks to them, one picked at random
+/// each time.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Quips {
+    pub lines: Vec<String>,
+}
+
+impl<'a> From<&[&'a str]> for Quips {
+    fn from(lines: &[&'a str]) -> Self {
+        Self {
+            lines: lines.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// A townsperson with a [`crate::quests::Quest`] to hand out, called `quest`.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone)]
+pub struct QuestGiver {
+    pub quest: String,
+}
+
+/// A staircase or other way off of the level, that takes the player to
+/// `destination` when they use it.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct LevelExit {
+    pub destination: crate::LevelId,
+}
+
+/// A magic doorway between the town and somewhere in the dungeon, which works
+/// like any other [`LevelExit`] it's paired with. Stepping through one comes out
+/// at the portal on the other side, and both close once the player has come back
+/// down through them.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Portal;
+
+/// An entity that can swim, and so doesn't drown in [`crate::TileType::DeepWater`].
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Swimmer;
+
+/// Lights up everything within `range` tiles that it can shine on, in `color`.
+/// Carried light sources shine from wherever whoever's carrying them is.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct LightSource {
+    pub range: i32,
+    pub color: RGB,
+}
+
+/// Who an entity sides with. How factions feel about each other is decided by
+/// [`crate::factions::reaction()`].
+#[derive(Component, PartialEq, Eq, Hash, Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum Faction {
+    Player,
+    /// Goblins, orcs, and their hangers-on.
+    Goblinoids,
+    /// Animals, which go for anything that isn't one of their own.
+    Wildlife,
+    /// The town watch, and everyone they look out for.
+    Townsfolk,
+}
+
 /// Describes which tiles are visible to an entity, and what the entity's
 /// view range is.
 #[derive(Component, Debug, ConvertSaveload, Clone)]
@@ -232,24 +942,392 @@ pub struct WantsToMelee {
     pub target: Entity,
 }
 
-/// The cumulative sum of damage that will be inflicted on an entity this ECS tick.
-#[derive(Component, Debug, Default, Clone, ConvertSaveload)]
-pub struct SufferDamage {
-    pub amount: Vec<i32>,
+/// Indicates that an entity can't be seen by the player until it's been discovered.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Hidden;
+
+/// Indicates that an entity does something to whoever steps onto its tile.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct EntryTrigger;
+
+/// Indicates that an [`EntryTrigger`] is used up after going off once.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct SingleActivation;
+
+/// Indicates that an entity moved to a new tile this ECS tick.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct EntityMoved;
+
+/// Indicates that an entity is the player's stash, where they can store items
+/// that they don't want to risk losing in the dungeon.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Stash;
+
+/// A chest, or anything else that holds items of its own in its [`InBackpack`].
+/// Bumping into one opens it, so things can be taken out of it or put in.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Container;
+
+/// Indicates that an item is being kept in the player's [`Stash`]. Stashed items
+/// don't have a [`Position`], and are kept when the player changes levels.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct InStash;
+
+/// Indicates that an item wins the game for the player, when they use it or
+/// carry it back up to the surface.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct WinsGame;
+
+/// An entity's pool of mana, which gets spent to cast [`Spell`]s.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Mana {
+    pub max_mana: i32,
+    pub mana: i32,
 }
 
-impl SufferDamage {
-    /// Add a new damage source to a victim entity's SufferDamage component.
-    pub fn new_damage(store: &mut WriteStorage<Self>, victim: Entity, amount: i32) {
-        if let Some(suffering) = store.get_mut(victim) {
-            suffering.amount.push(amount);
-        } else {
-            let dmg = Self {
-                amount: vec![amount],
-            };
-            store.insert(victim, dmg).expect(
-                "Unable to insert a brand-new incoming damage list into store for victim entity",
-            );
+/// Indicates that an entity is a spell, rather than an item. Spells are cast like
+/// using an item, with the same effect components, but cost mana instead of being
+/// used up.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Spell {
+    /// How much [`Mana`] it takes to cast the spell.
+    pub mana_cost: i32,
+}
+
+/// The [`Spell`]s that an entity knows how to cast.
+#[derive(Component, Debug, Default, Clone)]
+pub struct KnownSpells {
+    pub spells: Vec<Entity>,
+}
+
+// The ConvertSaveload derive can't handle a Vec of entities, so map each of them
+// to and from its marker by hand.
+impl<M: Marker + Serialize> ConvertSaveload<M> for KnownSpells
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = Vec<M>;
+    type Error = std::convert::Infallible;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(self.spells.iter().filter_map(|spell| ids(*spell)).collect())
+    }
+
+    fn convert_from<F>(data: Self::Data, ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Self {
+            spells: data.into_iter().filter_map(ids).collect(),
+        })
+    }
+}
+
+/// How far along an entity is in levelling up.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
+    /// Level-ups that the player hasn't picked an upgrade for yet.
+    pub unspent_upgrades: i32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            xp: 0,
+            unspent_upgrades: 0,
+        }
+    }
+}
+
+impl Experience {
+    /// How much experience it takes to get from this level to the next.
+    pub const fn xp_to_next_level(&self) -> i32 {
+        self.level * 40
+    }
+}
+
+/// The attributes that level-up upgrades can raise.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize, strum::Display)]
+pub enum Attribute {
+    /// Adds to melee power, and lets 10 more pounds be carried per point.
+    Might,
+    /// Adds to defense.
+    Toughness,
+    /// Adds 5 max HP per point.
+    Vitality,
+    /// Adds 3 max mana per point.
+    Intellect,
+}
+
+/// Points an entity has put into each [`Attribute`] over the course of the run.
+/// Their effects are folded into [`CombatStats`] and [`Mana`] as they're gained.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Attributes {
+    pub might: i32,
+    pub toughness: i32,
+    pub vitality: i32,
+    pub intellect: i32,
+}
+
+impl Attributes {
+    /// How many pounds can be carried with no points in [`Attribute::Might`].
+    const BASE_CARRY_CAPACITY: i32 = 30;
+    /// How many more pounds can be carried for each point in [`Attribute::Might`].
+    const CARRY_CAPACITY_PER_MIGHT: i32 = 10;
+
+    /// How many pounds of items, all [`Weight`]s added up, can be carried at once.
+    pub const fn carry_capacity(&self) -> i32 {
+        Self::BASE_CARRY_CAPACITY + self.might * Self::CARRY_CAPACITY_PER_MIGHT
+    }
+}
+
+/// Skills that can be trained to get better at things.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize, strum::Display)]
+pub enum Skill {
+    /// Each rank adds 1 to melee damage.
+    Melee,
+    /// Each rank blocks 1 more melee damage.
+    Defense,
+    /// Each rank makes spells cost 1 less mana, down to a minimum of 1.
+    Magic,
+}
+
+/// How many ranks an entity has in each [`Skill`], and how much practice it's put
+/// into each one towards the next rank.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Skills {
+    pub melee: i32,
+    pub defense: i32,
+    pub magic: i32,
+    #[serde(default)]
+    pub melee_practice: i32,
+    #[serde(default)]
+    pub defense_practice: i32,
+    #[serde(default)]
+    pub magic_practice: i32,
+}
+
+impl Skills {
+    /// How many ranks are in `skill`.
+    pub const fn rank(&self, skill: Skill) -> i32 {
+        match skill {
+            Skill::Melee => self.melee,
+            Skill::Defense => self.defense,
+            Skill::Magic => self.magic,
+        }
+    }
+
+    /// Mutable access to how many ranks are in `skill`.
+    pub fn rank_mut(&mut self, skill: Skill) -> &mut i32 {
+        match skill {
+            Skill::Melee => &mut self.melee,
+            Skill::Defense => &mut self.defense,
+            Skill::Magic => &mut self.magic,
         }
     }
+
+    /// How many times `skill` has to be used to get from rank `rank` to the next.
+    pub const fn practice_to_next_rank(rank: i32) -> i32 {
+        20 * (rank + 1)
+    }
+
+    /// Use `skill` once, ranking it up if that was enough practice. Returns `true`
+    /// if it ranked up.
+    pub fn practice(&mut self, skill: Skill) -> bool {
+        let rank = self.rank(skill);
+        let practice = match skill {
+            Skill::Melee => &mut self.melee_practice,
+            Skill::Defense => &mut self.defense_practice,
+            Skill::Magic => &mut self.magic_practice,
+        };
+
+        *practice += 1;
+        if *practice < Self::practice_to_next_rank(rank) {
+            return false;
+        }
+        *practice = 0;
+        *self.rank_mut(skill) += 1;
+        true
+    }
+
+    /// How much mana a spell with a base cost of `mana_cost` takes to cast.
+    pub fn spell_cost(&self, mana_cost: i32) -> i32 {
+        (mana_cost - self.magic).max(1)
+    }
+}
+
+/// Passive bonuses that can be picked up when levelling up.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize, strum::Display)]
+pub enum Perk {
+    /// Waiting heals twice as fast.
+    #[strum(to_string = "Fast Healing")]
+    FastHealing,
+    /// Waiting restores mana twice as fast.
+    Meditation,
+}
+
+/// The [`Perk`]s an entity has picked up.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Perks {
+    pub perks: Vec<Perk>,
+}
+
+/// How willing a monster is to keep fighting. Seeing its allies die wears it down,
+/// and once it hits zero the monster breaks, and either routs or surrenders.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Morale {
+    pub value: i32,
+    /// The monster runs away from the player once it's down to less than this
+    /// fraction of its max HP. Zero for monsters that fight to the death.
+    #[serde(default)]
+    pub flee_below: f32,
+}
+
+impl Default for Morale {
+    fn default() -> Self {
+        Self {
+            value: 10,
+            flee_below: 0.25,
+        }
+    }
+}
+
+impl Morale {
+    /// Whether the monster has lost the will to fight.
+    pub const fn is_broken(&self) -> bool {
+        self.value <= 0
+    }
+}
+
+/// A monster that others rally around. Seeing a leader die is much worse for
+/// [`Morale`] than seeing anyone else die.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Leader;
+
+/// The leader of a pack of monsters that hunt together, which keeps track of the
+/// whole pack's plans. See [`crate::PackAI`].
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct GroupLeader {
+    /// How many monsters the pack started out with, leader included.
+    pub pack_size: i32,
+    /// Whether the pack has stopped waiting, and gone in for the kill.
+    pub attacking: bool,
+}
+
+/// A monster that hunts in the pack led by `leader`.
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct PackMember {
+    pub leader: Entity,
+}
+
+/// A unique monster that guards the level it's found on. Its health gets a bar
+/// of its own while the player can see it.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct Boss;
+
+/// Something a monster with [`SpecialAbilities`] can do instead of fighting.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
+pub enum Ability {
+    /// Call `count` of the monster called `monster` to its side.
+    Summon { monster: String, count: i32 },
+    /// Pound the ground, doing `damage` to everything within `radius` tiles.
+    Slam { radius: i32, damage: i32 },
+}
+
+/// An [`Ability`], and how long until it can be used again.
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
+pub struct SpecialAbility {
+    pub ability: Ability,
+    /// How many turns the ability takes to come back after being used.
+    pub cooldown: i32,
+    /// How many turns are left until the ability can be used again.
+    pub ready_in: i32,
+}
+
+impl SpecialAbility {
+    /// `ability`, ready to use and back again `cooldown` turns after each use.
+    pub const fn new(ability: Ability, cooldown: i32) -> Self {
+        Self {
+            ability,
+            cooldown,
+            ready_in: 0,
+        }
+    }
+}
+
+/// The scripted tricks a monster (usually a [`Boss`]) has up its sleeve, which
+/// are used by [`crate::BossAI`].
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone)]
+pub struct SpecialAbilities {
+    pub abilities: Vec<SpecialAbility>,
+    /// How much power the monster gains when it's dropped below half health, and
+    /// flies into a rage. Zero for monsters that never enrage.
+    pub enrage_power: i32,
+    pub enraged: bool,
+    /// Whether the monster spent this turn using an ability, and so doesn't get to
+    /// fight as well.
+    #[serde(skip)]
+    pub used_this_turn: bool,
+}
+
+/// What a monster's AI is currently trying to do.
+#[derive(Component, PartialEq, Eq, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub enum AiState {
+    /// Going after the player, or whatever it last heard.
+    #[default]
+    Fighting,
+    /// Keeping its distance from the player until the rest of its pack is ready
+    /// to attack together.
+    Lurking,
+    /// Running for the edge of the map at (`x`, `y`), to escape the level.
+    Routing { x: i32, y: i32 },
+    /// Running away from the player after taking a beating (see
+    /// [`Morale::flee_below`]), but not yet beaten. Fights back if cornered.
+    Fleeing,
+    /// Given up. Surrendered monsters aren't hostile, and hand over whatever they're
+    /// carrying when the player bumps into them.
+    Surrendered,
+}
+
+/// A short-lived visual effect, spawned by [`crate::effects`]. Deleted once its
+/// lifetime runs out.
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub struct ParticleLifetime {
+    /// How many more milliseconds the particle stays on screen.
+    pub lifetime_ms: f32,
+}
+
+/// A particle made of text, like a damage number, that floats up from its
+/// [`Position`] as its [`ParticleLifetime`] runs out. Never saved.
+#[derive(Component, Debug, Clone)]
+pub struct FloatingText {
+    pub text: String,
+    pub fg: RGB,
+    /// How long the text is on screen for in total, in milliseconds.
+    pub duration_ms: f32,
+}
+
+/// A path that a monster worked out to `goal`, which it follows for as long as
+/// it's still heading near there, rather than finding its way again every turn.
+/// Never saved.
+#[derive(Component, Debug, Clone)]
+pub struct CachedPath {
+    pub goal: rltk::Point,
+    /// The map indexes of the tiles left to walk, in reverse order so that the
+    /// next step is last.
+    pub steps: Vec<usize>,
+}
+
+/// Whoever last hurt an entity, so that they can be credited if it dies of its
+/// wounds. Only lasts until something without an attacker (like a trap, or
+/// drowning) hurts it instead. Never saved.
+#[derive(Component, Debug, Clone)]
+pub struct LastAttacker {
+    pub attacker: Entity,
 }
@@ -0,0 +1,294 @@
+//! Display and gameplay settings, loaded from `config.toml` next to the game when
+//! it starts up. Anything that the file leaves out (or everything, if there's no
+//! file at all) falls back to its default. See `config.example.toml` for every
+//! setting. On the web, the "file" is kept in the browser's `localStorage`.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+pub use crate::l10n::Language;
+pub use crate::palette::PaletteKind;
+use crate::storage;
+
+/// Where the config file is read from, relative to the working directory.
+pub const CONFIG_PATH: &str = "config.toml";
+
+/// The most times bigger than its own size that the font can be drawn from the
+/// options menu. Bigger scales can still be set in the config file.
+pub const MAX_MENU_SCALE: u32 = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file {path}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse config file {path}")]
+    Parse {
+        path: String,
+        source: toml::de::Error,
+    },
+
+    #[error("Invalid `{setting}` in config file {path}: it has to be more than zero")]
+    Invalid { path: String, setting: &'static str },
+
+    #[error("Failed to serialize settings for config file {path}")]
+    Serialization {
+        path: String,
+        source: toml::ser::Error,
+    },
+
+    #[error("Failed to write config file {path}")]
+    Write {
+        path: String,
+        source: std::io::Error,
+    },
+}
+
+/// Where the config was loaded from, and so where settings changed in the options
+/// menu are written back to.
+#[derive(Clone, Debug)]
+pub struct ConfigPath(pub PathBuf);
+
+impl Default for ConfigPath {
+    fn default() -> Self {
+        Self(PathBuf::from(CONFIG_PATH))
+    }
+}
+
+/// Every setting from the config file.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub display: DisplayConfig,
+    pub gameplay: GameplayConfig,
+}
+
+/// Settings for how the game is drawn.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// The most frames drawn per second.
+    pub fps_cap: f32,
+    /// The font that everything is drawn in. Can be switched in the options menu
+    /// too, which saves it here for the next time the game starts.
+    pub font: Font,
+    /// How many times bigger than the font's own size to draw it. Turn it up to
+    /// stop the game from being tiny on high-DPI screens. Can be changed in the
+    /// options menu too, like the font.
+    pub scale: u32,
+    /// The colours the map and UI are drawn in. Can be switched in the options
+    /// menu too.
+    pub palette: PaletteKind,
+    /// Whether to mirror what's on screen as text for screen readers. Can be
+    /// switched in the options menu too, or turned on with `--screen-reader`.
+    pub screen_reader: bool,
+    /// The language all of the game's text is shown in.
+    pub language: Language,
+    /// Whether scanlines are drawn over the screen, like on an old CRT.
+    pub scanlines: bool,
+    /// Show the entire map and all entities in it, regardless of what's actually
+    /// visible. Tooltips and such work long-range too. Always on in builds with
+    /// the `debug-map-view` feature, or when started with `--debug-map`.
+    pub debug_map_view: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            fps_cap: 60.0,
+            font: Font::default(),
+            scale: 1,
+            palette: PaletteKind::default(),
+            screen_reader: false,
+            language: Language::default(),
+            scanlines: true,
+            debug_map_view: cfg!(feature = "debug-map-view"),
+        }
+    }
+}
+
+impl DisplayConfig {
+    /// How many pixels wide and tall each character on the console is drawn, once
+    /// it's been scaled up.
+    pub fn tile_size(&self) -> (u32, u32) {
+        let (width, height) = self.font.size();
+        let scale = self.scale.max(1);
+        (width * scale, height * scale)
+    }
+}
+
+/// One of the fonts built into RLTK.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Font {
+    /// Square 8×8 characters, so the map isn't stretched out.
+    #[default]
+    Terminal8x8,
+    /// Taller 8×16 characters, which are easier to read.
+    Vga8x16,
+}
+
+impl Font {
+    /// The next font along, wrapping back around to the first.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Terminal8x8 => Self::Vga8x16,
+            Self::Vga8x16 => Self::Terminal8x8,
+        }
+    }
+
+    /// The name of the font's image, to load it by.
+    pub fn file(self) -> &'static str {
+        match self {
+            Self::Terminal8x8 => "terminal8x8.png",
+            Self::Vga8x16 => "vga8x16.png",
+        }
+    }
+
+    /// How many pixels wide and tall each of the font's characters are.
+    pub fn size(self) -> (u32, u32) {
+        match self {
+            Self::Terminal8x8 => (8, 8),
+            Self::Vga8x16 => (8, 16),
+        }
+    }
+}
+
+/// Settings that change how the game plays.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplayConfig {
+    /// How many hit points the player starts a run with.
+    pub starting_hp: i32,
+    /// The die rolled for how many monsters and items go in each room. Bigger
+    /// dice make for more crowded levels.
+    pub spawn_die: i32,
+    /// Whether the letter keys (H, J, K, L, and U, I, N, M for diagonals) move
+    /// the player and cursors around, as well as the arrow keys and numpad.
+    pub vi_keys: bool,
+}
+
+impl Default for GameplayConfig {
+    fn default() -> Self {
+        Self {
+            starting_hp: 30,
+            spawn_die: 7,
+            vi_keys: true,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config from [`CONFIG_PATH`], or use the defaults if there's no
+    /// config file.
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from(Path::new(CONFIG_PATH))
+    }
+
+    /// Load the config from the file at `path`, or use the defaults if there's
+    /// no file there.
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        let text = match storage::read(&path.to_string_lossy()) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(source) => {
+                return Err(ConfigError::Read {
+                    path: path.display().to_string(),
+                    source,
+                })
+            }
+        };
+
+        let config: Self = toml::from_slice(&text).map_err(|source| ConfigError::Parse {
+            path: path.display().to_string(),
+            source,
+        })?;
+        match config.invalid_setting() {
+            Some(setting) => Err(ConfigError::Invalid {
+                path: path.display().to_string(),
+                setting,
+            }),
+            None => Ok(config),
+        }
+    }
+
+    /// Write `font` and `scale` into the config file at `path`, keeping every
+    /// other setting already in it. The window is only set up when the game
+    /// starts, so they take effect the next time it does.
+    pub fn save_window_settings(path: &Path, font: Font, scale: u32) -> Result<(), ConfigError> {
+        let mut config = Self::load_from(path)?;
+        config.display.font = font;
+        config.display.scale = scale;
+
+        let text = toml::to_string(&config).map_err(|source| ConfigError::Serialization {
+            path: path.display().to_string(),
+            source,
+        })?;
+        storage::write(&path.to_string_lossy(), text.as_bytes()).map_err(|source| {
+            ConfigError::Write {
+                path: path.display().to_string(),
+                source,
+            }
+        })
+    }
+
+    /// The first setting that the game can't run with, if there is one.
+    fn invalid_setting(&self) -> Option<&'static str> {
+        if self.display.fps_cap.is_nan() || self.display.fps_cap <= 0.0 {
+            Some("display.fps_cap")
+        } else if self.gameplay.starting_hp <= 0 {
+            Some("gameplay.starting_hp")
+        } else if self.gameplay.spawn_die <= 0 {
+            Some("gameplay.spawn_die")
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_config_matches_defaults() {
+        let example = Config::load_from(Path::new("config.example.toml")).unwrap();
+        let mut defaults = Config::default();
+        // The example leaves it off, even in builds where it defaults to on
+        defaults.display.debug_map_view = false;
+        assert_eq!(example, defaults);
+    }
+
+    #[test]
+    fn window_settings_are_saved() {
+        let path = std::env::temp_dir().join("roguelike_window_config.toml");
+        std::fs::write(&path, "[gameplay]\nspawn_die = 3\n").unwrap();
+        Config::save_window_settings(&path, Font::Vga8x16, 2).unwrap();
+        let config = Config::load_from(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.display.font, Font::Vga8x16);
+        assert_eq!(config.display.scale, 2);
+        assert_eq!(config.gameplay.spawn_die, 3);
+    }
+
+    #[test]
+    fn settings_below_one_are_rejected() {
+        let path = std::env::temp_dir().join("roguelike_invalid_config.toml");
+        std::fs::write(&path, "[gameplay]\nspawn_die = 0\n").unwrap();
+        let result = Config::load_from(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                setting: "gameplay.spawn_die",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+//! Crimes against the town. Shoplifting from a [`crate::Vendor`] or attacking a
+//! [`Guard`] gets raised as a [`GameEvent::CrimeCommitted`], which costs the player
+//! some [`Reputation`] and sends the whole town guard after them.
+
+use rltk::{Point, RandomNumberGenerator, RGB};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::game_events::GameEventListener;
+use crate::l10n::tr;
+use crate::{GameEvent, GameLog, Guard, LastSeenPlayer, Monster, Renderable, Viewshed};
+
+/// How many spots guards search around the scene of a crime before giving up.
+const GUARD_SEARCH_TURNS: i32 = 10;
+
+/// Something the player can do that the town won't stand for.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Crime {
+    /// Taking a vendor's stock without paying for it.
+    Theft,
+    /// Attacking one of the townsfolk.
+    Assault,
+}
+
+impl Crime {
+    /// How much [`Reputation`] committing the crime costs.
+    pub const fn reputation_cost(self) -> i32 {
+        match self {
+            Self::Theft => 10,
+            Self::Assault => 25,
+        }
+    }
+}
+
+/// What the factions of the dungeon think of the player. Anything below zero, and
+/// they won't deal with them.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Reputation {
+    pub town: i32,
+}
+
+impl Reputation {
+    /// Whether the townsfolk refuse to trade with the player.
+    pub const fn is_wanted_in_town(&self) -> bool {
+        self.town < 0
+    }
+}
+
+/// Whether anyone notices a crime happening at `scene`. Guards always notice
+/// anything they can see, and the victim usually does too.
+pub fn is_witnessed(ecs: &World, scene: Point) -> bool {
+    let guards = ecs.read_storage::<Guard>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let seen_by_guard = (&guards, &viewsheds)
+        .join()
+        .any(|(_, viewshed)| viewshed.visible_tiles.contains(&scene));
+
+    seen_by_guard
+        || ecs
+            .write_resource::<RandomNumberGenerator>()
+            .roll_dice(1, 3)
+            > 1
+}
+
+/// Punishes the player for every crime they're caught committing.
+pub struct CrimeListener;
+
+impl GameEventListener for CrimeListener {
+    fn on_event(&mut self, ecs: &World, event: &GameEvent) {
+        let GameEvent::CrimeCommitted { crime, scene, .. } = event else {
+            return;
+        };
+
+        ecs.write_resource::<Reputation>().town -= crime.reputation_cost();
+        alert_guards(ecs, *scene);
+    }
+}
+
+/// Turn every guard on the level hostile, and send them all to converge on `scene`.
+fn alert_guards(ecs: &World, scene: Point) {
+    let entities = ecs.entities();
+    let guards = ecs.read_storage::<Guard>();
+    let mut monsters = ecs.write_storage::<Monster>();
+    let mut last_seen = ecs.write_storage::<LastSeenPlayer>();
+    let mut renderables = ecs.write_storage::<Renderable>();
+
+    let mut newly_hostile = false;
+    for (guard, _) in (&entities, &guards).join() {
+        last_seen
+            .insert(
+                guard,
+                LastSeenPlayer {
+                    x: scene.x,
+                    y: scene.y,
+                    search_turns: GUARD_SEARCH_TURNS,
+                },
+            )
+            .expect("Unable to send guard to the scene of a crime");
+
+        if !monsters.contains(guard) {
+            monsters
+                .insert(guard, Monster)
+                .expect("Unable to turn guard hostile");
+            if let Some(renderable) = renderables.get_mut(guard) {
+                renderable.fg = RGB::named(rltk::RED);
+            }
+            newly_hostile = true;
+        }
+    }
+
+    if newly_hostile {
+        ecs.write_resource::<GameLog>()
+            .entry()
+            .message(&tr("guards-alerted").colored_arg(
+                "shout",
+                tr("guards-shout"),
+                RGB::named(rltk::RED),
+            ))
+            .log();
+    }
+}
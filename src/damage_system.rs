@@ -1,27 +1,12 @@
-use rltk::console;
 use specs::prelude::*;
 
-use crate::{CombatStats, GameLog, Name, Player, SufferDamage};
+use crate::l10n::{self, tr};
+use crate::{
+    spawner, CombatStats, Equipped, GameEvent, GameEvents, GameLog, InBackpack, LastAttacker,
+    Leader, Map, Name, Player, PlayerEntity, Position, Purse, RunState,
+};
 
-/// Applies damage to entities that are schedules to [`SufferDamage`] this ECS tick.
-pub struct DamageSystem;
-
-impl<'a> System<'a> for DamageSystem {
-    type SystemData = (
-        WriteStorage<'a, CombatStats>,
-        WriteStorage<'a, SufferDamage>,
-    );
-
-    fn run(&mut self, (mut stats, mut damage): Self::SystemData) {
-        for (mut stats, damage) in (&mut stats, &damage).join() {
-            stats.hp -= damage.amount.iter().sum::<i32>();
-        }
-
-        damage.clear();
-    }
-}
-
-/// Delete any entities with 0 HP.
+/// Delete any entities with 0 HP. If the player died, it's game over.
 pub fn delete_the_dead(ecs: &mut World) {
     let mut dead: Vec<Entity> = Vec::new();
 
@@ -29,22 +14,57 @@ pub fn delete_the_dead(ecs: &mut World) {
         let combat_stats = ecs.read_storage::<CombatStats>();
         let players = ecs.read_storage::<Player>();
         let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let leaders = ecs.read_storage::<Leader>();
+        let last_attackers = ecs.read_storage::<LastAttacker>();
         let entities = ecs.entities();
+        let map = ecs.fetch::<Map>();
+        let player_entity = **ecs.fetch::<PlayerEntity>();
         let mut log = ecs.write_resource::<GameLog>();
+        let mut events = ecs.write_resource::<GameEvents>();
+        let already_over = *ecs.fetch::<RunState>() == RunState::GameOver;
 
         for (entity, stats) in (&entities, &combat_stats).join() {
             if stats.hp < 1 {
                 let player = players.get(entity);
                 match player {
                     // don't delete the player entity; trigger a game over instead
-                    Some(_) => console::log("You are dead"),
+                    Some(_) if !already_over => {
+                        *ecs.write_resource::<RunState>() = RunState::GameOver;
+                        events.push(GameEvent::PlayerKilled);
+                    }
+                    Some(_) => {}
 
                     // delete the dead entity
                     None => {
                         let victim_name = names.get(entity);
-                        if let Some(victim_name) = victim_name {
-                            log.log(format!("{victim_name} is dead"));
+                        // Only tell the player about deaths they saw or caused
+                        let seen = positions
+                            .get(entity)
+                            .is_some_and(|pos| map.visible_tiles[map.xy_idx(pos.x, pos.y)])
+                            || last_attackers
+                                .get(entity)
+                                .is_some_and(|hit| hit.attacker == player_entity);
+                        if let Some(victim_name) = victim_name.filter(|_| seen) {
+                            let msg = tr("monster-dies").colored_arg(
+                                "monster",
+                                l10n::name(&victim_name.name),
+                                rltk::RGB::named(rltk::YELLOW),
+                            );
+                            log.entry()
+                                .colored_message(&msg, rltk::RGB::named(rltk::RED))
+                                .log();
                         }
+                        events.push(GameEvent::MonsterKilled {
+                            entity,
+                            name: victim_name.map(|n| n.to_string()).unwrap_or_default(),
+                            xp: stats.max_hp,
+                            pos: positions
+                                .get(entity)
+                                .map_or(rltk::Point::zero(), |pos| rltk::Point::new(pos.x, pos.y)),
+                            leader: leaders.contains(entity),
+                            killer: last_attackers.get(entity).map(|hit| hit.attacker),
+                        });
                         dead.push(entity)
                     }
                 }
@@ -52,8 +72,97 @@ pub fn delete_the_dead(ecs: &mut World) {
         }
     }
 
+    drop_carried_items(ecs, &dead);
+    drop_gold(ecs, &dead);
+
     for victim in dead {
         ecs.delete_entity(victim)
             .expect("Unable to delete dead (0 HP) entity");
     }
 }
+
+/// Leave a pile of whatever gold the `dead` had in their [`Purse`]s where they died.
+fn drop_gold(ecs: &mut World, dead: &[Entity]) {
+    let piles: Vec<(Position, i32)> = {
+        let positions = ecs.read_storage::<Position>();
+        let purses = ecs.read_storage::<Purse>();
+        dead.iter()
+            .filter_map(|victim| Some((*positions.get(*victim)?, purses.get(*victim)?.gold)))
+            .filter(|(_, gold)| *gold > 0)
+            .collect()
+    };
+
+    for (pos, gold) in piles {
+        spawner::spawn_gold(ecs, pos.x, pos.y, gold);
+    }
+}
+
+/// Scatter everything the `dead` were carrying onto the floor where they died,
+/// so that killing a monster gets back anything it picked up.
+fn drop_carried_items(ecs: &mut World, dead: &[Entity]) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut backpack = ecs.write_storage::<InBackpack>();
+    let mut equipped = ecs.write_storage::<Equipped>();
+
+    let carried: Vec<(Entity, Position)> = (&entities, backpack.maybe(), equipped.maybe())
+        .join()
+        .filter_map(|(item, in_pack, worn)| {
+            let owner = in_pack.map(|p| p.owner).or(worn.map(|e| e.owner))?;
+            if !dead.contains(&owner) {
+                return None;
+            }
+            positions.get(owner).map(|pos| (item, *pos))
+        })
+        .collect();
+
+    for (item, pos) in carried {
+        backpack.remove(item);
+        equipped.remove(item);
+        positions
+            .insert(item, pos)
+            .expect("Unable to drop dead entity's item");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{self, EffectQueue, EffectType, Targets};
+    use crate::{game_events, PlayerEntity, RunStats, State};
+
+    /// Spawn a goblin and have `attacker` finish it off.
+    fn kill_goblin(gs: &mut State, attacker: Option<Entity>) {
+        let goblin = spawner::spawn_named(&mut gs.ecs, 1, 1, "Goblin").unwrap();
+        gs.ecs.write_resource::<EffectQueue>().add_effect(
+            EffectType::Damage {
+                amount: 1000,
+                attacker,
+            },
+            Targets::Single { target: goblin },
+        );
+        effects::run_effects_queue(&mut gs.ecs);
+        delete_the_dead(&mut gs.ecs);
+        game_events::dispatch_game_events(&mut gs.ecs);
+        gs.ecs.maintain();
+    }
+
+    #[test]
+    fn only_the_players_kills_count() {
+        let mut gs = State::new().unwrap();
+        gs.start_new_run();
+        // Only count kills, so that the test doesn't unlock achievements on disk
+        let mut event_bus = game_events::GameEventBus::default();
+        event_bus.subscribe(game_events::RunStatsListener);
+        gs.ecs.insert(event_bus);
+        let player = **gs.ecs.fetch::<PlayerEntity>();
+        let rival = spawner::spawn_named(&mut gs.ecs, 2, 2, "Orc").unwrap();
+
+        kill_goblin(&mut gs, None);
+        kill_goblin(&mut gs, Some(rival));
+        assert_eq!(gs.ecs.fetch::<RunStats>().monsters_killed, 0);
+
+        kill_goblin(&mut gs, Some(player));
+        assert_eq!(gs.ecs.fetch::<RunStats>().monsters_killed, 1);
+    }
+}
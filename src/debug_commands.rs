@@ -0,0 +1,161 @@
+//! Commands for the debug console, which is opened with the backtick key in builds
+//! with the `debug-console` feature. They're for poking at the game while working
+//! on it, and skip all of the usual rules.
+
+use specs::prelude::*;
+
+use crate::{spawner, CombatStats, GameLog, Map, Monster, PlayerEntity, RunState};
+
+/// Every command, for the `help` command to list.
+const HELP: &str = "Commands: spawn <name> <x> <y>, heal, descend, reveal, kill-all, help";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DebugCommandError {
+    #[error("Unknown command `{0}`. Try `help`.")]
+    UnknownCommand(String),
+
+    #[error("Usage: {0}")]
+    Usage(&'static str),
+
+    #[error("({x}, {y}) is off the map")]
+    OffTheMap { x: i32, y: i32 },
+
+    #[error("There's nothing called `{0}` to spawn")]
+    UnknownEntity(String),
+}
+
+/// A command typed into the debug console.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum DebugCommand {
+    /// Spawn the entity called `name` at (`x`, `y`).
+    Spawn { name: String, x: i32, y: i32 },
+    /// Restore the player to full health.
+    Heal,
+    /// Go straight down to the next level, stairs or no stairs.
+    Descend,
+    /// Reveal the whole level.
+    Reveal,
+    /// Kill every monster on the level.
+    KillAll,
+    /// List the commands.
+    Help,
+}
+
+impl std::str::FromStr for DebugCommand {
+    type Err = DebugCommandError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return Ok(Self::Help);
+        };
+
+        match command.to_lowercase().as_str() {
+            "spawn" => {
+                const USAGE: &str = "spawn <name> <x> <y>";
+                let [name @ .., x, y] = args else {
+                    return Err(DebugCommandError::Usage(USAGE));
+                };
+                let (Ok(x), Ok(y)) = (x.parse(), y.parse()) else {
+                    return Err(DebugCommandError::Usage(USAGE));
+                };
+                if name.is_empty() {
+                    return Err(DebugCommandError::Usage(USAGE));
+                }
+
+                // Names are title case, e.g. `kobold hoarder` spawns a "Kobold Hoarder"
+                let name = name
+                    .iter()
+                    .map(|word| {
+                        let mut chars = word.chars();
+                        chars.next().map_or_else(String::new, |first| {
+                            first
+                                .to_uppercase()
+                                .chain(chars.flat_map(char::to_lowercase))
+                                .collect()
+                        })
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                Ok(Self::Spawn { name, x, y })
+            }
+            "heal" => Ok(Self::Heal),
+            "descend" => Ok(Self::Descend),
+            "reveal" => Ok(Self::Reveal),
+            "kill-all" => Ok(Self::KillAll),
+            "help" => Ok(Self::Help),
+            _ => Err(DebugCommandError::UnknownCommand(command.to_string())),
+        }
+    }
+}
+
+/// Parse and run `text` as a debug command, logging what happened. Returns the
+/// state the game should go to next.
+pub fn run_command(ecs: &mut World, text: &str) -> RunState {
+    let result = text
+        .parse::<DebugCommand>()
+        .and_then(|command| run(ecs, command));
+
+    match result {
+        Ok(runstate) => runstate,
+        Err(e) => {
+            ecs.fetch_mut::<GameLog>().log(format!("Debug: {e}"));
+            RunState::AwaitingInput
+        }
+    }
+}
+
+fn run(ecs: &mut World, command: DebugCommand) -> Result<RunState, DebugCommandError> {
+    let player = **ecs.fetch::<PlayerEntity>();
+
+    let message = match command {
+        DebugCommand::Spawn { name, x, y } => {
+            let on_map = {
+                let map = ecs.fetch::<Map>();
+                x >= 0 && x < map.width && y >= 0 && y < map.height
+            };
+            if !on_map {
+                return Err(DebugCommandError::OffTheMap { x, y });
+            }
+            spawner::spawn_named(ecs, x, y, &name)
+                .ok_or_else(|| DebugCommandError::UnknownEntity(name.clone()))?;
+            format!("Spawned a {name} at ({x}, {y}).")
+        }
+
+        DebugCommand::Heal => {
+            if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(player) {
+                stats.hp = stats.max_hp;
+            }
+            "Healed the player.".to_string()
+        }
+
+        DebugCommand::Descend => match ecs.fetch::<Map>().level_id().below() {
+            Some(destination) => {
+                ecs.fetch_mut::<GameLog>().log("Debug: Descending.");
+                return Ok(RunState::ChangeLevel { destination });
+            }
+            None => "There's no going any deeper.".to_string(),
+        },
+
+        DebugCommand::Reveal => {
+            ecs.fetch_mut::<Map>().revealed_tiles.fill(true);
+            "Revealed the level.".to_string()
+        }
+
+        DebugCommand::KillAll => {
+            let monsters = ecs.read_storage::<Monster>();
+            let mut combat_stats = ecs.write_storage::<CombatStats>();
+            let mut killed = 0;
+            for (_, stats) in (&monsters, &mut combat_stats).join() {
+                stats.hp = 0;
+                killed += 1;
+            }
+            format!("Killed {killed} monsters.")
+        }
+
+        DebugCommand::Help => HELP.to_string(),
+    };
+
+    ecs.fetch_mut::<GameLog>().log(format!("Debug: {message}"));
+    Ok(RunState::AwaitingInput)
+}
@@ -0,0 +1,70 @@
+use specs::prelude::*;
+
+use crate::effects::{EffectQueue, EffectType, Targets};
+use crate::l10n::tr;
+use crate::{CombatStats, GameLog, Map, PlayerEntity, Position, RunState, Swimmer, TileType};
+
+/// How much damage a turn spent in deep water does to anything that can't swim.
+const DROWNING_DAMAGE: i32 = 3;
+
+/// A system that hurts everything that's in [`TileType::DeepWater`] without being
+/// a [`Swimmer`], once per turn.
+pub struct DrowningSystem;
+
+impl<'a> System<'a> for DrowningSystem {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, PlayerEntity>,
+        ReadExpect<'a, RunState>,
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Swimmer>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            map,
+            player_entity,
+            runstate,
+            entities,
+            positions,
+            combat_stats,
+            swimmers,
+            mut effect_queue,
+            mut log,
+        ): Self::SystemData,
+    ) {
+        // The player can get more than one go a turn, but the monsters only get one,
+        // so drown things on theirs
+        if *runstate != RunState::MonsterTurn {
+            return;
+        }
+
+        for (entity, pos, _stats, _) in (&entities, &positions, &combat_stats, !&swimmers).join() {
+            if map.tiles[map.xy_idx(pos.x, pos.y)] != TileType::DeepWater {
+                continue;
+            }
+
+            effect_queue.add_effect(
+                EffectType::Damage {
+                    amount: DROWNING_DAMAGE,
+                    attacker: None,
+                },
+                Targets::Single { target: entity },
+            );
+            if entity == **player_entity {
+                log.entry()
+                    .colored_message(
+                        &tr("drowning")
+                            .arg("damage", tr("hit-points").arg("amount", DROWNING_DAMAGE)),
+                        rltk::RGB::named(rltk::RED),
+                    )
+                    .log();
+            }
+        }
+    }
+}
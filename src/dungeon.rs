@@ -0,0 +1,266 @@
+//! Keeps track of every level the player has visited, so that they can go back
+//! to them later.
+//!
+//! The dungeon is split into [`Branch`]es. The main one runs from the town all the
+//! way down to the Amulet, and side branches open off of it at certain depths.
+//! Levels are joined by [`LevelExit`]s, which know which [`LevelId`] they lead to.
+//!
+//! When the player leaves a level, its [`Map`] is stored in the
+//! [`MasterDungeonMap`] and everything on it is "frozen" by swapping its
+//! [`Position`] for an [`OtherLevelPosition`]. Coming back thaws it all out again.
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::{
+    spawner, LevelExit, Map, OtherLevelPosition, PlayerEntity, PlayerPos, Portal, Position,
+    TileType,
+};
+
+/// A part of the dungeon, with its own run of levels.
+#[derive(
+    PartialEq, Eq, Hash, Copy, Clone, Debug, Default, Serialize, Deserialize, strum::Display,
+)]
+pub enum Branch {
+    /// The main dungeon, from the town down to the Amulet.
+    #[default]
+    Main,
+    /// A short run of caves that opens off of the main dungeon.
+    Caves,
+}
+
+impl Branch {
+    /// The level that the branch opens off of, or `None` for the main dungeon.
+    pub const fn entrance(self) -> Option<LevelId> {
+        match self {
+            Self::Main => None,
+            Self::Caves => Some(LevelId::main(2)),
+        }
+    }
+
+    /// How deep the branch goes.
+    pub const fn bottom(self) -> i32 {
+        match self {
+            Self::Main => spawner::FINAL_DEPTH,
+            Self::Caves => 4,
+        }
+    }
+
+    /// The side branches that open off of the level `level`.
+    pub fn opening_off(level: LevelId) -> impl Iterator<Item = Self> {
+        [Self::Caves]
+            .into_iter()
+            .filter(move |branch| branch.entrance() == Some(level))
+    }
+}
+
+/// Where a level is in the dungeon.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct LevelId {
+    pub branch: Branch,
+    pub depth: i32,
+}
+
+impl LevelId {
+    /// The first level, which doubles as the town.
+    pub const TOWN: Self = Self::main(1);
+
+    /// The level at `depth` in the main dungeon.
+    pub const fn main(depth: i32) -> Self {
+        Self {
+            branch: Branch::Main,
+            depth,
+        }
+    }
+
+    /// The level that the stairs up lead to: the one above in the same branch, or
+    /// the level that the branch opens off of. `None` for the town.
+    pub fn above(self) -> Option<Self> {
+        match self.branch.entrance() {
+            Some(entrance) if self.depth == entrance.depth + 1 => Some(entrance),
+            _ if self.depth > 1 => Some(Self {
+                depth: self.depth - 1,
+                ..self
+            }),
+            _ => None,
+        }
+    }
+
+    /// The level below this one in the same branch, if the branch goes that deep.
+    pub fn below(self) -> Option<Self> {
+        (self.depth < self.branch.bottom()).then_some(Self {
+            depth: self.depth + 1,
+            ..self
+        })
+    }
+}
+
+/// Every level that the player has visited, other than the current one.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MasterDungeonMap {
+    /// Levels in the main dungeon, keyed by depth.
+    maps: FxHashMap<i32, Map>,
+    /// Levels in side branches, keyed by depth.
+    #[serde(default)]
+    branch_maps: FxHashMap<Branch, FxHashMap<i32, Map>>,
+}
+
+impl MasterDungeonMap {
+    /// Remember `map` so it can be restored when the player comes back to it.
+    pub fn store_map(&mut self, map: &Map) {
+        let maps = match map.branch {
+            Branch::Main => &mut self.maps,
+            branch => self.branch_maps.entry(branch).or_default(),
+        };
+        maps.insert(map.depth, map.clone());
+    }
+
+    /// Get a copy of the map of `level`, if the player has been there before.
+    pub fn get_map(&self, level: LevelId) -> Option<Map> {
+        let maps = match level.branch {
+            Branch::Main => Some(&self.maps),
+            branch => self.branch_maps.get(&branch),
+        };
+        maps?.get(&level.depth).map(|map| {
+            let mut map = map.clone();
+            // The tile content index is stale, and gets rebuilt every tick anyways.
+            map.tile_content = vec![Vec::new(); map.tiles.len()];
+            map
+        })
+    }
+}
+
+/// Freeze every entity on the current level except the player, so that they stay
+/// put while the player is elsewhere.
+pub fn freeze_level_entities(ecs: &mut World) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut other_level_positions = ecs.write_storage::<OtherLevelPosition>();
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let LevelId { branch, depth } = ecs.fetch::<Map>().level_id();
+
+    let mut frozen = Vec::new();
+    for (entity, pos) in (&entities, &positions).join() {
+        if entity != player_entity {
+            other_level_positions
+                .insert(
+                    entity,
+                    OtherLevelPosition {
+                        x: pos.x,
+                        y: pos.y,
+                        depth,
+                        branch,
+                    },
+                )
+                .expect("Unable to freeze entity's position");
+            frozen.push(entity);
+        }
+    }
+
+    for entity in frozen {
+        positions.remove(entity);
+    }
+}
+
+/// Thaw out every entity that was frozen on `level`.
+pub fn thaw_level_entities(ecs: &mut World, level: LevelId) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut other_level_positions = ecs.write_storage::<OtherLevelPosition>();
+
+    let mut thawed = Vec::new();
+    for (entity, pos) in (&entities, &other_level_positions).join() {
+        if pos.depth == level.depth && pos.branch == level.branch {
+            positions
+                .insert(entity, Position::from((pos.x, pos.y)))
+                .expect("Unable to thaw entity's position");
+            thawed.push(entity);
+        }
+    }
+
+    for entity in thawed {
+        other_level_positions.remove(entity);
+    }
+}
+
+/// Where the player should arrive on the current level, coming from `from`: the
+/// exit that leads back there, if there is one.
+pub fn arrival_point(ecs: &World, from: LevelId) -> Option<Position> {
+    let exits = ecs.read_storage::<LevelExit>();
+    let positions = ecs.read_storage::<Position>();
+    (&exits, &positions)
+        .join()
+        .find(|(exit, _)| exit.destination == from)
+        .map(|(_, pos)| *pos)
+}
+
+/// Whether the player is standing on a [`Portal`].
+pub fn standing_on_portal(ecs: &World) -> bool {
+    let player_pos = ecs.fetch::<PlayerPos>();
+    let portals = ecs.read_storage::<Portal>();
+    let positions = ecs.read_storage::<Position>();
+    (&portals, &positions)
+        .join()
+        .any(|(_, pos)| pos.x == player_pos.x && pos.y == player_pos.y)
+}
+
+/// Where the player comes out of a [`Portal`] on the current level after stepping
+/// through its other side on `from`, if there is one.
+pub fn portal_arrival(ecs: &World, from: LevelId) -> Option<Position> {
+    let portals = ecs.read_storage::<Portal>();
+    let exits = ecs.read_storage::<LevelExit>();
+    let positions = ecs.read_storage::<Position>();
+    (&portals, &exits, &positions)
+        .join()
+        .find(|(_, exit, _)| exit.destination == from)
+        .map(|(_, _, pos)| *pos)
+}
+
+/// Open a [`Portal`] where the player is standing, leading to `destination`.
+/// Once the player is there, [`open_return_portal()`] opens the way back.
+pub fn open_portal(ecs: &mut World, destination: LevelId) {
+    let player_pos = **ecs.fetch::<PlayerPos>();
+    spawner::spawn_portal(
+        ecs,
+        Position::from((player_pos.x, player_pos.y)),
+        destination,
+    );
+}
+
+/// Open a [`Portal`] back to `destination` right next to the player, or under
+/// them if there's no room.
+pub fn open_return_portal(ecs: &mut World, destination: LevelId) {
+    let player_pos = **ecs.fetch::<PlayerPos>();
+    let spot = {
+        let map = ecs.fetch::<Map>();
+        let positions = ecs.read_storage::<Position>();
+        let occupied = |x: i32, y: i32| positions.join().any(|pos| pos.x == x && pos.y == y);
+        [
+            (-1, 0),
+            (1, 0),
+            (0, -1),
+            (0, 1),
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+            (1, 1),
+        ]
+        .into_iter()
+        .map(|(dx, dy)| (player_pos.x + dx, player_pos.y + dy))
+        .filter(|&(x, y)| x > 0 && x < map.width - 1 && y > 0 && y < map.height - 1)
+        .find(|&(x, y)| map.tiles[map.xy_idx(x, y)] == TileType::Floor && !occupied(x, y))
+        .unwrap_or((player_pos.x, player_pos.y))
+    };
+    spawner::spawn_portal(ecs, Position::from(spot), destination);
+}
+
+/// Close every [`Portal`], on every level.
+pub fn close_portals(ecs: &mut World) {
+    let portals: Vec<Entity> = (&ecs.entities(), &ecs.read_storage::<Portal>())
+        .join()
+        .map(|(entity, _)| entity)
+        .collect();
+    ecs.delete_entities(&portals)
+        .expect("Unable to close portals");
+}
@@ -0,0 +1,356 @@
+//! A queue of effects waiting to be applied to the world.
+//!
+//! Systems decide _what_ should happen (a sword hits, a potion is drunk, a trap
+//! goes off) and [`EffectQueue::add_effect()`] it, and [`run_effects_queue()`]
+//! later works out what that means for everything it touches. Effects can queue
+//! up more effects in turn, so a trap can set off damage, a status effect, and a
+//! puff of particles without knowing how any of them work.
+
+use std::collections::VecDeque;
+
+use rltk::RGB;
+use specs::prelude::*;
+
+use crate::l10n::{self, tr};
+use crate::screen_effects::ScreenEffects;
+use crate::{
+    CombatStats, FloatingText, GameEvent, GameEvents, GameLog, Hidden, InflictsDamage,
+    InflictsStatusEffect, LastAttacker, Map, Name, ParticleLifetime, PlayerEntity, Position,
+    Renderable, SingleActivation, StatusEffect, StatusEffects,
+};
+
+/// A single hit that takes at least this fraction of the player's max hp shakes
+/// the screen.
+const HEAVY_HIT_FRACTION: i32 = 5;
+
+/// How long particles hang around on screen, in milliseconds.
+pub const PARTICLE_LIFETIME_MS: f32 = 200.0;
+
+/// How long floating damage numbers hang around on screen, in milliseconds.
+const DAMAGE_NUMBER_LIFETIME_MS: f32 = 700.0;
+
+/// Something that can happen to an entity or a tile.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EffectType {
+    /// Knock `amount` hp off of the target. `attacker` is whoever's to blame, if
+    /// anybody is.
+    Damage {
+        amount: i32,
+        attacker: Option<Entity>,
+    },
+    /// Restore up to `amount` hp to the target.
+    Healing { amount: i32 },
+    /// Inflict a lingering status `effect` on the target for `turns` turns.
+    Status { effect: StatusEffect, turns: i32 },
+    /// Briefly flash a glyph over the target.
+    Particle {
+        glyph: rltk::FontCharType,
+        fg: RGB,
+        lifetime_ms: f32,
+    },
+    /// Float some text up from the target, like the damage it just took.
+    FloatingText {
+        text: String,
+        fg: RGB,
+        lifetime_ms: f32,
+    },
+    /// The target stepped on the [`crate::EntryTrigger`] `trigger`, setting it off.
+    TriggerFire { trigger: Entity },
+}
+
+/// What an effect applies to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Targets {
+    Single {
+        target: Entity,
+    },
+    /// Everything standing on any of these map tile indexes.
+    Tiles {
+        tiles: Vec<usize>,
+    },
+}
+
+/// An effect waiting in the [`EffectQueue`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectSpawner {
+    pub effect_type: EffectType,
+    pub targets: Targets,
+}
+
+/// Effects that have been queued up, but not yet applied.
+#[derive(Debug, Default)]
+pub struct EffectQueue {
+    pending: VecDeque<EffectSpawner>,
+}
+
+impl EffectQueue {
+    /// Queue up `effect_type` to be applied to `targets`.
+    pub fn add_effect(&mut self, effect_type: EffectType, targets: Targets) {
+        self.pending.push_back(EffectSpawner {
+            effect_type,
+            targets,
+        });
+    }
+}
+
+/// Apply every queued effect, in the order they were queued. This includes any
+/// effects that get queued up along the way.
+pub fn run_effects_queue(ecs: &mut World) {
+    loop {
+        let effect = ecs.write_resource::<EffectQueue>().pending.pop_front();
+        let Some(effect) = effect else {
+            break;
+        };
+        target_applicator(ecs, &effect);
+    }
+}
+
+fn target_applicator(ecs: &mut World, effect: &EffectSpawner) {
+    match &effect.targets {
+        Targets::Single { target } => affect_entity(ecs, &effect.effect_type, *target),
+        Targets::Tiles { tiles } => {
+            for tile_idx in tiles.iter().copied() {
+                affect_tile(ecs, &effect.effect_type, tile_idx);
+            }
+        }
+    }
+}
+
+fn affect_tile(ecs: &mut World, effect_type: &EffectType, tile_idx: usize) {
+    if let EffectType::Particle { .. } | EffectType::FloatingText { .. } = effect_type {
+        let pos = Position::from(ecs.fetch::<Map>().idx_xy(tile_idx));
+        spawn_particle(ecs, effect_type, pos);
+        spawn_floating_text(ecs, effect_type, pos);
+        return;
+    }
+
+    let content = ecs.fetch::<Map>().tile_content[tile_idx].clone();
+    for entity in content {
+        affect_entity(ecs, effect_type, entity);
+    }
+}
+
+fn affect_entity(ecs: &mut World, effect_type: &EffectType, target: Entity) {
+    match effect_type {
+        EffectType::Damage { amount, attacker } => inflict_damage(ecs, target, *amount, *attacker),
+        EffectType::Healing { amount } => heal_damage(ecs, target, *amount),
+        EffectType::Status { effect, turns } => inflict_status(ecs, target, *effect, *turns),
+        EffectType::Particle { .. } => {
+            let pos = ecs.read_storage::<Position>().get(target).copied();
+            if let Some(pos) = pos {
+                spawn_particle(ecs, effect_type, pos);
+            }
+        }
+        EffectType::FloatingText { .. } => {
+            let pos = ecs.read_storage::<Position>().get(target).copied();
+            if let Some(pos) = pos {
+                spawn_floating_text(ecs, effect_type, pos);
+            }
+        }
+        EffectType::TriggerFire { trigger } => fire_trigger(ecs, *trigger, target),
+    }
+}
+
+fn inflict_damage(ecs: &mut World, target: Entity, amount: i32, attacker: Option<Entity>) {
+    // Only living things can be hurt
+    let heavy_hit = if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(target) {
+        stats.hp -= amount;
+        amount * HEAVY_HIT_FRACTION >= stats.max_hp
+    } else {
+        return;
+    };
+
+    // Whoever dealt the last blow gets the credit for it, and nobody does if
+    // the target drowned, starved, or walked into a trap
+    let mut last_attackers = ecs.write_storage::<LastAttacker>();
+    match attacker {
+        Some(attacker) => {
+            last_attackers
+                .insert(target, LastAttacker { attacker })
+                .expect("Unable to record last attacker");
+        }
+        None => {
+            last_attackers.remove(target);
+        }
+    }
+    drop(last_attackers);
+
+    if heavy_hit && *ecs.fetch::<PlayerEntity>() == target {
+        ecs.fetch_mut::<ScreenEffects>().heavy_hit();
+    }
+
+    let mut queue = ecs.write_resource::<EffectQueue>();
+    queue.add_effect(
+        EffectType::Particle {
+            glyph: rltk::to_cp437('‼'),
+            fg: RGB::named(rltk::ORANGE),
+            lifetime_ms: PARTICLE_LIFETIME_MS,
+        },
+        Targets::Single { target },
+    );
+    queue.add_effect(
+        EffectType::FloatingText {
+            text: amount.to_string(),
+            fg: RGB::named(rltk::RED),
+            lifetime_ms: DAMAGE_NUMBER_LIFETIME_MS,
+        },
+        Targets::Single { target },
+    );
+}
+
+fn heal_damage(ecs: &mut World, target: Entity, amount: i32) {
+    if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(target) {
+        stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+    } else {
+        return;
+    }
+
+    ecs.write_resource::<EffectQueue>().add_effect(
+        EffectType::Particle {
+            glyph: rltk::to_cp437('♥'),
+            fg: RGB::named(rltk::GREEN),
+            lifetime_ms: PARTICLE_LIFETIME_MS,
+        },
+        Targets::Single { target },
+    );
+}
+
+fn inflict_status(ecs: &mut World, target: Entity, effect: StatusEffect, turns: i32) {
+    // Only living things can be affected
+    if !ecs.read_storage::<CombatStats>().contains(target) {
+        return;
+    }
+    StatusEffects::inflict(
+        &mut ecs.write_storage::<StatusEffects>(),
+        target,
+        effect,
+        turns,
+    );
+
+    ecs.write_resource::<EffectQueue>().add_effect(
+        EffectType::Particle {
+            glyph: rltk::to_cp437('?'),
+            fg: RGB::named(rltk::MAGENTA),
+            lifetime_ms: PARTICLE_LIFETIME_MS,
+        },
+        Targets::Single { target },
+    );
+}
+
+/// Set off `trigger` on `target`, queueing up whatever the trap does.
+fn fire_trigger(ecs: &mut World, trigger: Entity, target: Entity) {
+    if let Some(name) = ecs.read_storage::<Name>().get(trigger) {
+        let msg = tr("trap-triggers").colored_arg(
+            "trap",
+            l10n::name(&name.name),
+            RGB::named(rltk::ORANGE),
+        );
+        ecs.write_resource::<GameLog>().entry().message(&msg).log();
+    }
+
+    // Traps aren't much of a secret once they've gone off
+    ecs.write_storage::<Hidden>().remove(trigger);
+
+    let damage = ecs
+        .read_storage::<InflictsDamage>()
+        .get(trigger)
+        .map(|damage| damage.damage);
+    let status = ecs
+        .read_storage::<InflictsStatusEffect>()
+        .get(trigger)
+        .copied();
+    {
+        let mut queue = ecs.write_resource::<EffectQueue>();
+        if let Some(amount) = damage {
+            queue.add_effect(
+                EffectType::Damage {
+                    amount,
+                    attacker: None,
+                },
+                Targets::Single { target },
+            );
+            ecs.write_resource::<GameEvents>()
+                .push(GameEvent::TrapTriggered {
+                    trap: trigger,
+                    target,
+                    amount,
+                });
+        }
+        if let Some(inflicts) = status {
+            queue.add_effect(
+                EffectType::Status {
+                    effect: inflicts.effect,
+                    turns: inflicts.turns,
+                },
+                Targets::Single { target },
+            );
+        }
+    }
+
+    if ecs.read_storage::<SingleActivation>().contains(trigger) {
+        ecs.delete_entity(trigger)
+            .expect("Unable to delete used-up trap");
+    }
+}
+
+fn spawn_particle(ecs: &mut World, effect_type: &EffectType, pos: Position) {
+    let EffectType::Particle {
+        glyph,
+        fg,
+        lifetime_ms,
+    } = *effect_type
+    else {
+        return;
+    };
+
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph,
+            fg,
+            bg: RGB::named(rltk::BLACK),
+            render_order: 0,
+        })
+        .with(ParticleLifetime { lifetime_ms })
+        .build();
+}
+
+fn spawn_floating_text(ecs: &mut World, effect_type: &EffectType, pos: Position) {
+    let EffectType::FloatingText {
+        text,
+        fg,
+        lifetime_ms,
+    } = effect_type
+    else {
+        return;
+    };
+
+    ecs.create_entity()
+        .with(pos)
+        .with(FloatingText {
+            text: text.clone(),
+            fg: *fg,
+            duration_ms: *lifetime_ms,
+        })
+        .with(ParticleLifetime {
+            lifetime_ms: *lifetime_ms,
+        })
+        .build();
+}
+
+/// Age every particle by `frame_time_ms`, and delete the ones that have faded away.
+pub fn cull_dead_particles(ecs: &mut World, frame_time_ms: f32) {
+    let dead: Vec<Entity> = {
+        let mut particles = ecs.write_storage::<ParticleLifetime>();
+        (&ecs.entities(), &mut particles)
+            .join()
+            .filter_map(|(entity, particle)| {
+                particle.lifetime_ms -= frame_time_ms;
+                (particle.lifetime_ms < 0.0).then_some(entity)
+            })
+            .collect()
+    };
+
+    ecs.delete_entities(&dead)
+        .expect("Unable to delete faded particles");
+}
@@ -0,0 +1,39 @@
+//! Who fights who. Every [`Faction`] has a [`Reaction`] to every other, and
+//! monsters go after the nearest thing they can see that they'd attack, whether
+//! that's the player or a monster from a rival faction.
+
+use specs::prelude::*;
+
+use crate::{Faction, Reputation};
+
+/// How one [`Faction`] behaves towards another.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Reaction {
+    Ignore,
+    Attack,
+}
+
+/// How members of `faction` react to members of `other`. The town is only
+/// hostile to the player once they're wanted there.
+pub fn reaction(faction: Faction, other: Faction, reputation: &Reputation) -> Reaction {
+    use Faction::*;
+
+    match (faction, other) {
+        (a, b) if a == b => Reaction::Ignore,
+        (Townsfolk, Player) | (Player, Townsfolk) if !reputation.is_wanted_in_town() => {
+            Reaction::Ignore
+        }
+        _ => Reaction::Attack,
+    }
+}
+
+/// The faction that `entity` belongs to. Anything without a [`Faction`] of its
+/// own is from a save made before there were any, when every monster was out for
+/// the player alone.
+pub fn faction_of(entity: Entity, player: Entity, factions: &ReadStorage<Faction>) -> Faction {
+    if entity == player {
+        Faction::Player
+    } else {
+        factions.get(entity).copied().unwrap_or(Faction::Goblinoids)
+    }
+}
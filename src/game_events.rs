@@ -0,0 +1,225 @@
+//! A bus for announcing things that happen during gameplay.
+//!
+//! Gameplay code [`GameEvents::push()`]es a [`GameEvent`] whenever something
+//! interesting happens, and every [`GameEventListener`] subscribed to the
+//! [`GameEventBus`] gets told about it once the turn is over. Bookkeeping like
+//! [`RunStats`] lives in listeners, so new ones can be added without touching
+//! combat or inventory code.
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::crime::Crime;
+use crate::{InBackpack, PlayerEntity, RunState, StatusEffect, WinsGame};
+
+/// Something that happened during gameplay.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    /// `attacker` hit `target` in melee for `amount` damage.
+    MeleeHit {
+        attacker: Entity,
+        target: Entity,
+        amount: i32,
+    },
+    /// `attacker` shot `target` from a distance for `amount` damage.
+    RangedHit {
+        attacker: Entity,
+        target: Entity,
+        amount: i32,
+    },
+    /// `target` set off `trap`, taking `amount` damage.
+    TrapTriggered {
+        trap: Entity,
+        target: Entity,
+        amount: i32,
+    },
+    /// `target` took `amount` damage from a lingering status `effect`.
+    StatusDamage {
+        target: Entity,
+        effect: StatusEffect,
+        amount: i32,
+    },
+    /// A monster was killed. The entity itself is deleted by the time listeners hear
+    /// about it, so everything else worth knowing about it is included.
+    MonsterKilled {
+        entity: Entity,
+        name: String,
+        /// How much experience killing it was worth.
+        xp: i32,
+        /// Where it died.
+        pos: rltk::Point,
+        /// Whether it was a [`crate::Leader`].
+        leader: bool,
+        /// Whoever dealt the killing blow, if anybody did.
+        killer: Option<Entity>,
+    },
+    /// `collector` picked `item` up off of the floor.
+    ItemPickedUp { collector: Entity, item: Entity },
+    /// `user` used up, equipped, or otherwise activated `item`.
+    ItemUsed { user: Entity, item: Entity },
+    /// `user` was caught in the blast of their own `item`, like a fireball
+    /// thrown too close.
+    CaughtInOwnBlast { user: Entity, item: Entity },
+    /// The player died.
+    PlayerKilled,
+    /// The player went down to a new level at `depth`.
+    LevelEntered { depth: i32 },
+    /// `criminal` was caught committing `crime` against `victim`, at `scene`.
+    CrimeCommitted {
+        criminal: Entity,
+        victim: Entity,
+        crime: Crime,
+        scene: rltk::Point,
+    },
+}
+
+/// Events that have been raised this turn, but not yet dispatched.
+#[derive(Debug, Default)]
+pub struct GameEvents {
+    pending: Vec<GameEvent>,
+}
+
+impl GameEvents {
+    /// Queue up an event to be sent to every listener at the end of the turn.
+    pub fn push(&mut self, event: GameEvent) {
+        self.pending.push(event);
+    }
+}
+
+/// Something that wants to hear about every [`GameEvent`].
+pub trait GameEventListener: Send + Sync {
+    fn on_event(&mut self, ecs: &World, event: &GameEvent);
+}
+
+/// The set of [`GameEventListener`]s that [`dispatch_game_events()`] notifies.
+#[derive(Default)]
+pub struct GameEventBus {
+    listeners: Vec<Box<dyn GameEventListener>>,
+}
+
+impl GameEventBus {
+    /// Start sending events to `listener`.
+    pub fn subscribe(&mut self, listener: impl GameEventListener + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+}
+
+/// Send every pending [`GameEvent`] to every subscribed listener, in the order
+/// they were raised.
+pub fn dispatch_game_events(ecs: &mut World) {
+    let events = std::mem::take(&mut ecs.write_resource::<GameEvents>().pending);
+    if events.is_empty() {
+        return;
+    }
+
+    // Take the bus out of the world so that listeners can freely access other resources
+    let mut bus = ecs
+        .remove::<GameEventBus>()
+        .expect("Game event bus should be inserted before the game starts");
+    for event in events.iter() {
+        for listener in bus.listeners.iter_mut() {
+            listener.on_event(ecs, event);
+        }
+    }
+    ecs.insert(bus);
+}
+
+/// Statistics about the current run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunStats {
+    pub monsters_killed: u32,
+    pub damage_dealt: i32,
+    pub damage_taken: i32,
+    pub items_picked_up: u32,
+    pub items_used: u32,
+    pub deepest_level: i32,
+}
+
+impl Default for RunStats {
+    fn default() -> Self {
+        Self {
+            monsters_killed: 0,
+            damage_dealt: 0,
+            damage_taken: 0,
+            items_picked_up: 0,
+            items_used: 0,
+            deepest_level: 1,
+        }
+    }
+}
+
+/// Keeps the [`RunStats`] resource up to date.
+pub struct RunStatsListener;
+
+impl GameEventListener for RunStatsListener {
+    fn on_event(&mut self, ecs: &World, event: &GameEvent) {
+        let player = **ecs.fetch::<PlayerEntity>();
+        let mut stats = ecs.write_resource::<RunStats>();
+
+        match event {
+            GameEvent::MeleeHit {
+                attacker,
+                target,
+                amount,
+            }
+            | GameEvent::RangedHit {
+                attacker,
+                target,
+                amount,
+            } => {
+                if *attacker == player {
+                    stats.damage_dealt += amount;
+                } else if *target == player {
+                    stats.damage_taken += amount;
+                }
+            }
+            GameEvent::TrapTriggered { target, amount, .. }
+            | GameEvent::StatusDamage { target, amount, .. }
+                if *target == player =>
+            {
+                stats.damage_taken += amount;
+            }
+
+            GameEvent::MonsterKilled { killer, .. } if *killer == Some(player) => {
+                stats.monsters_killed += 1;
+            }
+
+            GameEvent::ItemPickedUp { collector, .. } if *collector == player => {
+                stats.items_picked_up += 1;
+            }
+            GameEvent::ItemUsed { user, .. } if *user == player => stats.items_used += 1,
+
+            GameEvent::LevelEntered { depth } => {
+                stats.deepest_level = i32::max(stats.deepest_level, *depth);
+            }
+
+            _ => {}
+        }
+    }
+}
+
+/// Ends the run in victory when the player uses an item that [`WinsGame`], or
+/// makes it back to the surface carrying one.
+pub struct VictoryListener;
+
+impl GameEventListener for VictoryListener {
+    fn on_event(&mut self, ecs: &World, event: &GameEvent) {
+        let player = **ecs.fetch::<PlayerEntity>();
+        let wins_game = ecs.read_storage::<WinsGame>();
+
+        let won = match event {
+            GameEvent::ItemUsed { user, item } => *user == player && wins_game.contains(*item),
+            GameEvent::LevelEntered { depth: 1 } => {
+                let backpack = ecs.read_storage::<InBackpack>();
+                (&backpack, &wins_game)
+                    .join()
+                    .any(|(in_pack, _)| in_pack.owner == player)
+            }
+            _ => false,
+        };
+
+        if won {
+            *ecs.write_resource::<RunState>() = RunState::Victory;
+        }
+    }
+}
@@ -1,20 +1,123 @@
+use std::fmt;
+
+use rltk::RGB;
+
+use crate::l10n::Translation;
+
 /// Use to log messages to the game's console.
 #[derive(Debug, Default, Clone)]
 pub struct GameLog {
-    pub entries: Vec<String>,
+    pub entries: Vec<LogEntry>,
+    /// The current turn number, which every new entry is stamped with.
+    pub turn: u32,
+}
+
+/// One message in the [`GameLog`], made up of differently-coloured pieces of text.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// The turn the message was logged on.
+    pub turn: u32,
+    pub spans: Vec<LogSpan>,
+}
+
+/// A piece of a [`LogEntry`] that's all one colour.
+#[derive(Debug, Clone)]
+pub struct LogSpan {
+    pub text: String,
+    pub color: RGB,
 }
 
 impl GameLog {
     /// Add an entry to the game log.
     pub fn log<S: ToString>(&mut self, msg: S) {
-        let msg = msg.to_string();
-        self.entries.push(msg);
+        self.entry().append(msg).log();
+    }
+
+    /// Start building a multi-coloured entry. Nothing is added to the log until
+    /// [`LogEntryBuilder::log()`] is called.
+    pub fn entry(&mut self) -> LogEntryBuilder<'_> {
+        LogEntryBuilder {
+            log: self,
+            spans: Vec::new(),
+        }
+    }
+
+    /// Move on to the next turn.
+    pub fn next_turn(&mut self) {
+        self.turn += 1;
     }
 }
 
 /// Initialize a new GameLog from a set of messages.
 impl From<Vec<String>> for GameLog {
     fn from(entries: Vec<String>) -> Self {
-        Self { entries }
+        let mut log = Self::default();
+        for entry in entries {
+            log.log(entry);
+        }
+        log
+    }
+}
+
+impl LogEntry {
+    /// The entry's text, without any colours.
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for span in self.spans.iter() {
+            f.write_str(&span.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds up a [`LogEntry`] out of coloured pieces. See [`GameLog::entry()`].
+#[must_use = "the entry isn't logged until `log()` is called"]
+pub struct LogEntryBuilder<'a> {
+    log: &'a mut GameLog,
+    spans: Vec<LogSpan>,
+}
+
+impl LogEntryBuilder<'_> {
+    /// Add some plain white text.
+    pub fn append<S: ToString>(self, text: S) -> Self {
+        self.colored(text, RGB::named(rltk::WHITE))
+    }
+
+    /// Add some text in `color`.
+    pub fn colored<S: ToString>(mut self, text: S, color: RGB) -> Self {
+        self.spans.push(LogSpan {
+            text: text.to_string(),
+            color,
+        });
+        self
+    }
+
+    /// Add a translated message, in white apart from any arguments given colours
+    /// of their own.
+    pub fn message(self, msg: &Translation) -> Self {
+        self.colored_message(msg, RGB::named(rltk::WHITE))
+    }
+
+    /// Add a translated message in `color`, apart from any arguments given colours
+    /// of their own.
+    pub fn colored_message(mut self, msg: &Translation, color: RGB) -> Self {
+        for (text, arg_color) in msg.spans() {
+            self = self.colored(text, arg_color.unwrap_or(color));
+        }
+        self
+    }
+
+    /// Add the finished entry to the log.
+    pub fn log(self) {
+        let turn = self.log.turn;
+        self.log.entries.push(LogEntry {
+            turn,
+            spans: self.spans,
+        });
     }
 }
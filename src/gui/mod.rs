@@ -0,0 +1,2174 @@
+use rltk::{Point, Rltk, VirtualKeyCode, RGB};
+use specs::prelude::*;
+use strum::IntoEnumIterator;
+
+mod modal;
+
+use self::modal::lettered_list;
+pub use self::modal::{
+    list_menu, message_box, table_menu, yes_no_dialogue, ItemMenuResult, MenuPage, MenuStyle,
+    TextEntryResult,
+};
+
+use crate::accessibility::announce;
+use crate::achievements::Achievement;
+use crate::camera::{Camera, VIEW_HEIGHT, VIEW_WIDTH};
+use crate::l10n::{self, tr, Translation};
+use crate::quests;
+use crate::raws::Upgrade;
+use crate::{
+    blast_area, carried_weight, enchantable_items, identification, is_vi_key,
+    nothing_to_offer_message, progression, resolve_trajectory, service_offers, Achievements,
+    AiState, AreaOfEffect, Attributes, BlockChance, Boss, Branch, CombatStats, Config, Consumable,
+    Container, CritRangeBonus, DefenseBonus, Description, Enchants, EntryTrigger, EquipmentSlot,
+    Equippable, Equipped, Experience, GameLog, Guard, Hidden, HighScores, HudLayout, HungerClock,
+    HungerState, InBackpack, InStash, InflictsDamage, Item, KnownSpells, LogEntry, Mana, Map,
+    MasterItemNames, MeleeAttack, MeleePowerBonus, Monster, Name, Palette, Player, PlayerEntity,
+    PlayerPos, Position, ProvidesHealing, Purse, QuestGiver, QuestJournal, Quips, Ranged, Rarity,
+    RunState, RunStats, ScreenReader, Service, ServiceNpc, ServiceOffer, Skills, Spell, Stash,
+    State, StatusEffects, TileType, Value, Vendor, Viewshed, Weight,
+};
+
+/// Draw the UI onto the game screen.
+pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
+    let palette = *ecs.fetch::<Palette>();
+    let color_bg = palette.background;
+    let color_bg_cursor = palette.selected;
+    let color_fg = palette.text;
+    let color_fg_accent = palette.title;
+    let color_fg_health = palette.danger;
+
+    let layout = ecs.fetch::<HudLayout>().layout();
+
+    // Draw borders around the HUD panel, if this layout has one
+    if let Some(panel) = layout.panel {
+        ctx.draw_box(
+            panel.x1,
+            panel.y1,
+            panel.width(),
+            panel.height(),
+            color_fg,
+            color_bg,
+        );
+    }
+
+    // Display as many log messages as we can fit
+    let log = ecs.fetch::<GameLog>();
+    for (y, entry) in (layout.log.y1..layout.log.y2).zip(log.entries.iter().rev()) {
+        print_log_entry(ctx, &palette, layout.log.x1, y, layout.log.width(), entry);
+    }
+
+    // Draw the player's depth, health, and health bar on the status line
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let players = ecs.read_storage::<Player>();
+    let map = ecs.fetch::<Map>();
+    let Point { x, y } = layout.status;
+    for (_player, stats) in (&players, &combat_stats).join() {
+        let depth = match map.branch {
+            Branch::Main => format!("Depth: {}", map.depth),
+            branch => format!("Depth: {} ({branch})", map.depth),
+        };
+        ctx.print_color(x, y, color_fg_accent, color_bg, &depth);
+
+        let health_str = format!(" HP: {} / {} ", stats.hp, stats.max_hp);
+        ctx.print_color(x + 10, y, color_fg_accent, color_bg, &health_str);
+
+        ctx.draw_bar_horizontal(
+            x + 26,
+            y,
+            layout.health_bar_width,
+            stats.hp,
+            stats.max_hp,
+            color_fg_health,
+            color_bg,
+        );
+    }
+
+    // Show how much gold the player has
+    let purses = ecs.read_storage::<Purse>();
+    for (_player, purse) in (&players, &purses).join() {
+        let gold = format!(" Gold: {} ", purse.gold);
+        ctx.print_color(
+            layout.gold.x,
+            layout.gold.y,
+            color_fg_accent,
+            color_bg,
+            &gold,
+        );
+    }
+
+    // Show how much mana the player has left for spells
+    let mana = ecs.read_storage::<Mana>();
+    for (_player, mana) in (&players, &mana).join() {
+        let mana = format!(" MP: {} / {} ", mana.mana, mana.max_mana);
+        ctx.print_color(layout.mana.x, layout.mana.y, palette.info, color_bg, &mana);
+    }
+
+    // Say how hungry the player is, unless they're neither full nor hungry, and
+    // then list whatever status effects they're under
+    let hunger_clocks = ecs.read_storage::<HungerClock>();
+    let status_effects = ecs.read_storage::<StatusEffects>();
+    for (player, _) in (&ecs.entities(), &players).join() {
+        let mut x = layout.effects.x;
+        let hunger = hunger_clocks
+            .get(player)
+            .and_then(|clock| match clock.state {
+                HungerState::Normal => None,
+                HungerState::WellFed => Some((clock.state, palette.info)),
+                HungerState::Hungry => Some((clock.state, palette.warning)),
+                HungerState::Starving => Some((clock.state, palette.danger)),
+            });
+        if let Some((state, color)) = hunger {
+            let label = format!(" {state} ");
+            ctx.print_color(x, layout.effects.y, color, color_bg, &label);
+            x += label.chars().count() as i32;
+        }
+
+        let Some(effects) = status_effects.get(player) else {
+            continue;
+        };
+        for active in effects.active.iter() {
+            // Buffs say what they're adding, so the player knows what they're losing
+            // when they wear off
+            let tag = match (active.effect.power_bonus(), active.effect.defense_bonus()) {
+                (0, 0) => String::new(),
+                (power, 0) => format!(" +{power} pow"),
+                (_, defense) => format!(" +{defense} def"),
+            };
+            let label = format!(" {}{tag} ({}) ", active.effect, active.turns);
+            ctx.print_color(
+                x,
+                layout.effects.y,
+                palette.status_effect(active.effect),
+                color_bg,
+                &label,
+            );
+            x += label.chars().count() as i32;
+        }
+    }
+
+    draw_boss_health(ecs, ctx);
+
+    // Draw mouse cursor on top of EVERYTHING
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    ctx.set_bg(mouse_x, mouse_y, color_bg_cursor);
+
+    // Draw mouse tooltips on top of that
+    draw_tooltips(ecs, ctx);
+}
+
+/// How wide a boss's health bar is.
+const BOSS_BAR_WIDTH: i32 = 40;
+
+/// Draw the health of whichever [`Boss`] the player can see in a bar across the
+/// top of the map, under its name.
+fn draw_boss_health(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let bosses = ecs.read_storage::<Boss>();
+    let names = ecs.read_storage::<Name>();
+    let positions = ecs.read_storage::<Position>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+
+    let boss = (&bosses, &names, &positions, &combat_stats)
+        .join()
+        .find(|(_, _, pos, stats)| stats.hp > 0 && map.visible_tiles[map.xy_idx(pos.x, pos.y)]);
+    let Some((_, name, _, stats)) = boss else {
+        return;
+    };
+
+    let palette = ecs.fetch::<Palette>();
+    let x = (VIEW_WIDTH - BOSS_BAR_WIDTH) / 2;
+    let label = format!(" {name} ");
+    ctx.print_color(
+        (VIEW_WIDTH - label.chars().count() as i32) / 2,
+        0,
+        palette.boss,
+        palette.background,
+        &label,
+    );
+    ctx.draw_bar_horizontal(
+        x,
+        1,
+        BOSS_BAR_WIDTH,
+        stats.hp,
+        stats.max_hp,
+        palette.boss,
+        palette.background,
+    );
+}
+
+/// Print `entry` at (`x`, `y`) in its colours, cut off at `width` characters.
+fn print_log_entry(
+    ctx: &mut Rltk,
+    palette: &Palette,
+    x: i32,
+    y: i32,
+    width: i32,
+    entry: &LogEntry,
+) {
+    let mut x = x;
+    let mut remaining = width.max(0) as usize;
+    for span in entry.spans.iter() {
+        let text: String = span.text.chars().take(remaining).collect();
+        let len = text.chars().count();
+        ctx.print_color(x, y, span.color, palette.background, text);
+        x += len as i32;
+        remaining -= len;
+    }
+}
+
+/// The widest a tooltip's text can get before it wraps, in characters.
+const TOOLTIP_WIDTH: usize = 30;
+
+/// Draw a tooltip describing whatever the mouse is hovering over, in a box next to
+/// the cursor that's kept on screen.
+fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let palette = ecs.fetch::<Palette>();
+    let debug_map_view = ecs.fetch::<Config>().display.debug_map_view;
+    let positions = ecs.read_storage::<Position>();
+    let hidden = ecs.read_storage::<Hidden>();
+
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    let Some(hovered) = Camera::new(ecs).screen_to_map(Point::new(mouse_x, mouse_y)) else {
+        return;
+    };
+    if !(map.visible_tiles[map.xy_idx(hovered.x, hovered.y)] || debug_map_view) {
+        return;
+    }
+
+    let mut lines: Vec<(String, RGB)> = Vec::new();
+    for (entity, position, _) in (&ecs.entities(), &positions, !&hidden).join() {
+        if position.x != hovered.x || position.y != hovered.y {
+            continue;
+        }
+        let entity_lines = tooltip_lines(ecs, entity);
+        if !lines.is_empty() && !entity_lines.is_empty() {
+            lines.push((String::new(), palette.panel_text));
+        }
+        lines.extend(entity_lines);
+    }
+    if lines.is_empty() {
+        return;
+    }
+
+    // Put the box beside the cursor, on whichever side has room, and nudge it up
+    // if it would run off the bottom of the screen
+    let (screen_width, screen_height) = ctx.get_char_size();
+    let width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0) as i32 + 3;
+    let height = lines.len() as i32 + 1;
+    let x = if mouse_x + 1 + width < screen_width as i32 {
+        mouse_x + 1
+    } else {
+        (mouse_x - 1 - width).max(0)
+    };
+    let y = mouse_y.min(screen_height as i32 - 1 - height).max(0);
+
+    let bg = palette.panel;
+    ctx.draw_box(x, y, width, height, palette.text, bg);
+    for (i, (line, fg)) in lines.iter().enumerate() {
+        ctx.print_color(x + 2, y + 1 + i as i32, *fg, bg, line);
+    }
+}
+
+/// The lines of a tooltip describing `entity`, each with the colour to print it in.
+/// Stats and descriptions of unidentified items are left out, so that they don't
+/// give away what the item is.
+fn tooltip_lines(ecs: &World, entity: Entity) -> Vec<(String, RGB)> {
+    let Some(name) = ecs.read_storage::<Name>().get(entity).cloned() else {
+        return Vec::new();
+    };
+    let item_names = ecs.fetch::<MasterItemNames>();
+    let palette = ecs.fetch::<Palette>();
+    let stat_color = palette.panel_text;
+
+    let rarity = item_names.display_rarity(&name.name, ecs.read_storage::<Rarity>().get(entity));
+    let title_color = if rarity > Rarity::Common {
+        palette.rarity(rarity)
+    } else {
+        palette.title
+    };
+    let mut lines = vec![(item_names.display_name(&name.name), title_color)];
+
+    if let Some(stats) = ecs.read_storage::<CombatStats>().get(entity) {
+        lines.push((
+            format!("HP: {}/{}", stats.hp.max(0), stats.max_hp),
+            stat_color,
+        ));
+    }
+
+    if !item_names.is_identified(&name.name) {
+        return lines;
+    }
+
+    if rarity > Rarity::Common {
+        lines.push((rarity.to_string(), title_color));
+    }
+
+    if let Some(damage) = ecs.read_storage::<InflictsDamage>().get(entity) {
+        lines.push((
+            tr("stat-damage").arg("value", damage.damage).to_string(),
+            stat_color,
+        ));
+    }
+    if let Some(healing) = ecs.read_storage::<ProvidesHealing>().get(entity) {
+        lines.push((
+            tr("stat-heals")
+                .arg("value", healing.heal_amount)
+                .to_string(),
+            stat_color,
+        ));
+    }
+    if let Some(attack) = ecs.read_storage::<MeleeAttack>().get(entity) {
+        lines.push((
+            tr("stat-attack").arg("value", attack.damage).to_string(),
+            stat_color,
+        ));
+    }
+    if let Some(bonus) = ecs.read_storage::<MeleePowerBonus>().get(entity) {
+        lines.push((
+            tr("stat-power").arg("value", bonus.power).to_string(),
+            stat_color,
+        ));
+    }
+    if let Some(bonus) = ecs.read_storage::<CritRangeBonus>().get(entity) {
+        lines.push((
+            tr("stat-crit-range").arg("value", bonus.range).to_string(),
+            stat_color,
+        ));
+    }
+    if let Some(bonus) = ecs.read_storage::<DefenseBonus>().get(entity) {
+        lines.push((
+            tr("stat-defense").arg("value", bonus.defense).to_string(),
+            stat_color,
+        ));
+    }
+    if let Some(block) = ecs.read_storage::<BlockChance>().get(entity) {
+        lines.push((
+            tr("stat-block").arg("value", block.percent).to_string(),
+            stat_color,
+        ));
+    }
+    if let Some(description) = ecs.read_storage::<Description>().get(entity) {
+        lines.extend(
+            wrap_text(&l10n::name(&description.text), TOOLTIP_WIDTH)
+                .into_iter()
+                .map(|line| (line, palette.text)),
+        );
+    }
+
+    lines
+}
+
+/// Split `text` into lines no wider than `width` characters, breaking between words.
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> ItemMenuResult<Entity> {
+    let accent_color = gs.ecs.fetch::<Palette>().title;
+    let title = {
+        let player_entity = **gs.ecs.fetch::<PlayerEntity>();
+        let load = carried_weight(
+            player_entity,
+            &gs.ecs.read_storage::<Weight>(),
+            &gs.ecs.read_storage::<InBackpack>(),
+            &gs.ecs.read_storage::<Equipped>(),
+        );
+        let capacity = gs
+            .ecs
+            .read_storage::<Attributes>()
+            .get(player_entity)
+            .copied()
+            .unwrap_or_default()
+            .carry_capacity();
+        tr("inventory-load")
+            .arg("load", load)
+            .arg("capacity", capacity)
+    };
+    let player_entity = **gs.ecs.fetch::<PlayerEntity>();
+    generic_item_selection_dialogue(
+        gs,
+        ctx,
+        player_entity,
+        title,
+        tr("footer-cancel"),
+        accent_color,
+    )
+}
+
+/// Show a dialogue that allows the player to select an item to drop.
+pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> ItemMenuResult<Entity> {
+    let accent_color = gs.ecs.fetch::<Palette>().warning;
+    let player_entity = **gs.ecs.fetch::<PlayerEntity>();
+    generic_item_selection_dialogue(
+        gs,
+        ctx,
+        player_entity,
+        tr("drop-which-item"),
+        tr("footer-cancel"),
+        accent_color,
+    )
+}
+
+/// Show the player's equipped items that the enchanting `scroll` they just read
+/// can be used on. It's been read already, so there's no backing out.
+pub fn enchant_item_menu(gs: &mut State, ctx: &mut Rltk, scroll: Entity) -> ItemMenuResult<Entity> {
+    let Some(target) = gs
+        .ecs
+        .read_storage::<Enchants>()
+        .get(scroll)
+        .map(|e| e.target)
+    else {
+        return ItemMenuResult::Cancel;
+    };
+    let player_entity = **gs.ecs.fetch::<PlayerEntity>();
+    let items = enchantable_items(&gs.ecs, player_entity, target);
+    let names: Vec<String> = items
+        .iter()
+        .map(|item| identification::display_name(&gs.ecs, *item).unwrap_or_default())
+        .collect();
+    let colors: Vec<RGB> = items
+        .iter()
+        .map(|item| identification::display_color(&gs.ecs, *item))
+        .collect();
+    let accent_color = gs.ecs.fetch::<Palette>().gold;
+    let title = tr("enchant-which-item").to_string();
+    let footer = tr("footer-pick-one").to_string();
+    let style = MenuStyle::new(&title, &footer, accent_color)
+        .not_cancellable()
+        .row_colors(&colors);
+
+    match list_menu(&gs.ecs, ctx, &style, &names) {
+        ItemMenuResult::Selected(j) => ItemMenuResult::Selected(items[j]),
+        ItemMenuResult::Cancel => ItemMenuResult::Cancel,
+        ItemMenuResult::NoResponse => ItemMenuResult::NoResponse,
+    }
+}
+
+/// Something the player picked to throw.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Throwable {
+    Item(Entity),
+    /// A rock off the floor, to distract monsters with the noise it makes.
+    Rock,
+}
+
+/// Show a dialogue that allows the player to select an item to throw, or to
+/// throw a rock instead.
+pub fn throw_item_menu(gs: &mut State, ctx: &mut Rltk) -> ItemMenuResult<Throwable> {
+    let accent_color = gs.ecs.fetch::<Palette>().warning;
+    let player_entity = **gs.ecs.fetch::<PlayerEntity>();
+    match generic_item_selection_dialogue(
+        gs,
+        ctx,
+        player_entity,
+        tr("throw-which-item"),
+        tr("footer-throw"),
+        accent_color,
+    ) {
+        ItemMenuResult::Selected(item) => ItemMenuResult::Selected(Throwable::Item(item)),
+        ItemMenuResult::Cancel => ItemMenuResult::Cancel,
+        ItemMenuResult::NoResponse if ctx.key == Some(VirtualKeyCode::Tab) => {
+            ItemMenuResult::Selected(Throwable::Rock)
+        }
+        ItemMenuResult::NoResponse => ItemMenuResult::NoResponse,
+    }
+}
+
+/// Show the spells that the player knows, along with how much mana each costs.
+pub fn show_spellbook(gs: &mut State, ctx: &mut Rltk) -> ItemMenuResult<Entity> {
+    let player_entity = gs.ecs.fetch::<PlayerEntity>();
+    let known_spells = gs.ecs.read_storage::<KnownSpells>();
+    let spells = gs.ecs.read_storage::<Spell>();
+    let skills = gs.ecs.read_storage::<Skills>();
+    let names = gs.ecs.read_storage::<Name>();
+    let skills = skills.get(**player_entity).copied().unwrap_or_default();
+
+    let spellbook: Vec<(Entity, String)> = known_spells
+        .get(**player_entity)
+        .map(|known| known.spells.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|spell| {
+            let cost = skills.spell_cost(spells.get(*spell)?.mana_cost);
+            Some((
+                *spell,
+                tr("spell-cost")
+                    .arg("spell", l10n::name(&names.get(*spell)?.name))
+                    .arg("cost", cost)
+                    .to_string(),
+            ))
+        })
+        .collect();
+
+    item_list_dialogue(
+        &gs.ecs,
+        ctx,
+        tr("cast-which-spell"),
+        tr("footer-cancel"),
+        gs.ecs.fetch::<Palette>().info,
+        &spellbook,
+    )
+}
+
+/// Let the player pick one of the items in `owner`'s backpack.
+fn generic_item_selection_dialogue<S: ToString, F: ToString>(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    owner: Entity,
+    title: S,
+    footer: F,
+    accent_color: RGB,
+) -> ItemMenuResult<Entity> {
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let item_names = gs.ecs.fetch::<MasterItemNames>();
+    let entities = gs.ecs.entities();
+
+    let inventory: Vec<(Entity, String)> = (&entities, &backpack, &names)
+        .join()
+        .filter(|(_, pack_item, _)| pack_item.owner == owner)
+        .map(|(entity, _, name)| (entity, item_names.display_name(&name.name)))
+        .collect();
+
+    item_list_dialogue(&gs.ecs, ctx, title, footer, accent_color, &inventory)
+}
+
+/// Draw a lettered list of `items`, and return whichever one the player picks.
+fn item_list_dialogue<S: ToString, F: ToString>(
+    ecs: &World,
+    ctx: &mut Rltk,
+    title: S,
+    footer: F,
+    accent_color: RGB,
+    items: &[(Entity, String)],
+) -> ItemMenuResult<Entity> {
+    let title = title.to_string();
+    let footer = footer.to_string();
+    let names: Vec<String> = items.iter().map(|(_, name)| name.clone()).collect();
+    let colors: Vec<RGB> = items
+        .iter()
+        .map(|(item, _)| identification::display_color(ecs, *item))
+        .collect();
+
+    match list_menu(
+        ecs,
+        ctx,
+        &MenuStyle::new(&title, &footer, accent_color).row_colors(&colors),
+        &names,
+    ) {
+        ItemMenuResult::Selected(j) => ItemMenuResult::Selected(items[j].0),
+        ItemMenuResult::Cancel => ItemMenuResult::Cancel,
+        ItemMenuResult::NoResponse => ItemMenuResult::NoResponse,
+    }
+}
+
+/// Something the player wants to do with their stash.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum StashAction {
+    Deposit(Entity),
+    Withdraw(Entity),
+    /// Flip between depositing and withdrawing.
+    SwitchTab,
+}
+
+/// Show the player's backpack (or, if `withdrawing`, the contents of their stash)
+/// so they can move items between the two.
+pub fn stash_menu(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    withdrawing: bool,
+) -> ItemMenuResult<StashAction> {
+    let items: Vec<(Entity, String)> = if withdrawing {
+        let names = gs.ecs.read_storage::<Name>();
+        let stashed = gs.ecs.read_storage::<InStash>();
+        let item_names = gs.ecs.fetch::<MasterItemNames>();
+        (&gs.ecs.entities(), &stashed, &names)
+            .join()
+            .map(|(entity, _, name)| (entity, item_names.display_name(&name.name)))
+            .collect()
+    } else {
+        let player_entity = **gs.ecs.fetch::<PlayerEntity>();
+        let names = gs.ecs.read_storage::<Name>();
+        let backpack = gs.ecs.read_storage::<InBackpack>();
+        let item_names = gs.ecs.fetch::<MasterItemNames>();
+        (&gs.ecs.entities(), &backpack, &names)
+            .join()
+            .filter(|(_, pack_item, _)| pack_item.owner == player_entity)
+            .map(|(entity, _, name)| (entity, item_names.display_name(&name.name)))
+            .collect()
+    };
+
+    let palette = *gs.ecs.fetch::<Palette>();
+    let (title, accent_color) = if withdrawing {
+        (tr("withdraw-which-item"), palette.info)
+    } else {
+        (tr("deposit-which-item"), palette.warning)
+    };
+
+    match item_list_dialogue(
+        &gs.ecs,
+        ctx,
+        title,
+        tr("footer-switch-tabs"),
+        accent_color,
+        &items,
+    ) {
+        ItemMenuResult::Selected(item) if withdrawing => {
+            ItemMenuResult::Selected(StashAction::Withdraw(item))
+        }
+        ItemMenuResult::Selected(item) => ItemMenuResult::Selected(StashAction::Deposit(item)),
+        ItemMenuResult::Cancel => ItemMenuResult::Cancel,
+        ItemMenuResult::NoResponse if ctx.key == Some(VirtualKeyCode::Tab) => {
+            ItemMenuResult::Selected(StashAction::SwitchTab)
+        }
+        ItemMenuResult::NoResponse => ItemMenuResult::NoResponse,
+    }
+}
+
+/// Something the player wants to do with a [`Container`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ContainerAction {
+    Take(Entity),
+    Store(Entity),
+    /// Flip between taking and storing.
+    SwitchTab,
+}
+
+/// Show what's in `container` (or, if `storing`, the player's backpack) so the
+/// player can move items between the two.
+pub fn container_menu(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    container: Entity,
+    storing: bool,
+) -> ItemMenuResult<ContainerAction> {
+    let palette = *gs.ecs.fetch::<Palette>();
+    let player_entity = **gs.ecs.fetch::<PlayerEntity>();
+    let name = gs
+        .ecs
+        .read_storage::<Name>()
+        .get(container)
+        .map_or_else(String::new, |name| l10n::name(&name.name));
+
+    let (owner, title, accent_color) = if storing {
+        (
+            player_entity,
+            tr("put-what-in").arg("container", &name),
+            palette.warning,
+        )
+    } else {
+        (
+            container,
+            tr("take-what-from").arg("container", &name),
+            palette.info,
+        )
+    };
+
+    match generic_item_selection_dialogue(
+        gs,
+        ctx,
+        owner,
+        title,
+        tr("footer-switch-tabs"),
+        accent_color,
+    ) {
+        ItemMenuResult::Selected(item) if storing => {
+            ItemMenuResult::Selected(ContainerAction::Store(item))
+        }
+        ItemMenuResult::Selected(item) => ItemMenuResult::Selected(ContainerAction::Take(item)),
+        ItemMenuResult::Cancel => ItemMenuResult::Cancel,
+        ItemMenuResult::NoResponse if ctx.key == Some(VirtualKeyCode::Tab) => {
+            ItemMenuResult::Selected(ContainerAction::SwitchTab)
+        }
+        ItemMenuResult::NoResponse => ItemMenuResult::NoResponse,
+    }
+}
+
+/// Something the player wants to do at a [`crate::Vendor`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum VendorAction {
+    Buy(Entity),
+    Sell(Entity),
+    /// Try to take an item without paying for it.
+    Steal(Entity),
+    /// Flip between buying and selling.
+    SwitchTab,
+}
+
+/// Show what `vendor` has for sale (or, if `selling`, what they'll pay for the
+/// player's things), with prices taken from each item's [`Value`] and, when
+/// selling, the player's [`Attributes`].
+pub fn vendor_menu(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    vendor: Entity,
+    selling: bool,
+) -> ItemMenuResult<VendorAction> {
+    let player_entity = **gs.ecs.fetch::<PlayerEntity>();
+    let attributes = gs
+        .ecs
+        .read_storage::<Attributes>()
+        .get(player_entity)
+        .copied()
+        .unwrap_or_default();
+    let items: Vec<(Entity, Vec<String>)> = {
+        let names = gs.ecs.read_storage::<Name>();
+        let values = gs.ecs.read_storage::<Value>();
+        let backpack = gs.ecs.read_storage::<InBackpack>();
+        let item_names = gs.ecs.fetch::<MasterItemNames>();
+        let owner = if selling { player_entity } else { vendor };
+        (&gs.ecs.entities(), &backpack, &names, &values)
+            .join()
+            .filter(|(_, pack_item, _, _)| pack_item.owner == owner)
+            .map(|(entity, _, name, value)| {
+                let price = if selling {
+                    value.sell_price(&attributes)
+                } else {
+                    value.gold
+                };
+                let name = item_names.display_name(&name.name);
+                (
+                    entity,
+                    vec![name, tr("gold-amount").arg("amount", price).to_string()],
+                )
+            })
+            .collect()
+    };
+
+    let gold = gs
+        .ecs
+        .read_storage::<Purse>()
+        .get(player_entity)
+        .map_or(0, |purse| purse.gold);
+    let palette = *gs.ecs.fetch::<Palette>();
+    let (title, accent_color) = if selling {
+        (tr("sell-which-item").to_string(), palette.warning)
+    } else {
+        (tr("buy-which-item").to_string(), palette.gold)
+    };
+
+    let footer = if selling {
+        tr("vendor-footer-sell").arg("gold", gold)
+    } else {
+        tr("vendor-footer-buy").arg("gold", gold)
+    }
+    .to_string();
+    let rows: Vec<Vec<String>> = items.iter().map(|(_, row)| row.clone()).collect();
+    let colors: Vec<RGB> = items
+        .iter()
+        .map(|(item, _)| identification::display_color(&gs.ecs, *item))
+        .collect();
+
+    match table_menu(
+        &gs.ecs,
+        ctx,
+        &MenuStyle::new(&title, &footer, accent_color).row_colors(&colors),
+        &rows,
+    ) {
+        ItemMenuResult::Selected(j) if selling => {
+            ItemMenuResult::Selected(VendorAction::Sell(items[j].0))
+        }
+        ItemMenuResult::Selected(j) if ctx.shift => {
+            ItemMenuResult::Selected(VendorAction::Steal(items[j].0))
+        }
+        ItemMenuResult::Selected(j) => ItemMenuResult::Selected(VendorAction::Buy(items[j].0)),
+        ItemMenuResult::Cancel => ItemMenuResult::Cancel,
+        ItemMenuResult::NoResponse if ctx.key == Some(VirtualKeyCode::Tab) => {
+            ItemMenuResult::Selected(VendorAction::SwitchTab)
+        }
+        ItemMenuResult::NoResponse => ItemMenuResult::NoResponse,
+    }
+}
+
+/// Where the targeting cursor is while the player picks a target with
+/// [`ranged_target()`], so that it can be steered with the keyboard as well as
+/// the mouse.
+#[derive(Clone, Debug, Default)]
+pub struct TargetingCursor {
+    cursor: Option<Point>,
+    last_mouse_pos: Option<Point>,
+}
+
+/// Whether to ask for confirmation before firing something that will hit the
+/// player or one of their allies.
+#[derive(Clone, Copy, Debug)]
+pub struct FriendlyFireConfirmation {
+    pub enabled: bool,
+}
+
+impl Default for FriendlyFireConfirmation {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Find everything friendly that firing `item` at `target` would hit: the player,
+/// and anything else that can be hurt but isn't a [`Monster`].
+pub fn friendly_fire_risks(ecs: &World, item: Entity, target: Point) -> Vec<Entity> {
+    let map = ecs.fetch::<Map>();
+    let player_pos = ecs.fetch::<PlayerPos>();
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let monsters = ecs.read_storage::<Monster>();
+    let positions = ecs.read_storage::<Position>();
+
+    let impact = ecs
+        .read_storage::<Ranged>()
+        .get(item)
+        .map_or(target, |ranged| {
+            resolve_trajectory(&map, **player_pos, target, ranged.range).impact
+        });
+    let cells = match ecs.read_storage::<AreaOfEffect>().get(item) {
+        Some(aoe) => blast_area(&map, impact, aoe.radius),
+        None => vec![impact],
+    };
+
+    cells
+        .iter()
+        .flat_map(|cell| map.tile_content[map.xy_idx(cell.x, cell.y)].iter().copied())
+        .filter(|entity| combat_stats.contains(*entity) && !monsters.contains(*entity))
+        .filter(|entity| {
+            // Don't give away allies the player can't see
+            *entity == player_entity
+                || positions
+                    .get(*entity)
+                    .is_some_and(|pos| map.visible_tiles[map.xy_idx(pos.x, pos.y)])
+        })
+        .collect()
+}
+
+/// Describe who's at risk from friendly fire, e.g. "you and the Goblin".
+pub fn describe_friendly_fire(ecs: &World, risks: &[Entity]) -> String {
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let names = ecs.read_storage::<Name>();
+    let described: Vec<String> = risks
+        .iter()
+        .map(|entity| {
+            if *entity == player_entity {
+                tr("friendly-fire-you").to_string()
+            } else {
+                names.get(*entity).map_or_else(
+                    || tr("friendly-fire-someone").to_string(),
+                    |name| {
+                        tr("friendly-fire-named")
+                            .arg("name", l10n::name(&name.name))
+                            .to_string()
+                    },
+                )
+            }
+        })
+        .collect();
+    described.join(&tr("friendly-fire-and").to_string())
+}
+
+/// Let the player pick a target tile for a ranged item or something to throw.
+///
+/// Any tile the player has seen that's within `range` can be targeted, and the
+/// path the shot will take is previewed under the cursor, along with the blast
+/// if `item` has an [`AreaOfEffect`]. The cursor follows the mouse, but can also
+/// be moved with the movement keys, or jumped between visible monsters with TAB.
+/// ENTER or a left click picks the target.
+pub fn ranged_target(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    range: i32,
+    item: Option<Entity>,
+) -> ItemMenuResult<Point> {
+    let result = pick_ranged_target(gs, ctx, range, item);
+    if result != ItemMenuResult::NoResponse {
+        // Start over from the player next time
+        *gs.ecs.write_resource::<TargetingCursor>() = TargetingCursor::default();
+    }
+    result
+}
+
+fn pick_ranged_target(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    range: i32,
+    item: Option<Entity>,
+) -> ItemMenuResult<Point> {
+    let player_pos = gs.ecs.fetch::<PlayerPos>();
+    let player_entity = gs.ecs.fetch::<PlayerEntity>();
+    let map = gs.ecs.fetch::<Map>();
+    let debug_map_view = gs.ecs.fetch::<Config>().display.debug_map_view;
+    let names = gs.ecs.read_storage::<Name>();
+    let positions = gs.ecs.read_storage::<Position>();
+    let monsters = gs.ecs.read_storage::<Monster>();
+    let viewsheds = gs.ecs.read_storage::<Viewshed>();
+    let mut targeting = gs.ecs.write_resource::<TargetingCursor>();
+    let palette = *gs.ecs.fetch::<Palette>();
+    let camera = Camera::new(&gs.ecs);
+
+    ctx.print_color(
+        5,
+        0,
+        palette.title,
+        palette.background,
+        tr("targeting-help").to_string(),
+    );
+
+    // Highlight available target cells
+    let mut available_cells = Vec::new();
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let cell = Point::new(x, y);
+            let idx = map.xy_idx(x, y);
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(**player_pos, cell);
+            if (map.revealed_tiles[idx] || debug_map_view)
+                && map.tiles[idx] != TileType::Wall
+                && distance <= range as f32
+            {
+                camera.set_bg(ctx, cell, palette.range);
+                available_cells.push(cell);
+            }
+        }
+    }
+
+    // The cursor starts on the player, and jumps to the mouse whenever it moves
+    // over the map
+    let (mouse_x, mouse_y) = ctx.mouse_pos();
+    let mouse_pos = Point::new(mouse_x, mouse_y);
+    let mut cursor = targeting.cursor.unwrap_or(**player_pos);
+    if targeting
+        .last_mouse_pos
+        .is_some_and(|last| last != mouse_pos)
+    {
+        if let Some(hovered) = camera.screen_to_map(mouse_pos) {
+            cursor = hovered;
+        }
+    }
+    targeting.last_mouse_pos = Some(mouse_pos);
+
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) => return ItemMenuResult::Cancel,
+        Some(VirtualKeyCode::Tab) => {
+            // Jump to the next monster the player can see, nearest first
+            let mut targets: Vec<Point> = (&monsters, &positions)
+                .join()
+                .map(|(_, pos)| Point::new(pos.x, pos.y))
+                .filter(|pos| {
+                    available_cells.contains(pos)
+                        && viewsheds
+                            .get(**player_entity)
+                            .is_some_and(|viewshed| viewshed.visible_tiles.contains(pos))
+                })
+                .collect();
+            targets.sort_by(|a, b| {
+                let dist_a = rltk::DistanceAlg::Pythagoras.distance2d(**player_pos, *a);
+                let dist_b = rltk::DistanceAlg::Pythagoras.distance2d(**player_pos, *b);
+                dist_a.total_cmp(&dist_b)
+            });
+            if let Some(next) = targets
+                .iter()
+                .position(|target| *target == cursor)
+                .map_or(targets.first(), |current| {
+                    targets.get((current + 1) % targets.len())
+                })
+            {
+                cursor = *next;
+            }
+        }
+        Some(key) => {
+            if let Some((delta_x, delta_y)) =
+                cursor_delta(key, gs.ecs.fetch::<Config>().gameplay.vi_keys)
+            {
+                cursor = Point::new(
+                    (cursor.x + delta_x).clamp(0, map.width - 1),
+                    (cursor.y + delta_y).clamp(0, map.height - 1),
+                );
+            }
+        }
+        None => {}
+    }
+    targeting.cursor = Some(cursor);
+
+    // Draw the cursor, along with where a shot would actually go.
+    let valid_target = available_cells.contains(&cursor);
+    let seen_here: Vec<String> = (&names, &positions)
+        .join()
+        .filter(|(_, pos)| pos.x == cursor.x && pos.y == cursor.y)
+        .filter(|_| map.visible_tiles[map.xy_idx(cursor.x, cursor.y)] || debug_map_view)
+        .map(|(name, _)| l10n::name(&name.name))
+        .collect();
+    let description = if seen_here.is_empty() {
+        tr("target-nothing").to_string()
+    } else {
+        seen_here.join(", ")
+    };
+
+    if valid_target {
+        let trajectory = resolve_trajectory(&map, **player_pos, cursor, range);
+        for cell in trajectory.path.iter() {
+            camera.set_bg(ctx, *cell, palette.info_dim);
+        }
+        camera.set_bg(ctx, cursor, palette.info);
+        let blast_radius = item.and_then(|item| {
+            gs.ecs
+                .read_storage::<AreaOfEffect>()
+                .get(item)
+                .map(|aoe| aoe.radius)
+        });
+        if let Some(radius) = blast_radius {
+            for cell in blast_area(&map, trajectory.impact, radius) {
+                camera.set_bg(ctx, cell, palette.warning_dim);
+            }
+        }
+        camera.set_bg(ctx, trajectory.impact, palette.warning);
+
+        // Warn the player if they're about to hit themselves or an ally
+        let risks = item.map_or_else(Vec::new, |item| friendly_fire_risks(&gs.ecs, item, cursor));
+        let warning = if risks.is_empty() {
+            String::new()
+        } else {
+            let warning = tr("friendly-fire-warning")
+                .arg("targets", describe_friendly_fire(&gs.ecs, &risks))
+                .to_string();
+            ctx.print_color(5, 1, palette.danger, palette.background, &warning);
+            format!(" {warning}")
+        };
+        announce(
+            &gs.ecs,
+            "target",
+            format!(
+                "{}{warning}",
+                tr("target-in-range")
+                    .arg("description", &description)
+                    .arg("x", cursor.x)
+                    .arg("y", cursor.y)
+            ),
+        );
+
+        if ctx.left_click
+            || matches!(
+                ctx.key,
+                Some(VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter)
+            )
+        {
+            return ItemMenuResult::Selected(cursor);
+        }
+    } else {
+        camera.set_bg(ctx, cursor, palette.danger);
+        announce(
+            &gs.ecs,
+            "target",
+            tr("target-out-of-range")
+                .arg("description", &description)
+                .arg("x", cursor.x)
+                .arg("y", cursor.y),
+        );
+
+        if ctx.left_click {
+            return ItemMenuResult::Cancel;
+        }
+    }
+
+    ItemMenuResult::NoResponse
+}
+
+/// Show what a [`crate::ServiceNpc`] has for sale, and let the player pick something to buy.
+pub fn service_menu(gs: &mut State, ctx: &mut Rltk, npc: Entity) -> ItemMenuResult<ServiceOffer> {
+    let Some(npc_info) = gs.ecs.read_storage::<ServiceNpc>().get(npc).copied() else {
+        return ItemMenuResult::Cancel;
+    };
+    let npc_name = gs
+        .ecs
+        .read_storage::<Name>()
+        .get(npc)
+        .map(|name| l10n::name(&name.name))
+        .unwrap_or_default();
+    let gold = gs
+        .ecs
+        .read_storage::<Purse>()
+        .get(**gs.ecs.fetch::<PlayerEntity>())
+        .map_or(0, |purse| purse.gold);
+    let offers = service_offers(&gs.ecs, npc_info.service);
+
+    let footer = tr("services-footer")
+        .arg("gold", tr("gold-amount").arg("amount", gold))
+        .to_string();
+    let nothing_to_offer = nothing_to_offer_message(npc_info.service).to_string();
+    let rows: Vec<Vec<String>> = offers
+        .iter()
+        .map(|offer| {
+            vec![
+                offer.description.clone(),
+                tr("gold-amount").arg("amount", offer.price).to_string(),
+            ]
+        })
+        .collect();
+    let style = MenuStyle::new(&npc_name, &footer, gs.ecs.fetch::<Palette>().title)
+        .empty_message(&nothing_to_offer);
+
+    match table_menu(&gs.ecs, ctx, &style, &rows) {
+        ItemMenuResult::Selected(j) => ItemMenuResult::Selected(offers[j].clone()),
+        ItemMenuResult::Cancel => ItemMenuResult::Cancel,
+        ItemMenuResult::NoResponse => ItemMenuResult::NoResponse,
+    }
+}
+
+/// Show what a [`crate::Bystander`] says when the player talks to them: `line` of
+/// their [`Quips`]. Returns `true` once the player is done listening.
+pub fn dialogue_box(gs: &mut State, ctx: &mut Rltk, speaker: Entity, line: usize) -> bool {
+    let name = gs
+        .ecs
+        .read_storage::<Name>()
+        .get(speaker)
+        .map(|name| l10n::name(&name.name))
+        .unwrap_or_default();
+    let text = gs
+        .ecs
+        .read_storage::<Quips>()
+        .get(speaker)
+        .and_then(|quips| quips.lines.get(line).map(|line| l10n::name(line)))
+        .unwrap_or_else(|| "...".to_string());
+
+    message_box(&gs.ecs, ctx, &name, &format!("\"{text}\""))
+}
+
+/// Ask whether the player will take on the quest that `giver` offers.
+pub fn offer_quest(gs: &mut State, ctx: &mut Rltk, giver: Entity) -> ItemMenuResult<bool> {
+    let Some(quest) = gs
+        .ecs
+        .read_storage::<QuestGiver>()
+        .get(giver)
+        .and_then(|giver| quests::quest(&giver.quest))
+    else {
+        return ItemMenuResult::Cancel;
+    };
+
+    yes_no_dialogue(&gs.ecs, ctx, format!("\"{}\"", l10n::name(quest.pitch)))
+}
+
+/// Show the quests the player has taken on, and how far along each one is.
+pub fn show_journal(gs: &mut State, ctx: &mut Rltk) -> ItemMenuResult<usize> {
+    let rows: Vec<Vec<String>> = gs
+        .ecs
+        .fetch::<QuestJournal>()
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let quest = entry.quest()?;
+            let status = if entry.is_complete() {
+                tr("quest-done").to_string()
+            } else {
+                format!("{}/{}", entry.progress, quest.steps())
+            };
+            Some(vec![
+                l10n::name(quest.name),
+                quest.objective().to_string(),
+                tr("quest-for-giver")
+                    .arg("giver", l10n::name(quest.giver))
+                    .to_string(),
+                status,
+            ])
+        })
+        .collect();
+    let accent_color = gs.ecs.fetch::<Palette>().info;
+    let title = tr("quest-journal").to_string();
+    let footer = tr("footer-close").to_string();
+    let empty_message = tr("quest-journal-empty").to_string();
+    let style = MenuStyle::new(&title, &footer, accent_color).empty_message(&empty_message);
+
+    table_menu(&gs.ecs, ctx, &style, &rows)
+}
+
+/// Show the best runs on the [`HighScores`] table, with the run that was just
+/// finished picked out. Returns `true` once the player is done looking.
+pub fn show_high_scores(ecs: &World, ctx: &mut Rltk) -> bool {
+    let high_scores = ecs.fetch::<HighScores>();
+    let palette = ecs.fetch::<Palette>();
+    let bg_color = palette.background;
+    let now = crate::storage::unix_time();
+
+    ctx.print_color_centered(12, palette.gold, bg_color, tr("high-scores").to_string());
+    let lines: Vec<String> = high_scores
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(rank, entry)| {
+            let outcome = if entry.won {
+                tr("high-score-won")
+            } else {
+                tr("high-score-died")
+            };
+            tr("high-score-entry")
+                .arg("rank", format!("{:>2}", rank + 1))
+                .arg("score", format!("{:>6}", entry.score))
+                .arg("outcome", outcome)
+                .arg("depth", entry.deepest_level)
+                .arg("kills", entry.monsters_killed)
+                .arg("gold", entry.gold)
+                .arg("turns", entry.turns)
+                .arg("when", time_ago(now.saturating_sub(entry.finished_at)))
+                .to_string()
+        })
+        .collect();
+    if lines.is_empty() {
+        ctx.print_color_centered(
+            15,
+            palette.muted,
+            bg_color,
+            tr("high-scores-empty").to_string(),
+        );
+    }
+    for (rank, (y, line)) in (15..).zip(lines.iter()).enumerate() {
+        let color = if high_scores.latest == Some(rank) {
+            palette.selected
+        } else {
+            palette.text
+        };
+        ctx.print_color_centered(y, color, bg_color, line);
+    }
+    announce(
+        ecs,
+        "menu",
+        format!(
+            "{} {} {}",
+            tr("high-scores"),
+            lines.join(" "),
+            tr("press-any-key-for-menu")
+        ),
+    );
+
+    ctx.print_color_centered(
+        27,
+        palette.selected,
+        bg_color,
+        tr("press-any-key-for-menu").to_string(),
+    );
+
+    ctx.key.is_some()
+}
+
+/// Show every achievement, and whether it's been unlocked yet.
+pub fn show_achievements(gs: &mut State, ctx: &mut Rltk) -> ItemMenuResult<usize> {
+    let rows: Vec<Vec<String>> = {
+        let achievements = gs.ecs.fetch::<Achievements>();
+        Achievement::iter()
+            .map(|achievement| {
+                let status = if achievements.is_unlocked(achievement) {
+                    tr("achievement-unlocked-status")
+                } else {
+                    tr("achievement-locked-status")
+                };
+                vec![
+                    l10n::name(achievement.name()),
+                    l10n::name(achievement.description()),
+                    status.to_string(),
+                ]
+            })
+            .collect()
+    };
+    let accent_color = gs.ecs.fetch::<Palette>().gold;
+    let title = tr("achievements").to_string();
+    let footer = tr("footer-close").to_string();
+    let style = MenuStyle::new(&title, &footer, accent_color);
+
+    table_menu(&gs.ecs, ctx, &style, &rows)
+}
+
+/// Show the upgrades that the player can pick from after levelling up.
+pub fn level_up_menu(gs: &mut State, ctx: &mut Rltk) -> ItemMenuResult<Upgrade> {
+    let upgrades = progression::available_upgrades(&gs.ecs);
+    let level = gs
+        .ecs
+        .read_storage::<Experience>()
+        .get(**gs.ecs.fetch::<PlayerEntity>())
+        .map_or(1, |experience| experience.level);
+
+    let title = tr("level-up-title").arg("level", level).to_string();
+    let footer = tr("footer-pick-one").to_string();
+    let rows: Vec<Vec<String>> = upgrades
+        .iter()
+        .map(|upgrade| vec![l10n::name(&upgrade.name), l10n::name(&upgrade.description)])
+        .collect();
+    let accent_color = gs.ecs.fetch::<Palette>().gold;
+    let style = MenuStyle::new(&title, &footer, accent_color).not_cancellable();
+
+    match table_menu(&gs.ecs, ctx, &style, &rows) {
+        ItemMenuResult::Selected(j) => ItemMenuResult::Selected(upgrades[j].clone()),
+        ItemMenuResult::Cancel => ItemMenuResult::Cancel,
+        ItemMenuResult::NoResponse => ItemMenuResult::NoResponse,
+    }
+}
+
+/// Possible selection options from the main menu.
+#[derive(
+    PartialEq,
+    Copy,
+    Clone,
+    Debug,
+    strum::Display,
+    strum::EnumCount,
+    strum::AsRefStr,
+    strum::EnumIter,
+    num_enum::IntoPrimitive,
+    num_enum::TryFromPrimitive,
+)]
+#[repr(u8)]
+pub enum MainMenuSelection {
+    #[strum(to_string = "Recover last session?")]
+    RecoverSession = 0,
+    #[strum(to_string = "Start new game")]
+    NewGame,
+    #[strum(to_string = "Load game")]
+    LoadGame,
+    #[strum(to_string = "High scores")]
+    HighScores,
+    #[strum(to_string = "Achievements")]
+    Achievements,
+    #[strum(to_string = "Options")]
+    Options,
+    #[strum(to_string = "Quit")]
+    Quit,
+}
+
+impl MainMenuSelection {
+    /// What the option is called on the menu, in the player's language.
+    pub fn label(self) -> Translation {
+        match self {
+            Self::RecoverSession => tr("menu-recover-session"),
+            Self::NewGame => tr("menu-new-game"),
+            Self::LoadGame => tr("menu-load-game"),
+            Self::HighScores => tr("menu-high-scores"),
+            Self::Achievements => tr("menu-achievements"),
+            Self::Options => tr("menu-options"),
+            Self::Quit => tr("menu-quit"),
+        }
+    }
+}
+
+/// The result of interaction with the main menu.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum MainMenuResult {
+    /// Indicates the user switched between different menu items (going up/down),
+    /// has gone up a level in the menu or tried to quit (pressed <kbd>Esc</kbd>),
+    /// or has genuinely selected nothing somehow.
+    NoSelection(MainMenuSelection),
+    /// Indicates the user actually selected a menu option.
+    Selected(MainMenuSelection),
+}
+
+/// Display the main menu and handle input this tick.
+pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
+    use MainMenuResult::*;
+    use MainMenuSelection::*;
+
+    let (save_exists, autosaved_options) = {
+        let saves = gs.ecs.fetch::<crate::saveload_system::SaveIndex>();
+        (saves.any_saved(), saves.recoverable)
+    };
+    let can_recover = autosaved_options.is_some();
+    // Remind the player what they're resuming
+    let label = |opt: MainMenuSelection| match autosaved_options {
+        Some(options) if opt == RecoverSession => {
+            format!("{} ({})", opt.label(), options.summary())
+        }
+        _ => opt.label().to_string(),
+    };
+    let runstate = gs.ecs.fetch::<RunState>();
+
+    let palette = *gs.ecs.fetch::<Palette>();
+    let bg_color = palette.background;
+    let title_color = palette.title;
+    let cur_option_color = palette.selected;
+    let option_color = palette.text;
+
+    let mut y = 15;
+
+    ctx.print_color_centered(y, title_color, bg_color, "Rust Roguelike");
+
+    if let RunState::MainMenu {
+        menu_selection: selection,
+    } = *runstate
+    {
+        // Only offer to load the game if a save exists, or to recover a session
+        // that crashed if there's an autosave from it!
+        let options: Vec<MainMenuSelection> = MainMenuSelection::iter()
+            .filter(|opt| match opt {
+                RecoverSession => can_recover,
+                LoadGame => save_exists,
+                // There's no quitting a web page, short of closing it
+                Quit => cfg!(not(target_arch = "wasm32")),
+                _ => true,
+            })
+            .collect();
+
+        // Display the menu
+        y += 9;
+        for &opt in &options {
+            let color = if selection == opt {
+                cur_option_color
+            } else {
+                option_color
+            };
+            ctx.print_color_centered(y, color, bg_color, label(opt));
+            y += 1;
+        }
+
+        announce(&gs.ecs, "menu", format!("Main menu: {}", label(selection)));
+
+        // Handle user input
+        match ctx.key {
+            // If nothing was pressed, change nothing
+            None => NoSelection(selection),
+
+            Some(key) => match key {
+                // Quit on escape
+                VirtualKeyCode::Escape if options.contains(&Quit) => NoSelection(Quit),
+
+                // Moving up
+                VirtualKeyCode::Up | VirtualKeyCode::K | VirtualKeyCode::Numpad8 => {
+                    let cur = options
+                        .iter()
+                        .position(|opt| *opt == selection)
+                        .unwrap_or(0);
+                    NoSelection(options[(cur + options.len() - 1) % options.len()])
+                }
+
+                // Moving down
+                VirtualKeyCode::Down | VirtualKeyCode::J | VirtualKeyCode::Numpad2 => {
+                    let cur = options
+                        .iter()
+                        .position(|opt| *opt == selection)
+                        .unwrap_or(0);
+                    NoSelection(options[(cur + 1) % options.len()])
+                }
+
+                // Select an option
+                VirtualKeyCode::Return => Selected(selection),
+
+                _ => NoSelection(selection),
+            },
+        }
+    } else {
+        // If this function is called while not in the MainMenu run state,
+        // just return NoSelection with a NewGame option
+        NoSelection(NewGame)
+    }
+}
+
+/// Show every save slot and what's saved in it, and let the player pick one. When
+/// `loading`, only slots with a game saved in them can be picked.
+pub fn save_slot_menu(ecs: &World, ctx: &mut Rltk, loading: bool) -> ItemMenuResult<usize> {
+    let slots = ecs
+        .fetch::<crate::saveload_system::SaveIndex>()
+        .slots
+        .clone();
+    let now = crate::storage::unix_time();
+
+    let rows: Vec<Vec<String>> = slots
+        .iter()
+        .map(|slot| match slot {
+            Some(header) if !header.is_compatible() => {
+                vec![header.name.clone(), tr("save-incompatible").to_string()]
+            }
+            Some(header) => vec![
+                header.name.clone(),
+                tr("save-depth").arg("depth", header.depth).to_string(),
+                time_ago(now.saturating_sub(header.saved_at)),
+                header.run_options.summary(),
+            ],
+            None => vec![tr("save-empty").to_string()],
+        })
+        .collect();
+
+    let accent_color = ecs.fetch::<Palette>().title;
+    let (title, footer) = if loading {
+        (tr("load-which-game"), tr("footer-go-back"))
+    } else {
+        (tr("save-which-slot"), tr("footer-keep-playing"))
+    };
+    let (title, footer) = (title.to_string(), footer.to_string());
+    let style = MenuStyle::new(&title, &footer, accent_color);
+
+    match table_menu(ecs, ctx, &style, &rows) {
+        ItemMenuResult::Selected(slot)
+            if loading && !slots[slot].as_ref().is_some_and(|h| h.is_compatible()) =>
+        {
+            ItemMenuResult::NoResponse
+        }
+        result => result,
+    }
+}
+
+/// Roughly how long ago something happened, `seconds` ago. For example, `5m ago`.
+fn time_ago(seconds: u64) -> String {
+    match seconds {
+        0..=59 => tr("just-now"),
+        60..=3599 => tr("minutes-ago").arg("count", seconds / 60),
+        3600..=86399 => tr("hours-ago").arg("count", seconds / 3600),
+        _ => tr("days-ago").arg("count", seconds / 86400),
+    }
+    .to_string()
+}
+
+/// The result of a tick of examine mode.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ExamineResult {
+    /// Leave examine mode.
+    Cancel,
+    /// Nothing happened this tick.
+    NoResponse,
+    /// The player moved the examine cursor to a new tile.
+    MoveCursor(Point),
+    /// The player wants to write a note on the tile under the cursor.
+    WriteNote(Point),
+}
+
+/// Let the player move a cursor around the visible parts of the map with the
+/// keyboard, and look at whatever's under it.
+pub fn examine(gs: &mut State, ctx: &mut Rltk, cursor: Point) -> ExamineResult {
+    let map = gs.ecs.fetch::<Map>();
+    let debug_map_view = gs.ecs.fetch::<Config>().display.debug_map_view;
+    let positions = gs.ecs.read_storage::<Position>();
+    let hidden = gs.ecs.read_storage::<Hidden>();
+
+    let palette = *gs.ecs.fetch::<Palette>();
+    let bg = palette.background;
+    let title_color = palette.title;
+
+    Camera::new(&gs.ecs).set_bg(ctx, cursor, palette.selected);
+
+    // Describe whatever's under the cursor along the top of the screen, one line each
+    let idx = map.xy_idx(cursor.x, cursor.y);
+    let mut descriptions: Vec<String> = if map.visible_tiles[idx] || debug_map_view {
+        (&gs.ecs.entities(), &positions, !&hidden)
+            .join()
+            .filter(|(_, pos, _)| pos.x == cursor.x && pos.y == cursor.y)
+            .filter_map(|(entity, _, _)| describe_entity(&gs.ecs, entity))
+            .collect()
+    } else {
+        vec![tr("examine-cant-see").to_string()]
+    };
+    if descriptions.is_empty() {
+        descriptions.push(tr("examine-nothing").to_string());
+    }
+    let description = descriptions.join(" ");
+    announce(
+        &gs.ecs,
+        "examine",
+        match map.note_at(cursor.x, cursor.y) {
+            Some(note) => format!(
+                "{description} {}. ({}, {})",
+                tr("examine-note").arg("text", &note.text),
+                cursor.x,
+                cursor.y
+            ),
+            None => format!("{description} ({}, {})", cursor.x, cursor.y),
+        },
+    );
+
+    ctx.print_color(1, 0, title_color, bg, tr("examine-title").to_string());
+    for (y, line) in (0..).zip(descriptions.iter()) {
+        ctx.print_color(10, y, palette.text, bg, line);
+    }
+    if let Some(note) = map.note_at(cursor.x, cursor.y) {
+        ctx.print_color(
+            1,
+            descriptions.len() as i32,
+            palette.info,
+            bg,
+            tr("examine-note").arg("text", &note.text).to_string(),
+        );
+    }
+    ctx.print_color(
+        1,
+        map.height - 1,
+        title_color,
+        bg,
+        tr("examine-help").to_string(),
+    );
+
+    let (delta_x, delta_y) = match ctx.key {
+        None => return ExamineResult::NoResponse,
+        Some(VirtualKeyCode::Escape) => return ExamineResult::Cancel,
+        Some(VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter) => {
+            return ExamineResult::WriteNote(cursor)
+        }
+        Some(key) => match cursor_delta(key, gs.ecs.fetch::<Config>().gameplay.vi_keys) {
+            Some(delta) => delta,
+            None => return ExamineResult::NoResponse,
+        },
+    };
+
+    // Keep the cursor to what the player can actually see
+    let next = Point::new(
+        (cursor.x + delta_x).clamp(0, map.width - 1),
+        (cursor.y + delta_y).clamp(0, map.height - 1),
+    );
+    if map.visible_tiles[map.xy_idx(next.x, next.y)] || debug_map_view {
+        ExamineResult::MoveCursor(next)
+    } else {
+        ExamineResult::NoResponse
+    }
+}
+
+/// A one-line description of `entity` for examine mode, built out of what the
+/// player can tell about it at a glance. For example, `Goblin: hostile, wounded.`
+fn describe_entity(ecs: &World, entity: Entity) -> Option<String> {
+    let name = identification::display_name(ecs, entity)?;
+    let mut details: Vec<String> = Vec::new();
+
+    if ecs.read_storage::<Player>().contains(entity) {
+        details.push(tr("examine-player").to_string());
+    } else if ecs.read_storage::<Monster>().contains(entity) {
+        details.push(
+            match ecs.read_storage::<AiState>().get(entity) {
+                Some(AiState::Routing { .. }) => tr("examine-fleeing"),
+                Some(AiState::Surrendered) => tr("examine-surrendered"),
+                _ => tr("examine-hostile"),
+            }
+            .to_string(),
+        );
+    } else if ecs.read_storage::<Guard>().contains(entity) {
+        details.push(tr("examine-guard").to_string());
+    }
+
+    if let Some(stats) = ecs.read_storage::<CombatStats>().get(entity) {
+        let health = match stats.hp * 4 / stats.max_hp.max(1) {
+            4.. => tr("examine-unhurt"),
+            2..=3 => tr("examine-wounded"),
+            _ => tr("examine-badly-wounded"),
+        };
+        details.push(health.to_string());
+    }
+
+    if ecs.read_storage::<Vendor>().contains(entity) {
+        details.push(tr("examine-vendor").to_string());
+    }
+    if let Some(npc) = ecs.read_storage::<ServiceNpc>().get(entity) {
+        details.push(
+            match npc.service {
+                Service::Healer => tr("examine-healer"),
+                Service::Sage => tr("examine-sage"),
+            }
+            .to_string(),
+        );
+    }
+    if ecs.read_storage::<Stash>().contains(entity) {
+        details.push(tr("examine-stash").to_string());
+    }
+    if ecs.read_storage::<Container>().contains(entity) {
+        details.push(tr("examine-container").to_string());
+    }
+    if ecs.read_storage::<EntryTrigger>().contains(entity) {
+        details.push(tr("examine-trap").to_string());
+    }
+
+    if ecs.read_storage::<Item>().contains(entity) {
+        if let Some(equippable) = ecs.read_storage::<Equippable>().get(entity) {
+            details.push(
+                match equippable.slot {
+                    EquipmentSlot::Melee => tr("examine-weapon"),
+                    EquipmentSlot::Shield => tr("examine-shield"),
+                    EquipmentSlot::Head => tr("examine-head"),
+                    EquipmentSlot::Torso => tr("examine-torso"),
+                    EquipmentSlot::Feet => tr("examine-feet"),
+                }
+                .to_string(),
+            );
+        }
+        if ecs.read_storage::<Consumable>().contains(entity) {
+            details.push(tr("examine-consumable").to_string());
+        }
+        if let Some(value) = ecs.read_storage::<Value>().get(entity) {
+            details.push(tr("examine-value").arg("gold", value.gold).to_string());
+        }
+    }
+
+    if details.is_empty() {
+        Some(format!("{name}."))
+    } else {
+        Some(format!("{name}: {}.", details.join(", ")))
+    }
+}
+
+/// Which way a movement key moves a cursor around the map, if it's a movement key.
+/// The vi-style letter keys only count if `vi_keys` is on.
+fn cursor_delta(key: VirtualKeyCode, vi_keys: bool) -> Option<(i32, i32)> {
+    if !vi_keys && is_vi_key(key) {
+        return None;
+    }
+
+    match key {
+        VirtualKeyCode::Left | VirtualKeyCode::H | VirtualKeyCode::Numpad4 => Some((-1, 0)),
+        VirtualKeyCode::Right | VirtualKeyCode::L | VirtualKeyCode::Numpad6 => Some((1, 0)),
+        VirtualKeyCode::Up | VirtualKeyCode::K | VirtualKeyCode::Numpad8 => Some((0, -1)),
+        VirtualKeyCode::Down | VirtualKeyCode::J | VirtualKeyCode::Numpad2 => Some((0, 1)),
+        VirtualKeyCode::Numpad7 | VirtualKeyCode::U => Some((-1, -1)),
+        VirtualKeyCode::Numpad9 | VirtualKeyCode::I => Some((1, -1)),
+        VirtualKeyCode::Numpad1 | VirtualKeyCode::N => Some((-1, 1)),
+        VirtualKeyCode::Numpad3 | VirtualKeyCode::M => Some((1, 1)),
+        _ => None,
+    }
+}
+
+/// Why the last save the player tried to load couldn't be loaded.
+#[derive(Clone, Debug, Default)]
+pub struct LoadFailure {
+    pub message: String,
+}
+
+/// Show why a save couldn't be loaded, from the [`LoadFailure`] resource. Returns
+/// `true` once the player has read it.
+pub fn load_failed(ecs: &World, ctx: &mut Rltk) -> bool {
+    let message = ecs.fetch::<LoadFailure>().message.clone();
+    message_box(ecs, ctx, &tr("load-failed").to_string(), &message)
+}
+
+/// A line of text that the player is currently typing in.
+#[derive(Clone, Debug, Default)]
+pub struct TextEntryBuffer {
+    pub text: String,
+}
+
+/// Let the player type a single line of text into the [`TextEntryBuffer`] resource.
+pub fn text_entry<S: ToString>(gs: &mut State, ctx: &mut Rltk, title: S) -> TextEntryResult {
+    let mut buffer = gs.ecs.write_resource::<TextEntryBuffer>();
+    modal::text_entry(&gs.ecs, ctx, &title.to_string(), &mut buffer.text)
+}
+
+/// What the player did in the [`show_log()`] viewer.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum LogViewerResult {
+    NoResponse,
+    /// Scroll so that this many of the newest entries are hidden below the viewer.
+    Scroll(usize),
+    Close,
+}
+
+/// Show the whole game log, with the newest entries at the bottom and the turn
+/// each was logged on down the side. `offset` is how many of the newest entries
+/// have been scrolled out of view.
+pub fn show_log(ecs: &World, ctx: &mut Rltk, offset: usize) -> LogViewerResult {
+    let footer = tr("log-footer").to_string();
+
+    let log = ecs.fetch::<GameLog>();
+    let width = VIEW_WIDTH;
+    let height = VIEW_HEIGHT;
+    let page = (height - 2) as usize;
+    let max_offset = log.entries.len().saturating_sub(page);
+    let offset = offset.min(max_offset);
+    let end = log.entries.len() - offset;
+    let start = end.saturating_sub(page);
+    let shown = &log.entries[start..end];
+
+    let title = tr("log-title")
+        .arg("first", start + 1)
+        .arg("last", end)
+        .arg("total", log.entries.len())
+        .to_string();
+    let texts: Vec<String> = shown.iter().map(LogEntry::text).collect();
+    announce(
+        ecs,
+        "menu",
+        format!("{title}: {}. {footer}", texts.join("; ")),
+    );
+
+    let palette = ecs.fetch::<Palette>();
+    ctx.draw_box(
+        0,
+        0,
+        width - 1,
+        height - 1,
+        palette.text,
+        palette.background,
+    );
+    ctx.print_color(2, 0, palette.title, palette.background, &title);
+    ctx.print_color(2, height - 1, palette.title, palette.background, &footer);
+    for (y, entry) in (1..).zip(shown.iter()) {
+        let turn = format!("{:>6} ", entry.turn);
+        ctx.print_color(1, y, palette.muted, palette.background, &turn);
+        let x = 1 + turn.len() as i32;
+        print_log_entry(ctx, &palette, x, y, width - 1 - x, entry);
+    }
+
+    match ctx.key {
+        None => LogViewerResult::NoResponse,
+        Some(VirtualKeyCode::Escape) => LogViewerResult::Close,
+        Some(VirtualKeyCode::PageUp) => LogViewerResult::Scroll((offset + page).min(max_offset)),
+        Some(VirtualKeyCode::PageDown) => LogViewerResult::Scroll(offset.saturating_sub(page)),
+        Some(VirtualKeyCode::Up) => LogViewerResult::Scroll((offset + 1).min(max_offset)),
+        Some(VirtualKeyCode::Down) => LogViewerResult::Scroll(offset.saturating_sub(1)),
+        Some(_) => LogViewerResult::NoResponse,
+    }
+}
+
+/// Show a list of every note left on the current level, along with a minimap
+/// showing where they are. Selecting a note makes the player travel to it.
+pub fn show_notes(gs: &mut State, ctx: &mut Rltk) -> ItemMenuResult<Point> {
+    let map = gs.ecs.fetch::<Map>();
+    let debug_map_view = gs.ecs.fetch::<Config>().display.debug_map_view;
+    let player_pos = gs.ecs.fetch::<PlayerPos>();
+    let palette = *gs.ecs.fetch::<Palette>();
+
+    let white = palette.text;
+    let black = palette.background;
+    let accent_color = palette.info;
+
+    // The minimap shows the level at half scale, so every cell covers 2×2 tiles
+    let minimap_width = (map.width + 1) / 2;
+    let minimap_height = (map.height + 1) / 2;
+    let x = (VIEW_WIDTH - minimap_width) / 2 - 1;
+    let y = 1;
+    const MAX_LISTED_NOTES: usize = 20;
+    let listed_notes = map.notes.len().min(MAX_LISTED_NOTES);
+    let box_height = minimap_height + listed_notes.max(1) as i32 + 3;
+
+    ctx.draw_box(x, y, minimap_width + 1, box_height, white, black);
+    let title = tr("notes-title").to_string();
+    let footer = tr("footer-cancel").to_string();
+    ctx.print_color(x + 2, y, accent_color, black, &title);
+    ctx.print_color(x + 2, y + box_height, accent_color, black, &footer);
+
+    for my in 0..minimap_height {
+        for mx in 0..minimap_width {
+            let tiles = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .iter()
+                .map(|(dx, dy)| (mx * 2 + dx, my * 2 + dy))
+                .filter(|(tx, ty)| *tx < map.width && *ty < map.height)
+                .map(|(tx, ty)| map.xy_idx(tx, ty))
+                .filter(|idx| map.revealed_tiles[*idx] || debug_map_view)
+                .map(|idx| map.tiles[idx])
+                .collect::<Vec<_>>();
+
+            let (glyph, fg) = if tiles.contains(&TileType::DownStairs) {
+                ('>', palette.stairs)
+            } else if tiles.contains(&TileType::UpStairs) {
+                ('<', palette.stairs)
+            } else if tiles.iter().any(|tile| tile.is_water()) {
+                ('~', palette.shallow_water)
+            } else if tiles.contains(&TileType::Floor) {
+                ('.', RGB::from_f32(0.0, 0.5, 0.5))
+            } else if !tiles.is_empty() {
+                ('#', RGB::from_f32(0.0, 1.0, 0.0))
+            } else {
+                continue;
+            };
+            ctx.set(x + 1 + mx, y + 1 + my, fg, black, rltk::to_cp437(glyph));
+        }
+    }
+    ctx.set(
+        x + 1 + player_pos.x / 2,
+        y + 1 + player_pos.y / 2,
+        palette.title,
+        black,
+        rltk::to_cp437('@'),
+    );
+
+    announce(
+        &gs.ecs,
+        "menu",
+        lettered_list(
+            &title,
+            map.notes.iter().take(listed_notes).map(|note| {
+                tr("note-location")
+                    .arg("text", &note.text)
+                    .arg("x", note.x)
+                    .arg("y", note.y)
+            }),
+            &footer,
+        ),
+    );
+
+    let list_y = y + minimap_height + 2;
+    if map.notes.is_empty() {
+        ctx.print(x + 2, list_y, tr("notes-empty").to_string());
+    }
+    for (j, note) in map.notes.iter().take(listed_notes).enumerate() {
+        let letter = 97 + j as rltk::FontCharType;
+        ctx.set(
+            x + 1 + note.x / 2,
+            y + 1 + note.y / 2,
+            black,
+            accent_color,
+            letter,
+        );
+
+        ctx.set(x + 2, list_y + j as i32, white, black, rltk::to_cp437('('));
+        ctx.set(x + 3, list_y + j as i32, accent_color, black, letter);
+        ctx.set(x + 4, list_y + j as i32, white, black, rltk::to_cp437(')'));
+        ctx.print(x + 6, list_y + j as i32, &note.text);
+    }
+
+    match ctx.key {
+        None => ItemMenuResult::NoResponse,
+        Some(VirtualKeyCode::Escape) => ItemMenuResult::Cancel,
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < listed_notes as i32 {
+                let note = &map.notes[selection as usize];
+                ItemMenuResult::Selected(Point::new(note.x, note.y))
+            } else {
+                ItemMenuResult::NoResponse
+            }
+        }
+    }
+}
+
+/// Something that can be picked in the options menu.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum OptionsEntry {
+    /// Switch to a different [`HudLayout`].
+    Layout(HudLayout),
+    /// Turn the [`crate::accessibility::ScreenReader`] on or off.
+    ScreenReader,
+    /// Turn [`FriendlyFireConfirmation`] on or off.
+    FriendlyFireConfirmation,
+    /// Cycle through how hard the screen shakes, or turn it off.
+    ScreenShake,
+    /// Cycle through the [`crate::Palette`]s.
+    Palette,
+    /// Turn [`crate::render::MonsterHealthBars`] on or off.
+    MonsterHealthBars,
+    /// Cycle through the [`crate::Difficulty`] that new runs start on.
+    Difficulty,
+    /// Turn permadeath on or off for new runs.
+    Permadeath,
+    /// Turn hints on or off for new runs.
+    Hints,
+    /// Type in a seed for new runs to start from, or clear it to go back to random.
+    Seed,
+    /// Cycle through the [`crate::config::Font`]s the game starts up with.
+    Font,
+    /// Cycle through how many times bigger the font is drawn when the game starts.
+    Scale,
+}
+
+/// Why the font and scale picked in the options menu couldn't be saved, if they
+/// couldn't.
+#[derive(Clone, Debug, Default)]
+pub struct OptionsSaveError(pub Option<String>);
+
+/// The result of interaction with the options menu.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum OptionsMenuResult {
+    /// The user moved the highlight to a different entry, or did nothing.
+    NoSelection(OptionsEntry),
+    /// The user picked an entry.
+    Selected(OptionsEntry),
+    /// The user backed out of the menu.
+    Cancel,
+}
+
+/// Display the options menu, where the player can pick a [`HudLayout`] or toggle the
+/// screen reader, and handle input this tick.
+pub fn options_menu(gs: &mut State, ctx: &mut Rltk) -> OptionsMenuResult {
+    let RunState::OptionsMenu { selection } = *gs.ecs.fetch::<RunState>() else {
+        return OptionsMenuResult::Cancel;
+    };
+    let current = *gs.ecs.fetch::<HudLayout>();
+    let screen_reader_enabled = gs.ecs.fetch::<ScreenReader>().enabled;
+    let confirm_friendly_fire = gs.ecs.fetch::<FriendlyFireConfirmation>().enabled;
+    let screen_shake = *gs.ecs.fetch::<crate::screen_effects::ScreenShake>();
+    let health_bars = gs.ecs.fetch::<crate::render::MonsterHealthBars>().enabled;
+    let new_run = *gs.ecs.fetch::<crate::NewRunOptions>();
+    let (font, scale) = {
+        let config = gs.ecs.fetch::<Config>();
+        (config.display.font, config.display.scale)
+    };
+    let save_error = gs.ecs.fetch::<OptionsSaveError>().0.clone();
+    let on_off = |enabled: bool| {
+        if enabled {
+            tr("option-on")
+        } else {
+            tr("option-off")
+        }
+    };
+
+    let palette = *gs.ecs.fetch::<Palette>();
+    let bg_color = palette.background;
+    let title_color = palette.title;
+    let cur_option_color = palette.selected;
+    let option_color = palette.text;
+
+    let entries: Vec<OptionsEntry> = HudLayout::iter()
+        .map(OptionsEntry::Layout)
+        .chain([
+            OptionsEntry::ScreenReader,
+            OptionsEntry::FriendlyFireConfirmation,
+            OptionsEntry::ScreenShake,
+            OptionsEntry::Palette,
+            OptionsEntry::MonsterHealthBars,
+            OptionsEntry::Difficulty,
+            OptionsEntry::Permadeath,
+            OptionsEntry::Hints,
+            OptionsEntry::Seed,
+            OptionsEntry::Font,
+            OptionsEntry::Scale,
+        ])
+        .collect();
+    let label = |entry: &OptionsEntry| {
+        match entry {
+            OptionsEntry::Layout(layout) if *layout == current => {
+                tr("option-layout-current").arg("layout", l10n::name(layout.as_ref()))
+            }
+            OptionsEntry::Layout(layout) => {
+                tr("option-layout").arg("layout", l10n::name(layout.as_ref()))
+            }
+            OptionsEntry::ScreenReader => {
+                tr("option-screen-reader").arg("value", on_off(screen_reader_enabled))
+            }
+            OptionsEntry::FriendlyFireConfirmation => {
+                tr("option-friendly-fire").arg("value", on_off(confirm_friendly_fire))
+            }
+            OptionsEntry::ScreenShake => {
+                tr("option-screen-shake").arg("value", l10n::name(&screen_shake.to_string()))
+            }
+            OptionsEntry::Palette => {
+                tr("option-palette").arg("value", l10n::name(&palette.kind.to_string()))
+            }
+            OptionsEntry::MonsterHealthBars => {
+                tr("option-health-bars").arg("value", on_off(health_bars))
+            }
+            OptionsEntry::Difficulty => {
+                tr("option-difficulty").arg("value", l10n::name(&new_run.difficulty.to_string()))
+            }
+            OptionsEntry::Permadeath => {
+                tr("option-permadeath").arg("value", on_off(new_run.permadeath))
+            }
+            OptionsEntry::Hints => tr("option-hints").arg("value", on_off(new_run.hints)),
+            OptionsEntry::Seed => match new_run.fixed_seed {
+                Some(seed) => tr("option-seed").arg("value", seed),
+                None => tr("option-seed").arg("value", tr("option-seed-random")),
+            },
+            OptionsEntry::Font => {
+                let (width, height) = font.size();
+                tr("option-font").arg("value", format!("{width}×{height}"))
+            }
+            OptionsEntry::Scale => tr("option-scale").arg("value", scale),
+        }
+        .to_string()
+    };
+
+    let mut y = 15;
+    let title = tr("options-title").to_string();
+    ctx.print_color_centered(y, title_color, bg_color, &title);
+
+    y += 9;
+    for entry in entries.iter() {
+        let color = if *entry == selection {
+            cur_option_color
+        } else {
+            option_color
+        };
+        ctx.print_color_centered(y, color, bg_color, label(entry));
+        y += 1;
+    }
+    ctx.print_color_centered(
+        y + 1,
+        title_color,
+        bg_color,
+        tr("footer-go-back").to_string(),
+    );
+    if let Some(error) = &save_error {
+        ctx.print_color_centered(y + 3, palette.danger, bg_color, error);
+    }
+    announce(&gs.ecs, "menu", format!("{title}: {}", label(&selection)));
+
+    let idx = entries.iter().position(|e| *e == selection).unwrap_or(0);
+    match ctx.key {
+        None => OptionsMenuResult::NoSelection(selection),
+        Some(VirtualKeyCode::Escape) => OptionsMenuResult::Cancel,
+        Some(VirtualKeyCode::Up | VirtualKeyCode::K | VirtualKeyCode::Numpad8) => {
+            OptionsMenuResult::NoSelection(entries[(idx + entries.len() - 1) % entries.len()])
+        }
+        Some(VirtualKeyCode::Down | VirtualKeyCode::J | VirtualKeyCode::Numpad2) => {
+            OptionsMenuResult::NoSelection(entries[(idx + 1) % entries.len()])
+        }
+        Some(VirtualKeyCode::Return) => OptionsMenuResult::Selected(selection),
+        Some(_) => OptionsMenuResult::NoSelection(selection),
+    }
+}
+
+/// The result of interaction with the game over or victory screens.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum GameOverResult {
+    NoSelection,
+    QuitToMenu,
+}
+
+/// Show the player how their run went, now that they're dead.
+pub fn game_over(ecs: &World, ctx: &mut Rltk) -> GameOverResult {
+    run_summary(
+        ecs,
+        ctx,
+        &tr("game-over-title").to_string(),
+        ecs.fetch::<Palette>().title,
+    )
+}
+
+/// Congratulate the player on winning, and show them how their run went.
+pub fn victory(ecs: &World, ctx: &mut Rltk) -> GameOverResult {
+    run_summary(
+        ecs,
+        ctx,
+        &tr("victory-title").to_string(),
+        ecs.fetch::<Palette>().gold,
+    )
+}
+
+/// Show a screen headed by `title` with the [`RunStats`] for the run that just ended.
+fn run_summary(ecs: &World, ctx: &mut Rltk, title: &str, title_color: RGB) -> GameOverResult {
+    let stats = ecs.fetch::<RunStats>();
+    let options = ecs.fetch::<crate::RunOptions>();
+    let palette = ecs.fetch::<Palette>();
+    let bg_color = palette.background;
+    let text_color = palette.text;
+
+    ctx.print_color_centered(15, title_color, bg_color, title);
+    let lines = [
+        tr("summary-depth").arg("depth", stats.deepest_level),
+        tr("summary-kills").arg("kills", stats.monsters_killed),
+        tr("summary-damage")
+            .arg("dealt", stats.damage_dealt)
+            .arg("taken", stats.damage_taken),
+        tr("summary-items")
+            .arg("picked-up", stats.items_picked_up)
+            .arg("used", stats.items_used),
+        tr("summary-options").arg("options", options.summary()),
+    ]
+    .map(|line| line.to_string());
+    for (y, line) in (18..).zip(lines.iter()) {
+        ctx.print_color_centered(y, text_color, bg_color, line);
+    }
+    announce(
+        ecs,
+        "menu",
+        format!(
+            "{title} {} {}",
+            lines.join(" "),
+            tr("press-any-key-for-menu")
+        ),
+    );
+
+    ctx.print_color_centered(
+        24,
+        palette.selected,
+        bg_color,
+        tr("press-any-key-for-menu").to_string(),
+    );
+
+    match ctx.key {
+        None => GameOverResult::NoSelection,
+        Some(_) => GameOverResult::QuitToMenu,
+    }
+}
@@ -0,0 +1,482 @@
+//! Reusable modal dialogs: lettered menus and tables, yes/no questions, and text
+//! entry.
+//!
+//! Every modal is drawn as a box centred on the map with the same styling, handles
+//! keys the same way (letters to pick, ESCAPE to back out, PAGE UP/DOWN to flip
+//! through long lists), and mirrors itself to the screen reader.
+
+use rltk::{Rltk, VirtualKeyCode, RGB};
+use specs::prelude::*;
+
+use crate::accessibility::announce;
+use crate::camera::{VIEW_HEIGHT, VIEW_WIDTH};
+use crate::l10n::tr;
+use crate::{Palette, Rect};
+
+/// How many entries are shown on each page of a menu.
+const PAGE_SIZE: usize = 20;
+
+/// Menus are never narrower than this, however short their entries are.
+const MIN_MENU_WIDTH: i32 = 31;
+
+/// Space between the edge of a menu and its entries.
+const MENU_PADDING: i32 = 1;
+
+/// Space between the columns of a table.
+const COLUMN_GAP: usize = 2;
+
+/// The longest line of text that can be typed into [`text_entry()`].
+const MAX_TEXT_LENGTH: usize = 60;
+
+/// Things that can happen when the user does something with a menu.
+#[derive(PartialEq, Clone)]
+pub enum ItemMenuResult<T: PartialEq + Clone> {
+    Cancel,
+    NoResponse,
+    Selected(T),
+}
+
+/// How a menu looks and behaves.
+#[derive(Clone, Copy, Debug)]
+pub struct MenuStyle<'a> {
+    pub title: &'a str,
+    pub footer: &'a str,
+    /// The colour of the title, footer, and entry letters.
+    pub accent: RGB,
+    /// Whether ESCAPE backs out of the menu.
+    pub cancellable: bool,
+    /// Shown in place of the entries when there aren't any.
+    pub empty_message: &'a str,
+    /// The colour of each entry, in order. Entries without one are plain text.
+    pub row_colors: &'a [RGB],
+}
+
+impl<'a> MenuStyle<'a> {
+    pub fn new(title: &'a str, footer: &'a str, accent: RGB) -> Self {
+        Self {
+            title,
+            footer,
+            accent,
+            cancellable: true,
+            empty_message: "",
+            row_colors: &[],
+        }
+    }
+
+    /// Make the player pick something before the menu goes away.
+    pub fn not_cancellable(self) -> Self {
+        Self {
+            cancellable: false,
+            ..self
+        }
+    }
+
+    /// Show each entry in the matching colour from `colors`.
+    pub fn row_colors(self, colors: &'a [RGB]) -> Self {
+        Self {
+            row_colors: colors,
+            ..self
+        }
+    }
+
+    /// Show `message` when there's nothing in the menu.
+    pub fn empty_message(self, message: &'a str) -> Self {
+        Self {
+            empty_message: message,
+            ..self
+        }
+    }
+}
+
+/// Which page of a menu is being shown, so that it survives from one frame to the
+/// next. Only one menu is open at a time, so it's reset whenever the title changes.
+#[derive(Debug, Default)]
+pub struct MenuPage {
+    title: String,
+    page: usize,
+}
+
+/// Show a lettered list of `entries`, and return the index of whichever one the
+/// player picks.
+pub fn list_menu(
+    ecs: &World,
+    ctx: &mut Rltk,
+    style: &MenuStyle,
+    entries: &[String],
+) -> ItemMenuResult<usize> {
+    let rows: Vec<Vec<String>> = entries.iter().map(|entry| vec![entry.clone()]).collect();
+    table_menu(ecs, ctx, style, &rows)
+}
+
+/// Show a lettered table of `rows`, with each column lined up, and return the
+/// index of whichever row the player picks.
+pub fn table_menu(
+    ecs: &World,
+    ctx: &mut Rltk,
+    style: &MenuStyle,
+    rows: &[Vec<String>],
+) -> ItemMenuResult<usize> {
+    let lines = table_lines(rows);
+    let page_count = lines.len().div_ceil(PAGE_SIZE).max(1);
+
+    // Flip pages before drawing, so the new page shows up straight away
+    let page = {
+        let mut menu_page = ecs.fetch_mut::<MenuPage>();
+        if menu_page.title != style.title {
+            menu_page.title = style.title.to_string();
+            menu_page.page = 0;
+        }
+        match ctx.key {
+            Some(VirtualKeyCode::PageDown) => menu_page.page += 1,
+            Some(VirtualKeyCode::PageUp) => menu_page.page = menu_page.page.saturating_sub(1),
+            _ => {}
+        }
+        menu_page.page = menu_page.page.min(page_count - 1);
+        menu_page.page
+    };
+    let first = page * PAGE_SIZE;
+    let shown = &lines[first..lines.len().min(first + PAGE_SIZE)];
+
+    let footer = if page_count > 1 {
+        format!("{} PGUP/PGDN {}/{page_count}", style.footer, page + 1)
+    } else {
+        style.footer.to_string()
+    };
+
+    if shown.is_empty() {
+        announce(
+            ecs,
+            "menu",
+            format!("{}: {} {footer}", style.title, style.empty_message),
+        );
+    } else {
+        announce(ecs, "menu", lettered_list(style.title, shown, &footer));
+    }
+
+    // Widen the menu to fit long entries, like prices or unidentified scroll titles
+    let longest = shown
+        .iter()
+        .map(|line| line.len() as i32 + 4)
+        .chain([style.empty_message.len() as i32])
+        .max()
+        .unwrap_or(0);
+    let width = MIN_MENU_WIDTH
+        .max(longest + 1 + 2 * MENU_PADDING)
+        .max(style.title.len() as i32 + 2 + 2 * MENU_PADDING)
+        .max(footer.len() as i32 + 2 + 2 * MENU_PADDING);
+    let height = shown.len().max(1) as i32 + 2 + MENU_PADDING;
+    let palette = ecs.fetch::<Palette>();
+    let (x, y) = draw_frame(
+        ctx,
+        &palette,
+        width,
+        height,
+        style.title,
+        &footer,
+        style.accent,
+    );
+
+    if shown.is_empty() {
+        ctx.print(x, y, style.empty_message);
+    }
+    for (j, (y, line)) in (y..).zip(shown.iter()).enumerate() {
+        ctx.set(x, y, palette.text, palette.background, rltk::to_cp437('('));
+        ctx.set(
+            x + 1,
+            y,
+            style.accent,
+            palette.background,
+            97 + j as rltk::FontCharType,
+        );
+        ctx.set(
+            x + 2,
+            y,
+            palette.text,
+            palette.background,
+            rltk::to_cp437(')'),
+        );
+        match style.row_colors.get(first + j) {
+            Some(color) => ctx.print_color(x + 4, y, *color, palette.background, line),
+            None => ctx.print(x + 4, y, line),
+        }
+    }
+
+    let result = match ctx.key {
+        None => ItemMenuResult::NoResponse,
+        Some(VirtualKeyCode::Escape) if style.cancellable => ItemMenuResult::Cancel,
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && (selection as usize) < shown.len() {
+                ItemMenuResult::Selected(first + selection as usize)
+            } else {
+                ItemMenuResult::NoResponse
+            }
+        }
+    };
+
+    // Start over from the first page next time
+    if result != ItemMenuResult::NoResponse {
+        *ecs.fetch_mut::<MenuPage>() = MenuPage::default();
+    }
+
+    result
+}
+
+/// Lay out `rows` as lines of text, padding every column but the last to the same
+/// width.
+fn table_lines(rows: &[Vec<String>]) -> Vec<String> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|col| {
+            rows.iter()
+                .filter_map(|row| row.get(col))
+                .map(String::len)
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    rows.iter()
+        .map(|row| {
+            let mut line = String::new();
+            for (col, cell) in row.iter().enumerate() {
+                line.push_str(cell);
+                if col + 1 < row.len() {
+                    let padding = widths[col] - cell.len() + COLUMN_GAP;
+                    line.extend(std::iter::repeat_n(' ', padding));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// Draw the box that every modal sits in, centred on the map, with `title` along
+/// the top and `footer` along the bottom. Returns where the contents should start.
+fn draw_frame(
+    ctx: &mut Rltk,
+    palette: &Palette,
+    width: i32,
+    height: i32,
+    title: &str,
+    footer: &str,
+    accent: RGB,
+) -> (i32, i32) {
+    const MAP_RECT: Rect = Rect::new(0, 0, VIEW_WIDTH, VIEW_HEIGHT);
+    let (cx, cy) = MAP_RECT.center();
+    let rect = Rect::new_centered(cx, cy, width, height);
+
+    ctx.draw_box(
+        rect.x1,
+        rect.y1,
+        width,
+        rect.height(),
+        palette.text,
+        palette.background,
+    );
+    ctx.print_color(
+        rect.x1 + 1 + MENU_PADDING,
+        rect.y1,
+        accent,
+        palette.background,
+        title,
+    );
+    ctx.print_color(
+        rect.x1 + 1 + MENU_PADDING,
+        rect.y2,
+        accent,
+        palette.background,
+        footer,
+    );
+
+    (rect.x1 + 1 + MENU_PADDING, rect.y1 + 1 + MENU_PADDING)
+}
+
+/// Describe a menu for the screen reader, e.g. `Inventory: a) Dagger; b) Shield. ESCAPE to cancel`.
+pub fn lettered_list<I, T>(title: &str, entries: I, footer: &str) -> String
+where
+    I: IntoIterator<Item = T>,
+    T: std::fmt::Display,
+{
+    let entries: Vec<String> = entries
+        .into_iter()
+        .enumerate()
+        .map(|(j, entry)| format!("{}) {entry}", (b'a' + j as u8) as char))
+        .collect();
+
+    if entries.is_empty() {
+        format!("{title}: {}. {footer}", tr("menu-nothing"))
+    } else {
+        format!("{title}: {}. {footer}", entries.join("; "))
+    }
+}
+
+/// Ask the player a yes/no question. Returns `Selected(true)` if they answered yes.
+pub fn yes_no_dialogue<S: ToString>(
+    ecs: &World,
+    ctx: &mut Rltk,
+    question: S,
+) -> ItemMenuResult<bool> {
+    let footer = tr("footer-cancel").to_string();
+
+    let question = question.to_string();
+    announce(ecs, "menu", format!("{question} {}", tr("yes-no-spoken")));
+
+    let width = MIN_MENU_WIDTH.max(question.len() as i32 + 2 + 2 * MENU_PADDING);
+    let palette = ecs.fetch::<Palette>();
+    let (x, y) = draw_frame(
+        ctx,
+        &palette,
+        width,
+        3 + MENU_PADDING,
+        &question,
+        &footer,
+        palette.title,
+    );
+    ctx.print(x, y, tr("yes-no").to_string());
+
+    match ctx.key {
+        None => ItemMenuResult::NoResponse,
+        Some(VirtualKeyCode::Y) => ItemMenuResult::Selected(true),
+        Some(VirtualKeyCode::N) => ItemMenuResult::Selected(false),
+        Some(VirtualKeyCode::Escape) => ItemMenuResult::Cancel,
+        Some(_) => ItemMenuResult::NoResponse,
+    }
+}
+
+/// Show `text` in a box until the player presses any key. Returns `true` once
+/// they have.
+pub fn message_box(ecs: &World, ctx: &mut Rltk, title: &str, text: &str) -> bool {
+    let footer = tr("footer-any-key").to_string();
+
+    announce(ecs, "menu", format!("{title}: {text} {footer}"));
+
+    let lines = super::wrap_text(text, (MIN_MENU_WIDTH * 3 / 2) as usize);
+    let width = lines
+        .iter()
+        .map(|line| line.len() as i32)
+        .chain([title.len() as i32, footer.chars().count() as i32])
+        .max()
+        .unwrap_or(0)
+        .max(MIN_MENU_WIDTH - 2 * MENU_PADDING)
+        + 2
+        + 2 * MENU_PADDING;
+    let palette = ecs.fetch::<Palette>();
+    let (x, y) = draw_frame(
+        ctx,
+        &palette,
+        width,
+        lines.len() as i32 + 1 + 2 * MENU_PADDING,
+        title,
+        &footer,
+        palette.title,
+    );
+    for (i, line) in lines.iter().enumerate() {
+        ctx.print(x, y + i as i32, line);
+    }
+
+    ctx.key.is_some()
+}
+
+/// The result of a tick of text entry.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum TextEntryResult {
+    Cancel,
+    NoResponse,
+    /// The player pressed <kbd>Enter</kbd> to finish typing.
+    Done,
+}
+
+/// Let the player type a single line of text into `text`.
+pub fn text_entry(ecs: &World, ctx: &mut Rltk, title: &str, text: &mut String) -> TextEntryResult {
+    let footer = tr("footer-text-entry").to_string();
+
+    let result = match ctx.key {
+        None => TextEntryResult::NoResponse,
+        Some(VirtualKeyCode::Escape) => TextEntryResult::Cancel,
+        Some(VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter) => TextEntryResult::Done,
+        Some(VirtualKeyCode::Back) => {
+            text.pop();
+            TextEntryResult::NoResponse
+        }
+        Some(key) => {
+            if let Some(c) = key_to_char(key, ctx.shift) {
+                if text.len() < MAX_TEXT_LENGTH {
+                    text.push(c);
+                }
+            }
+            TextEntryResult::NoResponse
+        }
+    };
+
+    announce(ecs, "menu", format!("{title}: {text}. {footer}"));
+
+    let width = MAX_TEXT_LENGTH as i32 + 3 + 2 * MENU_PADDING;
+    let palette = ecs.fetch::<Palette>();
+    let (x, y) = draw_frame(
+        ctx,
+        &palette,
+        width,
+        3 + MENU_PADDING,
+        title,
+        &footer,
+        palette.title,
+    );
+    ctx.print(x, y, &*text);
+    ctx.set_bg(x + text.len() as i32, y, palette.selected);
+
+    result
+}
+
+/// Convert a key press into the character it would type, if any.
+fn key_to_char(key: VirtualKeyCode, shift: bool) -> Option<char> {
+    use VirtualKeyCode::*;
+
+    let c = match key {
+        A => 'a',
+        B => 'b',
+        C => 'c',
+        D => 'd',
+        E => 'e',
+        F => 'f',
+        G => 'g',
+        H => 'h',
+        I => 'i',
+        J => 'j',
+        K => 'k',
+        L => 'l',
+        M => 'm',
+        N => 'n',
+        O => 'o',
+        P => 'p',
+        Q => 'q',
+        R => 'r',
+        S => 's',
+        T => 't',
+        U => 'u',
+        V => 'v',
+        W => 'w',
+        X => 'x',
+        Y => 'y',
+        Z => 'z',
+        Key0 | Numpad0 => return Some('0'),
+        Key1 | Numpad1 => return Some('1'),
+        Key2 | Numpad2 => return Some('2'),
+        Key3 | Numpad3 => return Some('3'),
+        Key4 | Numpad4 => return Some('4'),
+        Key5 | Numpad5 => return Some('5'),
+        Key6 | Numpad6 => return Some('6'),
+        Key7 | Numpad7 => return Some('7'),
+        Key8 | Numpad8 => return Some('8'),
+        Key9 | Numpad9 => return Some('9'),
+        Space => return Some(' '),
+        Minus => return Some(if shift { '_' } else { '-' }),
+        Comma => return Some(','),
+        Period => return Some('.'),
+        Apostrophe => return Some(if shift { '"' } else { '\'' }),
+        Slash => return Some(if shift { '?' } else { '/' }),
+        _ => return None,
+    };
+
+    Some(if shift { c.to_ascii_uppercase() } else { c })
+}
@@ -0,0 +1,194 @@
+//! A local leaderboard of the best finished runs, kept in its own file next to the
+//! game. Every run that ends, in death or victory, is scored and put on the table
+//! if it's good enough.
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::{storage, GameLog, PlayerEntity, Purse, RunStats};
+
+/// Where the high score table is kept, relative to the working directory.
+pub const HIGH_SCORES_PATH: &str = "./high_scores.ron";
+
+/// How many runs the table holds.
+pub const MAX_HIGH_SCORES: usize = 10;
+
+/// Points for each level of the dungeon reached.
+const POINTS_PER_DEPTH: i32 = 100;
+
+/// Points for each monster killed.
+const POINTS_PER_KILL: i32 = 10;
+
+/// One point is taken off for every this many turns the run took.
+const TURNS_PER_PENALTY_POINT: i32 = 10;
+
+/// Points for winning the game outright.
+const VICTORY_BONUS: i32 = 1000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HighScoresError {
+    #[error("Unable to read high scores from `{path}`")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("The high scores at `{path}` are corrupted")]
+    Parse {
+        path: String,
+        source: ron::error::SpannedError,
+    },
+
+    #[error("Failed to serialize high scores")]
+    Serialization { source: ron::Error },
+
+    #[error("Unable to write high scores to `{path}`")]
+    Write {
+        path: String,
+        source: std::io::Error,
+    },
+}
+
+/// How a finished run went.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    pub score: i32,
+    pub deepest_level: i32,
+    pub monsters_killed: u32,
+    pub gold: i32,
+    pub turns: u32,
+    pub won: bool,
+    /// When the run ended, in seconds since the Unix epoch.
+    pub finished_at: u64,
+}
+
+impl ScoreEntry {
+    /// Score a run: deeper and bloodier is better, gold counts for its worth, and
+    /// dawdling costs a little. Winning is worth the most of all.
+    pub fn new(stats: &RunStats, gold: i32, turns: u32, won: bool) -> Self {
+        let score = stats.deepest_level * POINTS_PER_DEPTH
+            + stats.monsters_killed as i32 * POINTS_PER_KILL
+            + gold
+            - turns as i32 / TURNS_PER_PENALTY_POINT
+            + if won { VICTORY_BONUS } else { 0 };
+
+        Self {
+            score: score.max(0),
+            deepest_level: stats.deepest_level,
+            monsters_killed: stats.monsters_killed,
+            gold,
+            turns,
+            won,
+            finished_at: storage::unix_time(),
+        }
+    }
+}
+
+/// The best [`MAX_HIGH_SCORES`] runs, best first.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HighScores {
+    pub entries: Vec<ScoreEntry>,
+    /// Where the run that was just finished ended up in [`Self::entries`], if it
+    /// made the table.
+    #[serde(skip)]
+    pub latest: Option<usize>,
+}
+
+impl HighScores {
+    /// Load the table from [`HIGH_SCORES_PATH`]. It's empty if nothing's been
+    /// saved there yet.
+    pub fn load() -> Result<Self, HighScoresError> {
+        let data = match storage::read(HIGH_SCORES_PATH) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(source) => {
+                return Err(HighScoresError::Read {
+                    path: HIGH_SCORES_PATH.to_string(),
+                    source,
+                })
+            }
+        };
+
+        ron::de::from_bytes(&data).map_err(|source| HighScoresError::Parse {
+            path: HIGH_SCORES_PATH.to_string(),
+            source,
+        })
+    }
+
+    /// Write the table to [`HIGH_SCORES_PATH`].
+    pub fn save(&self) -> Result<(), HighScoresError> {
+        let data = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|source| HighScoresError::Serialization { source })?;
+        storage::write(HIGH_SCORES_PATH, data.as_bytes()).map_err(|source| HighScoresError::Write {
+            path: HIGH_SCORES_PATH.to_string(),
+            source,
+        })
+    }
+
+    /// Put `entry` on the table, if it's good enough, and remember it as the
+    /// latest run. Ties go to whoever got there first.
+    fn insert(&mut self, entry: ScoreEntry) {
+        let rank = self
+            .entries
+            .iter()
+            .position(|other| other.score < entry.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_HIGH_SCORES);
+        self.latest = (rank < MAX_HIGH_SCORES).then_some(rank);
+    }
+}
+
+/// Score the run that just ended, and save it to the table if it made the cut.
+/// Call before the run is reset.
+pub fn record_run(ecs: &World, won: bool) -> Result<(), HighScoresError> {
+    let player = **ecs.fetch::<PlayerEntity>();
+    let gold = ecs
+        .read_storage::<Purse>()
+        .get(player)
+        .map_or(0, |purse| purse.gold);
+    let entry = ScoreEntry::new(
+        &ecs.fetch::<RunStats>(),
+        gold,
+        ecs.fetch::<GameLog>().turn,
+        won,
+    );
+
+    let mut high_scores = ecs.fetch_mut::<HighScores>();
+    high_scores.insert(entry);
+    high_scores.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: i32) -> ScoreEntry {
+        ScoreEntry {
+            score,
+            deepest_level: 1,
+            monsters_killed: 0,
+            gold: 0,
+            turns: 0,
+            won: false,
+            finished_at: 0,
+        }
+    }
+
+    #[test]
+    fn keeps_only_the_best_runs() {
+        let mut high_scores = HighScores::default();
+        for score in 0..MAX_HIGH_SCORES as i32 {
+            high_scores.insert(entry(score * 10));
+        }
+
+        high_scores.insert(entry(45));
+        assert_eq!(high_scores.latest, Some(5));
+        assert_eq!(high_scores.entries.len(), MAX_HIGH_SCORES);
+        assert_eq!(high_scores.entries.last().map(|e| e.score), Some(10));
+
+        high_scores.insert(entry(5));
+        assert_eq!(high_scores.latest, None);
+        assert!(high_scores.entries.iter().all(|e| e.score != 5));
+    }
+}
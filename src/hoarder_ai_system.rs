@@ -0,0 +1,178 @@
+use rltk::Point;
+use specs::prelude::*;
+
+use crate::{
+    AiState, EntityMoved, HeardNoise, Hoarder, HomeLocation, InBackpack, Item, LastSeenPlayer, Map,
+    PlayerPos, Position, RunState, StatusEffects, Viewshed, WantsToDropItem, WantsToPickupItem,
+};
+
+/// A system that makes [`Hoarder`]s collect any items they can see and carry
+/// them back to their [`HomeLocation`].
+///
+/// Hoarders only go looking for loot while the player is out of sight;
+/// otherwise they fight like any other monster (see [`crate::MonsterAI`]).
+pub struct HoarderAI;
+
+/// What a hoarder has decided to do this turn.
+enum HoarderAction {
+    PickUp(Entity),
+    Drop(Entity),
+    MoveTowards(Point),
+}
+
+impl<'a> System<'a> for HoarderAI {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, PlayerPos>,
+        ReadExpect<'a, RunState>,
+        Entities<'a>,
+        WriteStorage<'a, Viewshed>,
+        ReadStorage<'a, Hoarder>,
+        ReadStorage<'a, HomeLocation>,
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, StatusEffects>,
+        ReadStorage<'a, HeardNoise>,
+        ReadStorage<'a, LastSeenPlayer>,
+        WriteStorage<'a, WantsToPickupItem>,
+        WriteStorage<'a, WantsToDropItem>,
+        WriteStorage<'a, EntityMoved>,
+        ReadStorage<'a, AiState>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut map,
+            player_pos,
+            runstate,
+            entities,
+            mut viewsheds,
+            hoarders,
+            homes,
+            mut positions,
+            items,
+            backpack,
+            status_effects,
+            heard_noises,
+            last_seen,
+            mut wants_pickup,
+            mut wants_drop,
+            mut entity_moved,
+            ai_states,
+        ): Self::SystemData,
+    ) {
+        // Only run when it's the monsters' turn!
+        if *runstate != RunState::MonsterTurn {
+            return;
+        }
+
+        let mut actions = Vec::new();
+
+        for (entity, hoarder, home, viewshed, pos) in
+            (&entities, &hoarders, &homes, &viewsheds, &positions).join()
+        {
+            // Confused hoarders can't do anything, routing or surrendered ones have other
+            // things on their mind, hoarders that can see the player are busy fighting,
+            // and hoarders that heard something or are chasing where they last saw the
+            // player are off investigating.
+            if ai_states
+                .get(entity)
+                .is_some_and(|state| *state != AiState::Fighting)
+                || status_effects
+                    .get(entity)
+                    .and_then(StatusEffects::skips_turn)
+                    .is_some()
+                || heard_noises.contains(entity)
+                || last_seen.contains(entity)
+                || viewshed.visible_tiles.contains(&*player_pos)
+            {
+                continue;
+            }
+
+            let here = Point::new(pos.x, pos.y);
+            let home = Point::new(home.x, home.y);
+            let carried: Vec<Entity> = (&entities, &backpack)
+                .join()
+                .filter(|(_, in_pack)| in_pack.owner == entity)
+                .map(|(item, _)| item)
+                .collect();
+
+            // Look for the closest visible item that isn't already in the nest
+            let target_item = if carried.len() < hoarder.capacity {
+                (&entities, &items, &positions)
+                    .join()
+                    .map(|(item, _, item_pos)| (item, Point::new(item_pos.x, item_pos.y)))
+                    .filter(|(_, item_pos)| {
+                        *item_pos != home && viewshed.visible_tiles.contains(item_pos)
+                    })
+                    .min_by(|(_, a), (_, b)| {
+                        let dist_a = rltk::DistanceAlg::Pythagoras.distance2d(here, *a);
+                        let dist_b = rltk::DistanceAlg::Pythagoras.distance2d(here, *b);
+                        dist_a.total_cmp(&dist_b)
+                    })
+            } else {
+                None
+            };
+
+            let action = match target_item {
+                Some((item, item_pos)) if item_pos == here => HoarderAction::PickUp(item),
+                Some((_, item_pos)) => HoarderAction::MoveTowards(item_pos),
+                None if here == home => match carried.first() {
+                    Some(item) => HoarderAction::Drop(*item),
+                    None => continue,
+                },
+                None => HoarderAction::MoveTowards(home),
+            };
+            actions.push((entity, action));
+        }
+
+        for (entity, action) in actions {
+            match action {
+                HoarderAction::PickUp(item) => {
+                    wants_pickup
+                        .insert(
+                            entity,
+                            WantsToPickupItem {
+                                collected_by: entity,
+                                item,
+                            },
+                        )
+                        .expect("Unable to insert hoarder's intent to pick up an item");
+                }
+
+                HoarderAction::Drop(item) => {
+                    wants_drop
+                        .insert(entity, WantsToDropItem { item })
+                        .expect("Unable to insert hoarder's intent to drop an item");
+                }
+
+                HoarderAction::MoveTowards(target) => {
+                    let pos = positions.get_mut(entity).unwrap();
+                    let path = rltk::a_star_search(
+                        map.xy_idx(pos.x, pos.y),
+                        map.xy_idx(target.x, target.y),
+                        &*map,
+                    );
+
+                    if path.success && path.steps.len() > 1 {
+                        let mut idx = map.xy_idx(pos.x, pos.y);
+                        map.blocked.set(idx, false);
+
+                        (pos.x, pos.y) = map.idx_xy(path.steps[1]);
+                        idx = map.xy_idx(pos.x, pos.y);
+                        map.blocked.set(idx, true);
+
+                        if let Some(viewshed) = viewsheds.get_mut(entity) {
+                            viewshed.dirty = true;
+                        }
+                        entity_moved
+                            .insert(entity, EntityMoved)
+                            .expect("Unable to insert marker for hoarder movement");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use specs::prelude::*;
+
+use crate::effects::{EffectQueue, EffectType, Targets};
+use crate::l10n::tr;
+use crate::{GameLog, HungerClock, HungerState, PlayerEntity, RunState};
+
+/// How much damage a turn spent starving does.
+const STARVATION_DAMAGE: i32 = 1;
+
+/// A system that winds down every [`HungerClock`] once per turn, moving whoever's
+/// carrying it on to the next [`HungerState`] when it runs out. Anything that's
+/// starving takes a little damage every turn until it eats.
+pub struct HungerSystem;
+
+impl<'a> System<'a> for HungerSystem {
+    type SystemData = (
+        ReadExpect<'a, PlayerEntity>,
+        ReadExpect<'a, RunState>,
+        Entities<'a>,
+        WriteStorage<'a, HungerClock>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(
+        &mut self,
+        (player_entity, runstate, entities, mut hunger_clocks, mut effect_queue, mut log): Self::SystemData,
+    ) {
+        // Every turn starts with the player's, so only get hungrier once per turn
+        if *runstate != RunState::PlayerTurn {
+            return;
+        }
+
+        for (entity, clock) in (&entities, &mut hunger_clocks).join() {
+            let is_player = entity == **player_entity;
+
+            if clock.state == HungerState::Starving {
+                effect_queue.add_effect(
+                    EffectType::Damage {
+                        amount: STARVATION_DAMAGE,
+                        attacker: None,
+                    },
+                    Targets::Single { target: entity },
+                );
+                if is_player {
+                    log.entry()
+                        .colored_message(
+                            &tr("starvation-damage")
+                                .arg("damage", tr("hit-points").arg("amount", STARVATION_DAMAGE)),
+                            rltk::RGB::named(rltk::RED),
+                        )
+                        .log();
+                }
+                continue;
+            }
+
+            clock.turns -= 1;
+            if clock.turns > 0 {
+                continue;
+            }
+
+            *clock = HungerClock::new(clock.state.next());
+            if is_player {
+                match clock.state {
+                    HungerState::Normal => log.log(tr("hunger-normal")),
+                    HungerState::Hungry => log
+                        .entry()
+                        .colored_message(&tr("hunger-hungry"), rltk::RGB::named(rltk::ORANGE))
+                        .log(),
+                    HungerState::Starving => log
+                        .entry()
+                        .colored_message(&tr("hunger-starving"), rltk::RGB::named(rltk::RED))
+                        .log(),
+                    HungerState::WellFed => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Unidentified items. Potions and scrolls start out under obfuscated names
+//! ("swirly purple potion"), which are shuffled every run. Using one, or paying
+//! the sage, reveals what that kind of item really is from then on.
+
+use rltk::{RandomNumberGenerator, RGB};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::{l10n, Name, Palette, Rarity};
+
+/// Every potion that gets an obfuscated name.
+const POTIONS: &[&str] = &[
+    "Health Potion",
+    "Haste Potion",
+    "Strength Potion",
+    "Fortitude Potion",
+];
+
+/// Every scroll that gets an obfuscated name.
+const SCROLLS: &[&str] = &[
+    "Magic Missile Scroll",
+    "Fireball Scroll",
+    "Confusion Scroll",
+    "Slowness Scroll",
+    "Fear Scroll",
+    "Magic Mapping Scroll",
+    "Town Portal Scroll",
+    "Enchant Weapon Scroll",
+    "Enchant Armor Scroll",
+];
+
+const POTION_ADJECTIVES: &[&str] = &[
+    "swirly", "bubbly", "murky", "fizzy", "cloudy", "smoky", "glowing", "oily",
+];
+
+const POTION_COLORS: &[&str] = &[
+    "purple", "green", "amber", "crimson", "silver", "black", "blue", "pink",
+];
+
+const SCROLL_SYLLABLES: &[&str] = &[
+    "zel", "gor", "ash", "vex", "mun", "tor", "ith", "ka", "lo", "bra", "nix", "quo",
+];
+
+/// The obfuscated names given to each kind of unidentified item this run, and
+/// which kinds the player has identified.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MasterItemNames {
+    /// Obfuscated names, keyed by the item's real name.
+    obfuscated: FxHashMap<String, String>,
+    identified: FxHashSet<String>,
+}
+
+impl MasterItemNames {
+    /// Come up with a fresh set of obfuscated names for a new run.
+    pub fn new(rng: &mut RandomNumberGenerator) -> Self {
+        let mut obfuscated = FxHashMap::default();
+        let mut used = FxHashSet::default();
+
+        for potion in POTIONS {
+            let name = loop {
+                let adjective = POTION_ADJECTIVES[rng.range(0, POTION_ADJECTIVES.len())];
+                let color = POTION_COLORS[rng.range(0, POTION_COLORS.len())];
+                let name = format!("{adjective} {color} potion");
+                if used.insert(name.clone()) {
+                    break name;
+                }
+            };
+            obfuscated.insert(potion.to_string(), name);
+        }
+
+        for scroll in SCROLLS {
+            let name = loop {
+                let syllables = rng.range(2, 5);
+                let title: String = (0..syllables)
+                    .map(|_| SCROLL_SYLLABLES[rng.range(0, SCROLL_SYLLABLES.len())])
+                    .collect();
+                let name = format!("scroll titled \"{}\"", title.to_uppercase());
+                if used.insert(name.clone()) {
+                    break name;
+                }
+            };
+            obfuscated.insert(scroll.to_string(), name);
+        }
+
+        Self {
+            obfuscated,
+            identified: FxHashSet::default(),
+        }
+    }
+
+    /// Whether the player knows what items called `name` really are.
+    pub fn is_identified(&self, name: &str) -> bool {
+        !self.obfuscated.contains_key(name) || self.identified.contains(name)
+    }
+
+    /// Reveal what items called `name` really are. Returns `true` if they weren't
+    /// already identified.
+    pub fn identify(&mut self, name: &str) -> bool {
+        !self.is_identified(name) && self.identified.insert(name.to_string())
+    }
+
+    /// What the player should see an item called `name` as, in their language.
+    pub fn display_name(&self, name: &str) -> String {
+        if self.is_identified(name) {
+            l10n::name(name)
+        } else {
+            self.obfuscated[name].clone()
+        }
+    }
+
+    /// How rare the player should see an item called `name` as. Items that haven't
+    /// been identified all look common, so that their rarity doesn't give away what
+    /// they are.
+    pub fn display_rarity(&self, name: &str, rarity: Option<&Rarity>) -> Rarity {
+        match rarity {
+            Some(rarity) if self.is_identified(name) => *rarity,
+            _ => Rarity::Common,
+        }
+    }
+}
+
+/// What the player should see `entity` as, taking unidentified items into account.
+pub fn display_name(ecs: &World, entity: Entity) -> Option<String> {
+    ecs.read_storage::<Name>()
+        .get(entity)
+        .map(|name| ecs.fetch::<MasterItemNames>().display_name(&name.name))
+}
+
+/// The colour `entity`'s name should be shown in, going by how rare the player
+/// knows it to be.
+pub fn display_color(ecs: &World, entity: Entity) -> RGB {
+    let rarity = ecs.read_storage::<Name>().get(entity).map(|name| {
+        ecs.fetch::<MasterItemNames>()
+            .display_rarity(&name.name, ecs.read_storage::<Rarity>().get(entity))
+    });
+    ecs.fetch::<Palette>().rarity(rarity.unwrap_or_default())
+}
@@ -1,30 +1,143 @@
+use std::ops::Deref;
+
+use rltk::{RandomNumberGenerator, RGB};
 use specs::prelude::*;
+use specs::storage::MaskedStorage;
 
+use crate::crime::{self, Crime};
+use crate::effects::{EffectQueue, EffectType, Targets, PARTICLE_LIFETIME_MS};
+use crate::l10n::{self, tr};
 use crate::{
-    AreaOfEffect, CombatStats, Confusion, Consumable, GameLog, InBackpack, InflictsDamage, Map,
-    Name, PlayerEntity, Position, ProvidesHealing, SufferDamage, WantsToDropItem,
-    WantsToPickupItem, WantsToUseItem,
+    blast_area, identification, resolve_trajectory, AreaOfEffect, Attributes, CombatStats,
+    Consumable, DefenseBonus, EnchantTarget, Enchanted, Enchants, EntityMoved, EquipmentSlot,
+    Equippable, Equipped, GameEvent, GameEvents, GameLog, Gold, HungerClock, HungerState,
+    InBackpack, InStash, InflictsDamage, InflictsStatusEffect, LevelId, MakesNoise, Mana, Map,
+    MasterItemNames, MeleePowerBonus, Name, Palette, PlayerEntity, PlayerPos, Position,
+    ProvidesFood, ProvidesHealing, ProvidesMapping, ProvidesTownPortal, Purse, Ranged, Rarity,
+    Shatters, Skills, Spell, Value, WantsToDropItem, WantsToMakeNoise, WantsToPickupItem,
+    WantsToThrowItem, WantsToTransferItem, WantsToUseItem, Weight, WinsGame, THROW_RANGE,
 };
 
+/// How many pounds of items `owner` is carrying, in their backpack or equipped.
+pub fn carried_weight<B, E>(
+    owner: Entity,
+    weights: &ReadStorage<Weight>,
+    backpack: &Storage<InBackpack, B>,
+    equipped: &Storage<Equipped, E>,
+) -> i32
+where
+    B: Deref<Target = MaskedStorage<InBackpack>>,
+    E: Deref<Target = MaskedStorage<Equipped>>,
+{
+    let in_backpack = (backpack, weights)
+        .join()
+        .filter(|(pack_item, _)| pack_item.owner == owner)
+        .map(|(_, weight)| weight.pounds);
+    let worn = (equipped, weights)
+        .join()
+        .filter(|(equipped, _)| equipped.owner == owner)
+        .map(|(_, weight)| weight.pounds);
+    in_backpack.chain(worn).sum()
+}
+
+/// Whether `carrier` has room for `item` on top of everything they're already
+/// carrying. Only those with [`Attributes`] have any limit on what they can carry.
+fn has_room_for<B, E>(
+    carrier: Entity,
+    item: Entity,
+    (weights, attributes): (&ReadStorage<Weight>, &ReadStorage<Attributes>),
+    backpack: &Storage<InBackpack, B>,
+    equipped: &Storage<Equipped, E>,
+) -> bool
+where
+    B: Deref<Target = MaskedStorage<InBackpack>>,
+    E: Deref<Target = MaskedStorage<Equipped>>,
+{
+    attributes.get(carrier).is_none_or(|attributes| {
+        let pounds = weights.get(item).map_or(0, |weight| weight.pounds);
+        carried_weight(carrier, weights, backpack, equipped) + pounds <= attributes.carry_capacity()
+    })
+}
+
+/// Whether the player has room for `item`, like [`has_room_for()`]. If they
+/// don't, they're told that it's too heavy.
+pub(crate) fn player_has_room_for(ecs: &World, item: Entity) -> bool {
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let has_room = has_room_for(
+        player_entity,
+        item,
+        (
+            &ecs.read_storage::<Weight>(),
+            &ecs.read_storage::<Attributes>(),
+        ),
+        &ecs.read_storage::<InBackpack>(),
+        &ecs.read_storage::<Equipped>(),
+    );
+    if !has_room {
+        let name = identification::display_name(ecs, item).unwrap_or_default();
+        ecs.fetch_mut::<GameLog>()
+            .log(tr("too-heavy").arg("item", name));
+    }
+    has_room
+}
+
 /// Searches for any entities that [`WantsToPickupItem`] and let's them pick
-/// it up and put it into their backpack for later use.
+/// it up and put it into their backpack for later use. Anything that would take
+/// them over their [`Attributes::carry_capacity()`] is left where it is.
 pub struct ItemCollectionSystem;
 
 impl<'a> System<'a> for ItemCollectionSystem {
     type SystemData = (
         ReadExpect<'a, PlayerEntity>,
         WriteExpect<'a, GameLog>,
+        WriteExpect<'a, GameEvents>,
         WriteStorage<'a, WantsToPickupItem>,
         WriteStorage<'a, Position>,
         ReadStorage<'a, Name>,
         WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, Weight>,
+        ReadStorage<'a, Attributes>,
+        ReadExpect<'a, MasterItemNames>,
+        ReadStorage<'a, Rarity>,
+        ReadExpect<'a, Palette>,
     );
 
     fn run(
         &mut self,
-        (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack): Self::SystemData,
+        (
+            player_entity,
+            mut gamelog,
+            mut events,
+            mut wants_pickup,
+            mut positions,
+            names,
+            mut backpack,
+            equipped,
+            weights,
+            attributes,
+            item_names,
+            rarities,
+            palette,
+        ): Self::SystemData,
     ) {
         for pickup in wants_pickup.join() {
+            if !has_room_for(
+                pickup.collected_by,
+                pickup.item,
+                (&weights, &attributes),
+                &backpack,
+                &equipped,
+            ) {
+                if pickup.collected_by == **player_entity {
+                    gamelog.log(tr("too-heavy").arg(
+                        "item",
+                        item_names.display_name(&names.get(pickup.item).unwrap().name),
+                    ));
+                }
+                continue;
+            }
+
             positions.remove(pickup.item);
             backpack
                 .insert(
@@ -34,11 +147,17 @@ impl<'a> System<'a> for ItemCollectionSystem {
                     },
                 )
                 .expect("Unable to insert backpack entry when entity tried to pick up item");
+            events.push(GameEvent::ItemPickedUp {
+                collector: pickup.collected_by,
+                item: pickup.item,
+            });
 
             if pickup.collected_by == **player_entity {
-                gamelog.log(format!(
-                    "You pick up the {}.",
-                    names.get(pickup.item).unwrap()
+                let name = &names.get(pickup.item).unwrap().name;
+                gamelog.log(tr("pick-up-item").colored_arg(
+                    "item",
+                    item_names.display_name(name),
+                    palette.rarity(item_names.display_rarity(name, rarities.get(pickup.item))),
                 ))
             }
         }
@@ -47,6 +166,129 @@ impl<'a> System<'a> for ItemCollectionSystem {
     }
 }
 
+/// Moves items between backpacks for any entity that [`WantsToTransferItem`], like
+/// the player looting a [`crate::Container`] or putting things away in one. Nothing is
+/// moved into a backpack that doesn't have room for it.
+pub struct ItemTransferSystem;
+
+impl<'a> System<'a> for ItemTransferSystem {
+    type SystemData = (
+        ReadExpect<'a, PlayerEntity>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToTransferItem>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, Weight>,
+        ReadStorage<'a, Attributes>,
+        ReadExpect<'a, MasterItemNames>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            player_entity,
+            mut gamelog,
+            entities,
+            mut wants_transfer,
+            names,
+            mut backpack,
+            equipped,
+            weights,
+            attributes,
+            item_names,
+        ): Self::SystemData,
+    ) {
+        for (entity, transfer) in (&entities, &wants_transfer).join() {
+            // The item might have been used up, or taken by someone else, by now
+            if backpack
+                .get(transfer.item)
+                .is_none_or(|pack_item| pack_item.owner != transfer.from)
+            {
+                continue;
+            }
+
+            let item_name = item_names.display_name(&names.get(transfer.item).unwrap().name);
+            if !has_room_for(
+                transfer.to,
+                transfer.item,
+                (&weights, &attributes),
+                &backpack,
+                &equipped,
+            ) {
+                if entity == **player_entity {
+                    gamelog.log(tr("too-heavy").arg("item", item_name));
+                }
+                continue;
+            }
+
+            backpack
+                .insert(transfer.item, InBackpack { owner: transfer.to })
+                .expect("Unable to move item into another backpack");
+
+            if entity == **player_entity {
+                let (id, other) = if transfer.to == entity {
+                    ("take-item", transfer.from)
+                } else {
+                    ("store-item", transfer.to)
+                };
+                let container = names
+                    .get(other)
+                    .map_or_else(String::new, |name| l10n::name(&name.name));
+                gamelog.log(tr(id).arg("item", item_name).arg("container", container));
+            }
+        }
+
+        wants_transfer.clear();
+    }
+}
+
+/// Anything with a [`Purse`] that walked onto a pile of [`Gold`] this tick picks it up.
+pub struct GoldCollectionSystem;
+
+impl<'a> System<'a> for GoldCollectionSystem {
+    type SystemData = (
+        ReadExpect<'a, PlayerEntity>,
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        ReadStorage<'a, EntityMoved>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Gold>,
+        WriteStorage<'a, Purse>,
+    );
+
+    fn run(
+        &mut self,
+        (player_entity, map, mut gamelog, entities, entity_moved, positions, gold, mut purses): Self::SystemData,
+    ) {
+        for (entity, _moved, pos, purse) in
+            (&entities, &entity_moved, &positions, &mut purses).join()
+        {
+            let idx = map.xy_idx(pos.x, pos.y);
+            for pile in map.tile_content[idx].iter().copied() {
+                let Some(gold) = gold.get(pile) else {
+                    continue;
+                };
+
+                purse.gold += gold.amount;
+                entities
+                    .delete(pile)
+                    .expect("Unable to delete picked-up gold");
+                if entity == **player_entity {
+                    let msg = tr("pick-up-gold").colored_arg(
+                        "gold",
+                        tr("gold-amount").arg("amount", gold.amount),
+                        RGB::named(rltk::GOLD),
+                    );
+                    gamelog.entry().message(&msg).log();
+                }
+            }
+        }
+    }
+}
+
 /// Whenever an entity [`WantsToDropItem`], remove the item from their inventory and
 /// place it at their location in the game world.
 pub struct ItemDropSystem;
@@ -59,11 +301,25 @@ impl<'a> System<'a> for ItemDropSystem {
         ReadStorage<'a, Name>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, InBackpack>,
+        ReadExpect<'a, MasterItemNames>,
+        ReadStorage<'a, Rarity>,
+        ReadExpect<'a, Palette>,
     );
 
     fn run(
         &mut self,
-        (player_entity, mut gamelog, entities, mut wants_drop, names, mut positions, mut backpack): Self::SystemData,
+        (
+            player_entity,
+            mut gamelog,
+            entities,
+            mut wants_drop,
+            names,
+            mut positions,
+            mut backpack,
+            item_names,
+            rarities,
+            palette,
+        ): Self::SystemData,
     ) {
         for (entity, to_drop) in (&entities, &wants_drop).join() {
             let dropper_pos = *positions.get(entity).unwrap();
@@ -74,9 +330,11 @@ impl<'a> System<'a> for ItemDropSystem {
             backpack.remove(to_drop.item);
 
             if entity == **player_entity {
-                gamelog.log(format!(
-                    "You drop the {}.",
-                    names.get(to_drop.item).unwrap()
+                let name = &names.get(to_drop.item).unwrap().name;
+                gamelog.log(tr("drop-item").colored_arg(
+                    "item",
+                    item_names.display_name(name),
+                    palette.rarity(item_names.display_rarity(name, rarities.get(to_drop.item))),
                 ));
             }
         }
@@ -85,6 +343,150 @@ impl<'a> System<'a> for ItemDropSystem {
     }
 }
 
+/// How much more damage a thrown weapon does than its [`MeleePowerBonus`].
+const THROWN_WEAPON_DAMAGE: i32 = 3;
+
+/// Whenever an entity [`WantsToThrowItem`], hurl the item out of their backpack
+/// towards its target. It stops short if something is in the way, just like a
+/// ranged item would.
+///
+/// Weapons hurt whoever they hit, and items that [`Shatters`] break and splash
+/// their healing, damage, and status effects over whatever's where they land.
+/// Anything still in one piece ends up on the floor there.
+pub struct ItemThrowSystem;
+
+impl<'a> System<'a> for ItemThrowSystem {
+    type SystemData = (
+        ReadExpect<'a, PlayerEntity>,
+        WriteExpect<'a, GameLog>,
+        ReadExpect<'a, Map>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToThrowItem>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, MeleePowerBonus>,
+        ReadStorage<'a, Shatters>,
+        ReadStorage<'a, ProvidesHealing>,
+        ReadStorage<'a, InflictsDamage>,
+        ReadStorage<'a, InflictsStatusEffect>,
+        WriteExpect<'a, EffectQueue>,
+        ReadExpect<'a, MasterItemNames>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            player_entity,
+            mut gamelog,
+            map,
+            entities,
+            mut wants_throw,
+            names,
+            mut positions,
+            mut backpack,
+            combat_stats,
+            melee_bonuses,
+            shatters,
+            healing,
+            damage_inflictors,
+            inflicts_status,
+            mut effects,
+            item_names,
+        ): Self::SystemData,
+    ) {
+        for (entity, throw) in (&entities, &wants_throw).join() {
+            if backpack
+                .get(throw.item)
+                .is_none_or(|pack_item| pack_item.owner != entity)
+            {
+                continue;
+            }
+            let Some(thrower_pos) = positions.get(entity) else {
+                continue;
+            };
+
+            let from = rltk::Point::new(thrower_pos.x, thrower_pos.y);
+            let impact = resolve_trajectory(&map, from, throw.target, THROW_RANGE).impact;
+            let idx = map.xy_idx(impact.x, impact.y);
+            let is_player = entity == **player_entity;
+            let item_name = item_names.display_name(&names.get(throw.item).unwrap().name);
+
+            backpack.remove(throw.item);
+            if is_player {
+                gamelog.log(tr("throw-item").arg("item", &item_name));
+            }
+
+            if let Some(weapon) = melee_bonuses.get(throw.item) {
+                let damage = weapon.power + THROWN_WEAPON_DAMAGE;
+                let hit = map.tile_content[idx]
+                    .iter()
+                    .filter(|mob| **mob != entity && combat_stats.contains(**mob));
+                for mob in hit {
+                    effects.add_effect(
+                        EffectType::Damage {
+                            amount: damage,
+                            attacker: Some(entity),
+                        },
+                        Targets::Single { target: *mob },
+                    );
+                    if is_player {
+                        gamelog.log(
+                            tr("thrown-item-hits")
+                                .arg("item", &item_name)
+                                .arg("target", l10n::name(&names.get(*mob).unwrap().name))
+                                .arg("damage", tr("hit-points").arg("amount", damage)),
+                        );
+                    }
+                }
+            }
+
+            if shatters.contains(throw.item) {
+                let splashed = Targets::Tiles { tiles: vec![idx] };
+                if let Some(healer) = healing.get(throw.item) {
+                    effects.add_effect(
+                        EffectType::Healing {
+                            amount: healer.heal_amount,
+                        },
+                        splashed.clone(),
+                    );
+                }
+                if let Some(damager) = damage_inflictors.get(throw.item) {
+                    effects.add_effect(
+                        EffectType::Damage {
+                            amount: damager.damage,
+                            attacker: Some(entity),
+                        },
+                        splashed.clone(),
+                    );
+                }
+                if let Some(status) = inflicts_status.get(throw.item) {
+                    effects.add_effect(
+                        EffectType::Status {
+                            effect: status.effect,
+                            turns: status.turns,
+                        },
+                        splashed,
+                    );
+                }
+                if is_player {
+                    gamelog.log(tr("item-shatters").arg("item", &item_name));
+                }
+                entities
+                    .delete(throw.item)
+                    .expect("Unable to delete shattered item");
+            } else {
+                positions
+                    .insert(throw.item, Position::from((impact.x, impact.y)))
+                    .expect("Unable to insert thrown item position");
+            }
+        }
+
+        wants_throw.clear();
+    }
+}
+
 /// A system that allows entities that [`WantsToUseItem`] to use their item.
 pub struct ItemUseSystem;
 
@@ -92,6 +494,7 @@ impl<'a> System<'a> for ItemUseSystem {
     type SystemData = (
         ReadExpect<'a, PlayerEntity>,
         WriteExpect<'a, GameLog>,
+        WriteExpect<'a, GameEvents>,
         ReadExpect<'a, Map>,
         Entities<'a>,
         WriteStorage<'a, WantsToUseItem>,
@@ -99,10 +502,31 @@ impl<'a> System<'a> for ItemUseSystem {
         ReadStorage<'a, ProvidesHealing>,
         ReadStorage<'a, InflictsDamage>,
         ReadStorage<'a, AreaOfEffect>,
-        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, InflictsStatusEffect>,
         ReadStorage<'a, Consumable>,
-        WriteStorage<'a, CombatStats>,
-        WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, CombatStats>,
+        WriteExpect<'a, EffectQueue>,
+        ReadStorage<'a, Equippable>,
+        WriteStorage<'a, Equipped>,
+        WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Ranged>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, MakesNoise>,
+        WriteStorage<'a, WantsToMakeNoise>,
+        ReadStorage<'a, WinsGame>,
+        (
+            ReadStorage<'a, ProvidesMapping>,
+            ReadStorage<'a, ProvidesTownPortal>,
+            ReadStorage<'a, ProvidesFood>,
+            WriteStorage<'a, HungerClock>,
+            ReadStorage<'a, Enchants>,
+        ),
+        (
+            ReadStorage<'a, Spell>,
+            WriteStorage<'a, Mana>,
+            ReadStorage<'a, Skills>,
+        ),
+        WriteExpect<'a, MasterItemNames>,
     );
 
     fn run(
@@ -110,6 +534,7 @@ impl<'a> System<'a> for ItemUseSystem {
         (
             player_entity,
             mut gamelog,
+            mut events,
             map,
             entities,
             mut wants_use_item,
@@ -117,32 +542,160 @@ impl<'a> System<'a> for ItemUseSystem {
             healing,
             damage_inflictors,
             areas_of_effect,
-            mut confused,
+            inflicts_status,
             consumables,
-            mut combat_stats,
-            mut suffer_damage,
+            combat_stats,
+            mut effects,
+            equippables,
+            mut equipped,
+            mut backpack,
+            ranged,
+            positions,
+            noisemakers,
+            mut wants_noise,
+            wins_game,
+            (mapping, town_portals, food, mut hunger_clocks, enchanters),
+            (spells, mut mana, skills),
+            mut item_names,
         ): Self::SystemData,
     ) {
         for (entity, use_item) in (&entities, &wants_use_item).join() {
             let mut used_item = false;
 
-            // Targeting
-            let mut targets = Vec::new();
-            if let Some(target) = use_item.target {
-                if let Some(aoe) = areas_of_effect.get(use_item.item) {
-                    // Item has an area of effect. Figure out which cells to target.
-                    let blast_cells = rltk::field_of_view(target, aoe.radius, &*map);
-                    for cell in blast_cells.iter().filter(|p| {
-                        p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1
-                    }) {
-                        let idx = map.xy_idx(cell.x, cell.y);
-                        for mob in map.tile_content[idx].iter() {
-                            targets.push(*mob);
+            // Ranged items stop short if something is in the way
+            let target_point = use_item.target.map(|target| {
+                match (ranged.get(use_item.item), positions.get(entity)) {
+                    (Some(ranged), Some(user_pos)) => {
+                        let user_pos = rltk::Point::new(user_pos.x, user_pos.y);
+                        resolve_trajectory(&map, user_pos, target, ranged.range).impact
+                    }
+                    _ => target,
+                }
+            });
+            // Every cell the item hits, which is just the one unless it has an area of effect
+            let target_area = target_point.map(|target| match areas_of_effect.get(use_item.item) {
+                Some(aoe) => blast_area(&map, target, aoe.radius),
+                None => vec![target],
+            });
+
+            // Spells have to be aimed at something in range before they cost anything
+            if let (Some(target), Some(area)) = (use_item.target, &target_area) {
+                if spells.contains(use_item.item) {
+                    let in_range = match (ranged.get(use_item.item), positions.get(entity)) {
+                        (Some(ranged), Some(user_pos)) => {
+                            let user_pos = rltk::Point::new(user_pos.x, user_pos.y);
+                            rltk::DistanceAlg::Pythagoras.distance2d(user_pos, target)
+                                <= ranged.range as f32
+                        }
+                        _ => true,
+                    };
+                    let hits_anything = area
+                        .iter()
+                        .any(|cell| !map.tile_content[map.xy_idx(cell.x, cell.y)].is_empty());
+                    if !in_range || !hits_anything {
+                        if entity == **player_entity {
+                            let msg = if in_range {
+                                tr("spell-no-target")
+                            } else {
+                                tr("spell-out-of-range")
+                            };
+                            gamelog.log(
+                                msg.arg(
+                                    "spell",
+                                    l10n::name(&names.get(use_item.item).unwrap().name),
+                                ),
+                            );
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            // Spells cost mana to cast, and fizzle if the caster doesn't have enough
+            let is_spell =
+                if let Some(spell) = spells.get(use_item.item) {
+                    let cost = skills
+                        .get(entity)
+                        .map_or(spell.mana_cost, |skills| skills.spell_cost(spell.mana_cost));
+                    match mana.get_mut(entity) {
+                        Some(pool) if pool.mana >= cost => pool.mana -= cost,
+                        _ => {
+                            if entity == **player_entity {
+                                gamelog.log(tr("not-enough-mana").arg(
+                                    "spell",
+                                    l10n::name(&names.get(use_item.item).unwrap().name),
+                                ));
+                            }
+                            continue;
                         }
                     }
+                    true
                 } else {
-                    // Assume single-tile target.
-                    let idx = map.xy_idx(target.x, target.y);
+                    false
+                };
+
+            // Using an unidentified item reveals what it is
+            if entity == **player_entity {
+                if let Some(name) = names.get(use_item.item) {
+                    let unidentified = item_names.display_name(&name.name);
+                    if item_names.identify(&name.name) {
+                        let msg = tr("item-identified")
+                            .arg("unidentified", unidentified)
+                            .colored_arg("item", l10n::name(&name.name), RGB::named(rltk::CYAN));
+                        gamelog.entry().message(&msg).log();
+                    }
+                }
+            }
+
+            // Equippable items get equipped instead of used up
+            if let Some(can_equip) = equippables.get(use_item.item) {
+                let unequipped = equip_item(
+                    &entities,
+                    entity,
+                    use_item.item,
+                    can_equip.slot,
+                    &mut equipped,
+                    &mut backpack,
+                );
+
+                if entity == **player_entity {
+                    for old_item in unequipped {
+                        gamelog.log(
+                            tr("unequip-item")
+                                .arg("item", l10n::name(&names.get(old_item).unwrap().name)),
+                        );
+                    }
+                    gamelog.log(
+                        tr("equip-item")
+                            .arg("item", l10n::name(&names.get(use_item.item).unwrap().name)),
+                    );
+                }
+                events.push(GameEvent::ItemUsed {
+                    user: entity,
+                    item: use_item.item,
+                });
+
+                continue;
+            }
+
+            // Targeting
+            let mut targets = Vec::new();
+            if let Some(area) = target_area {
+                // Items with an area of effect show off every cell they hit
+                if areas_of_effect.contains(use_item.item) {
+                    effects.add_effect(
+                        EffectType::Particle {
+                            glyph: rltk::to_cp437('░'),
+                            fg: rltk::RGB::named(rltk::ORANGE),
+                            lifetime_ms: PARTICLE_LIFETIME_MS,
+                        },
+                        Targets::Tiles {
+                            tiles: area.iter().map(|cell| map.xy_idx(cell.x, cell.y)).collect(),
+                        },
+                    );
+                }
+                for cell in area.iter() {
+                    let idx = map.xy_idx(cell.x, cell.y);
                     for mob in map.tile_content[idx].iter() {
                         targets.push(*mob);
                     }
@@ -152,18 +705,128 @@ impl<'a> System<'a> for ItemUseSystem {
                 targets.push(entity);
             }
 
+            // If the item makes noise, set it off wherever it landed
+            if let (Some(noisemaker), Some(target)) = (noisemakers.get(use_item.item), target_point)
+            {
+                wants_noise
+                    .insert(
+                        entity,
+                        WantsToMakeNoise {
+                            x: target.x,
+                            y: target.y,
+                            volume: noisemaker.volume,
+                        },
+                    )
+                    .expect("Unable to insert intent to make noise");
+                if *player_entity == entity {
+                    gamelog.log(
+                        tr("item-goes-off")
+                            .arg("item", l10n::name(&names.get(use_item.item).unwrap().name)),
+                    );
+                }
+                used_item = true;
+            }
+
+            // Using the artifact that wins the game is, well, how you win the game
+            if wins_game.contains(use_item.item) {
+                if *player_entity == entity {
+                    gamelog.log(
+                        tr("raise-artifact")
+                            .arg("item", l10n::name(&names.get(use_item.item).unwrap().name)),
+                    );
+                }
+                used_item = true;
+            }
+
+            // Eating fills the eater up, whether or not it agrees with them
+            if food.contains(use_item.item) {
+                if let Some(clock) = hunger_clocks.get_mut(entity) {
+                    *clock = HungerClock::new(HungerState::WellFed);
+                }
+                if *player_entity == entity {
+                    gamelog.log(
+                        tr("eat-item")
+                            .arg("item", l10n::name(&names.get(use_item.item).unwrap().name)),
+                    );
+                }
+                used_item = true;
+            }
+
+            // The map itself is swept into view once the turn is over
+            if mapping.contains(use_item.item) {
+                if *player_entity == entity {
+                    gamelog.log(
+                        tr("read-map")
+                            .arg("item", l10n::name(&names.get(use_item.item).unwrap().name)),
+                    );
+                }
+                used_item = true;
+            }
+
+            // The player is taken through the portal once the turn is over. There's no
+            // going back to town when they're already there, so the scroll is kept.
+            if town_portals.contains(use_item.item) {
+                let in_town = map.level_id() == LevelId::TOWN;
+                if *player_entity == entity {
+                    let msg = if in_town {
+                        tr("portal-fizzles")
+                    } else {
+                        tr("open-portal")
+                    };
+                    gamelog
+                        .log(msg.arg("item", l10n::name(&names.get(use_item.item).unwrap().name)));
+                }
+                used_item = !in_town;
+            }
+
+            // The reader picks what to enchant once the turn is over, and the scroll
+            // isn't used up until they have. With nothing to enchant, it's wasted.
+            if let Some(enchants) = enchanters.get(use_item.item) {
+                let has_target = equipped
+                    .join()
+                    .any(|worn| worn.owner == entity && enchants.target.fits(worn.slot));
+                if !has_target {
+                    if *player_entity == entity {
+                        gamelog.log(
+                            tr("enchant-nothing")
+                                .arg("item", l10n::name(&names.get(use_item.item).unwrap().name)),
+                        );
+                    }
+                    used_item = true;
+                }
+            }
+
             // If it inflicts damage, apply it to the target cell
             if let Some(damager) = damage_inflictors.get(use_item.item) {
                 used_item = false;
-                for mob in targets.iter() {
-                    SufferDamage::new_damage(&mut suffer_damage, *mob, damager.damage);
+                // Only living things can be hurt, but that includes the user
+                for mob in targets.iter().filter(|mob| combat_stats.contains(**mob)) {
+                    effects.add_effect(
+                        EffectType::Damage {
+                            amount: damager.damage,
+                            attacker: Some(entity),
+                        },
+                        Targets::Single { target: *mob },
+                    );
+                    if *mob == entity {
+                        events.push(GameEvent::CaughtInOwnBlast {
+                            user: entity,
+                            item: use_item.item,
+                        });
+                    }
                     if *player_entity == entity {
-                        let mob_name = names.get(*mob).unwrap();
-                        let item_name = names.get(use_item.item).unwrap();
-                        gamelog.log(format!(
-                            "You use {item_name} on {mob_name}, inflicting {} hp.",
-                            damager.damage
-                        ));
+                        let msg = if *mob == entity {
+                            tr("caught-in-own-blast")
+                        } else if is_spell {
+                            tr("cast-spell-damage")
+                        } else {
+                            tr("use-item-damage")
+                        };
+                        gamelog.log(
+                            msg.arg("item", l10n::name(&names.get(use_item.item).unwrap().name))
+                                .arg("target", l10n::name(&names.get(*mob).unwrap().name))
+                                .arg("damage", tr("hit-points").arg("amount", damager.damage)),
+                        );
                     }
 
                     used_item = true;
@@ -174,42 +837,62 @@ impl<'a> System<'a> for ItemUseSystem {
             if let Some(healer) = healing.get(use_item.item) {
                 used_item = false;
 
-                for target in targets.iter() {
-                    if let Some(stats) = combat_stats.get_mut(*target) {
-                        stats.hp = i32::min(stats.max_hp, stats.hp + healer.heal_amount);
-                        if *player_entity == entity {
-                            gamelog.log(format!(
-                                "You drink the {}, healing {} hp.",
-                                names.get(use_item.item).unwrap(),
-                                healer.heal_amount
-                            ));
-                        }
-                        used_item = true;
+                for target in targets.iter().filter(|mob| combat_stats.contains(**mob)) {
+                    effects.add_effect(
+                        EffectType::Healing {
+                            amount: healer.heal_amount,
+                        },
+                        Targets::Single { target: *target },
+                    );
+                    if *player_entity == entity {
+                        gamelog.log(
+                            tr("drink-healing")
+                                .arg("item", l10n::name(&names.get(use_item.item).unwrap().name))
+                                .arg("amount", tr("hit-points").arg("amount", healer.heal_amount)),
+                        );
                     }
+                    used_item = true;
                 }
             }
 
-            // If the item confuses entities, it's time to absolutely just outright blow their
-            // minds with the pure confusion
-            if let Some(confusion) = confused.get(use_item.item).copied() {
+            // If the item inflicts a status effect, it lingers on everything it hit
+            if let Some(inflicts) = inflicts_status.get(use_item.item) {
                 used_item = false;
-                for mob in targets.iter() {
+                // Only living things can be affected
+                for mob in targets.iter().filter(|mob| combat_stats.contains(**mob)) {
                     if *player_entity == entity {
-                        let mob_name = names.get(*mob).unwrap();
-                        let item_name = names.get(use_item.item).unwrap();
-                        gamelog.log(format!(
-                            "You use {item_name} on {mob_name}, confusing them."
-                        ));
+                        let msg = match (*mob == entity, is_spell) {
+                            (true, true) => tr("cast-spell-status-self"),
+                            (true, false) => tr("use-item-status-self"),
+                            (false, true) => tr("cast-spell-status"),
+                            (false, false) => tr("use-item-status"),
+                        };
+                        gamelog.log(
+                            msg.arg("item", l10n::name(&names.get(use_item.item).unwrap().name))
+                                .arg("target", l10n::name(&names.get(*mob).unwrap().name))
+                                .arg("effect", l10n::status_effect(inflicts.effect)),
+                        );
                     }
 
-                    confused
-                        .insert(*mob, confusion)
-                        .expect("Unable to insert Confusion component for entity");
+                    effects.add_effect(
+                        EffectType::Status {
+                            effect: inflicts.effect,
+                            turns: inflicts.turns,
+                        },
+                        Targets::Single { target: *mob },
+                    );
 
                     used_item = true;
                 }
             }
 
+            if used_item {
+                events.push(GameEvent::ItemUsed {
+                    user: entity,
+                    item: use_item.item,
+                });
+            }
+
             // Delete the item if it's consumable
             if used_item && consumables.get(use_item.item).is_some() {
                 entities
@@ -221,3 +904,373 @@ impl<'a> System<'a> for ItemUseSystem {
         wants_use_item.clear();
     }
 }
+
+/// Equip `item` into `slot` on `owner`, moving whatever they had equipped in that
+/// slot back into their backpack.
+///
+/// Returns the items that were unequipped to make room.
+pub fn equip_item(
+    entities: &Entities,
+    owner: Entity,
+    item: Entity,
+    slot: EquipmentSlot,
+    equipped: &mut WriteStorage<Equipped>,
+    backpack: &mut WriteStorage<InBackpack>,
+) -> Vec<Entity> {
+    let unequipped = (entities, &*equipped)
+        .join()
+        .filter(|(_, worn)| worn.owner == owner && worn.slot == slot)
+        .map(|(old_item, _)| old_item)
+        .collect::<Vec<_>>();
+
+    for old_item in unequipped.iter() {
+        equipped.remove(*old_item);
+        backpack
+            .insert(*old_item, InBackpack { owner })
+            .expect("Unable to move unequipped item into its owner's backpack");
+    }
+
+    backpack.remove(item);
+    equipped
+        .insert(item, Equipped { owner, slot })
+        .expect("Unable to insert Equipped component for item");
+
+    unequipped
+}
+
+/// Returns `true` if an item granting `candidate` (power, defense) bonuses is at
+/// least as good as one granting `current` bonuses in every way, and better in at
+/// least one.
+fn is_strict_upgrade(candidate: (i32, i32), current: (i32, i32)) -> bool {
+    candidate.0 >= current.0 && candidate.1 >= current.1 && candidate != current
+}
+
+/// Returns the best item in `owner`'s backpack for each [`EquipmentSlot`], as
+/// long as it's a strict upgrade over whatever they have equipped in that slot.
+pub fn find_equipment_upgrades(ecs: &World, owner: Entity) -> Vec<Entity> {
+    let entities = ecs.entities();
+    let equippables = ecs.read_storage::<Equippable>();
+    let equipped = ecs.read_storage::<Equipped>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let power_bonuses = ecs.read_storage::<MeleePowerBonus>();
+    let defense_bonuses = ecs.read_storage::<DefenseBonus>();
+
+    let bonuses = |item: Entity| {
+        (
+            power_bonuses.get(item).map_or(0, |bonus| bonus.power),
+            defense_bonuses.get(item).map_or(0, |bonus| bonus.defense),
+        )
+    };
+    let total = |(power, defense): (i32, i32)| power + defense;
+
+    let mut upgrades: Vec<(EquipmentSlot, Entity)> = Vec::new();
+    for (item, can_equip, carried) in (&entities, &equippables, &backpack).join() {
+        if carried.owner != owner {
+            continue;
+        }
+
+        let current = (&entities, &equipped)
+            .join()
+            .find(|(_, worn)| worn.owner == owner && worn.slot == can_equip.slot)
+            .map_or((0, 0), |(worn_item, _)| bonuses(worn_item));
+        let candidate = bonuses(item);
+        if !is_strict_upgrade(candidate, current) {
+            continue;
+        }
+
+        match upgrades
+            .iter_mut()
+            .find(|(slot, _)| *slot == can_equip.slot)
+        {
+            Some((_, best)) if total(candidate) > total(bonuses(*best)) => *best = item,
+            Some(_) => {}
+            None => upgrades.push((can_equip.slot, item)),
+        }
+    }
+
+    upgrades.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Equip every strict upgrade found by [`find_equipment_upgrades()`]. Returns the
+/// newly-equipped items.
+pub fn auto_equip(ecs: &mut World, owner: Entity) -> Vec<Entity> {
+    let upgrades = find_equipment_upgrades(ecs, owner);
+    equip_items(ecs, owner, &upgrades);
+    upgrades
+}
+
+/// Immediately equip `items` on `owner`, logging what happened if `owner` is the player.
+pub fn equip_items(ecs: &mut World, owner: Entity, items: &[Entity]) {
+    let entities = ecs.entities();
+    let player_entity = ecs.fetch::<PlayerEntity>();
+    let names = ecs.read_storage::<Name>();
+    let equippables = ecs.read_storage::<Equippable>();
+    let mut equipped = ecs.write_storage::<Equipped>();
+    let mut backpack = ecs.write_storage::<InBackpack>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    for item in items.iter() {
+        let slot = equippables.get(*item).unwrap().slot;
+        let unequipped = equip_item(&entities, owner, *item, slot, &mut equipped, &mut backpack);
+
+        if owner == **player_entity {
+            for old_item in unequipped {
+                gamelog.log(
+                    tr("unequip-item").arg("item", l10n::name(&names.get(old_item).unwrap().name)),
+                );
+            }
+            gamelog.log(tr("equip-item").arg("item", l10n::name(&names.get(*item).unwrap().name)));
+        }
+    }
+}
+
+/// The chance, out of 100, that enchanting an item destroys it instead, for the
+/// enchantment itself and for every time that the item has already been
+/// [`Enchanted`].
+const ENCHANT_BREAK_CHANCE: i32 = 5;
+
+/// The items that `owner` has equipped which can be enchanted for `target`.
+pub fn enchantable_items(ecs: &World, owner: Entity, target: EnchantTarget) -> Vec<Entity> {
+    (&ecs.entities(), &ecs.read_storage::<Equipped>())
+        .join()
+        .filter(|(_, worn)| worn.owner == owner && target.fits(worn.slot))
+        .map(|(item, _)| item)
+        .collect()
+}
+
+/// Use up the enchanting `scroll` that the player read on `item`, adding one to
+/// its power or defense bonus. The more often the item has been enchanted
+/// already, the more likely it is to be destroyed instead.
+pub fn enchant_item(ecs: &mut World, scroll: Entity, item: Entity) {
+    let Some(target) = ecs.read_storage::<Enchants>().get(scroll).map(|e| e.target) else {
+        return;
+    };
+    let bonus = match target {
+        EnchantTarget::Weapon => ecs
+            .read_storage::<MeleePowerBonus>()
+            .get(item)
+            .map_or(0, |bonus| bonus.power),
+        EnchantTarget::Armor => ecs
+            .read_storage::<DefenseBonus>()
+            .get(item)
+            .map_or(0, |bonus| bonus.defense),
+    };
+    let times_enchanted = ecs
+        .read_storage::<Enchanted>()
+        .get(item)
+        .map_or(0, |enchanted| enchanted.times);
+    let break_chance = ENCHANT_BREAK_CHANCE * (1 + times_enchanted);
+    let destroyed = ecs
+        .write_resource::<RandomNumberGenerator>()
+        .roll_dice(1, 100)
+        <= break_chance;
+    let name = identification::display_name(ecs, item).unwrap_or_default();
+    let color = identification::display_color(ecs, item);
+
+    let msg = if destroyed {
+        ecs.delete_entity(item)
+            .expect("Unable to delete item destroyed by enchanting");
+        tr("enchant-destroyed")
+    } else {
+        match target {
+            EnchantTarget::Weapon => {
+                ecs.write_storage::<MeleePowerBonus>()
+                    .insert(item, MeleePowerBonus { power: bonus + 1 })
+                    .expect("Unable to insert enchanted weapon's power bonus");
+            }
+            EnchantTarget::Armor => {
+                ecs.write_storage::<DefenseBonus>()
+                    .insert(item, DefenseBonus { defense: bonus + 1 })
+                    .expect("Unable to insert enchanted armor's defense bonus");
+            }
+        }
+        ecs.write_storage::<Enchanted>()
+            .insert(
+                item,
+                Enchanted {
+                    times: times_enchanted + 1,
+                },
+            )
+            .expect("Unable to count enchanted item's enchantment");
+        tr("enchant-item")
+    };
+    ecs.fetch_mut::<GameLog>()
+        .log(msg.colored_arg("item", name, color));
+
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    ecs.fetch_mut::<GameEvents>().push(GameEvent::ItemUsed {
+        user: player_entity,
+        item: scroll,
+    });
+    ecs.delete_entity(scroll)
+        .expect("Unable to delete enchanting scroll that just got read");
+}
+
+/// Move `item` out of the player's backpack and into their stash.
+pub fn deposit_item(ecs: &mut World, item: Entity) {
+    ecs.write_storage::<InBackpack>().remove(item);
+    ecs.write_storage::<InStash>()
+        .insert(item, InStash)
+        .expect("Unable to move item into the stash");
+
+    if let Some(name) = identification::display_name(ecs, item) {
+        let color = identification::display_color(ecs, item);
+        ecs.fetch_mut::<GameLog>()
+            .log(tr("stash-deposit").colored_arg("item", name, color));
+    }
+}
+
+/// Move `item` out of the stash and back into the player's backpack, if they
+/// have room for it.
+pub fn withdraw_item(ecs: &mut World, item: Entity) {
+    if !player_has_room_for(ecs, item) {
+        return;
+    }
+
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    ecs.write_storage::<InStash>().remove(item);
+    ecs.write_storage::<InBackpack>()
+        .insert(
+            item,
+            InBackpack {
+                owner: player_entity,
+            },
+        )
+        .expect("Unable to move item out of the stash");
+
+    if let Some(name) = identification::display_name(ecs, item) {
+        let color = identification::display_color(ecs, item);
+        ecs.fetch_mut::<GameLog>()
+            .log(tr("stash-withdraw").colored_arg("item", name, color));
+    }
+}
+
+/// Buy `item` from `vendor`, if the player can afford it and has room for it.
+pub fn buy_item(ecs: &mut World, vendor: Entity, item: Entity) {
+    if !player_has_room_for(ecs, item) {
+        return;
+    }
+
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let price = ecs
+        .read_storage::<Value>()
+        .get(item)
+        .map_or(0, |value| value.gold);
+    let name = identification::display_name(ecs, item).unwrap_or_default();
+    let color = identification::display_color(ecs, item);
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let mut backpack = ecs.write_storage::<InBackpack>();
+    if backpack.get(item).map(|pack_item| pack_item.owner) != Some(vendor) {
+        return;
+    }
+
+    let mut purses = ecs.write_storage::<Purse>();
+    let Some(purse) = purses.get_mut(player_entity).filter(|p| p.gold >= price) else {
+        gamelog.log(tr("cannot-afford").colored_arg("item", name, color));
+        return;
+    };
+    purse.gold -= price;
+
+    backpack
+        .insert(
+            item,
+            InBackpack {
+                owner: player_entity,
+            },
+        )
+        .expect("Unable to move bought item into the player's backpack");
+    gamelog.log(
+        tr("buy-item")
+            .colored_arg("item", name, color)
+            .arg("price", tr("gold-amount").arg("amount", price)),
+    );
+}
+
+/// Try to walk off with `item` from `vendor`'s stock without paying for it. If
+/// anyone notices, it's a [`Crime::Theft`].
+pub fn steal_item(ecs: &mut World, vendor: Entity, item: Entity) {
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    if ecs
+        .read_storage::<InBackpack>()
+        .get(item)
+        .map(|pack_item| pack_item.owner)
+        != Some(vendor)
+        || !player_has_room_for(ecs, item)
+    {
+        return;
+    }
+
+    ecs.write_storage::<InBackpack>()
+        .insert(
+            item,
+            InBackpack {
+                owner: player_entity,
+            },
+        )
+        .expect("Unable to move stolen item into the player's backpack");
+
+    let name = identification::display_name(ecs, item).unwrap_or_default();
+    let vendor_name = ecs
+        .read_storage::<Name>()
+        .get(vendor)
+        .map(|name| l10n::name(&name.name))
+        .unwrap_or_default();
+    let scene = *ecs.fetch::<PlayerPos>();
+
+    if crime::is_witnessed(ecs, *scene) {
+        let msg = tr("caught-stealing")
+            .colored_arg("vendor", vendor_name, RGB::named(rltk::GOLD))
+            .arg("item", name);
+        ecs.fetch_mut::<GameLog>()
+            .entry()
+            .colored_message(&msg, RGB::named(rltk::RED))
+            .log();
+        ecs.write_resource::<GameEvents>()
+            .push(GameEvent::CrimeCommitted {
+                criminal: player_entity,
+                victim: vendor,
+                crime: Crime::Theft,
+                scene: *scene,
+            });
+    } else {
+        ecs.fetch_mut::<GameLog>().log(
+            tr("pocket-item")
+                .arg("item", name)
+                .arg("vendor", vendor_name),
+        );
+    }
+}
+
+/// Sell `item` from the player's backpack to `vendor`.
+pub fn sell_item(ecs: &mut World, vendor: Entity, item: Entity) {
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let attributes = ecs
+        .read_storage::<Attributes>()
+        .get(player_entity)
+        .copied()
+        .unwrap_or_default();
+    let Some(price) = ecs
+        .read_storage::<Value>()
+        .get(item)
+        .map(|value| value.sell_price(&attributes))
+    else {
+        return;
+    };
+
+    ecs.write_storage::<InBackpack>()
+        .insert(item, InBackpack { owner: vendor })
+        .expect("Unable to move sold item into the vendor's stock");
+    if let Some(purse) = ecs.write_storage::<Purse>().get_mut(player_entity) {
+        purse.gold += price;
+    }
+
+    if let Some(name) = identification::display_name(ecs, item) {
+        let color = identification::display_color(ecs, item);
+        ecs.fetch_mut::<GameLog>().log(
+            tr("sell-item")
+                .colored_arg("item", name, color)
+                .arg("price", tr("gold-amount").arg("amount", price)),
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Equipment that's made up as it's spawned, rather than always being the same.
+//!
+//! Artifacts are one-of-a-kind pieces of equipment, put together from scratch
+//! whenever the spawn table turns up an `"Artifact"`. Each gets a name of its own
+//! ("Skullcrusher, Blade of the Deep"), a random mix of bonuses that grows with the
+//! depth it's found at, and a colour that nothing else shares.
+//!
+//! Ordinary weapons and armor can also have magic [`Affix`]es from the raws rolled
+//! onto them ("Flaming Longsword of Might"), which add to their stats.
+
+use rltk::{RandomNumberGenerator, HSV, RGB};
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+
+use crate::raws::{Affix, AffixItems, AffixModifier, AffixPosition, Raws};
+use crate::rng_table::RngTable;
+use crate::{
+    Affixed, BlockChance, CritRangeBonus, DefenseBonus, Dice, EquipmentSlot, Equippable, Item,
+    LightSource, Map, MeleeAttack, MeleePowerBonus, Name, Position, Rarity, Renderable,
+    Serializable, Value, Weight,
+};
+
+/// The first half of an artifact's own name.
+const NAME_STARTS: &[&str] = &[
+    "Skull", "Doom", "Storm", "Blood", "Night", "Grim", "Soul", "Frost", "Bone", "Ash",
+];
+
+/// The second half of an artifact's own name.
+const NAME_ENDS: &[&str] = &[
+    "crusher", "bane", "reaver", "render", "breaker", "keeper", "ward", "fang", "caller", "song",
+];
+
+/// Where an artifact is said to come from.
+const ORIGINS: &[&str] = &[
+    "the Deep",
+    "the Fallen King",
+    "the Last Dawn",
+    "the Hollow Moon",
+    "the Red Wastes",
+    "the Forgotten",
+    "the Abyss",
+    "the North Wind",
+];
+
+/// What an artifact can be, for each slot it goes in.
+const fn kinds(slot: EquipmentSlot) -> &'static [&'static str] {
+    match slot {
+        EquipmentSlot::Melee => &["Blade", "Axe", "Maul", "Spear"],
+        EquipmentSlot::Shield => &["Aegis", "Bulwark"],
+        EquipmentSlot::Torso => &["Hauberk", "Mantle", "Cuirass"],
+        EquipmentSlot::Head => &["Crown", "Visor", "Helm"],
+        EquipmentSlot::Feet => &["Greaves", "Treads"],
+    }
+}
+
+/// Every slot that an artifact can be made for.
+const SLOTS: &[EquipmentSlot] = &[
+    EquipmentSlot::Melee,
+    EquipmentSlot::Shield,
+    EquipmentSlot::Torso,
+    EquipmentSlot::Head,
+    EquipmentSlot::Feet,
+];
+
+/// The chance, out of 100, that an artifact gets each of its optional bonuses.
+const EXTRA_BONUS_CHANCE: i32 = 40;
+
+/// How much gold an artifact is worth for every point of bonus it gives.
+const GOLD_PER_BONUS: i32 = 15;
+
+fn pick<T: Copy>(rng: &mut RandomNumberGenerator, options: &[T]) -> T {
+    options[rng.range(0, options.len())]
+}
+
+/// Come up with a name for an artifact of `kind`, like "Skullcrusher, Blade of the
+/// Deep".
+fn artifact_name(rng: &mut RandomNumberGenerator, kind: &str) -> String {
+    format!(
+        "{}{}, {kind} of {}",
+        pick(rng, NAME_STARTS),
+        pick(rng, NAME_ENDS),
+        pick(rng, ORIGINS)
+    )
+}
+
+/// Spawn a brand-new artifact at (`x`, `y`), as good as the current level
+/// deserves.
+pub fn spawn_artifact(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let depth = ecs.fetch::<Map>().depth;
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+    let slot = pick(&mut rng, SLOTS);
+    let kind = pick(&mut rng, kinds(slot));
+    let name = artifact_name(&mut rng, kind);
+    let mut roll_extra = || rng.roll_dice(1, 100) <= EXTRA_BONUS_CHANCE;
+
+    // Every artifact is good at what its slot is for, and maybe something else
+    let (melee_attack, power, defense) = if slot == EquipmentSlot::Melee {
+        let attack = MeleeAttack {
+            damage: Dice::new(1 + depth / 4, 8, depth / 3),
+        };
+        let defense = roll_extra().then_some(1 + depth / 4);
+        (Some(attack), Some(2 + depth / 2), defense)
+    } else {
+        let power = roll_extra().then_some(1 + depth / 4);
+        (None, power, Some(2 + depth / 3))
+    };
+    let crit_range = roll_extra().then_some(1 + depth / 5);
+    let block_chance = (slot == EquipmentSlot::Shield || roll_extra()).then_some(10 + depth * 2);
+
+    let bonus_points = power.unwrap_or(0)
+        + defense.unwrap_or(0)
+        + crit_range.unwrap_or(0)
+        + block_chance.unwrap_or(0) / 5;
+    // Somewhere on the colour wheel that plain equipment doesn't use
+    let fg = HSV::from_f32(rng.range(0.0, 1.0), 0.6, 1.0).to_rgb();
+    drop(rng);
+
+    let glyph = match slot {
+        EquipmentSlot::Melee => '/',
+        EquipmentSlot::Shield => '(',
+        _ => '[',
+    };
+
+    ecs.create_entity()
+        .with(Item)
+        .with(Value {
+            gold: 50 + bonus_points * GOLD_PER_BONUS,
+        })
+        .with(Weight {
+            pounds: 2 + defense.unwrap_or(0) * 2,
+        })
+        .with(Equippable { slot })
+        .maybe_with(melee_attack)
+        .maybe_with(power.map(|power| MeleePowerBonus { power }))
+        .maybe_with(defense.map(|defense| DefenseBonus { defense }))
+        .maybe_with(crit_range.map(|range| CritRangeBonus { range }))
+        .maybe_with(block_chance.map(|percent| BlockChance { percent }))
+        .with(Name::from(name))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437(glyph),
+            fg,
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+/// The chance, out of 100, that a piece of equipment on the first level gets a
+/// prefix, and separately a suffix.
+const AFFIX_CHANCE: i32 = 10;
+
+/// How much more likely each affix is for every level further down.
+const AFFIX_CHANCE_PER_DEPTH: i32 = 2;
+
+/// The colour of the light given off by affixes with [`AffixModifier::Light`].
+const AFFIX_LIGHT_COLOR: (f32, f32, f32) = (1.0, 0.6, 0.3);
+
+/// Maybe roll a prefix and a suffix onto `item`, if it's a piece of equipment,
+/// adding them to its name and their modifiers to its stats. Every affix makes the
+/// item a little rarer, up to [`Rarity::Rare`].
+pub fn roll_affixes(ecs: &mut World, item: Entity) {
+    let Some(slot) = ecs.read_storage::<Equippable>().get(item).map(|e| e.slot) else {
+        return;
+    };
+    let depth = ecs.fetch::<Map>().depth;
+    let chance = AFFIX_CHANCE + AFFIX_CHANCE_PER_DEPTH * (depth - 1);
+
+    let (prefix, suffix) = {
+        let raws = ecs.fetch::<Raws>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let mut roll = |position| {
+            if rng.roll_dice(1, 100) > chance {
+                return None;
+            }
+            let fits = |affix: &&Affix| {
+                affix.position == position
+                    && depth >= affix.min_depth
+                    && match affix.items {
+                        AffixItems::Weapons => slot == EquipmentSlot::Melee,
+                        AffixItems::Armor => slot != EquipmentSlot::Melee,
+                        AffixItems::Any => true,
+                    }
+            };
+            let table = raws
+                .affixes
+                .iter()
+                .filter(fits)
+                .fold(RngTable::<Affix>::new(), |table, affix| {
+                    table.add(affix.clone(), affix.weight)
+                });
+            table.roll(&mut rng).cloned()
+        };
+        (roll(AffixPosition::Prefix), roll(AffixPosition::Suffix))
+    };
+    if prefix.is_none() && suffix.is_none() {
+        return;
+    }
+
+    let base = ecs
+        .read_storage::<Name>()
+        .get(item)
+        .map(|name| name.name.clone())
+        .unwrap_or_default();
+    let mut name = base.clone();
+    if let Some(prefix) = &prefix {
+        name = format!("{} {name}", prefix.name);
+    }
+    if let Some(suffix) = &suffix {
+        name = format!("{name} {}", suffix.name);
+    }
+
+    for affix in prefix.iter().chain(suffix.iter()) {
+        for modifier in &affix.modifiers {
+            apply_modifier(ecs, item, *modifier);
+        }
+        if let Some(value) = ecs.write_storage::<Value>().get_mut(item) {
+            value.gold += affix.value;
+        }
+        let mut rarities = ecs.write_storage::<Rarity>();
+        let rarity = match rarities.get(item).copied().unwrap_or_default() {
+            Rarity::Common => Rarity::Uncommon,
+            Rarity::Uncommon | Rarity::Rare => Rarity::Rare,
+            Rarity::Legendary => Rarity::Legendary,
+        };
+        rarities
+            .insert(item, rarity)
+            .expect("Unable to insert affixed item's rarity");
+    }
+
+    ecs.write_storage::<Name>()
+        .insert(item, Name::from(name))
+        .expect("Unable to rename affixed item");
+    ecs.write_storage::<Affixed>()
+        .insert(item, Affixed { base })
+        .expect("Unable to insert affixed item's base name");
+}
+
+/// Add whatever `modifier` gives to `item`'s stats.
+fn apply_modifier(ecs: &mut World, item: Entity, modifier: AffixModifier) {
+    match modifier {
+        AffixModifier::Power(power) => {
+            let mut bonuses = ecs.write_storage::<MeleePowerBonus>();
+            let bonus = bonuses.get(item).map_or(0, |bonus| bonus.power);
+            bonuses
+                .insert(
+                    item,
+                    MeleePowerBonus {
+                        power: bonus + power,
+                    },
+                )
+                .expect("Unable to insert affix's power bonus");
+        }
+        AffixModifier::Defense(defense) => {
+            let mut bonuses = ecs.write_storage::<DefenseBonus>();
+            let bonus = bonuses.get(item).map_or(0, |bonus| bonus.defense);
+            bonuses
+                .insert(
+                    item,
+                    DefenseBonus {
+                        defense: bonus + defense,
+                    },
+                )
+                .expect("Unable to insert affix's defense bonus");
+        }
+        AffixModifier::Damage(damage) => {
+            if let Some(attack) = ecs.write_storage::<MeleeAttack>().get_mut(item) {
+                attack.damage.bonus += damage;
+            }
+        }
+        AffixModifier::CritRange(range) => {
+            let mut bonuses = ecs.write_storage::<CritRangeBonus>();
+            let bonus = bonuses.get(item).map_or(0, |bonus| bonus.range);
+            bonuses
+                .insert(
+                    item,
+                    CritRangeBonus {
+                        range: bonus + range,
+                    },
+                )
+                .expect("Unable to insert affix's crit range bonus");
+        }
+        AffixModifier::BlockChance(percent) => {
+            let mut chances = ecs.write_storage::<BlockChance>();
+            let chance = chances.get(item).map_or(0, |chance| chance.percent);
+            chances
+                .insert(
+                    item,
+                    BlockChance {
+                        percent: chance + percent,
+                    },
+                )
+                .expect("Unable to insert affix's block chance");
+        }
+        AffixModifier::Light(range) => {
+            let mut lights = ecs.write_storage::<LightSource>();
+            let range = lights
+                .get(item)
+                .map_or(range, |light| light.range.max(range));
+            let (r, g, b) = AFFIX_LIGHT_COLOR;
+            lights
+                .insert(
+                    item,
+                    LightSource {
+                        range,
+                        color: RGB::from_f32(r, g, b),
+                    },
+                )
+                .expect("Unable to insert affix's light");
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Translations of the game's text, in the style of gettext or Fluent.
+//!
+//! Every message is looked up by an ID (like `"melee-hit"`) in the catalog for
+//! the current [`Language`], and has its `{placeholders}` filled in afterwards, so
+//! translations are free to put things in a different order. The names of things
+//! are translated separately, keyed by their English name. Anything a catalog is
+//! missing falls back to English.
+//!
+//! The catalogs live in `raws/lang/`, and are baked into the binary like the rest
+//! of the raws.
+
+use std::fmt;
+use std::sync::{LazyLock, RwLock};
+
+use rltk::RGB;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::raws::RawsError;
+use crate::StatusEffect;
+
+const EN_RON: &str = include_str!("../raws/lang/en.ron");
+const FR_RON: &str = include_str!("../raws/lang/fr.ron");
+
+/// English, which every other catalog falls back to.
+static ENGLISH: LazyLock<Catalog> = LazyLock::new(|| {
+    Language::English
+        .catalog()
+        .expect("English catalog is broken")
+});
+
+/// The catalog for the language picked with [`set_language()`], or `None` when
+/// that's English.
+static CURRENT: RwLock<Option<Catalog>> = RwLock::new(None);
+
+/// A language the game's text can be shown in.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize, strum::EnumIter)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    #[default]
+    English,
+    French,
+}
+
+impl Language {
+    /// The name of the language's catalog file, and what's in it.
+    fn source(self) -> (&'static str, &'static str) {
+        match self {
+            Self::English => ("lang/en.ron", EN_RON),
+            Self::French => ("lang/fr.ron", FR_RON),
+        }
+    }
+
+    fn catalog(self) -> Result<Catalog, RawsError> {
+        let (file, text) = self.source();
+        ron::from_str(text).map_err(|source| RawsError::Parse { file, source })
+    }
+}
+
+/// Everything translated into one language.
+#[derive(Debug, Default, Deserialize)]
+struct Catalog {
+    /// Message templates, keyed by message ID.
+    #[serde(default)]
+    messages: FxHashMap<String, String>,
+    /// The names of things, keyed by their English name.
+    #[serde(default)]
+    names: FxHashMap<String, String>,
+}
+
+/// Show all of the game's text in `lang` from now on.
+pub fn set_language(lang: Language) -> Result<(), RawsError> {
+    let catalog = match lang {
+        Language::English => None,
+        _ => Some(lang.catalog()?),
+    };
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = catalog;
+    Ok(())
+}
+
+/// Look up `lookup` in the current catalog, then in English.
+fn find<T>(lookup: impl Fn(&Catalog) -> Option<T>) -> Option<T> {
+    let current = CURRENT.read().unwrap_or_else(|e| e.into_inner());
+    current
+        .as_ref()
+        .and_then(&lookup)
+        .or_else(|| lookup(&ENGLISH))
+}
+
+/// Start translating the message with the ID `id`. Its placeholders are filled in
+/// with [`Translation::arg()`]. If no catalog has the message, the ID itself is
+/// shown, so that it's easy to spot.
+pub fn tr(id: &str) -> Translation {
+    Translation {
+        template: find(|catalog| catalog.messages.get(id).cloned())
+            .unwrap_or_else(|| id.to_string()),
+        args: Vec::new(),
+    }
+}
+
+/// The name of a thing called `english` in English, in the current language.
+pub fn name(english: &str) -> String {
+    find(|catalog| catalog.names.get(english).cloned()).unwrap_or_else(|| english.to_string())
+}
+
+/// The word for an entity that's under `effect`, like "confused".
+pub fn status_effect(effect: StatusEffect) -> Translation {
+    tr(&status_effect_id(effect))
+}
+
+fn status_effect_id(effect: StatusEffect) -> String {
+    format!("status-{}", effect.to_string().to_lowercase())
+}
+
+/// A translated message, waiting for its placeholders to be filled in. See [`tr()`].
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Translation {
+    template: String,
+    args: Vec<(&'static str, String, Option<RGB>)>,
+}
+
+impl Translation {
+    /// Fill the `{name}` placeholder in with `value`.
+    pub fn arg<S: ToString>(mut self, name: &'static str, value: S) -> Self {
+        self.args.push((name, value.to_string(), None));
+        self
+    }
+
+    /// Fill the `{name}` placeholder in with `value`, shown in `color`.
+    pub fn colored_arg<S: ToString>(mut self, name: &'static str, value: S, color: RGB) -> Self {
+        self.args.push((name, value.to_string(), Some(color)));
+        self
+    }
+
+    /// The finished message, split up into pieces that are all one colour. Pieces
+    /// with no colour of their own should be shown in whatever colour the rest of
+    /// the message is.
+    pub fn spans(&self) -> Vec<(String, Option<RGB>)> {
+        let mut spans: Vec<(String, Option<RGB>)> = Vec::new();
+        let mut push = |text: &str, color: Option<RGB>| match spans.last_mut() {
+            _ if text.is_empty() => {}
+            Some((last, last_color)) if *last_color == color => last.push_str(text),
+            _ => spans.push((text.to_string(), color)),
+        };
+
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let placeholder = &rest[start + 1..start + len];
+            match self.args.iter().find(|(name, ..)| *name == placeholder) {
+                Some((_, value, color)) => {
+                    push(&rest[..start], None);
+                    push(value, *color);
+                }
+                // Leave anything that isn't filled in as it is
+                None => push(&rest[..=start + len], None),
+            }
+            rest = &rest[start + len + 1..];
+        }
+        push(rest, None);
+
+        spans
+    }
+}
+
+impl fmt::Display for Translation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (text, _) in self.spans() {
+            f.write_str(&text)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    /// Every `{placeholder}` in `template`, sorted so order doesn't matter.
+    fn placeholders(template: &str) -> Vec<&str> {
+        let mut placeholders: Vec<&str> = template
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+            .collect();
+        placeholders.sort_unstable();
+        placeholders
+    }
+
+    #[test]
+    fn catalogs_match_english() {
+        for lang in Language::iter() {
+            let catalog = lang.catalog().unwrap();
+            for (id, template) in catalog.messages.iter() {
+                let english = ENGLISH
+                    .messages
+                    .get(id)
+                    .unwrap_or_else(|| panic!("{lang:?} has {id}, but English doesn't"));
+                assert_eq!(
+                    placeholders(template),
+                    placeholders(english),
+                    "{lang:?} {id} has different placeholders to English",
+                );
+            }
+        }
+    }
+
+    /// The contents of every Rust source file under `dir`.
+    fn sources_in(dir: &std::path::Path) -> Vec<(std::path::PathBuf, String)> {
+        let mut sources = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                sources.extend(sources_in(&path));
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                let source = std::fs::read_to_string(&path).unwrap();
+                sources.push((path, source));
+            }
+        }
+        sources
+    }
+
+    #[test]
+    fn every_message_used_exists() {
+        let src = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        // This file's own tests look up missing messages on purpose
+        let sources = sources_in(&src)
+            .into_iter()
+            .filter(|(path, _)| !path.ends_with("l10n.rs"));
+        for (path, source) in sources {
+            for (at, call) in source.match_indices("tr(\"") {
+                // Skip over the likes of `push_str("...")`
+                if source[..at].ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                    continue;
+                }
+                let id = source[at + call.len()..].split('"').next().unwrap();
+                assert!(
+                    ENGLISH.messages.contains_key(id),
+                    "{id} (used in {}) isn't in English",
+                    path.display()
+                );
+            }
+        }
+        for effect in StatusEffect::iter() {
+            let id = status_effect_id(effect);
+            assert!(ENGLISH.messages.contains_key(&id), "{id} isn't in English");
+        }
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        let hit = Translation {
+            template: "{attacker} hits {target}, for {damage}.".to_string(),
+            args: Vec::new(),
+        }
+        .colored_arg("attacker", "Orc", RGB::named(rltk::YELLOW))
+        .arg("target", "Player")
+        .arg("damage", "3 hp");
+
+        assert_eq!(hit.to_string(), "Orc hits Player, for 3 hp.");
+        assert_eq!(
+            hit.spans(),
+            vec![
+                ("Orc".to_string(), Some(RGB::named(rltk::YELLOW))),
+                (" hits Player, for 3 hp.".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_messages_fall_back() {
+        assert_eq!(tr("no-such-message").to_string(), "no-such-message");
+        assert_eq!(name("No Such Thing"), "No Such Thing");
+    }
+}
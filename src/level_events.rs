@@ -0,0 +1,246 @@
+//! Rare timed events that shake up the level the player is on, like cave-ins,
+//! floods, and monsters migrating up from the depths.
+//!
+//! The [`LevelEventScheduler`] counts down monster turns, and fires off a random
+//! event whenever it runs out. Events that reshape the map are checked with
+//! [`validate_map()`] afterwards, and undone if they'd cut the player off.
+
+use std::collections::VecDeque;
+
+use rltk::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::l10n::tr;
+use crate::map_builders::{validate_map, MapValidationError};
+use crate::{spawner, GameLog, Map, PlayerPos, Position, TileType, Viewshed};
+
+/// The fewest turns between two level events.
+const MIN_TURNS_BETWEEN_EVENTS: i32 = 300;
+
+/// The most turns between two level events.
+const MAX_TURNS_BETWEEN_EVENTS: i32 = 600;
+
+/// How far from its epicentre a cave-in reshapes the walls.
+const CAVE_IN_RADIUS: i32 = 3;
+
+/// Cave-ins never happen this close to the player, so they aren't buried alive.
+const CAVE_IN_SAFE_DISTANCE: i32 = 4;
+
+/// How many steps from its source a flood spreads.
+const FLOOD_RADIUS: i32 = 5;
+
+/// How many steps from its source a flood is deep enough to swim in.
+const FLOOD_DEEP_RADIUS: i32 = 2;
+
+/// Floods never start this close to the player, so they aren't swept away.
+const FLOOD_SAFE_DISTANCE: i32 = 4;
+
+/// Counts down the turns until the next level event.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LevelEventScheduler {
+    turns_until_next: i32,
+}
+
+impl Default for LevelEventScheduler {
+    fn default() -> Self {
+        Self {
+            turns_until_next: MAX_TURNS_BETWEEN_EVENTS,
+        }
+    }
+}
+
+/// Something that can happen to a level out of the blue.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum LevelEvent {
+    /// Part of the level collapses, turning some floor to rubble and opening up
+    /// some walls.
+    CaveIn,
+    /// Water wells up somewhere on the level, and spreads out over the floor
+    /// around it.
+    Flood,
+    /// A band of monsters wanders up from the level below.
+    Migration,
+}
+
+/// Tick the [`LevelEventScheduler`] down by a turn, and fire off an event if it's
+/// time. Should be run once per monster turn.
+pub fn run_level_events(ecs: &mut World) {
+    // Nothing ever happens in town
+    if ecs.fetch::<Map>().depth <= 1 {
+        return;
+    }
+
+    let event = {
+        let mut scheduler = ecs.write_resource::<LevelEventScheduler>();
+        scheduler.turns_until_next -= 1;
+        if scheduler.turns_until_next > 0 {
+            return;
+        }
+
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        scheduler.turns_until_next = rng.range(MIN_TURNS_BETWEEN_EVENTS, MAX_TURNS_BETWEEN_EVENTS);
+        match rng.roll_dice(1, 3) {
+            1 => LevelEvent::CaveIn,
+            2 => LevelEvent::Flood,
+            _ => LevelEvent::Migration,
+        }
+    };
+
+    match event {
+        LevelEvent::CaveIn => cave_in(ecs),
+        LevelEvent::Flood => flood(ecs),
+        LevelEvent::Migration => migration(ecs),
+    }
+}
+
+/// Collapse part of the level. Empty floor tiles may be filled with rubble, and
+/// walls may crumble away into new passages.
+fn cave_in(ecs: &mut World) {
+    let player_pos = *ecs.fetch::<PlayerPos>();
+    let mut map = ecs.write_resource::<Map>();
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+
+    let candidates: Vec<usize> = (0..map.tiles.len())
+        .filter(|&idx| map.tiles[idx] == TileType::Floor)
+        .filter(|&idx| {
+            let distance = rltk::DistanceAlg::Pythagoras
+                .distance2d(rltk::Point::from_tuple(map.idx_xy(idx)), *player_pos);
+            distance > (CAVE_IN_RADIUS + CAVE_IN_SAFE_DISTANCE) as f32
+        })
+        .collect();
+    if candidates.is_empty() {
+        return;
+    }
+    let epicentre = candidates[rng.range(0, candidates.len())];
+    let (cx, cy) = map.idx_xy(epicentre);
+
+    let before = map.tiles.clone();
+    for y in (cy - CAVE_IN_RADIUS).max(1)..=(cy + CAVE_IN_RADIUS).min(map.height - 2) {
+        for x in (cx - CAVE_IN_RADIUS).max(1)..=(cx + CAVE_IN_RADIUS).min(map.width - 2) {
+            let idx = map.xy_idx(x, y);
+            if rng.roll_dice(1, 3) != 1 {
+                continue;
+            }
+            match map.tiles[idx] {
+                TileType::Floor if map.tile_content[idx].is_empty() => {
+                    map.tiles[idx] = TileType::Wall;
+                }
+                TileType::Wall => map.tiles[idx] = TileType::Floor,
+                _ => {}
+            }
+        }
+    }
+
+    // Never strand the player away from the stairs or the rest of the level
+    match validate_map(&map, Position::from((player_pos.x, player_pos.y))) {
+        Ok(()) | Err(MapValidationError::NoStairs) => {}
+        Err(_) => {
+            map.tiles = before;
+            return;
+        }
+    }
+
+    // Everything that could see the collapse needs to take another look
+    map.populate_blocked();
+    for viewshed in (&mut ecs.write_storage::<Viewshed>()).join() {
+        viewshed.dirty = true;
+    }
+
+    ecs.fetch_mut::<GameLog>().log(tr("cave-in"));
+}
+
+/// Flood part of the level. Water spreads out across the floor from a random spot
+/// well away from the player, deep near where it started and shallow further out.
+fn flood(ecs: &mut World) {
+    let player_pos = *ecs.fetch::<PlayerPos>();
+    let mut map = ecs.write_resource::<Map>();
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+
+    let candidates: Vec<usize> = (0..map.tiles.len())
+        .filter(|&idx| map.tiles[idx] == TileType::Floor)
+        .filter(|&idx| {
+            let distance = rltk::DistanceAlg::Pythagoras
+                .distance2d(rltk::Point::from_tuple(map.idx_xy(idx)), *player_pos);
+            distance > (FLOOD_RADIUS + FLOOD_SAFE_DISTANCE) as f32
+        })
+        .collect();
+    if candidates.is_empty() {
+        return;
+    }
+    let source = candidates[rng.range(0, candidates.len())];
+
+    // Spread out over the floor a step at a time, so the water runs down corridors
+    // instead of through walls
+    let mut steps = vec![None; map.tiles.len()];
+    steps[source] = Some(0);
+    let mut frontier = VecDeque::from([source]);
+    while let Some(idx) = frontier.pop_front() {
+        let step = steps[idx].unwrap_or_default();
+        map.tiles[idx] = if step <= FLOOD_DEEP_RADIUS && map.tile_content[idx].is_empty() {
+            TileType::DeepWater
+        } else {
+            TileType::ShallowWater
+        };
+        if step == FLOOD_RADIUS {
+            continue;
+        }
+
+        let (x, y) = map.idx_xy(idx);
+        for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            let next = map.xy_idx(nx, ny);
+            if steps[next].is_none() && map.tiles[next] == TileType::Floor {
+                steps[next] = Some(step + 1);
+                frontier.push_back(next);
+            }
+        }
+    }
+
+    // Everything that could see the flood needs to take another look
+    map.populate_blocked();
+    for viewshed in (&mut ecs.write_storage::<Viewshed>()).join() {
+        viewshed.dirty = true;
+    }
+
+    ecs.fetch_mut::<GameLog>().log(tr("flood"));
+}
+
+/// Bring a few monsters up onto the level, arriving from the stairs down.
+fn migration(ecs: &mut World) {
+    let arrivals: Vec<usize> = {
+        let map = ecs.fetch::<Map>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+
+        let Some(stairs_idx) = map
+            .tiles
+            .iter()
+            .position(|tile| *tile == TileType::DownStairs)
+        else {
+            return;
+        };
+        let (sx, sy) = map.idx_xy(stairs_idx);
+
+        let mut free: Vec<usize> = (sy - 1..=sy + 1)
+            .flat_map(|y| (sx - 1..=sx + 1).map(move |x| (x, y)))
+            .map(|(x, y)| map.xy_idx(x, y))
+            .filter(|&idx| map.tiles[idx] == TileType::Floor && !map.blocked[idx])
+            .collect();
+
+        let count = (rng.roll_dice(1, 3) + 1) as usize;
+        let mut arrivals = Vec::new();
+        while arrivals.len() < count && !free.is_empty() {
+            arrivals.push(free.swap_remove(rng.range(0, free.len())));
+        }
+        arrivals
+    };
+    if arrivals.is_empty() {
+        return;
+    }
+
+    let depth = ecs.fetch::<Map>().depth;
+    for idx in arrivals {
+        spawner::spawn_wandering_monster(ecs, idx, depth);
+    }
+
+    ecs.fetch_mut::<GameLog>().log(tr("monsters-migrate"));
+}
@@ -0,0 +1,1610 @@
+//! The game logic behind the roguelike: every component, system, and map
+//! builder, and the [`State`] that ties them together. The game itself is a thin
+//! binary that just calls [`run_game()`], so that tests, benchmarks, and other
+//! tools can use the same logic.
+
+mod accessibility;
+mod achievements;
+mod boss_ai_system;
+mod bystander_ai_system;
+mod camera;
+mod cli;
+mod components;
+pub mod config;
+mod crime;
+mod damage_system;
+#[cfg(feature = "debug-console")]
+mod debug_commands;
+mod drowning_system;
+pub mod dungeon;
+mod effects;
+mod factions;
+mod game_events;
+mod gamelog;
+mod gui;
+mod high_scores;
+mod hoarder_ai_system;
+mod hunger_system;
+mod identification;
+mod inventory_system;
+mod item_factory;
+mod l10n;
+mod level_events;
+mod lighting_system;
+mod map;
+pub mod map_builders;
+mod map_indexing_system;
+mod melee_combat_system;
+mod monster_ai_system;
+mod morale;
+mod music;
+mod noise_system;
+mod pack_ai_system;
+mod palette;
+mod player;
+mod progression;
+mod quests;
+mod raws;
+mod rect;
+mod render;
+mod replay;
+mod respawns;
+mod restocking;
+mod rewind;
+pub mod rng_table;
+mod run_options;
+mod saveload_system;
+mod screen_effects;
+mod services;
+pub mod spawner;
+mod status_effect_system;
+mod storage;
+pub mod systems;
+mod trajectory;
+mod trigger_system;
+mod ui_layout;
+mod visibility_system;
+
+pub use self::accessibility::ScreenReader;
+pub use self::achievements::Achievements;
+pub use self::boss_ai_system::BossAI;
+pub use self::bystander_ai_system::BystanderAI;
+pub use self::cli::Args;
+pub use self::components::*;
+pub use self::config::Config;
+pub use self::crime::Reputation;
+pub use self::drowning_system::DrowningSystem;
+pub use self::dungeon::{Branch, LevelId, MasterDungeonMap};
+pub use self::game_events::{GameEvent, GameEvents, RunStats};
+pub use self::gamelog::{GameLog, LogEntry};
+pub use self::high_scores::HighScores;
+pub use self::hoarder_ai_system::HoarderAI;
+pub use self::hunger_system::HungerSystem;
+pub use self::identification::MasterItemNames;
+pub use self::inventory_system::*;
+pub use self::level_events::LevelEventScheduler;
+pub use self::lighting_system::LightingSystem;
+pub use self::map::*;
+pub use self::map_indexing_system::MapIndexingSystem;
+pub use self::melee_combat_system::MeleeCombatSystem;
+pub use self::monster_ai_system::MonsterAI;
+pub use self::noise_system::NoiseSystem;
+pub use self::pack_ai_system::PackAI;
+pub use self::palette::Palette;
+pub use self::player::*;
+pub use self::quests::QuestJournal;
+pub use self::rect::Rect;
+pub use self::run_options::{Difficulty, NewRunOptions, RunOptions};
+pub use self::services::{buy_service, nothing_to_offer_message, service_offers, ServiceOffer};
+pub use self::status_effect_system::StatusEffectSystem;
+pub use self::trajectory::{blast_area, resolve_trajectory};
+pub use self::trigger_system::TriggerSystem;
+pub use self::ui_layout::{HudLayout, UiLayout};
+pub use self::visibility_system::VisibilitySystem;
+
+use color_eyre::eyre::Context;
+use rltk::RandomNumberGenerator;
+use rltk::{GameState, Rltk, RltkBuilder};
+use specs::prelude::*;
+use specs::saveload::SimpleMarkerAllocator;
+
+use self::l10n::tr;
+
+/// How many rows of the level a magic map sweeps into view each frame.
+const MAGIC_MAP_ROWS_PER_FRAME: i32 = 2;
+
+/// The game is either "Running" or "Waiting for Input."
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum RunState {
+    AwaitingInput,
+    PreRun,
+    PlayerTurn,
+    MonsterTurn,
+    ShowInventory,
+    ShowDropItem,
+    /// Show the spells that the player knows, to pick one to cast.
+    ShowSpellbook,
+    /// The player levelled up, and needs to pick an upgrade.
+    LevelUp,
+    /// Show the item-targeting UI
+    ShowTargeting {
+        /// The item's range
+        range: i32,
+        /// A reference to the item (or spell) entity
+        item: Entity,
+    },
+    /// Show the main menu.
+    MainMenu {
+        menu_selection: gui::MainMenuSelection,
+    },
+    /// Show the options menu, with `selection` highlighted.
+    OptionsMenu {
+        selection: gui::OptionsEntry,
+    },
+    /// Type in the seed that new runs start from.
+    EnterSeed,
+    /// Show every achievement, and which ones have been unlocked.
+    ShowAchievements,
+    /// Show the best finished runs.
+    ShowHighScores,
+    /// Pick one of the saved games to load.
+    LoadGameMenu,
+    /// A save couldn't be loaded. Say why, then go back to the main menu with
+    /// `menu_selection` highlighted.
+    LoadFailed {
+        menu_selection: gui::MainMenuSelection,
+    },
+    /// Pick a save slot to save the game into, and quit to the main menu.
+    SaveGame,
+    /// Take the player off of the current level, to `destination`.
+    ChangeLevel {
+        destination: LevelId,
+    },
+    /// Open a portal back to town under the player, and take them through it.
+    OpenTownPortal,
+    /// The player died. Show them how their run went.
+    GameOver,
+    /// The player won! Show them how their run went.
+    Victory,
+    /// Move a cursor around the map to look at things and leave notes.
+    Examine {
+        x: i32,
+        y: i32,
+    },
+    /// Type in the text of a note being left on the map at (`x`, `y`).
+    WriteNote {
+        x: i32,
+        y: i32,
+    },
+    /// Show a list of the notes left on the current level.
+    ShowNotes,
+    /// Put the game back the way it was at the start of the previous turn.
+    RewindTurn,
+    /// Type in a command for the debug console.
+    #[cfg(feature = "debug-console")]
+    DebugConsole,
+    /// Show the whole game log, scrolled up by `offset` entries from the newest.
+    ShowLog {
+        offset: usize,
+    },
+    /// Show what a friendly NPC has for sale.
+    TalkToNpc {
+        npc: Entity,
+    },
+    /// Ask whether the player will take on the quest that `giver` offers.
+    OfferQuest {
+        giver: Entity,
+    },
+    /// Show the quests the player has taken on.
+    ShowJournal,
+    /// Show `line` of what a [`Bystander`] has to say, from their [`Quips`].
+    ShowDialogue {
+        speaker: Entity,
+        line: usize,
+    },
+    /// Move items between the player's backpack and their stash.
+    ShowStash {
+        withdrawing: bool,
+    },
+    /// Take items out of a [`Container`], or (if `storing`) put items in it.
+    ShowContainer {
+        container: Entity,
+        storing: bool,
+    },
+    /// Buy items from a [`Vendor`], or (if `selling`) sell items to them.
+    ShowVendor {
+        vendor: Entity,
+        selling: bool,
+    },
+    /// Sweep the rest of the level's layout into view after the player reads a
+    /// magic map, one `row` of tiles at a time.
+    MagicMapReveal {
+        row: i32,
+    },
+    /// Pick something to throw.
+    ShowThrowItem,
+    /// Pick somewhere to throw `item` at.
+    ThrowItem {
+        item: Entity,
+    },
+    /// Pick somewhere to throw a rock, to distract monsters with the noise.
+    ThrowRock,
+    /// Pick which equipped item to enchant with the `scroll` the player just read.
+    ShowEnchantItem {
+        scroll: Entity,
+    },
+    /// Ask whether the player wants to equip an upgrade they just picked up.
+    ConfirmEquip {
+        item: Entity,
+    },
+    /// Ask whether the player really wants to fire `item` at (`x`, `y`), when it'll
+    /// hit them or one of their allies.
+    ConfirmFriendlyFire {
+        item: Entity,
+        x: i32,
+        y: i32,
+    },
+}
+
+/// Global game state.
+pub struct State {
+    pub ecs: World,
+    /// Runs every system, in the order laid out by [`systems::build_dispatcher()`].
+    dispatcher: Dispatcher<'static, 'static>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            ecs: World::new(),
+            dispatcher: systems::build_dispatcher(),
+        }
+    }
+}
+
+impl State {
+    /// Runs all ECS systems for one ECS tick.
+    pub fn run_systems(&mut self) {
+        self.dispatcher.dispatch(&self.ecs);
+        boss_ai_system::spawn_summons(&mut self.ecs);
+        effects::run_effects_queue(&mut self.ecs);
+
+        self.ecs.maintain();
+    }
+
+    /// Build the new `level`, replace the current [`Map`] resource with it, and
+    /// populate it with monsters and items. If the player has already been there,
+    /// the old level is restored instead.
+    ///
+    /// `from` is the level the player is leaving, and decides which exit they
+    /// arrive at.
+    ///
+    /// Returns where the player should be placed on the new level.
+    fn generate_world_map(&mut self, level: LevelId, from: LevelId) -> Position {
+        let existing_map = self.ecs.fetch::<MasterDungeonMap>().get_map(level);
+        if let Some(map) = existing_map {
+            // Levels from before exits were entities only have their stairs to go by
+            let arrival_stairs = if level.depth < from.depth {
+                TileType::DownStairs
+            } else {
+                TileType::UpStairs
+            };
+            let stairs = map
+                .tiles
+                .iter()
+                .position(|tile| *tile == arrival_stairs)
+                .map(|idx| Position::from(map.idx_xy(idx)));
+
+            self.ecs.insert(map);
+            dungeon::thaw_level_entities(&mut self.ecs, level);
+            return dungeon::arrival_point(&self.ecs, from)
+                .or(stairs)
+                .expect("Previously-visited level has no way in to arrive at");
+        }
+
+        let mut builder = {
+            let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
+            map_builders::build_valid_level(level, &mut rng)
+        };
+
+        let start = builder.get_starting_position();
+        let mut map = builder.get_map();
+        map.branch = level.branch;
+        map.respawns_left = respawns::respawn_budget(level.depth);
+        let start_idx = map.xy_idx(start.x, start.y);
+        // Every level below the town has a way back up where the player arrives
+        let up = level.above();
+        if up.is_some() {
+            map.tiles[start_idx] = TileType::UpStairs;
+        }
+
+        // There's no going any deeper than the bottom of a branch. At the bottom of
+        // the main dungeon, the Amulet waits where the stairs down would have been.
+        let down = level.below();
+        let down_idx = map
+            .tiles
+            .iter()
+            .position(|tile| *tile == TileType::DownStairs);
+        let amulet_idx = match (down, down_idx) {
+            (None, Some(idx)) => {
+                map.tiles[idx] = TileType::Floor;
+                Some(idx).filter(|_| level.branch == Branch::Main)
+            }
+            _ => None,
+        };
+
+        self.ecs.insert(map);
+        builder.spawn_entities(&mut self.ecs);
+        if let Some(idx) = amulet_idx {
+            spawner::spawn_amulet(&mut self.ecs, idx);
+        }
+
+        if let Some(up) = up {
+            spawner::spawn_stairs(&mut self.ecs, start_idx, up);
+        }
+        if let (Some(down), Some(idx)) = (down, down_idx) {
+            spawner::spawn_stairs(&mut self.ecs, idx, down);
+        }
+        spawner::spawn_boss(&mut self.ecs, level, start);
+        self.raise_ghost(level, start);
+        for branch in Branch::opening_off(level) {
+            spawner::spawn_branch_entrance(&mut self.ecs, branch, start);
+        }
+
+        // The first level doubles as the town, with friendly NPCs waiting near the entrance
+        if level == LevelId::main(1) {
+            spawner::spawn_town_npcs(&mut self.ecs, start);
+        }
+
+        start
+    }
+
+    /// Maybe bring back the ghost of a character that died on `level` in an earlier
+    /// run. Once a ghost has risen, its bones are gone for good.
+    fn raise_ghost(&mut self, level: LevelId, start: Position) {
+        let Some(bones) = saveload_system::load_bones(level) else {
+            return;
+        };
+        let roll = self
+            .ecs
+            .write_resource::<RandomNumberGenerator>()
+            .range(0, spawner::GHOST_ODDS);
+        if roll != 0 {
+            return;
+        }
+
+        if spawner::spawn_ghost(&mut self.ecs, &bones, start).is_some() {
+            saveload_system::delete_bones(level)
+                .wrap_err("Failed to lay bones to rest")
+                .unwrap();
+        }
+    }
+
+    /// Set up everything needed for a brand-new run: a fresh first level, a new
+    /// player, and empty logs and stats.
+    pub fn start_new_run(&mut self) {
+        // Lock in the options picked for this run, with the seed that everything
+        // random in the run is rolled from
+        let seed = self.ecs.fetch::<NewRunOptions>().next_seed();
+        self.ecs.insert(RandomNumberGenerator::seeded(seed));
+        let run_options = RunOptions {
+            seed,
+            ..**self.ecs.fetch::<NewRunOptions>()
+        };
+        self.ecs.insert(run_options);
+        self.ecs
+            .fetch_mut::<replay::Replay>()
+            .start_run(run_options);
+
+        self.ecs.insert(GameEvents::default());
+        self.ecs.insert(effects::EffectQueue::default());
+        self.ecs.insert(RunStats::default());
+        self.ecs.insert(MasterDungeonMap::default());
+        self.ecs.insert(LevelEventScheduler::default());
+        self.ecs.insert(Reputation::default());
+        self.ecs.insert(QuestJournal::default());
+        self.ecs.insert(respawns::RespawnTimer::default());
+        self.ecs.insert(rewind::RewindBuffer::default());
+        let item_names =
+            MasterItemNames::new(&mut self.ecs.write_resource::<RandomNumberGenerator>());
+        self.ecs.insert(item_names);
+
+        // Build the first level, and fill it with monsters and items
+        let Position {
+            x: player_x,
+            y: player_y,
+        } = self.generate_world_map(LevelId::main(1), LevelId::main(1));
+
+        // Create the player
+        let player_entity = spawner::player(&mut self.ecs, player_x, player_y);
+
+        self.ecs.insert(PlayerPos::new(player_x, player_y));
+        self.ecs.insert(player_entity);
+        self.ecs.insert(TravelPath::default());
+        self.ecs.insert(Resting::default());
+        self.ecs
+            .insert(GameLog::from(vec![tr("welcome").to_string()]));
+        run_options::log_hint(&self.ecs, 1);
+    }
+
+    /// Throw away everything from the current run, and start a new one.
+    fn reset_run(&mut self) {
+        let everything: Vec<Entity> = self.ecs.entities().join().collect();
+        self.ecs
+            .delete_entities(&everything)
+            .expect("Unable to delete entities left over from the last run");
+
+        self.start_new_run();
+    }
+
+    /// Throw away everything from a run that's ended, and start over from scratch.
+    fn game_over_cleanup(&mut self, won: bool) {
+        high_scores::record_run(&self.ecs, won)
+            .wrap_err("Failed to record high score")
+            .unwrap();
+        // Nobody comes back to haunt the town
+        if !won && self.ecs.fetch::<Map>().depth > LevelId::TOWN.depth {
+            saveload_system::save_bones(&self.ecs)
+                .wrap_err("Failed to leave bones")
+                .unwrap();
+        }
+        saveload_system::end_session()
+            .wrap_err("Failed to end session")
+            .unwrap();
+
+        self.reset_run();
+    }
+
+    /// Pick up the game in save slot `slot` (counting from zero) where it was left
+    /// off. If the save can't be loaded, the current game is left as it was.
+    fn load_slot(&mut self, slot: usize) -> Result<(), saveload_system::LoadGameError> {
+        saveload_system::load_game(&mut self.ecs, slot)?;
+
+        // Ensures permadeath, for runs that asked for it
+        if self.ecs.fetch::<RunOptions>().permadeath {
+            saveload_system::delete_save(slot)
+                .wrap_err("Failed to delete loaded save file")
+                .unwrap();
+        }
+        self.resume_loaded_game();
+
+        Ok(())
+    }
+
+    /// Pick up the session that was running when the game last crashed. If the
+    /// autosave can't be loaded, the current game is left as it was.
+    fn recover_session(&mut self) -> Result<(), saveload_system::LoadGameError> {
+        saveload_system::recover_session(&mut self.ecs)?;
+        self.resume_loaded_game();
+
+        Ok(())
+    }
+
+    /// Get a game that was just loaded going again.
+    fn resume_loaded_game(&mut self) {
+        self.ecs.fetch_mut::<replay::Replay>().stop();
+        rewind::reset(&mut self.ecs)
+            .wrap_err("Failed to record turn for rewinding")
+            .unwrap();
+        saveload_system::begin_session(&mut self.ecs)
+            .wrap_err("Failed to start session")
+            .unwrap();
+    }
+
+    /// Get ready to tell the player why a save couldn't be loaded. Returns the
+    /// state that does, which goes back to the main menu with `menu_selection`
+    /// highlighted afterwards.
+    fn load_failed(
+        &mut self,
+        error: &saveload_system::LoadGameError,
+        menu_selection: gui::MainMenuSelection,
+    ) -> RunState {
+        let message = match error {
+            saveload_system::LoadGameError::IncompatibleSave { .. } => tr("incompatible-save"),
+            _ => tr("damaged-save"),
+        };
+        self.ecs.insert(gui::LoadFailure {
+            message: message.to_string(),
+        });
+        RunState::LoadFailed { menu_selection }
+    }
+
+    /// Take the player to `destination`, and tell them where they've gone.
+    fn goto_level(&mut self, destination: LevelId) {
+        let from = self.ecs.fetch::<Map>().level_id();
+        let via_portal = dungeon::standing_on_portal(&self.ecs);
+        self.change_level(destination);
+
+        if via_portal {
+            self.ecs
+                .fetch_mut::<GameLog>()
+                .log(tr("step-through-portal"));
+            return;
+        }
+
+        if destination.depth < from.depth {
+            self.ecs.fetch_mut::<GameLog>().log(tr("ascend-level"));
+            return;
+        }
+        let message = if destination.branch != from.branch {
+            tr("enter-branch")
+                .arg("branch", l10n::name(&destination.branch.to_string()))
+                .to_string()
+        } else {
+            tr("descend-level").to_string()
+        };
+
+        // Give the player back some health on the way down
+        let player_entity = self.ecs.fetch::<PlayerEntity>();
+        let mut gamelog = self.ecs.fetch_mut::<GameLog>();
+        let mut all_combat_stats = self.ecs.write_component::<CombatStats>();
+        if let Some(player_combat_stats) = all_combat_stats.get_mut(**player_entity) {
+            if player_combat_stats.hp >= player_combat_stats.max_hp / 2 {
+                gamelog.log(message);
+            } else {
+                gamelog.log(format!("{message} {}", tr("descend-heal")));
+                player_combat_stats.hp = player_combat_stats.max_hp / 2;
+            }
+        }
+    }
+
+    /// Open a portal from the current level to town, and take the player through
+    /// it. A second portal is left open in town, leading back to where they were.
+    fn open_town_portal(&mut self) {
+        let here = self.ecs.fetch::<Map>().level_id();
+        dungeon::close_portals(&mut self.ecs);
+        dungeon::open_portal(&mut self.ecs, LevelId::TOWN);
+        self.change_level(LevelId::TOWN);
+        dungeon::open_return_portal(&mut self.ecs, here);
+        self.ecs.fetch_mut::<GameLog>().log(tr("portal-to-town"));
+    }
+
+    /// Leave the current level, freezing it in the [`MasterDungeonMap`], and move the
+    /// player to `destination`.
+    ///
+    /// Going through a [`Portal`] puts the player at the portal on the other side,
+    /// and closes both once they've left town.
+    fn change_level(&mut self, destination: LevelId) {
+        let via_portal = dungeon::standing_on_portal(&self.ecs);
+
+        // Freeze the level we're leaving, so it's still there when we come back
+        let from = {
+            let map = self.ecs.fetch::<Map>();
+            self.ecs
+                .write_resource::<MasterDungeonMap>()
+                .store_map(&map);
+            map.level_id()
+        };
+        dungeon::freeze_level_entities(&mut self.ecs);
+
+        // Build (or restore) the new map and place the player
+        let Position {
+            x: player_x,
+            y: player_y,
+        } = {
+            let arrival = self.generate_world_map(destination, from);
+            if via_portal {
+                dungeon::portal_arrival(&self.ecs, from).unwrap_or(arrival)
+            } else {
+                arrival
+            }
+        };
+        if via_portal && destination != LevelId::TOWN {
+            dungeon::close_portals(&mut self.ecs);
+        }
+        if destination == LevelId::TOWN {
+            restocking::restock_vendors(&mut self.ecs);
+        }
+        self.ecs
+            .fetch_mut::<GameEvents>()
+            .push(GameEvent::LevelEntered {
+                depth: destination.depth,
+            });
+
+        let mut player_pos = self.ecs.fetch_mut::<PlayerPos>();
+        player_pos.x = player_x;
+        player_pos.y = player_y;
+
+        let mut positions = self.ecs.write_component::<Position>();
+        let player_entity = self.ecs.fetch::<PlayerEntity>();
+        if let Some(player_pos_component) = positions.get_mut(**player_entity) {
+            player_pos_component.x = player_x;
+            player_pos_component.y = player_y;
+        }
+
+        // Mark the player's visibility as dirty
+        let mut viewsheds = self.ecs.write_component::<Viewshed>();
+        if let Some(player_viewshed) = viewsheds.get_mut(**player_entity) {
+            player_viewshed.dirty = true;
+        }
+    }
+}
+
+/// Have the player use `item` on the `target` tile they picked.
+fn use_item_on_target(ecs: &World, item: Entity, target: rltk::Point) {
+    ecs.write_storage::<WantsToUseItem>()
+        .insert(
+            **ecs.fetch::<PlayerEntity>(),
+            WantsToUseItem {
+                item,
+                target: Some(target),
+            },
+        )
+        .expect("Unable to insert intent WantsToUseItem for player after selecting target");
+}
+
+/// Resize the console to fit `layout`.
+fn apply_hud_layout(ctx: &mut Rltk, layout: HudLayout) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let UiLayout {
+            console_width,
+            console_height,
+            ..
+        } = layout.layout();
+        ctx.set_char_size(console_width, console_height);
+    }
+
+    // The console can't be resized on the web, so stick with its original size
+    #[cfg(target_arch = "wasm32")]
+    let _ = (ctx, layout);
+}
+
+impl GameState for State {
+    fn tick(&mut self, ctx: &mut Rltk) {
+        ctx.cls();
+        effects::cull_dead_particles(&mut self.ecs, ctx.frame_time_ms);
+        self.ecs
+            .fetch_mut::<music::MusicDirector>()
+            .crossfade(ctx.frame_time_ms);
+
+        // Tick the ECS (or don't) depending on the current runstate. Make sure
+        // to transition to a new runstate after doing so.
+        let mut new_runstate;
+        {
+            let runstate = self.ecs.fetch::<RunState>();
+            new_runstate = *runstate;
+        }
+
+        // Record (or play back) the input for every frame of a run
+        if !matches!(
+            new_runstate,
+            RunState::MainMenu { .. }
+                | RunState::OptionsMenu { .. }
+                | RunState::EnterSeed
+                | RunState::ShowAchievements
+                | RunState::ShowHighScores
+                | RunState::LoadGameMenu
+                | RunState::LoadFailed { .. }
+        ) {
+            replay::process_input(&self.ecs, ctx)
+                .wrap_err("Failed to record input")
+                .unwrap();
+        }
+
+        // Only actually draw the main view if we're not on the main menu.
+        if !matches!(
+            new_runstate,
+            RunState::MainMenu { .. }
+                | RunState::OptionsMenu { .. }
+                | RunState::EnterSeed
+                | RunState::ShowAchievements
+                | RunState::ShowHighScores
+                | RunState::LoadGameMenu
+                | RunState::LoadFailed { .. }
+                | RunState::GameOver
+                | RunState::Victory
+        ) {
+            // Render the map
+            render::draw_map(&self.ecs, ctx);
+
+            // Render any entity that has a position
+            render::draw_entities(&self.ecs, ctx);
+            render::draw_health_bars(&self.ecs, ctx);
+
+            // Highlight tiles that the player has left notes on
+            render::draw_map_notes(&self.ecs, ctx);
+
+            // Float damage numbers over everything on the map
+            render::draw_floating_text(&self.ecs, ctx);
+
+            // Draw the GUI on top of everything
+            gui::draw_ui(&self.ecs, ctx);
+        }
+
+        // Shake and flash the screen, if anything's happened to warrant it
+        screen_effects::draw_screen_effects(&self.ecs, ctx);
+
+        match new_runstate {
+            RunState::MainMenu { .. } => match gui::main_menu(self, ctx) {
+                gui::MainMenuResult::NoSelection(cur_selection) => {
+                    new_runstate = RunState::MainMenu {
+                        menu_selection: cur_selection,
+                    }
+                }
+                gui::MainMenuResult::Selected(selected) => match selected {
+                    gui::MainMenuSelection::RecoverSession => {
+                        new_runstate = match self.recover_session() {
+                            Ok(()) => RunState::AwaitingInput,
+                            Err(e) => self.load_failed(&e, selected),
+                        };
+                    }
+                    gui::MainMenuSelection::NewGame => {
+                        // Start over, so that the run picks up any options that were
+                        // changed since the last one was set up
+                        self.reset_run();
+                        saveload_system::begin_session(&mut self.ecs)
+                            .wrap_err("Failed to start session")
+                            .unwrap();
+                        new_runstate = RunState::PreRun;
+                    }
+                    gui::MainMenuSelection::LoadGame => new_runstate = RunState::LoadGameMenu,
+                    gui::MainMenuSelection::HighScores => new_runstate = RunState::ShowHighScores,
+                    gui::MainMenuSelection::Achievements => {
+                        new_runstate = RunState::ShowAchievements
+                    }
+                    gui::MainMenuSelection::Options => {
+                        new_runstate = RunState::OptionsMenu {
+                            selection: gui::OptionsEntry::Layout(*self.ecs.fetch::<HudLayout>()),
+                        }
+                    }
+                    gui::MainMenuSelection::Quit => ctx.quit(),
+                },
+            },
+
+            RunState::OptionsMenu { .. } => match gui::options_menu(self, ctx) {
+                gui::OptionsMenuResult::NoSelection(selection) => {
+                    new_runstate = RunState::OptionsMenu { selection }
+                }
+                gui::OptionsMenuResult::Selected(gui::OptionsEntry::ScreenReader) => {
+                    let mut reader = self.ecs.fetch_mut::<ScreenReader>();
+                    reader.enabled = !reader.enabled;
+                }
+                gui::OptionsMenuResult::Selected(gui::OptionsEntry::FriendlyFireConfirmation) => {
+                    let mut confirmation = self.ecs.fetch_mut::<gui::FriendlyFireConfirmation>();
+                    confirmation.enabled = !confirmation.enabled;
+                }
+                gui::OptionsMenuResult::Selected(gui::OptionsEntry::ScreenShake) => {
+                    let mut shake = self.ecs.fetch_mut::<screen_effects::ScreenShake>();
+                    *shake = shake.next();
+                }
+                gui::OptionsMenuResult::Selected(gui::OptionsEntry::Palette) => {
+                    let kind = self.ecs.fetch::<Palette>().kind.next();
+                    self.ecs.insert(Palette::new(kind));
+                }
+                gui::OptionsMenuResult::Selected(gui::OptionsEntry::MonsterHealthBars) => {
+                    let mut health_bars = self.ecs.fetch_mut::<render::MonsterHealthBars>();
+                    health_bars.enabled = !health_bars.enabled;
+                }
+                gui::OptionsMenuResult::Selected(gui::OptionsEntry::Difficulty) => {
+                    let mut options = self.ecs.fetch_mut::<NewRunOptions>();
+                    options.difficulty = options.difficulty.next();
+                }
+                gui::OptionsMenuResult::Selected(gui::OptionsEntry::Permadeath) => {
+                    let mut options = self.ecs.fetch_mut::<NewRunOptions>();
+                    options.permadeath = !options.permadeath;
+                }
+                gui::OptionsMenuResult::Selected(gui::OptionsEntry::Hints) => {
+                    let mut options = self.ecs.fetch_mut::<NewRunOptions>();
+                    options.hints = !options.hints;
+                }
+                gui::OptionsMenuResult::Selected(gui::OptionsEntry::Seed) => {
+                    self.ecs.write_resource::<gui::TextEntryBuffer>().text = self
+                        .ecs
+                        .fetch::<NewRunOptions>()
+                        .fixed_seed
+                        .map(|seed| seed.to_string())
+                        .unwrap_or_default();
+                    new_runstate = RunState::EnterSeed;
+                }
+                gui::OptionsMenuResult::Selected(
+                    entry @ (gui::OptionsEntry::Font | gui::OptionsEntry::Scale),
+                ) => {
+                    let mut config = self.ecs.fetch_mut::<Config>();
+                    if entry == gui::OptionsEntry::Font {
+                        config.display.font = config.display.font.next();
+                    } else {
+                        config.display.scale = config.display.scale % config::MAX_MENU_SCALE + 1;
+                    }
+                    let saved = Config::save_window_settings(
+                        &self.ecs.fetch::<config::ConfigPath>().0,
+                        config.display.font,
+                        config.display.scale,
+                    );
+                    self.ecs.fetch_mut::<gui::OptionsSaveError>().0 =
+                        saved.err().map(|e| e.to_string());
+                }
+                gui::OptionsMenuResult::Selected(gui::OptionsEntry::Layout(layout)) => {
+                    apply_hud_layout(ctx, layout);
+                    self.ecs.insert(layout);
+                    new_runstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::Options,
+                    };
+                }
+                gui::OptionsMenuResult::Cancel => {
+                    new_runstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::Options,
+                    }
+                }
+            },
+
+            RunState::EnterSeed => match gui::text_entry(self, ctx, tr("seed-prompt")) {
+                gui::TextEntryResult::NoResponse => {}
+                result => {
+                    let text =
+                        std::mem::take(&mut self.ecs.write_resource::<gui::TextEntryBuffer>().text);
+                    if result == gui::TextEntryResult::Done {
+                        self.ecs.fetch_mut::<NewRunOptions>().fixed_seed =
+                            run_options::parse_seed(&text);
+                    }
+                    new_runstate = RunState::OptionsMenu {
+                        selection: gui::OptionsEntry::Seed,
+                    };
+                }
+            },
+
+            RunState::ShowHighScores => {
+                if gui::show_high_scores(&self.ecs, ctx) {
+                    new_runstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::HighScores,
+                    }
+                }
+            }
+
+            RunState::ShowAchievements => match gui::show_achievements(self, ctx) {
+                gui::ItemMenuResult::NoResponse | gui::ItemMenuResult::Selected(_) => {}
+                gui::ItemMenuResult::Cancel => {
+                    new_runstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::Achievements,
+                    }
+                }
+            },
+
+            RunState::LoadGameMenu => match gui::save_slot_menu(&self.ecs, ctx, true) {
+                gui::ItemMenuResult::Cancel => {
+                    new_runstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::LoadGame,
+                    }
+                }
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(slot) => {
+                    new_runstate = match self.load_slot(slot) {
+                        Ok(()) => RunState::AwaitingInput,
+                        Err(e) => self.load_failed(&e, gui::MainMenuSelection::LoadGame),
+                    };
+                }
+            },
+
+            RunState::LoadFailed { menu_selection } => {
+                if gui::load_failed(&self.ecs, ctx) {
+                    new_runstate = RunState::MainMenu { menu_selection };
+                }
+            }
+
+            RunState::SaveGame => match gui::save_slot_menu(&self.ecs, ctx, false) {
+                gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(slot) => {
+                    saveload_system::save_game(&mut self.ecs, slot)
+                        .wrap_err("Failed to save game")
+                        .unwrap();
+                    saveload_system::end_session()
+                        .wrap_err("Failed to end session")
+                        .unwrap();
+
+                    new_runstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::LoadGame,
+                    };
+                }
+            },
+
+            RunState::ChangeLevel { destination } => {
+                self.goto_level(destination);
+                saveload_system::autosave(&mut self.ecs)
+                    .wrap_err("Failed to autosave")
+                    .unwrap();
+                new_runstate = RunState::PreRun;
+            }
+
+            RunState::OpenTownPortal => {
+                self.open_town_portal();
+                saveload_system::autosave(&mut self.ecs)
+                    .wrap_err("Failed to autosave")
+                    .unwrap();
+                new_runstate = RunState::PreRun;
+            }
+
+            RunState::GameOver => match gui::game_over(&self.ecs, ctx) {
+                gui::GameOverResult::NoSelection => {}
+                gui::GameOverResult::QuitToMenu => {
+                    self.game_over_cleanup(false);
+                    new_runstate = RunState::ShowHighScores;
+                }
+            },
+
+            RunState::Victory => match gui::victory(&self.ecs, ctx) {
+                gui::GameOverResult::NoSelection => {}
+                gui::GameOverResult::QuitToMenu => {
+                    self.game_over_cleanup(true);
+                    new_runstate = RunState::ShowHighScores;
+                }
+            },
+
+            RunState::PreRun => {
+                self.run_systems();
+                rewind::record_turn(&mut self.ecs)
+                    .wrap_err("Failed to record turn for rewinding")
+                    .unwrap();
+                new_runstate = RunState::AwaitingInput;
+            }
+
+            RunState::AwaitingInput => {
+                new_runstate = player_input(self, ctx);
+            }
+
+            #[cfg(feature = "debug-console")]
+            RunState::DebugConsole => {
+                // The backtick key closes the console again
+                let result = if ctx.key == Some(rltk::VirtualKeyCode::Grave) {
+                    gui::TextEntryResult::Cancel
+                } else {
+                    gui::text_entry(self, ctx, "Debug console")
+                };
+                match result {
+                    gui::TextEntryResult::NoResponse => {}
+                    result => {
+                        let text = std::mem::take(
+                            &mut self.ecs.write_resource::<gui::TextEntryBuffer>().text,
+                        );
+                        new_runstate = if result == gui::TextEntryResult::Done {
+                            debug_commands::run_command(&mut self.ecs, &text)
+                        } else {
+                            RunState::AwaitingInput
+                        };
+                    }
+                }
+            }
+
+            RunState::RewindTurn => {
+                rewind::rewind_turn(&mut self.ecs)
+                    .wrap_err("Failed to rewind turn")
+                    .unwrap();
+                new_runstate = RunState::AwaitingInput;
+            }
+
+            RunState::PlayerTurn => {
+                self.ecs.fetch_mut::<GameLog>().next_turn();
+                let player_entity = **self.ecs.fetch::<PlayerEntity>();
+                let picked_up = self
+                    .ecs
+                    .read_storage::<WantsToPickupItem>()
+                    .get(player_entity)
+                    .map(|pickup| pickup.item);
+                let enchanting = self
+                    .ecs
+                    .read_storage::<WantsToUseItem>()
+                    .get(player_entity)
+                    .map(|use_item| use_item.item)
+                    .filter(|item| self.ecs.read_storage::<Enchants>().contains(*item));
+                let (reading_map, opening_portal) = self
+                    .ecs
+                    .read_storage::<WantsToUseItem>()
+                    .get(player_entity)
+                    .map_or((false, false), |use_item| {
+                        (
+                            self.ecs
+                                .read_storage::<ProvidesMapping>()
+                                .contains(use_item.item),
+                            self.ecs
+                                .read_storage::<ProvidesTownPortal>()
+                                .contains(use_item.item)
+                                && self.ecs.fetch::<Map>().level_id() != LevelId::TOWN,
+                        )
+                    });
+
+                self.run_systems();
+
+                // Offer to equip anything picked up this turn that's a strict upgrade
+                new_runstate = match picked_up
+                    .filter(|item| find_equipment_upgrades(&self.ecs, player_entity).contains(item))
+                {
+                    Some(item) => RunState::ConfirmEquip { item },
+                    None if reading_map => RunState::MagicMapReveal { row: 0 },
+                    None if opening_portal => RunState::OpenTownPortal,
+                    // The scroll is gone already if there was nothing to enchant
+                    None => match enchanting.filter(|scroll| self.ecs.is_alive(*scroll)) {
+                        Some(scroll) => RunState::ShowEnchantItem { scroll },
+                        None if player_gets_extra_turn(&self.ecs) => RunState::AwaitingInput,
+                        None => RunState::MonsterTurn,
+                    },
+                };
+            }
+            RunState::MonsterTurn => {
+                self.run_systems();
+                level_events::run_level_events(&mut self.ecs);
+                respawns::run_respawns(&mut self.ecs);
+                music::update_intensity(&self.ecs);
+                rewind::record_turn(&mut self.ecs)
+                    .wrap_err("Failed to record turn for rewinding")
+                    .unwrap();
+                new_runstate = RunState::AwaitingInput;
+            }
+
+            RunState::ShowInventory => match gui::show_inventory(self, ctx) {
+                gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(item_entity) => {
+                    let ranged_items = self.ecs.read_storage::<Ranged>();
+                    if let Some(ranged_item) = ranged_items.get(item_entity) {
+                        new_runstate = RunState::ShowTargeting {
+                            range: ranged_item.range,
+                            item: item_entity,
+                        };
+                    } else {
+                        let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                        intent
+                            .insert(
+                                **self.ecs.fetch::<PlayerEntity>(),
+                                WantsToUseItem {
+                                    item: item_entity,
+                                    target: None,
+                                },
+                            )
+                            .expect("Unable to insert intent WantsToUseItem for player");
+                        new_runstate = RunState::PlayerTurn;
+                    }
+                }
+            },
+
+            RunState::ShowEnchantItem { scroll } => {
+                match gui::enchant_item_menu(self, ctx, scroll) {
+                    gui::ItemMenuResult::Cancel | gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected(item) => {
+                        enchant_item(&mut self.ecs, scroll, item);
+                        new_runstate = if player_gets_extra_turn(&self.ecs) {
+                            RunState::AwaitingInput
+                        } else {
+                            RunState::MonsterTurn
+                        };
+                    }
+                }
+            }
+
+            RunState::ShowSpellbook => match gui::show_spellbook(self, ctx) {
+                gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(spell_entity) => {
+                    let player_entity = **self.ecs.fetch::<PlayerEntity>();
+                    let base_cost = self
+                        .ecs
+                        .read_storage::<Spell>()
+                        .get(spell_entity)
+                        .unwrap()
+                        .mana_cost;
+                    let mana_cost = self
+                        .ecs
+                        .read_storage::<Skills>()
+                        .get(player_entity)
+                        .map_or(base_cost, |skills| skills.spell_cost(base_cost));
+                    let mana = self
+                        .ecs
+                        .read_storage::<Mana>()
+                        .get(player_entity)
+                        .map_or(0, |pool| pool.mana);
+
+                    if mana < mana_cost {
+                        self.ecs.fetch_mut::<GameLog>().log(
+                            tr("not-enough-mana").arg(
+                                "spell",
+                                l10n::name(
+                                    &self
+                                        .ecs
+                                        .read_storage::<Name>()
+                                        .get(spell_entity)
+                                        .unwrap()
+                                        .name,
+                                ),
+                            ),
+                        );
+                        new_runstate = RunState::AwaitingInput;
+                    } else if let Some(ranged) = self.ecs.read_storage::<Ranged>().get(spell_entity)
+                    {
+                        // Spells are aimed just like ranged items
+                        new_runstate = RunState::ShowTargeting {
+                            range: ranged.range,
+                            item: spell_entity,
+                        };
+                    } else {
+                        self.ecs
+                            .write_storage::<WantsToUseItem>()
+                            .insert(
+                                player_entity,
+                                WantsToUseItem {
+                                    item: spell_entity,
+                                    target: None,
+                                },
+                            )
+                            .expect(
+                                "Unable to insert intent WantsToUseItem for player casting a spell",
+                            );
+                        new_runstate = RunState::PlayerTurn;
+                    }
+                }
+            },
+
+            RunState::LevelUp => match gui::level_up_menu(self, ctx) {
+                gui::ItemMenuResult::Cancel | gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(upgrade) => {
+                    progression::apply_upgrade(&mut self.ecs, &upgrade);
+                    new_runstate = RunState::AwaitingInput;
+                }
+            },
+
+            RunState::ShowDropItem => match gui::drop_item_menu(self, ctx) {
+                gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(item_entity) => {
+                    let mut intent = self.ecs.write_storage::<WantsToDropItem>();
+                    intent
+                        .insert(
+                            **self.ecs.fetch::<PlayerEntity>(),
+                            WantsToDropItem { item: item_entity },
+                        )
+                        .expect("Unable to insert intent WantsToDropItem for player");
+                    new_runstate = RunState::PlayerTurn;
+                }
+            },
+
+            RunState::TalkToNpc { npc } => match gui::service_menu(self, ctx, npc) {
+                gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(offer) => buy_service(&mut self.ecs, &offer),
+            },
+
+            RunState::OfferQuest { giver } => match gui::offer_quest(self, ctx, giver) {
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Cancel | gui::ItemMenuResult::Selected(false) => {
+                    new_runstate = RunState::AwaitingInput;
+                }
+                gui::ItemMenuResult::Selected(true) => {
+                    quests::accept_quest(&self.ecs, giver);
+                    new_runstate = RunState::AwaitingInput;
+                }
+            },
+
+            RunState::ShowJournal => match gui::show_journal(self, ctx) {
+                gui::ItemMenuResult::NoResponse | gui::ItemMenuResult::Selected(_) => {}
+                gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+            },
+
+            RunState::ShowDialogue { speaker, line } => {
+                if gui::dialogue_box(self, ctx, speaker, line) {
+                    new_runstate = RunState::AwaitingInput;
+                }
+            }
+
+            RunState::ShowStash { withdrawing } => match gui::stash_menu(self, ctx, withdrawing) {
+                gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(gui::StashAction::Deposit(item)) => {
+                    deposit_item(&mut self.ecs, item)
+                }
+                gui::ItemMenuResult::Selected(gui::StashAction::Withdraw(item)) => {
+                    withdraw_item(&mut self.ecs, item)
+                }
+                gui::ItemMenuResult::Selected(gui::StashAction::SwitchTab) => {
+                    new_runstate = RunState::ShowStash {
+                        withdrawing: !withdrawing,
+                    }
+                }
+            },
+
+            RunState::ShowContainer { container, storing } => {
+                let player_entity = **self.ecs.fetch::<PlayerEntity>();
+                let transfer = match gui::container_menu(self, ctx, container, storing) {
+                    gui::ItemMenuResult::Cancel => {
+                        new_runstate = RunState::AwaitingInput;
+                        None
+                    }
+                    gui::ItemMenuResult::NoResponse => None,
+                    gui::ItemMenuResult::Selected(gui::ContainerAction::Take(item)) => {
+                        Some(WantsToTransferItem {
+                            item,
+                            from: container,
+                            to: player_entity,
+                        })
+                    }
+                    gui::ItemMenuResult::Selected(gui::ContainerAction::Store(item)) => {
+                        Some(WantsToTransferItem {
+                            item,
+                            from: player_entity,
+                            to: container,
+                        })
+                    }
+                    gui::ItemMenuResult::Selected(gui::ContainerAction::SwitchTab) => {
+                        new_runstate = RunState::ShowContainer {
+                            container,
+                            storing: !storing,
+                        };
+                        None
+                    }
+                };
+
+                if let Some(transfer) = transfer {
+                    self.ecs
+                        .write_storage::<WantsToTransferItem>()
+                        .insert(player_entity, transfer)
+                        .expect("Unable to insert intent WantsToTransferItem for player");
+                    new_runstate = RunState::PlayerTurn;
+                }
+            }
+
+            RunState::ShowVendor { vendor, selling } => {
+                match gui::vendor_menu(self, ctx, vendor, selling) {
+                    gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected(gui::VendorAction::Buy(item)) => {
+                        buy_item(&mut self.ecs, vendor, item)
+                    }
+                    gui::ItemMenuResult::Selected(gui::VendorAction::Sell(item)) => {
+                        sell_item(&mut self.ecs, vendor, item)
+                    }
+                    gui::ItemMenuResult::Selected(gui::VendorAction::Steal(item)) => {
+                        steal_item(&mut self.ecs, vendor, item);
+                        new_runstate = RunState::PlayerTurn;
+                    }
+                    gui::ItemMenuResult::Selected(gui::VendorAction::SwitchTab) => {
+                        new_runstate = RunState::ShowVendor {
+                            vendor,
+                            selling: !selling,
+                        }
+                    }
+                }
+            }
+
+            RunState::MagicMapReveal { row } => {
+                let camera = camera::Camera::new(&self.ecs);
+                let sweep_color = self.ecs.fetch::<Palette>().info_dim;
+                let (last_row, height) = {
+                    let mut map = self.ecs.fetch_mut::<Map>();
+                    let last_row = i32::min(row + MAGIC_MAP_ROWS_PER_FRAME, map.height);
+                    for y in row..last_row {
+                        for x in 0..map.width {
+                            let idx = map.xy_idx(x, y);
+                            map.revealed_tiles.set(idx, true);
+                            camera.set_bg(ctx, rltk::Point::new(x, y), sweep_color);
+                        }
+                    }
+                    (last_row, map.height)
+                };
+
+                new_runstate = if last_row < height {
+                    RunState::MagicMapReveal { row: last_row }
+                } else if player_gets_extra_turn(&self.ecs) {
+                    RunState::AwaitingInput
+                } else {
+                    RunState::MonsterTurn
+                };
+            }
+
+            RunState::ShowThrowItem => match gui::throw_item_menu(self, ctx) {
+                gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(gui::Throwable::Item(item)) => {
+                    new_runstate = RunState::ThrowItem { item };
+                }
+                gui::ItemMenuResult::Selected(gui::Throwable::Rock) => {
+                    new_runstate = RunState::ThrowRock;
+                }
+            },
+
+            RunState::ThrowItem { item } => {
+                match gui::ranged_target(self, ctx, THROW_RANGE, None) {
+                    gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected(target) => {
+                        self.ecs
+                            .write_storage::<WantsToThrowItem>()
+                            .insert(
+                                **self.ecs.fetch::<PlayerEntity>(),
+                                WantsToThrowItem { item, target },
+                            )
+                            .expect("Unable to insert intent WantsToThrowItem for player");
+                        new_runstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+
+            RunState::ThrowRock => match gui::ranged_target(self, ctx, THROW_RANGE, None) {
+                gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(target) => {
+                    throw_rock(&mut self.ecs, target);
+                    new_runstate = RunState::PlayerTurn;
+                }
+            },
+
+            RunState::ShowTargeting { range, item } => {
+                match gui::ranged_target(self, ctx, range, Some(item)) {
+                    gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected(target) => {
+                        let confirm = self.ecs.fetch::<gui::FriendlyFireConfirmation>().enabled;
+                        if confirm && !gui::friendly_fire_risks(&self.ecs, item, target).is_empty()
+                        {
+                            new_runstate = RunState::ConfirmFriendlyFire {
+                                item,
+                                x: target.x,
+                                y: target.y,
+                            };
+                        } else {
+                            use_item_on_target(&self.ecs, item, target);
+                            new_runstate = RunState::PlayerTurn;
+                        }
+                    }
+                }
+            }
+
+            RunState::ConfirmFriendlyFire { item, x, y } => {
+                let target = rltk::Point::new(x, y);
+                let risks = gui::friendly_fire_risks(&self.ecs, item, target);
+                let question = tr("friendly-fire-question")
+                    .arg("targets", gui::describe_friendly_fire(&self.ecs, &risks));
+                match gui::yes_no_dialogue(&self.ecs, ctx, question) {
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Cancel | gui::ItemMenuResult::Selected(false) => {
+                        // Go back to picking a target
+                        let range = self
+                            .ecs
+                            .read_storage::<Ranged>()
+                            .get(item)
+                            .map_or(0, |ranged| ranged.range);
+                        new_runstate = RunState::ShowTargeting { range, item };
+                    }
+                    gui::ItemMenuResult::Selected(true) => {
+                        use_item_on_target(&self.ecs, item, target);
+                        new_runstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+
+            RunState::Examine { x, y } => match gui::examine(self, ctx, rltk::Point::new(x, y)) {
+                gui::ExamineResult::Cancel => new_runstate = RunState::AwaitingInput,
+                gui::ExamineResult::NoResponse => {}
+                gui::ExamineResult::MoveCursor(cursor) => {
+                    new_runstate = RunState::Examine {
+                        x: cursor.x,
+                        y: cursor.y,
+                    };
+                }
+                gui::ExamineResult::WriteNote(cursor) => {
+                    // Start editing from whatever note is already there
+                    let existing_note = self
+                        .ecs
+                        .fetch::<Map>()
+                        .note_at(cursor.x, cursor.y)
+                        .map(|note| note.text.clone());
+                    self.ecs.write_resource::<gui::TextEntryBuffer>().text =
+                        existing_note.unwrap_or_default();
+
+                    new_runstate = RunState::WriteNote {
+                        x: cursor.x,
+                        y: cursor.y,
+                    };
+                }
+            },
+
+            RunState::WriteNote { x, y } => match gui::text_entry(self, ctx, tr("note-prompt")) {
+                gui::TextEntryResult::Cancel => new_runstate = RunState::Examine { x, y },
+                gui::TextEntryResult::NoResponse => {}
+                gui::TextEntryResult::Done => {
+                    let text =
+                        std::mem::take(&mut self.ecs.write_resource::<gui::TextEntryBuffer>().text);
+                    let mut gamelog = self.ecs.fetch_mut::<GameLog>();
+                    if text.trim().is_empty() {
+                        gamelog.log(tr("note-erased"));
+                    } else {
+                        gamelog.log(tr("note-written").arg("text", text.trim()));
+                    }
+                    self.ecs.fetch_mut::<Map>().set_note(x, y, text);
+
+                    new_runstate = RunState::Examine { x, y };
+                }
+            },
+
+            RunState::ConfirmEquip { item } => {
+                let question = tr("equip-question").arg(
+                    "item",
+                    l10n::name(&self.ecs.read_storage::<Name>().get(item).unwrap().name),
+                );
+                match gui::yes_no_dialogue(&self.ecs, ctx, question) {
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Cancel | gui::ItemMenuResult::Selected(false) => {
+                        new_runstate = RunState::MonsterTurn
+                    }
+                    gui::ItemMenuResult::Selected(true) => {
+                        let player_entity = **self.ecs.fetch::<PlayerEntity>();
+                        equip_items(&mut self.ecs, player_entity, &[item]);
+                        new_runstate = RunState::MonsterTurn;
+                    }
+                }
+            }
+
+            RunState::ShowNotes => match gui::show_notes(self, ctx) {
+                gui::ItemMenuResult::Cancel => new_runstate = RunState::AwaitingInput,
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Selected(destination) => {
+                    travel_to(&mut self.ecs, destination.x, destination.y);
+                    new_runstate = RunState::AwaitingInput;
+                }
+            },
+
+            RunState::ShowLog { offset } => match gui::show_log(&self.ecs, ctx, offset) {
+                gui::LogViewerResult::NoResponse => {}
+                gui::LogViewerResult::Scroll(offset) => {
+                    new_runstate = RunState::ShowLog { offset };
+                }
+                gui::LogViewerResult::Close => new_runstate = RunState::AwaitingInput,
+            },
+        }
+
+        // Look through the saves again whenever a menu that lists them is opened,
+        // rather than on every frame it's open for
+        let lists_saves = matches!(
+            new_runstate,
+            RunState::MainMenu { .. } | RunState::LoadGameMenu | RunState::SaveGame
+        );
+        let old_runstate = *self.ecs.fetch::<RunState>();
+        if lists_saves
+            && std::mem::discriminant(&new_runstate) != std::mem::discriminant(&old_runstate)
+        {
+            self.ecs.insert(saveload_system::SaveIndex::scan());
+        }
+        {
+            let mut runwriter = self.ecs.write_resource::<RunState>();
+            *runwriter = new_runstate;
+        }
+        damage_system::delete_the_dead(&mut self.ecs);
+        game_events::dispatch_game_events(&mut self.ecs);
+        accessibility::update_screen_reader(&self.ecs);
+    }
+}
+
+impl State {
+    /// A fresh game state, with every component registered and every resource
+    /// that lasts between runs in place. Call [`Self::start_new_run()`] to get
+    /// a run going in it.
+    pub fn new() -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut gs = Self::default();
+
+        components::register_all_components(&mut gs.ecs);
+
+        gs.ecs.insert(Config::default());
+        gs.ecs.insert(rltk::RandomNumberGenerator::new());
+        gs.ecs.insert(raws::Raws::load().map_err(Box::new)?);
+        gs.ecs.insert(Achievements::load().map_err(Box::new)?);
+        gs.ecs.insert(HighScores::load().map_err(Box::new)?);
+        gs.ecs.insert(SimpleMarkerAllocator::<Serializable>::new());
+
+        let mut event_bus = game_events::GameEventBus::default();
+        event_bus.subscribe(run_options::HintListener);
+        event_bus.subscribe(game_events::RunStatsListener);
+        event_bus.subscribe(game_events::VictoryListener);
+        event_bus.subscribe(progression::ExperienceListener);
+        event_bus.subscribe(progression::SkillPracticeListener);
+        event_bus.subscribe(morale::MoraleListener);
+        event_bus.subscribe(crime::CrimeListener);
+        event_bus.subscribe(quests::QuestListener);
+        event_bus.subscribe(achievements::AchievementListener::default());
+        gs.ecs.insert(event_bus);
+        gs.ecs.insert(HudLayout::default());
+        gs.ecs.insert(ScreenReader::default());
+        // If the last session crashed, start off offering to recover it
+        let saves = saveload_system::SaveIndex::scan();
+        gs.ecs.insert(RunState::MainMenu {
+            menu_selection: if saves.can_recover_session() {
+                gui::MainMenuSelection::RecoverSession
+            } else {
+                gui::MainMenuSelection::NewGame
+            },
+        });
+        gs.ecs.insert(saves);
+        gs.ecs.insert(gui::TextEntryBuffer::default());
+        gs.ecs.insert(gui::LoadFailure::default());
+        gs.ecs.insert(gui::MenuPage::default());
+        gs.ecs.insert(gui::TargetingCursor::default());
+        gs.ecs.insert(gui::FriendlyFireConfirmation::default());
+        gs.ecs.insert(gui::OptionsSaveError::default());
+        gs.ecs.insert(config::ConfigPath::default());
+        gs.ecs.insert(NewRunOptions::default());
+        gs.ecs.insert(replay::Replay::default());
+        gs.ecs.insert(music::MusicDirector::default());
+        gs.ecs.insert(screen_effects::ScreenShake::default());
+        gs.ecs.insert(render::MonsterHealthBars::default());
+        gs.ecs.insert(Palette::default());
+        gs.ecs.insert(boss_ai_system::PendingSummons::default());
+        gs.ecs.insert(screen_effects::ScreenEffects::default());
+
+        Ok(gs)
+    }
+}
+
+/// Open the game window, and play until it's closed. Or with `--headless`, just
+/// set up the run and print its map.
+pub fn run_game(args: Args) -> rltk::BError {
+    let mut config = match &args.config {
+        Some(path) => Config::load_from(path),
+        None => Config::load(),
+    }
+    .map_err(Box::new)?;
+    config.display.debug_map_view |= args.debug_map;
+    config.display.screen_reader |= args.screen_reader;
+    l10n::set_language(config.display.language).map_err(Box::new)?;
+
+    let mut gs = State::new()?;
+    gs.ecs.insert(Palette::new(config.display.palette));
+    gs.ecs.fetch_mut::<ScreenReader>().enabled = config.display.screen_reader;
+    gs.ecs.insert(config.clone());
+    if let Some(path) = &args.config {
+        gs.ecs.insert(config::ConfigPath(path.clone()));
+    }
+
+    let new_run_options = NewRunOptions {
+        fixed_seed: args.seed,
+        ..Default::default()
+    };
+    gs.ecs.insert(new_run_options);
+
+    if args.headless {
+        gs.start_new_run();
+        if let Some(slot) = args.load {
+            gs.load_slot(slot)?;
+        }
+        print!("{}", render::map_to_text(&gs.ecs));
+        return Ok(());
+    }
+
+    let layout = HudLayout::default().layout();
+    let font = config.display.font;
+    let (font_width, font_height) = font.size();
+    let (tile_width, tile_height) = config.display.tile_size();
+    let mut context = RltkBuilder::new()
+        .with_dimensions(layout.console_width, layout.console_height)
+        .with_tile_dimensions(tile_width, tile_height)
+        .with_font(font.file(), font_width, font_height)
+        .with_simple_console(layout.console_width, layout.console_height, font.file())
+        .with_title("Rust Roguelike")
+        .with_fps_cap(config.display.fps_cap)
+        .with_fitscreen(true)
+        .with_fullscreen(args.fullscreen)
+        .build()?;
+    context.with_post_scanlines(config.display.scanlines);
+    context.with_mouse_visibility(false);
+
+    // Play back a recorded run straight away if one was passed in, starting it
+    // with the same options and seed that it was recorded with
+    if let Some(path) = &args.replay {
+        let replay = replay::ReplayFile::load(path).map_err(Box::new)?;
+        gs.ecs.insert(NewRunOptions {
+            options: replay.options,
+            fixed_seed: Some(replay.options.seed),
+        });
+        gs.start_new_run();
+        gs.ecs.fetch_mut::<replay::Replay>().play(replay.inputs);
+        gs.ecs.insert(new_run_options);
+        gs.ecs.insert(RunState::PreRun);
+    } else {
+        gs.start_new_run();
+    }
+
+    // Skip the main menu if a save was passed in
+    if let Some(slot) = args.load {
+        let runstate = match gs.load_slot(slot) {
+            Ok(()) => RunState::AwaitingInput,
+            Err(e) => gs.load_failed(&e, gui::MainMenuSelection::LoadGame),
+        };
+        gs.ecs.insert(runstate);
+    }
+
+    rltk::main_loop(context, gs)
+}
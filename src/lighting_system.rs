@@ -0,0 +1,70 @@
+use rltk::{field_of_view, Algorithm2D, Point, RGB};
+use specs::prelude::*;
+
+use crate::{InBackpack, LightSource, Map, PlayerEntity, Position, Viewshed};
+
+/// A system that works out how every tile on the map is lit by [`LightSource`]s,
+/// whether they're lying on the floor or being carried around.
+///
+/// On dark levels, the player's [`Viewshed`] depends on what's lit up, so it's
+/// refreshed whenever the lighting changes.
+pub struct LightingSystem;
+
+impl<'a> System<'a> for LightingSystem {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, PlayerEntity>,
+        Entities<'a>,
+        ReadStorage<'a, LightSource>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, InBackpack>,
+        WriteStorage<'a, Viewshed>,
+    );
+
+    fn run(
+        &mut self,
+        (mut map, player_entity, entities, light_sources, positions, backpacks, mut viewsheds): Self::SystemData,
+    ) {
+        let mut light = vec![RGB::from_f32(0.0, 0.0, 0.0); map.tiles.len()];
+
+        for (entity, source) in (&entities, &light_sources).join() {
+            let pos = positions.get(entity).or_else(|| {
+                backpacks
+                    .get(entity)
+                    .and_then(|in_pack| positions.get(in_pack.owner))
+            });
+            let Some(pos) = pos else {
+                continue;
+            };
+
+            // Light fades out towards the edge of its range
+            let center = Point::new(pos.x, pos.y);
+            for tile in field_of_view(center, source.range, &*map) {
+                if !map.in_bounds(tile) {
+                    continue;
+                }
+                let distance = rltk::DistanceAlg::Pythagoras.distance2d(center, tile);
+                let intensity = 1.0 - distance / (source.range as f32 + 1.0);
+                if intensity <= 0.0 {
+                    continue;
+                }
+
+                let lit = &mut light[map.xy_idx(tile.x, tile.y)];
+                *lit = RGB::from_f32(
+                    (lit.r + source.color.r * intensity).min(1.0),
+                    (lit.g + source.color.g * intensity).min(1.0),
+                    (lit.b + source.color.b * intensity).min(1.0),
+                );
+            }
+        }
+
+        if light != map.light {
+            map.light = light;
+            if map.dark {
+                if let Some(viewshed) = viewsheds.get_mut(**player_entity) {
+                    viewshed.dirty = true;
+                }
+            }
+        }
+    }
+}
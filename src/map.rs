@@ -1,29 +1,99 @@
-use std::{
-    cmp::{max, min},
-    fmt,
-};
+use std::fmt;
 
 use bitvec::bitvec;
 use bitvec::vec::BitVec;
 use derivative::Derivative;
-use rltk::{Algorithm2D, BaseMap, Point, RandomNumberGenerator};
+use rltk::{Algorithm2D, BaseMap, Point, RGB};
 use specs::Entity;
 
-use crate::Rect;
+use crate::{Branch, LevelId, Rect};
 
-/// The width of the map, in tiles.
-pub const MAPWIDTH: usize = 80;
-/// The height of the map, in tiles.
-pub const MAPHEIGHT: usize = 43;
-/// The total count of tiles in the map.
-pub const MAPSIZE: usize = MAPWIDTH * MAPHEIGHT;
+/// How big a level is, in tiles. Picked by the map builder for each new level.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct MapSize {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl MapSize {
+    /// Small, cramped levels that don't fill the screen.
+    pub const CRAMPED: Self = Self::new(56, 32);
+    /// Levels that fill the screen exactly.
+    pub const STANDARD: Self = Self::new(80, 43);
+    /// Sprawling levels, much bigger than the screen.
+    pub const SPRAWLING: Self = Self::new(120, 64);
+
+    pub const fn new(width: i32, height: i32) -> Self {
+        Self { width, height }
+    }
+
+    /// The total count of tiles in a level this size.
+    pub const fn tile_count(self) -> usize {
+        (self.width * self.height) as usize
+    }
+}
 
 /// All possible tile types.
-#[derive(PartialEq, Eq, Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
     DownStairs,
+    UpStairs,
+    /// Water that can be waded through, slowly.
+    ShallowWater,
+    /// Water too deep to stand in. Anything that can't swim drowns in it.
+    DeepWater,
+}
+
+impl TileType {
+    pub const fn is_water(self) -> bool {
+        matches!(self, Self::ShallowWater | Self::DeepWater)
+    }
+
+    /// How many turns it takes to move onto the tile, relative to plain floor.
+    /// Pathfinding goes around expensive tiles where it can, and the player is left
+    /// [`crate::StatusEffect::Wading`] for the extra turns.
+    pub const fn movement_cost(self) -> f32 {
+        match self {
+            Self::ShallowWater => 2.0,
+            Self::DeepWater => 4.0,
+            _ => 1.0,
+        }
+    }
+}
+
+/// How a level looks, and what tends to live there. Picked by depth when the
+/// level is built (see [`crate::map_builders::ThemeBuilder`]).
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub enum MapTheme {
+    /// Plain stone corridors, like the ones under the town.
+    #[default]
+    Dungeon,
+    /// Overgrown levels, walled in by trees.
+    Forest,
+    /// Damp caves, carved out of pale rock.
+    LimestoneCavern,
+    /// Halls hewn out by dwarves, long since taken over by orcs.
+    DwarfFortress,
+    /// The very bottom of the dungeon, and hot enough to show for it.
+    Hell,
+}
+
+impl MapTheme {
+    /// Returns `true` if levels in this theme are dark, so that the player can
+    /// only see what's lit up by a [`crate::LightSource`].
+    pub const fn is_dark(self) -> bool {
+        matches!(self, Self::LimestoneCavern | Self::DwarfFortress)
+    }
+}
+
+/// A note the player has left on a tile, e.g. "locked vault here".
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct MapNote {
+    pub x: i32,
+    pub y: i32,
+    pub text: String,
 }
 
 /// A level map. This includes all the tiles, rooms, and so on that constitute
@@ -48,6 +118,27 @@ pub struct Map {
     #[derivative(Default(value = "1"))]
     pub depth: i32,
 
+    /// Which branch of the dungeon the level is in.
+    #[serde(default)]
+    pub branch: Branch,
+
+    /// How the level looks, and what tends to spawn in it.
+    #[serde(default)]
+    pub theme: MapTheme,
+
+    /// Whether the level is dark, so that the player can only see the tiles that
+    /// are lit up, and the ones right next to them.
+    #[serde(default)]
+    pub dark: bool,
+
+    /// How brightly, and in what colour, each tile in [`Self::tiles`] is lit up by
+    /// [`crate::LightSource`]s. Black tiles aren't lit at all.
+    ///
+    /// Like [`Self::tile_content`], this is rebuilt every tick, so isn't saved.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub light: Vec<RGB>,
+
     /// All tiles that the player has revealed during their explorations.
     ///
     /// An element in this vector will be `true` if the player has revealed the
@@ -70,6 +161,15 @@ pub struct Map {
     /// [`Self::tiles`] is blocked from access.
     pub blocked: BitVec,
 
+    /// Notes the player has left on this level's tiles.
+    #[serde(default)]
+    pub notes: Vec<MapNote>,
+
+    /// How many more monsters can wander onto the level over time. See
+    /// [`crate::respawns`].
+    #[serde(default)]
+    pub respawns_left: i32,
+
     /// A record of which entities are present in each tile of the map.
     ///
     /// Note that this is ignored for the purposes of serialization and deserialization.
@@ -85,36 +185,14 @@ impl Map {
         (y as usize * self.width as usize) + x as usize
     }
 
-    /// Add a rectangular room made entirely of [`TileType::Floor`].
-    fn apply_room_to_map(&mut self, room: &Rect) {
-        for y in room.y1 + 1..=room.y2 {
-            for x in room.x1 + 1..=room.x2 {
-                let idx = self.xy_idx(x, y);
-                self.tiles[idx] = TileType::Floor;
-            }
-        }
-    }
-
-    /// Make a horizontal tunnel between two x-coordinates at a specific y-coordinate.
-    /// The tunnel is made entirely of [`TileType::Floor`].
-    fn apply_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
-        for x in min(x1, x2)..=max(x1, x2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < MAPSIZE {
-                self.tiles[idx] = TileType::Floor;
-            }
-        }
+    /// Convert an index into [`Self::tiles`] back into (x, y) coordinates.
+    pub const fn idx_xy(&self, idx: usize) -> (i32, i32) {
+        (idx as i32 % self.width, idx as i32 / self.width)
     }
 
-    /// Make a vertical tunnel between two y-coordinates at a specific x-coordinate.
-    /// The tunnel is made entirely of [`TileType::Floor`].
-    fn apply_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
-        for y in min(y1, y2)..=max(y1, y2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < MAPSIZE {
-                self.tiles[idx] = TileType::Floor;
-            }
-        }
+    /// How big the map is.
+    pub const fn size(&self) -> MapSize {
+        MapSize::new(self.width, self.height)
     }
 
     /// Populate [`Self::blocked`] with all statically-blocked tiles.
@@ -131,138 +209,174 @@ impl Map {
         }
     }
 
-    /// Create a new map with randomly-placed rooms that are connected by corridors.
-    ///
-    /// The map will have a width of 80 and a height of 50.
-    /// This uses the algorithm from http://rogueliketutorials.com/tutorials/tcod/part-3/.
-    pub fn new_map_rooms_and_corridors(rng: &mut RandomNumberGenerator, new_depth: i32) -> Self {
-        let mut map = Self {
-            tiles: vec![TileType::Wall; MAPSIZE],
+    /// Create a new map of the given `size`, made entirely of [`TileType::Wall`]s,
+    /// to be carved out by a [`crate::map_builders::MapBuilder`].
+    pub fn new(new_depth: i32, size: MapSize) -> Self {
+        let tile_count = size.tile_count();
+        Self {
+            tiles: vec![TileType::Wall; tile_count],
             rooms: Vec::new(),
-            width: MAPWIDTH as i32,
-            height: MAPHEIGHT as i32,
+            width: size.width,
+            height: size.height,
             depth: new_depth,
-            revealed_tiles: bitvec![0; MAPSIZE],
-            visible_tiles: bitvec![0; MAPSIZE],
-            blocked: bitvec![0; MAPSIZE],
-            tile_content: vec![Vec::new(); MAPSIZE],
-        };
+            branch: Branch::Main,
+            theme: MapTheme::default(),
+            dark: false,
+            light: vec![RGB::from_f32(0.0, 0.0, 0.0); tile_count],
+            revealed_tiles: bitvec![0; tile_count],
+            visible_tiles: bitvec![0; tile_count],
+            blocked: bitvec![0; tile_count],
+            notes: Vec::new(),
+            respawns_left: 0,
+            tile_content: vec![Vec::new(); tile_count],
+        }
+    }
 
-        const MAX_ROOMS: i32 = 30;
-        const MIN_SIZE: i32 = 6;
-        const MAX_SIZE: i32 = 10;
-
-        for _ in 0..MAX_ROOMS {
-            let w = rng.range(MIN_SIZE, MAX_SIZE);
-            let h = rng.range(MIN_SIZE, MAX_SIZE);
-            let x = rng.roll_dice(1, map.width - w - 1) - 1;
-            let y = rng.roll_dice(1, map.height - h - 1) - 1;
-            let new_room = Rect::new(x, y, w, h);
-
-            if !map
-                .rooms
-                .iter()
-                .any(|other_room| new_room.intersect(other_room))
-            {
-                map.apply_room_to_map(&new_room);
-
-                if !map.rooms.is_empty() {
-                    let (new_x, new_y) = new_room.center();
-                    let (prev_x, prev_y) = map.rooms[map.rooms.len() - 1].center();
-                    if rng.range(0, 2) == 1 {
-                        map.apply_horizontal_tunnel(prev_x, new_x, prev_y);
-                        map.apply_vertical_tunnel(prev_y, new_y, new_x);
-                    } else {
-                        map.apply_vertical_tunnel(prev_y, new_y, prev_x);
-                        map.apply_horizontal_tunnel(prev_x, new_x, new_y);
-                    }
-                }
-
-                map.rooms.push(new_room);
-            }
+    /// Where the level is in the dungeon.
+    pub fn level_id(&self) -> LevelId {
+        LevelId {
+            branch: self.branch,
+            depth: self.depth,
         }
+    }
 
-        // Add down stairs in the last room generated
-        let (stairs_x, stairs_y) = map.rooms[map.rooms.len() - 1].center();
-        let stairs_idx = map.xy_idx(stairs_x, stairs_y);
-        map.tiles[stairs_idx] = TileType::DownStairs;
+    /// Returns `true` if any [`crate::LightSource`] shines on the tile at `idx`.
+    pub fn is_lit(&self, idx: usize) -> bool {
+        self.light
+            .get(idx)
+            .is_some_and(|light| light.r > 0.0 || light.g > 0.0 || light.b > 0.0)
+    }
+
+    /// Returns the note the player left at (`x`, `y`), if any.
+    pub fn note_at(&self, x: i32, y: i32) -> Option<&MapNote> {
+        self.notes.iter().find(|note| note.x == x && note.y == y)
+    }
 
-        map
+    /// Leave a note at (`x`, `y`), replacing any note already there.
+    /// An empty (or all-whitespace) `text` removes the note instead.
+    pub fn set_note<S: ToString>(&mut self, x: i32, y: i32, text: S) {
+        let text = text.to_string().trim().to_string();
+        self.notes.retain(|note| note.x != x || note.y != y);
+        if !text.is_empty() {
+            self.notes.push(MapNote { x, y, text });
+        }
     }
 
     /// Returns `true` if a particular tile can be entered ("walked on") by an entity.
+    /// If `terrain_only` is set, only walls get in the way, and not whatever's
+    /// standing on the tile.
     ///
     /// Named "is_exit_valid" as it's used to implement [`BaseMap::get_available_exits`], which
     /// is used for pathfinding.
-    fn is_exit_valid(&self, x: i32, y: i32) -> bool {
+    fn is_exit_valid(&self, x: i32, y: i32, terrain_only: bool) -> bool {
         // bounds check
         if x < 1 || x > self.width - 1 || y < 1 || y > self.height - 1 {
             return false;
         }
 
         let idx = self.xy_idx(x, y);
-        !self.blocked[idx]
-    }
-}
-
-impl Algorithm2D for Map {
-    fn dimensions(&self) -> Point {
-        Point::new(self.width, self.height)
+        if terrain_only {
+            self.tiles[idx] != TileType::Wall
+        } else {
+            !self.blocked[idx]
+        }
     }
-}
 
-impl BaseMap for Map {
-    fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx] == TileType::Wall
+    /// How costly it is to step onto (`x`, `y`) from a neighbouring tile
+    /// `base_cost` away, or [`None`] if it can't be entered at all.
+    fn exit_cost(&self, x: i32, y: i32, base_cost: f32, terrain_only: bool) -> Option<f32> {
+        self.is_exit_valid(x, y, terrain_only)
+            .then(|| base_cost * self.tiles[self.xy_idx(x, y)].movement_cost())
     }
 
-    fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+    /// Every tile that can be stepped onto from `idx`, and how costly it is. See
+    /// [`Self::is_exit_valid()`] for what `terrain_only` means.
+    fn exits(&self, idx: usize, terrain_only: bool) -> rltk::SmallVec<[(usize, f32); 10]> {
         let mut exits = rltk::SmallVec::new();
 
-        let x = idx as i32 % self.width;
-        let y = idx as i32 / self.width;
+        let (x, y) = self.idx_xy(idx);
         let w = self.width as usize;
 
         // Check cardinal directions
-        if self.is_exit_valid(x - 1, y) {
-            exits.push((idx - 1, 1.0))
+        if let Some(cost) = self.exit_cost(x - 1, y, 1.0, terrain_only) {
+            exits.push((idx - 1, cost))
         };
-        if self.is_exit_valid(x + 1, y) {
-            exits.push((idx + 1, 1.0))
+        if let Some(cost) = self.exit_cost(x + 1, y, 1.0, terrain_only) {
+            exits.push((idx + 1, cost))
         };
-        if self.is_exit_valid(x, y - 1) {
-            exits.push((idx - w, 1.0))
+        if let Some(cost) = self.exit_cost(x, y - 1, 1.0, terrain_only) {
+            exits.push((idx - w, cost))
         };
-        if self.is_exit_valid(x, y + 1) {
-            exits.push((idx + w, 1.0))
+        if let Some(cost) = self.exit_cost(x, y + 1, 1.0, terrain_only) {
+            exits.push((idx + w, cost))
         };
 
         // Check diagonals
-        if self.is_exit_valid(x - 1, y - 1) {
-            exits.push(((idx - w) - 1, 1.45));
+        if let Some(cost) = self.exit_cost(x - 1, y - 1, 1.45, terrain_only) {
+            exits.push(((idx - w) - 1, cost));
         }
-        if self.is_exit_valid(x + 1, y - 1) {
-            exits.push(((idx - w) + 1, 1.45));
+        if let Some(cost) = self.exit_cost(x + 1, y - 1, 1.45, terrain_only) {
+            exits.push(((idx - w) + 1, cost));
         }
-        if self.is_exit_valid(x - 1, y + 1) {
-            exits.push(((idx + w) - 1, 1.45));
+        if let Some(cost) = self.exit_cost(x - 1, y + 1, 1.45, terrain_only) {
+            exits.push(((idx + w) - 1, cost));
         }
-        if self.is_exit_valid(x + 1, y + 1) {
-            exits.push(((idx + w) + 1, 1.45));
+        if let Some(cost) = self.exit_cost(x + 1, y + 1, 1.45, terrain_only) {
+            exits.push(((idx + w) + 1, cost));
         }
 
         exits
     }
+}
+
+impl Algorithm2D for Map {
+    fn dimensions(&self) -> Point {
+        Point::new(self.width, self.height)
+    }
+}
+
+impl BaseMap for Map {
+    fn is_opaque(&self, idx: usize) -> bool {
+        self.tiles[idx] == TileType::Wall
+    }
+
+    fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        self.exits(idx, false)
+    }
 
     fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
-        let w = self.width as usize;
-        let p1 = Point::new(idx1 % w, idx1 / w);
-        let p2 = Point::new(idx2 % w, idx2 / w);
+        let p1 = Point::from_tuple(self.idx_xy(idx1));
+        let p2 = Point::from_tuple(self.idx_xy(idx2));
 
         rltk::DistanceAlg::Pythagoras.distance2d(p1, p2)
     }
 }
 
+/// A [`Map`] as seen by pathfinding that only cares about the lay of the land,
+/// and ignores whoever's standing in the way. Whoever follows the path still has
+/// to check [`Map::blocked`] before each step.
+pub struct Terrain<'a>(pub &'a Map);
+
+impl Algorithm2D for Terrain<'_> {
+    fn dimensions(&self) -> Point {
+        self.0.dimensions()
+    }
+}
+
+impl BaseMap for Terrain<'_> {
+    fn is_opaque(&self, idx: usize) -> bool {
+        self.0.is_opaque(idx)
+    }
+
+    fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        self.0.exits(idx, true)
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        self.0.get_pathing_distance(idx1, idx2)
+    }
+}
+
 impl fmt::Debug for Map {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Map")
@@ -279,6 +393,7 @@ impl fmt::Debug for Map {
                 &format!("[bool; {}]", self.visible_tiles.len()),
             )
             .field("blocked", &format!("[bool; {}]", self.blocked.len()))
+            .field("notes", &self.notes)
             .field(
                 "tile_content",
                 &format!("[Vec<Entity>; {}]", self.tile_content.len()),
@@ -0,0 +1,171 @@
+use std::cmp::{max, min};
+
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+use super::common::{apply_room_to_map, draw_corridor};
+use super::MapBuilder;
+use crate::{spawner, Map, MapSize, Position, Rect, TileType};
+
+/// How many times the builder will try to carve a room out of a random partition.
+const MAX_ROOM_ATTEMPTS: usize = 240;
+
+/// Builds maps by recursively partitioning the level into rectangles (a
+/// "binary space partition") and placing a room inside some of them.
+///
+/// Compared to [`super::SimpleMapBuilder`], rooms end up more evenly packed
+/// and never overlap, since every room gets its own partition.
+pub struct BspDungeonBuilder {
+    map: Map,
+    starting_position: Position,
+    /// Partitions of the map that haven't had a room placed inside them yet.
+    rects: Vec<Rect>,
+}
+
+impl BspDungeonBuilder {
+    pub fn new(new_depth: i32, size: MapSize) -> Self {
+        Self {
+            map: Map::new(new_depth, size),
+            starting_position: Position::default(),
+            rects: Vec::new(),
+        }
+    }
+
+    /// Split `rect` into four quadrants and add them to the list of partitions.
+    fn add_subrects(&mut self, rect: Rect) {
+        let half_width = max(rect.width() / 2, 1);
+        let half_height = max(rect.height() / 2, 1);
+
+        self.rects
+            .push(Rect::new(rect.x1, rect.y1, half_width, half_height));
+        self.rects.push(Rect::new(
+            rect.x1,
+            rect.y1 + half_height,
+            half_width,
+            half_height,
+        ));
+        self.rects.push(Rect::new(
+            rect.x1 + half_width,
+            rect.y1,
+            half_width,
+            half_height,
+        ));
+        self.rects.push(Rect::new(
+            rect.x1 + half_width,
+            rect.y1 + half_height,
+            half_width,
+            half_height,
+        ));
+    }
+
+    /// Pick a random partition.
+    fn get_random_rect(&self, rng: &mut RandomNumberGenerator) -> Rect {
+        if self.rects.len() == 1 {
+            return self.rects[0];
+        }
+        let idx = (rng.roll_dice(1, self.rects.len() as i32) - 1) as usize;
+        self.rects[idx]
+    }
+
+    /// Returns `true` if `rect` (plus a two-tile margin) fits inside the map and
+    /// doesn't overlap anything that has already been dug out.
+    fn is_possible(&self, rect: Rect) -> bool {
+        let expanded = Rect::from(((rect.x1 - 2, rect.y1 - 2), (rect.x2 + 2, rect.y2 + 2)));
+
+        for y in expanded.y1..=expanded.y2 {
+            for x in expanded.x1..=expanded.x2 {
+                if x < 1 || x > self.map.width - 2 || y < 1 || y > self.map.height - 2 {
+                    return false;
+                }
+
+                let idx = self.map.xy_idx(x, y);
+                if self.map.tiles[idx] != TileType::Wall {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Partition the map and dig out as many rooms as will fit.
+    fn place_rooms(&mut self, rng: &mut RandomNumberGenerator) -> Vec<Rect> {
+        let mut rooms = Vec::new();
+
+        // Start with a single partition covering the whole map (minus a margin)
+        self.rects.clear();
+        self.rects
+            .push(Rect::new(2, 2, self.map.width - 5, self.map.height - 5));
+        let first_room = self.rects[0];
+        self.add_subrects(first_room);
+
+        // Repeatedly pick a partition, try to fit a room inside it, and subdivide it further
+        for _ in 0..MAX_ROOM_ATTEMPTS {
+            let rect = self.get_random_rect(rng);
+            let candidate = get_random_sub_rect(rect, rng);
+
+            if self.is_possible(candidate) {
+                apply_room_to_map(&mut self.map, &candidate);
+                rooms.push(candidate);
+                self.add_subrects(rect);
+            }
+        }
+
+        rooms
+    }
+}
+
+/// Pick a randomly-sized room somewhere inside the partition `rect`.
+fn get_random_sub_rect(rect: Rect, rng: &mut RandomNumberGenerator) -> Rect {
+    let width = max(3, rng.roll_dice(1, min(rect.width(), 10)) - 1) + 1;
+    let height = max(3, rng.roll_dice(1, min(rect.height(), 10)) - 1) + 1;
+    let x = rect.x1 + rng.roll_dice(1, 6) - 1;
+    let y = rect.y1 + rng.roll_dice(1, 6) - 1;
+
+    Rect::new(x, y, width, height)
+}
+
+impl MapBuilder for BspDungeonBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        // Unlucky rolls can leave no room at all, so start over until there's one
+        let mut rooms = self.place_rooms(rng);
+        while rooms.is_empty() {
+            self.map.tiles.fill(TileType::Wall);
+            rooms = self.place_rooms(rng);
+        }
+
+        // Connect rooms from left to right, so corridors tend to link neighbours
+        rooms.sort_by_key(|room| room.x1);
+        for pair in rooms.windows(2) {
+            let (room, next_room) = (pair[0], pair[1]);
+            let start_x = room.x1 + 1 + rng.roll_dice(1, room.width()) - 1;
+            let start_y = room.y1 + 1 + rng.roll_dice(1, room.height()) - 1;
+            let end_x = next_room.x1 + 1 + rng.roll_dice(1, next_room.width()) - 1;
+            let end_y = next_room.y1 + 1 + rng.roll_dice(1, next_room.height()) - 1;
+            draw_corridor(&mut self.map, start_x, start_y, end_x, end_y);
+        }
+
+        // Add down stairs in the right-most room, and start the player in the left-most one
+        let (stairs_x, stairs_y) = rooms[rooms.len() - 1].center();
+        let stairs_idx = self.map.xy_idx(stairs_x, stairs_y);
+        self.map.tiles[stairs_idx] = TileType::DownStairs;
+
+        self.starting_position = rooms[0].center().into();
+        self.map.rooms = rooms;
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        // Add monsters and items to each room (except the starting room)
+        for room in self.map.rooms.iter().skip(1) {
+            spawner::spawn_room(ecs, room, self.map.depth);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position
+    }
+}
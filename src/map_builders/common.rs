@@ -0,0 +1,133 @@
+use std::cmp::{max, min};
+
+use rltk::{DijkstraMap, RandomNumberGenerator};
+use rustc_hash::FxHashMap;
+
+use crate::{Map, Rect, TileType};
+
+/// Add a rectangular room made entirely of [`TileType::Floor`].
+pub fn apply_room_to_map(map: &mut Map, room: &Rect) {
+    for y in room.y1 + 1..=room.y2 {
+        for x in room.x1 + 1..=room.x2 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+/// Make a horizontal tunnel between two x-coordinates at a specific y-coordinate.
+/// The tunnel is made entirely of [`TileType::Floor`], and never digs into the
+/// map's outer wall.
+pub fn apply_horizontal_tunnel(map: &mut Map, x1: i32, x2: i32, y: i32) {
+    for x in min(x1, x2)..=max(x1, x2) {
+        dig_inside(map, x, y);
+    }
+}
+
+/// Make a vertical tunnel between two y-coordinates at a specific x-coordinate.
+/// The tunnel is made entirely of [`TileType::Floor`], and never digs into the
+/// map's outer wall.
+pub fn apply_vertical_tunnel(map: &mut Map, y1: i32, y2: i32, x: i32) {
+    for y in min(y1, y2)..=max(y1, y2) {
+        dig_inside(map, x, y);
+    }
+}
+
+/// Turn the tile at (`x`, `y`) into [`TileType::Floor`], unless it's on or past
+/// the edge of the map. Checking the coordinates rather than the tile index
+/// stops tunnels that run off one side of the map from wrapping around to the other.
+fn dig_inside(map: &mut Map, x: i32, y: i32) {
+    if x > 0 && x < map.width - 1 && y > 0 && y < map.height - 1 {
+        let idx = map.xy_idx(x, y);
+        map.tiles[idx] = TileType::Floor;
+    }
+}
+
+/// Dig a corridor of [`TileType::Floor`] from (`x1`, `y1`) to (`x2`, `y2`),
+/// stepping one tile at a time along the x-axis first and then the y-axis.
+pub fn draw_corridor(map: &mut Map, x1: i32, y1: i32, x2: i32, y2: i32) {
+    let mut x = x1;
+    let mut y = y1;
+
+    while x != x2 || y != y2 {
+        if x < x2 {
+            x += 1;
+        } else if x > x2 {
+            x -= 1;
+        } else if y < y2 {
+            y += 1;
+        } else if y > y2 {
+            y -= 1;
+        }
+
+        let idx = map.xy_idx(x, y);
+        map.tiles[idx] = TileType::Floor;
+    }
+}
+
+/// Turn every floor tile that can't be reached from `start_idx` into a wall, so
+/// that no level has sealed-off pockets the player can never get to.
+///
+/// Returns the index of the reachable tile that is farthest from `start_idx`,
+/// measured along a Dijkstra map rather than as the crow flies.
+pub fn remove_unreachable_areas_returning_most_distant(map: &mut Map, start_idx: usize) -> usize {
+    map.populate_blocked();
+    let dijkstra_map = DijkstraMap::new(
+        map.width,
+        map.height,
+        &[start_idx],
+        &*map,
+        (map.width * map.height) as f32,
+    );
+
+    let mut most_distant = (start_idx, 0.0_f32);
+    for (idx, tile) in map.tiles.iter_mut().enumerate() {
+        if *tile == TileType::Floor {
+            let distance = dijkstra_map.map[idx];
+            if distance == f32::MAX {
+                *tile = TileType::Wall;
+            } else if distance > most_distant.1 {
+                most_distant = (idx, distance);
+            }
+        }
+    }
+
+    most_distant.0
+}
+
+/// Group every floor tile on the map into irregular regions using cellular
+/// (Voronoi) noise, so that caves without rooms can still be spawned into.
+pub fn generate_voronoi_spawn_regions(
+    map: &Map,
+    rng: &mut RandomNumberGenerator,
+) -> FxHashMap<i32, Vec<usize>> {
+    let mut noise_areas: FxHashMap<i32, Vec<usize>> = FxHashMap::default();
+
+    let mut noise = rltk::FastNoise::seeded(rng.roll_dice(1, 65536) as u64);
+    noise.set_noise_type(rltk::NoiseType::Cellular);
+    noise.set_frequency(0.08);
+    noise.set_cellular_distance_function(rltk::CellularDistanceFunction::Manhattan);
+
+    for y in 1..map.height - 1 {
+        for x in 1..map.width - 1 {
+            let idx = map.xy_idx(x, y);
+            if map.tiles[idx] == TileType::Floor {
+                let cell_value = (noise.get_noise(x as f32, y as f32) * 10240.0) as i32;
+                noise_areas.entry(cell_value).or_default().push(idx);
+            }
+        }
+    }
+
+    noise_areas
+}
+
+/// Cull everything unreachable from `start_idx` with
+/// [`remove_unreachable_areas_returning_most_distant()`], and put the
+/// [`TileType::DownStairs`] on the farthest tile that's left.
+///
+/// Returns the index of the stairs.
+pub fn place_down_stairs_at_most_distant(map: &mut Map, start_idx: usize) -> usize {
+    let stairs_idx = remove_unreachable_areas_returning_most_distant(map, start_idx);
+    map.tiles[stairs_idx] = TileType::DownStairs;
+    stairs_idx
+}
@@ -0,0 +1,164 @@
+use rltk::RandomNumberGenerator;
+use rustc_hash::FxHashMap;
+use specs::prelude::*;
+
+use super::common::{generate_voronoi_spawn_regions, place_down_stairs_at_most_distant};
+use super::MapBuilder;
+use crate::{spawner, Map, MapSize, Position, TileType};
+
+/// Where each new digger starts its walk.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum DrunkSpawnMode {
+    /// Every digger starts from the middle of the map.
+    StartingPoint,
+    /// Every digger after the first starts from a random tile that has already
+    /// been dug out.
+    Random,
+}
+
+/// Knobs controlling the shape of a [`DrunkardsWalkBuilder`]'s caverns.
+#[derive(Copy, Clone, Debug)]
+pub struct DrunkardSettings {
+    pub spawn_mode: DrunkSpawnMode,
+    /// How many steps each digger takes before it passes out.
+    pub drunken_lifetime: i32,
+    /// Diggers keep being sent out until this fraction of the map is floor.
+    pub floor_percent: f32,
+}
+
+/// Builds open cavern levels by sending out "drunk" diggers that stumble around
+/// randomly, turning every wall they walk over into floor.
+pub struct DrunkardsWalkBuilder {
+    map: Map,
+    starting_position: Position,
+    settings: DrunkardSettings,
+    /// Floor tiles grouped into irregular regions to spawn things into.
+    noise_areas: FxHashMap<i32, Vec<usize>>,
+}
+
+impl DrunkardsWalkBuilder {
+    pub fn new(new_depth: i32, size: MapSize, settings: DrunkardSettings) -> Self {
+        Self {
+            map: Map::new(new_depth, size),
+            starting_position: Position::default(),
+            settings,
+            noise_areas: FxHashMap::default(),
+        }
+    }
+
+    /// A single, wide-open cave dug out from the middle of a sprawling map.
+    pub fn open_area(new_depth: i32) -> Self {
+        Self::new(
+            new_depth,
+            MapSize::SPRAWLING,
+            DrunkardSettings {
+                spawn_mode: DrunkSpawnMode::StartingPoint,
+                drunken_lifetime: 400,
+                floor_percent: 0.5,
+            },
+        )
+    }
+
+    /// Large open halls spread all over the map.
+    pub fn open_halls(new_depth: i32) -> Self {
+        Self::new(
+            new_depth,
+            MapSize::STANDARD,
+            DrunkardSettings {
+                spawn_mode: DrunkSpawnMode::Random,
+                drunken_lifetime: 400,
+                floor_percent: 0.5,
+            },
+        )
+    }
+
+    /// Lots of narrow, twisty passages, crammed into a small map.
+    pub fn winding_passages(new_depth: i32) -> Self {
+        Self::new(
+            new_depth,
+            MapSize::CRAMPED,
+            DrunkardSettings {
+                spawn_mode: DrunkSpawnMode::Random,
+                drunken_lifetime: 100,
+                floor_percent: 0.4,
+            },
+        )
+    }
+}
+
+impl MapBuilder for DrunkardsWalkBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        // Start in the middle of the map
+        self.starting_position = Position {
+            x: self.map.width / 2,
+            y: self.map.height / 2,
+        };
+        let start_idx = self
+            .map
+            .xy_idx(self.starting_position.x, self.starting_position.y);
+        self.map.tiles[start_idx] = TileType::Floor;
+
+        let total_tiles = self.map.width * self.map.height;
+        let desired_floor_tiles = (self.settings.floor_percent * total_tiles as f32) as usize;
+        let count_floor_tiles = |map: &Map| {
+            map.tiles
+                .iter()
+                .filter(|tile| **tile == TileType::Floor)
+                .count()
+        };
+
+        let mut digger_count = 0;
+        while count_floor_tiles(&self.map) < desired_floor_tiles {
+            let (mut drunk_x, mut drunk_y) =
+                if digger_count == 0 || self.settings.spawn_mode == DrunkSpawnMode::StartingPoint {
+                    (self.starting_position.x, self.starting_position.y)
+                } else {
+                    loop {
+                        let x = rng.roll_dice(1, self.map.width - 3) + 1;
+                        let y = rng.roll_dice(1, self.map.height - 3) + 1;
+                        if self.map.tiles[self.map.xy_idx(x, y)] == TileType::Floor {
+                            break (x, y);
+                        }
+                    }
+                };
+
+            for _ in 0..self.settings.drunken_lifetime {
+                let drunk_idx = self.map.xy_idx(drunk_x, drunk_y);
+                self.map.tiles[drunk_idx] = TileType::Floor;
+
+                match rng.roll_dice(1, 4) {
+                    1 if drunk_x > 2 => drunk_x -= 1,
+                    2 if drunk_x < self.map.width - 2 => drunk_x += 1,
+                    3 if drunk_y > 2 => drunk_y -= 1,
+                    4 if drunk_y < self.map.height - 2 => drunk_y += 1,
+                    _ => {}
+                }
+            }
+
+            digger_count += 1;
+        }
+
+        // Wall off anything the player can't reach, and put the stairs as far away as possible
+        place_down_stairs_at_most_distant(&mut self.map, start_idx);
+
+        // Don't spawn anything right on top of the player
+        self.noise_areas = generate_voronoi_spawn_regions(&self.map, rng);
+        for area in self.noise_areas.values_mut() {
+            area.retain(|idx| *idx != start_idx);
+        }
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        for area in self.noise_areas.values() {
+            spawner::spawn_region(ecs, area, self.map.depth);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position
+    }
+}
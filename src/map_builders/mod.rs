@@ -0,0 +1,111 @@
+//! Procedural level generation.
+//!
+//! Every algorithm implements [`MapBuilder`], and [`build_valid_level()`] picks
+//! one of them for each new level, depending on which branch of the dungeon it's in.
+
+mod bsp_dungeon;
+mod common;
+mod drunkard;
+mod prefab_builder;
+mod simple_map;
+mod themes;
+mod validation;
+mod water;
+mod waveform_collapse;
+
+pub use self::bsp_dungeon::BspDungeonBuilder;
+pub use self::drunkard::DrunkardsWalkBuilder;
+pub use self::prefab_builder::PrefabBuilder;
+pub use self::simple_map::SimpleMapBuilder;
+pub use self::themes::{level_theme, ThemeBuilder};
+pub use self::validation::{validate_map, MapValidationError};
+pub use self::water::WaterBuilder;
+pub use self::waveform_collapse::WaveformCollapseBuilder;
+
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+use crate::{Branch, LevelId, Map, MapSize, Position};
+
+/// A level generation algorithm.
+pub trait MapBuilder {
+    /// Generate the level's layout.
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator);
+
+    /// Fill the generated level with monsters, items, and so on.
+    fn spawn_entities(&mut self, ecs: &mut World);
+
+    /// Returns a copy of the generated map.
+    fn get_map(&self) -> Map;
+
+    /// Returns where the player should be placed in the generated map.
+    fn get_starting_position(&self) -> Position;
+}
+
+/// Randomly pick a map builder for a new level at depth `new_depth`.
+///
+/// Sometimes the picked builder's output is fed through a
+/// [`WaveformCollapseBuilder`] to scramble it into something new, and
+/// sometimes it gets decorated with prefab vaults.
+pub fn random_builder(new_depth: i32, rng: &mut RandomNumberGenerator) -> Box<dyn MapBuilder> {
+    let mut builder = random_base_builder(new_depth, rng);
+    if rng.roll_dice(1, 6) == 1 {
+        builder = Box::new(WaveformCollapseBuilder::new(new_depth, builder));
+    }
+    if rng.roll_dice(1, 3) == 1 {
+        builder = Box::new(PrefabBuilder::vaults(new_depth, builder));
+    }
+    builder
+}
+
+/// The sizes that rooms-and-corridors levels can be built at. Caves and prefab
+/// levels pick their own sizes.
+const ROOM_LEVEL_SIZES: [MapSize; 3] = [MapSize::CRAMPED, MapSize::STANDARD, MapSize::SPRAWLING];
+
+fn random_base_builder(new_depth: i32, rng: &mut RandomNumberGenerator) -> Box<dyn MapBuilder> {
+    let room_level_size = ROOM_LEVEL_SIZES[rng.range(0, ROOM_LEVEL_SIZES.len())];
+    match rng.roll_dice(1, 6) {
+        1 => Box::new(SimpleMapBuilder::new(new_depth, room_level_size)),
+        2 => Box::new(BspDungeonBuilder::new(new_depth, room_level_size)),
+        3 => Box::new(DrunkardsWalkBuilder::open_area(new_depth)),
+        4 => Box::new(DrunkardsWalkBuilder::open_halls(new_depth)),
+        5 => Box::new(DrunkardsWalkBuilder::winding_passages(new_depth)),
+        _ => Box::new(PrefabBuilder::level(
+            new_depth,
+            prefab_builder::GOBLIN_WARRENS,
+        )),
+    }
+}
+
+/// Randomly pick a builder for a new level in the caves at depth `new_depth`.
+/// They're always dug out by drunkards.
+fn cave_builder(new_depth: i32, rng: &mut RandomNumberGenerator) -> Box<dyn MapBuilder> {
+    match rng.roll_dice(1, 3) {
+        1 => Box::new(DrunkardsWalkBuilder::open_area(new_depth)),
+        2 => Box::new(DrunkardsWalkBuilder::open_halls(new_depth)),
+        _ => Box::new(DrunkardsWalkBuilder::winding_passages(new_depth)),
+    }
+}
+
+/// Build a new `level` using a randomly-picked builder suited to its branch, in
+/// the theme for its depth, with as much water as suits the theme.
+///
+/// Levels that fail [`validate_map()`] are thrown away and re-generated from
+/// scratch, so the player never gets stuck on an unwinnable level.
+pub fn build_valid_level(level: LevelId, rng: &mut RandomNumberGenerator) -> Box<dyn MapBuilder> {
+    loop {
+        let builder = match level.branch {
+            Branch::Main => random_builder(level.depth, rng),
+            Branch::Caves => cave_builder(level.depth, rng),
+        };
+        let theme = level_theme(level);
+        let builder = Box::new(WaterBuilder::for_theme(builder, theme));
+        let mut builder: Box<dyn MapBuilder> = Box::new(ThemeBuilder::new(builder, theme));
+        builder.build_map(rng);
+
+        match validate_map(&builder.get_map(), builder.get_starting_position()) {
+            Ok(()) => return builder,
+            Err(e) => rltk::console::log(format!("Re-generating invalid level: {e}")),
+        }
+    }
+}
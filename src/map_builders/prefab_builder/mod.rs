@@ -0,0 +1,252 @@
+mod prefab_levels;
+mod prefab_vaults;
+
+pub use self::prefab_levels::{PrefabLevel, GOBLIN_WARRENS};
+
+use rltk::RandomNumberGenerator;
+use rustc_hash::FxHashSet;
+use specs::prelude::*;
+
+use self::prefab_vaults::ALL_VAULTS;
+use super::MapBuilder;
+use crate::{spawner, Map, MapSize, Player, Position, TileType};
+
+/// How a [`PrefabBuilder`] makes use of its hand-authored templates.
+pub enum PrefabMode {
+    /// Use a hand-authored level as the entire map.
+    Level { level: PrefabLevel },
+    /// Build a level with another builder, then stamp some vaults on top of it.
+    Vaults { base: Box<dyn MapBuilder> },
+}
+
+/// Builds levels out of hand-authored ASCII templates.
+pub struct PrefabBuilder {
+    map: Map,
+    starting_position: Position,
+    mode: PrefabMode,
+    /// Entities to spawn from markers in the templates, by map tile index.
+    spawns: Vec<(usize, &'static str)>,
+    /// Every map tile covered by a vault.
+    vault_tiles: FxHashSet<usize>,
+}
+
+/// A template, parsed into rows of characters.
+struct ParsedTemplate {
+    width: i32,
+    height: i32,
+    chars: Vec<char>,
+}
+
+impl ParsedTemplate {
+    fn char_at(&self, x: i32, y: i32) -> char {
+        self.chars[(y * self.width + x) as usize]
+    }
+}
+
+/// Turn an ASCII template into a grid of characters. Short rows get padded
+/// out with walls.
+fn parse_template(template: &str) -> ParsedTemplate {
+    let rows: Vec<&str> = template
+        .lines()
+        .skip_while(|line| line.is_empty())
+        .collect();
+    let width = rows
+        .iter()
+        .map(|row| row.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut chars = Vec::with_capacity(width * rows.len());
+    for row in rows.iter() {
+        chars.extend(row.chars());
+        chars.extend(std::iter::repeat_n('#', width - row.chars().count()));
+    }
+
+    ParsedTemplate {
+        width: width as i32,
+        height: rows.len() as i32,
+        chars,
+    }
+}
+
+/// What a template character turns into: a tile, and optionally something to spawn on it.
+///
+/// - `#` is a wall, and `.` or ` ` is an empty floor tile.
+/// - `>` is the down stairs, and `@` is where the player starts.
+/// - `g`, `o`, and `k` are a goblin, an orc, and a kobold hoarder.
+/// - `!`, `/`, `(`, and `)` are a health potion, a dagger, a shield, and a
+///   magic missile scroll.
+fn char_to_tile(ch: char) -> (TileType, Option<&'static str>) {
+    match ch {
+        '#' => (TileType::Wall, None),
+        '>' => (TileType::DownStairs, None),
+        'g' => (TileType::Floor, Some("Goblin")),
+        'o' => (TileType::Floor, Some("Orc")),
+        'k' => (TileType::Floor, Some("Kobold Hoarder")),
+        '!' => (TileType::Floor, Some("Health Potion")),
+        '/' => (TileType::Floor, Some("Dagger")),
+        '(' => (TileType::Floor, Some("Shield")),
+        ')' => (TileType::Floor, Some("Magic Missile Scroll")),
+        '.' | ' ' | '@' => (TileType::Floor, None),
+        _ => {
+            rltk::console::log(format!("Unknown prefab template character {ch:?}"));
+            (TileType::Floor, None)
+        }
+    }
+}
+
+impl PrefabBuilder {
+    /// Use `level` as the whole map, which is sized to fit it exactly.
+    pub fn level(new_depth: i32, level: PrefabLevel) -> Self {
+        let template = parse_template(level.template);
+        let size = MapSize::new(template.width, template.height);
+        Self::new(Map::new(new_depth, size), PrefabMode::Level { level })
+    }
+
+    /// Build a level with `base`, then decorate it with a few vaults.
+    pub fn vaults(new_depth: i32, base: Box<dyn MapBuilder>) -> Self {
+        // Replaced by the base builder's map once it's been built
+        let map = Map {
+            depth: new_depth,
+            ..Map::default()
+        };
+        Self::new(map, PrefabMode::Vaults { base })
+    }
+
+    fn new(map: Map, mode: PrefabMode) -> Self {
+        Self {
+            map,
+            starting_position: Position::default(),
+            mode,
+            spawns: Vec::new(),
+            vault_tiles: FxHashSet::default(),
+        }
+    }
+
+    /// Copy `template` onto the map with its top-left corner at (`left_x`, `top_y`),
+    /// remembering any spawn markers in it.
+    fn stamp_template(&mut self, template: &ParsedTemplate, left_x: i32, top_y: i32) {
+        for y in 0..template.height {
+            for x in 0..template.width {
+                let ch = template.char_at(x, y);
+                let idx = self.map.xy_idx(left_x + x, top_y + y);
+                let (tile, spawn) = char_to_tile(ch);
+
+                self.map.tiles[idx] = tile;
+                if let Some(spawn) = spawn {
+                    self.spawns.push((idx, spawn));
+                }
+                if ch == '@' {
+                    self.starting_position = Position {
+                        x: left_x + x,
+                        y: top_y + y,
+                    };
+                }
+            }
+        }
+    }
+
+    fn build_level(&mut self, level: PrefabLevel) {
+        // The map is exactly the size of the level
+        let template = parse_template(level.template);
+        self.stamp_template(&template, 0, 0);
+    }
+
+    fn place_vaults(&mut self, rng: &mut RandomNumberGenerator) {
+        let depth = self.map.depth;
+        let mut vaults: Vec<_> = ALL_VAULTS
+            .iter()
+            .filter(|vault| depth >= vault.first_depth && depth <= vault.last_depth)
+            .collect();
+
+        let num_vaults = i32::min(rng.roll_dice(1, 3), vaults.len() as i32);
+        for _ in 0..num_vaults {
+            let vault_idx = (rng.roll_dice(1, vaults.len() as i32) - 1) as usize;
+            let template = parse_template(vaults.remove(vault_idx).template);
+
+            // Vaults can only go where there's nothing but open floor, well away from the player
+            let start = rltk::Point::new(self.starting_position.x, self.starting_position.y);
+            let mut candidates = Vec::new();
+            for top_y in 1..self.map.height - template.height - 1 {
+                for left_x in 1..self.map.width - template.width - 1 {
+                    let fits = (0..template.height).all(|y| {
+                        (0..template.width).all(|x| {
+                            let idx = self.map.xy_idx(left_x + x, top_y + y);
+                            self.map.tiles[idx] == TileType::Floor
+                                && !self.vault_tiles.contains(&idx)
+                                && rltk::DistanceAlg::Pythagoras
+                                    .distance2d(start, rltk::Point::new(left_x + x, top_y + y))
+                                    > 3.0
+                        })
+                    });
+                    if fits {
+                        candidates.push((left_x, top_y));
+                    }
+                }
+            }
+
+            if candidates.is_empty() {
+                continue;
+            }
+
+            let (left_x, top_y) =
+                candidates[(rng.roll_dice(1, candidates.len() as i32) - 1) as usize];
+            self.stamp_template(&template, left_x, top_y);
+            for y in top_y..top_y + template.height {
+                for x in left_x..left_x + template.width {
+                    self.vault_tiles.insert(self.map.xy_idx(x, y));
+                }
+            }
+        }
+    }
+}
+
+impl MapBuilder for PrefabBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        match &mut self.mode {
+            PrefabMode::Level { level } => {
+                let level = *level;
+                self.build_level(level);
+            }
+
+            PrefabMode::Vaults { base } => {
+                base.build_map(rng);
+                self.map = base.get_map();
+                self.starting_position = base.get_starting_position();
+                self.place_vaults(rng);
+            }
+        }
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        if let PrefabMode::Vaults { base } = &mut self.mode {
+            base.spawn_entities(ecs);
+
+            // Clear out anything the base builder put inside of a vault
+            let in_vaults: Vec<Entity> = {
+                let entities = ecs.entities();
+                let positions = ecs.read_storage::<Position>();
+                let players = ecs.read_storage::<Player>();
+                (&entities, &positions, !&players)
+                    .join()
+                    .filter(|(_, pos, _)| self.vault_tiles.contains(&self.map.xy_idx(pos.x, pos.y)))
+                    .map(|(entity, _, _)| entity)
+                    .collect()
+            };
+            ecs.delete_entities(&in_vaults)
+                .expect("Unable to delete entities spawned inside of a vault");
+        }
+
+        for (map_idx, name) in self.spawns.iter() {
+            spawner::spawn_entity(ecs, *map_idx, name);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position
+    }
+}
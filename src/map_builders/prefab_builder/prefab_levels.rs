@@ -0,0 +1,37 @@
+/// A hand-authored level.
+///
+/// See [`super::parse_template()`] for what each character in the template means.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct PrefabLevel {
+    pub template: &'static str,
+}
+
+/// A ring of goblin-infested chambers around a central hall, with the stairs
+/// out tucked away at the far end.
+pub const GOBLIN_WARRENS: PrefabLevel = PrefabLevel {
+    template: GOBLIN_WARRENS_TEMPLATE,
+};
+
+const GOBLIN_WARRENS_TEMPLATE: &str = "
+##################################################
+#@.....#.............#..............#...........##
+#......#.............#......g.......#......o.....#
+#......#....!........#..............#............#
+#..............................................!.#
+#......#.............#..............#............#
+#......#......g......#.......!......#.....g......#
+####.#######.##########.########.######.##########
+#...........#......................#.............#
+#....g......#....###########.......#.....k.......#
+#...........#....#....!....#.......#.............#
+#...........#....#..g...g..#.......#....!../.....#
+#.....!.....#....#.........#.......#.............#
+#...........#....####...####.......#.............#
+#...........#......................#.............#
+###.#########......................######.########
+#...........#......o......(........#.............#
+#...........#......................#......o......#
+#.....g.........................................>#
+#...........#..........!...........#.............#
+##################################################
+";
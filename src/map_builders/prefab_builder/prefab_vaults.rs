@@ -0,0 +1,75 @@
+/// A small hand-authored section that can be stamped on top of another
+/// builder's output.
+///
+/// Vaults are only ever placed where their whole footprint is open floor, so
+/// their outermost ring should be floor too, to keep them from cutting off
+/// the area around them.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct PrefabVault {
+    pub template: &'static str,
+    /// The shallowest level this vault can show up on.
+    pub first_depth: i32,
+    /// The deepest level this vault can show up on.
+    pub last_depth: i32,
+}
+
+pub const ALL_VAULTS: &[PrefabVault] = &[GOBLIN_DEN, KOBOLD_NEST, PILLARED_HALL, ORC_SHRINE];
+
+/// A walled-off den with a couple of goblins guarding a potion.
+const GOBLIN_DEN: PrefabVault = PrefabVault {
+    template: "
+.......
+.#####.
+.#g!g#.
+.#...#.
+.##.##.
+.......
+",
+    first_depth: 1,
+    last_depth: 6,
+};
+
+/// A kobold hoarder that has already collected a few things.
+const KOBOLD_NEST: PrefabVault = PrefabVault {
+    template: "
+.........
+.##...##.
+.#.....#.
+...k!/...
+.#.....#.
+.##...##.
+.........
+",
+    first_depth: 1,
+    last_depth: 100,
+};
+
+/// Rows of pillars with some loot lying between them.
+const PILLARED_HALL: PrefabVault = PrefabVault {
+    template: "
+.........
+.#.#.#.#.
+....!....
+.#.#.#.#.
+...(.)...
+.#.#.#.#.
+.........
+",
+    first_depth: 2,
+    last_depth: 100,
+};
+
+/// A pair of orcs standing guard over their treasure.
+const ORC_SHRINE: PrefabVault = PrefabVault {
+    template: "
+...........
+.####.####.
+.#.......#.
+.#..o/o..#.
+.#...!...#.
+.#########.
+...........
+",
+    first_depth: 3,
+    last_depth: 100,
+};
@@ -0,0 +1,86 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+use super::common::{apply_horizontal_tunnel, apply_room_to_map, apply_vertical_tunnel};
+use super::MapBuilder;
+use crate::{spawner, Map, MapSize, Position, Rect, TileType};
+
+/// Builds maps with randomly-placed rooms that are connected by corridors.
+///
+/// This uses the algorithm from http://rogueliketutorials.com/tutorials/tcod/part-3/.
+pub struct SimpleMapBuilder {
+    map: Map,
+    starting_position: Position,
+}
+
+impl SimpleMapBuilder {
+    pub fn new(new_depth: i32, size: MapSize) -> Self {
+        Self {
+            map: Map::new(new_depth, size),
+            starting_position: Position::default(),
+        }
+    }
+}
+
+impl MapBuilder for SimpleMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        const MAX_ROOMS: i32 = 30;
+        const MIN_SIZE: i32 = 6;
+        const MAX_SIZE: i32 = 10;
+
+        let map = &mut self.map;
+
+        for _ in 0..MAX_ROOMS {
+            let w = rng.range(MIN_SIZE, MAX_SIZE);
+            let h = rng.range(MIN_SIZE, MAX_SIZE);
+            let x = rng.roll_dice(1, map.width - w - 1) - 1;
+            let y = rng.roll_dice(1, map.height - h - 1) - 1;
+            let new_room = Rect::new(x, y, w, h);
+
+            if !map
+                .rooms
+                .iter()
+                .any(|other_room| new_room.intersect(other_room))
+            {
+                apply_room_to_map(map, &new_room);
+
+                if !map.rooms.is_empty() {
+                    let (new_x, new_y) = new_room.center();
+                    let (prev_x, prev_y) = map.rooms[map.rooms.len() - 1].center();
+                    if rng.range(0, 2) == 1 {
+                        apply_horizontal_tunnel(map, prev_x, new_x, prev_y);
+                        apply_vertical_tunnel(map, prev_y, new_y, new_x);
+                    } else {
+                        apply_vertical_tunnel(map, prev_y, new_y, prev_x);
+                        apply_horizontal_tunnel(map, prev_x, new_x, new_y);
+                    }
+                }
+
+                map.rooms.push(new_room);
+            }
+        }
+
+        // Add down stairs in the last room generated
+        let (stairs_x, stairs_y) = map.rooms[map.rooms.len() - 1].center();
+        let stairs_idx = map.xy_idx(stairs_x, stairs_y);
+        map.tiles[stairs_idx] = TileType::DownStairs;
+
+        // The player starts in the first room generated
+        self.starting_position = map.rooms[0].center().into();
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        // Add monsters and items to each room (except the starting room)
+        for room in self.map.rooms.iter().skip(1) {
+            spawner::spawn_room(ecs, room, self.map.depth);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position
+    }
+}
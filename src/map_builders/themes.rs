@@ -0,0 +1,53 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+use super::MapBuilder;
+use crate::{Branch, LevelId, Map, MapTheme, Position};
+
+/// Dresses up the level built by another builder in a [`MapTheme`], which
+/// changes how it's drawn, whether it's dark, and what tends to spawn in it.
+pub struct ThemeBuilder {
+    builder: Box<dyn MapBuilder>,
+    theme: MapTheme,
+}
+
+impl ThemeBuilder {
+    pub fn new(builder: Box<dyn MapBuilder>, theme: MapTheme) -> Self {
+        Self { builder, theme }
+    }
+}
+
+impl MapBuilder for ThemeBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.builder.build_map(rng);
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        self.builder.spawn_entities(ecs);
+    }
+
+    fn get_map(&self) -> Map {
+        Map {
+            theme: self.theme,
+            dark: self.theme.is_dark(),
+            ..self.builder.get_map()
+        }
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.builder.get_starting_position()
+    }
+}
+
+/// The theme that `level` is built in. The main dungeon changes theme every few
+/// levels on the way down, and side branches keep to one theme throughout.
+pub fn level_theme(level: LevelId) -> MapTheme {
+    match (level.branch, level.depth) {
+        (Branch::Caves, _) => MapTheme::LimestoneCavern,
+        (Branch::Main, ..=1) => MapTheme::Dungeon,
+        (Branch::Main, 2..=3) => MapTheme::Forest,
+        (Branch::Main, 4..=5) => MapTheme::LimestoneCavern,
+        (Branch::Main, 6..=8) => MapTheme::DwarfFortress,
+        (Branch::Main, _) => MapTheme::Hell,
+    }
+}
@@ -0,0 +1,240 @@
+use rltk::DijkstraMap;
+
+use crate::{Map, Position, Rect, TileType};
+
+/// Ways in which a generated level can be unwinnable.
+#[derive(Debug, thiserror::Error)]
+pub enum MapValidationError {
+    #[error("The starting position ({x}, {y}) is not a walkable tile")]
+    BlockedStart { x: i32, y: i32 },
+
+    #[error("The level has no down stairs")]
+    NoStairs,
+
+    #[error("The down stairs at ({x}, {y}) can't be reached from the starting position")]
+    StairsUnreachable { x: i32, y: i32 },
+
+    #[error("Spawn region {region:?} can't be reached from the starting position")]
+    SpawnRegionUnreachable { region: Rect },
+}
+
+/// Check that everything on a freshly-generated level can be reached by the player.
+///
+/// The down stairs, and every walkable tile that [`crate::spawner`] might place a
+/// monster or an item onto, must be connected to `start` by walkable tiles.
+pub fn validate_map(map: &Map, start: Position) -> Result<(), MapValidationError> {
+    // Builders never touch the blocked index, so compute it from the tiles alone.
+    let mut map = map.clone();
+    map.populate_blocked();
+
+    let start_idx = map.xy_idx(start.x, start.y);
+    if map.blocked[start_idx] {
+        return Err(MapValidationError::BlockedStart {
+            x: start.x,
+            y: start.y,
+        });
+    }
+
+    let dijkstra_map = DijkstraMap::new(
+        map.width,
+        map.height,
+        &[start_idx],
+        &map,
+        (map.width * map.height) as f32,
+    );
+    let is_reachable = |idx: usize| dijkstra_map.map[idx] < f32::MAX;
+
+    let stairs_idx = map
+        .tiles
+        .iter()
+        .position(|tile| *tile == TileType::DownStairs)
+        .ok_or(MapValidationError::NoStairs)?;
+    if !is_reachable(stairs_idx) {
+        let (x, y) = map.idx_xy(stairs_idx);
+        return Err(MapValidationError::StairsUnreachable { x, y });
+    }
+
+    for room in map.rooms.iter() {
+        for y in room.y1 + 1..=room.y2 {
+            for x in room.x1 + 1..=room.x2 {
+                let idx = map.xy_idx(x, y);
+                if !map.blocked[idx] && !is_reachable(idx) {
+                    return Err(MapValidationError::SpawnRegionUnreachable { region: *room });
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use rltk::RandomNumberGenerator;
+
+    use super::*;
+    use crate::map_builders::{
+        build_valid_level, common::apply_room_to_map, prefab_builder::GOBLIN_WARRENS,
+        BspDungeonBuilder, DrunkardsWalkBuilder, MapBuilder, PrefabBuilder, SimpleMapBuilder,
+        WaveformCollapseBuilder, ROOM_LEVEL_SIZES,
+    };
+    use crate::{Branch, LevelId, MapSize};
+
+    fn room_level_size() -> impl Strategy<Value = MapSize> {
+        prop::sample::select(ROOM_LEVEL_SIZES.to_vec())
+    }
+
+    /// Returns `true` if the floors of two rooms overlap. Rooms are allowed to share
+    /// a wall.
+    const fn floors_overlap(a: &Rect, b: &Rect) -> bool {
+        a.x1 < b.x2 && b.x1 < a.x2 && a.y1 < b.y2 && b.y1 < a.y2
+    }
+
+    /// Check that a level built from `seed` is valid, and holds up in ways that
+    /// [`validate_map()`] doesn't need to check for in a real game: nothing's been
+    /// dug out of the map's outer wall, _every_ walkable tile can be reached, and
+    /// rooms don't overlap.
+    fn assert_level_is_valid(map: &Map, start: Position, seed: u64) {
+        if let Err(e) = validate_map(map, start) {
+            panic!("Seed {seed} generated an invalid level: {e}");
+        }
+
+        let mut map = map.clone();
+        map.populate_blocked();
+
+        // Nothing gets past the outer wall, not even a tunnel
+        for idx in 0..map.tiles.len() {
+            let (x, y) = map.idx_xy(idx);
+            if x == 0 || y == 0 || x == map.width - 1 || y == map.height - 1 {
+                assert_eq!(
+                    map.tiles[idx],
+                    TileType::Wall,
+                    "Seed {seed} dug out the edge of the map at ({x}, {y})"
+                );
+            }
+        }
+
+        let start_idx = map.xy_idx(start.x, start.y);
+        let dijkstra_map = DijkstraMap::new(
+            map.width,
+            map.height,
+            &[start_idx],
+            &map,
+            (map.width * map.height) as f32,
+        );
+        for idx in (0..map.tiles.len()).filter(|idx| !map.blocked[*idx]) {
+            let (x, y) = map.idx_xy(idx);
+            assert!(
+                dijkstra_map.map[idx] < f32::MAX,
+                "Seed {seed} left ({x}, {y}) unreachable from the start"
+            );
+        }
+
+        for (i, a) in map.rooms.iter().enumerate() {
+            for b in map.rooms.iter().skip(i + 1) {
+                assert!(
+                    !floors_overlap(a, b),
+                    "Seed {seed} generated overlapping rooms {a:?} and {b:?}"
+                );
+            }
+        }
+    }
+
+    fn assert_builder_output_is_valid(mut builder: impl MapBuilder, seed: u64) {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        builder.build_map(&mut rng);
+        assert_level_is_valid(&builder.get_map(), builder.get_starting_position(), seed);
+    }
+
+    proptest! {
+        #[test]
+        fn simple_map_levels_are_connected(
+            seed in any::<u64>(),
+            depth in 1..20i32,
+            size in room_level_size(),
+        ) {
+            assert_builder_output_is_valid(SimpleMapBuilder::new(depth, size), seed);
+        }
+
+        #[test]
+        fn bsp_dungeon_levels_are_connected(
+            seed in any::<u64>(),
+            depth in 1..20i32,
+            size in room_level_size(),
+        ) {
+            assert_builder_output_is_valid(BspDungeonBuilder::new(depth, size), seed);
+        }
+
+        #[test]
+        fn drunkards_walk_levels_are_connected(seed in any::<u64>(), depth in 1..20i32) {
+            assert_builder_output_is_valid(DrunkardsWalkBuilder::open_area(depth), seed);
+            assert_builder_output_is_valid(DrunkardsWalkBuilder::open_halls(depth), seed);
+            assert_builder_output_is_valid(DrunkardsWalkBuilder::winding_passages(depth), seed);
+        }
+
+        #[test]
+        fn waveform_collapse_levels_are_connected(
+            seed in any::<u64>(),
+            depth in 1..20i32,
+            size in room_level_size(),
+        ) {
+            let source = Box::new(BspDungeonBuilder::new(depth, size));
+            assert_builder_output_is_valid(WaveformCollapseBuilder::new(depth, source), seed);
+        }
+
+        #[test]
+        fn prefab_and_vault_levels_are_connected(seed in any::<u64>(), depth in 1..20i32) {
+            assert_builder_output_is_valid(PrefabBuilder::level(depth, GOBLIN_WARRENS), seed);
+            let base = Box::new(DrunkardsWalkBuilder::open_area(depth));
+            assert_builder_output_is_valid(PrefabBuilder::vaults(depth, base), seed);
+        }
+
+        #[test]
+        fn random_levels_are_connected(seed in any::<u64>(), depth in 1..20i32, caves in any::<bool>()) {
+            let branch = if caves { Branch::Caves } else { Branch::Main };
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            let builder = build_valid_level(LevelId { branch, depth }, &mut rng);
+            assert_level_is_valid(&builder.get_map(), builder.get_starting_position(), seed);
+        }
+    }
+
+    #[test]
+    fn prefab_levels_are_connected() {
+        assert_builder_output_is_valid(PrefabBuilder::level(1, GOBLIN_WARRENS), 0);
+    }
+
+    #[test]
+    fn sealed_rooms_are_rejected() {
+        let mut map = Map::new(1, MapSize::STANDARD);
+        let start_room = Rect::new(2, 2, 5, 5);
+        let sealed_room = Rect::new(20, 20, 5, 5);
+        apply_room_to_map(&mut map, &start_room);
+        apply_room_to_map(&mut map, &sealed_room);
+        map.rooms = vec![start_room, sealed_room];
+
+        let stairs_idx = map.xy_idx(4, 4);
+        map.tiles[stairs_idx] = TileType::DownStairs;
+
+        assert!(matches!(
+            validate_map(&map, start_room.center().into()),
+            Err(MapValidationError::SpawnRegionUnreachable { region }) if region == sealed_room
+        ));
+    }
+
+    #[test]
+    fn unreachable_stairs_are_rejected() {
+        let mut map = Map::new(1, MapSize::STANDARD);
+        let start_room = Rect::new(2, 2, 5, 5);
+        apply_room_to_map(&mut map, &start_room);
+        map.rooms = vec![start_room];
+
+        let stairs_idx = map.xy_idx(30, 30);
+        map.tiles[stairs_idx] = TileType::DownStairs;
+
+        assert!(matches!(
+            validate_map(&map, start_room.center().into()),
+            Err(MapValidationError::StairsUnreachable { x: 30, y: 30 })
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+use rltk::{DijkstraMap, Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+use super::MapBuilder;
+use crate::{Map, MapTheme, Position, TileType};
+
+/// Floods the level built by another builder with rivers that cut right across
+/// it, and pools of water in its open spaces.
+pub struct WaterBuilder {
+    builder: Box<dyn MapBuilder>,
+    map: Map,
+    rivers: i32,
+    pools: i32,
+}
+
+impl WaterBuilder {
+    pub fn new(builder: Box<dyn MapBuilder>, rivers: i32, pools: i32) -> Self {
+        Self {
+            map: builder.get_map(),
+            builder,
+            rivers,
+            pools,
+        }
+    }
+
+    /// Add as much water as suits a level in `theme`.
+    pub fn for_theme(builder: Box<dyn MapBuilder>, theme: MapTheme) -> Self {
+        let (rivers, pools) = match theme {
+            MapTheme::Dungeon | MapTheme::Hell => (0, 0),
+            MapTheme::Forest => (1, 1),
+            MapTheme::LimestoneCavern => (0, 4),
+            MapTheme::DwarfFortress => (1, 0),
+        };
+        Self::new(builder, rivers, pools)
+    }
+
+    /// Returns `true` if water mustn't go at `idx`, e.g. because it's the stairs.
+    fn is_protected(&self, idx: usize) -> bool {
+        let start = self.builder.get_starting_position();
+        idx == self.map.xy_idx(start.x, start.y)
+            || matches!(
+                self.map.tiles[idx],
+                TileType::DownStairs | TileType::UpStairs
+            )
+    }
+
+    /// Flood the tile at (`x`, `y`) with `water`. Deep water is never made shallow again.
+    fn flood(&mut self, x: i32, y: i32, water: TileType) {
+        let idx = self.map.xy_idx(x, y);
+        if !self.is_protected(idx) && self.map.tiles[idx] != TileType::DeepWater {
+            self.map.tiles[idx] = water;
+        }
+    }
+
+    /// Carve a river that meanders from the left edge of the level to the right,
+    /// through walls and all. It's deep down the middle, except at the odd ford.
+    fn carve_river(&mut self, rng: &mut RandomNumberGenerator) {
+        let mut y = rng.range(3, self.map.height - 3);
+        for x in 1..self.map.width - 1 {
+            y = (y + rng.range(-1, 2)).clamp(2, self.map.height - 3);
+            let middle = if rng.roll_dice(1, 6) == 1 {
+                TileType::ShallowWater
+            } else {
+                TileType::DeepWater
+            };
+            self.flood(x, y, middle);
+            self.flood(x, y - 1, TileType::ShallowWater);
+            self.flood(x, y + 1, TileType::ShallowWater);
+        }
+    }
+
+    /// Wall back up any stretch of river that was carved through solid rock and
+    /// can't be reached from the start, so that it doesn't leave a sealed-off
+    /// pocket of water.
+    fn seal_off_stranded_water(&mut self) {
+        let start = self.builder.get_starting_position();
+        let start_idx = self.map.xy_idx(start.x, start.y);
+        self.map.populate_blocked();
+        let dijkstra_map = DijkstraMap::new(
+            self.map.width,
+            self.map.height,
+            &[start_idx],
+            &self.map,
+            (self.map.width * self.map.height) as f32,
+        );
+
+        for (idx, tile) in self.map.tiles.iter_mut().enumerate() {
+            if tile.is_water() && dijkstra_map.map[idx] == f32::MAX {
+                *tile = TileType::Wall;
+            }
+        }
+    }
+
+    /// Fill in a roughly-round pool around a random floor tile, deeper towards
+    /// its middle. Pools never spill over into walls.
+    fn fill_pool(&mut self, rng: &mut RandomNumberGenerator) {
+        let floors: Vec<usize> = (0..self.map.tiles.len())
+            .filter(|&idx| self.map.tiles[idx] == TileType::Floor && !self.is_protected(idx))
+            .collect();
+        if floors.is_empty() {
+            return;
+        }
+
+        let center = Point::from_tuple(self.map.idx_xy(floors[rng.range(0, floors.len())]));
+        let radius = rng.range(2, 4);
+        for y in (center.y - radius).max(1)..=(center.y + radius).min(self.map.height - 2) {
+            for x in (center.x - radius).max(1)..=(center.x + radius).min(self.map.width - 2) {
+                if self.map.tiles[self.map.xy_idx(x, y)] != TileType::Floor {
+                    continue;
+                }
+                let distance = rltk::DistanceAlg::Pythagoras.distance2d(center, Point::new(x, y));
+                if distance <= radius as f32 - 1.5 {
+                    self.flood(x, y, TileType::DeepWater);
+                } else if distance <= radius as f32 {
+                    self.flood(x, y, TileType::ShallowWater);
+                }
+            }
+        }
+    }
+}
+
+impl MapBuilder for WaterBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.builder.build_map(rng);
+        self.map = self.builder.get_map();
+
+        for _ in 0..self.rivers {
+            self.carve_river(rng);
+        }
+        if self.rivers > 0 {
+            self.seal_off_stranded_water();
+        }
+        for _ in 0..self.pools {
+            self.fill_pool(rng);
+        }
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        self.builder.spawn_entities(ecs);
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.builder.get_starting_position()
+    }
+}
@@ -0,0 +1,132 @@
+use rustc_hash::FxHashSet;
+
+use crate::{Map, TileType};
+
+/// A square pattern of tiles that the [`super::solver::Solver`] can stamp onto
+/// the map, along with which other patterns are allowed to sit next to it.
+#[derive(Clone, Debug)]
+pub struct MapChunk {
+    pub pattern: Vec<TileType>,
+    /// For each side of the chunk (see [`Direction`]), which tiles along that
+    /// edge can be walked through.
+    pub exits: [Vec<bool>; 4],
+    /// For each side of the chunk, the indexes of the chunks that may be
+    /// placed next to it on that side.
+    pub compatible_with: [Vec<usize>; 4],
+}
+
+/// The sides of a [`MapChunk`], used to index into its `exits` and `compatible_with`.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Direction {
+    North = 0,
+    South = 1,
+    West = 2,
+    East = 3,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::South,
+        Direction::West,
+        Direction::East,
+    ];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::East => Direction::West,
+        }
+    }
+}
+
+/// Cut `map` up into `chunk_size`-by-`chunk_size` patterns. Every chunk is also
+/// included flipped horizontally, vertically, and both, and duplicates are removed.
+///
+/// Stairs are turned into plain floor, since the builder places its own.
+pub fn build_patterns(map: &Map, chunk_size: i32) -> Vec<Vec<TileType>> {
+    let chunks_x = map.width / chunk_size;
+    let chunks_y = map.height / chunk_size;
+    let mut patterns = Vec::new();
+
+    for chunk_y in 0..chunks_y {
+        for chunk_x in 0..chunks_x {
+            for (flip_x, flip_y) in [(false, false), (true, false), (false, true), (true, true)] {
+                let mut pattern = Vec::with_capacity((chunk_size * chunk_size) as usize);
+
+                for y in 0..chunk_size {
+                    for x in 0..chunk_size {
+                        let src_x =
+                            chunk_x * chunk_size + if flip_x { chunk_size - 1 - x } else { x };
+                        let src_y =
+                            chunk_y * chunk_size + if flip_y { chunk_size - 1 - y } else { y };
+                        pattern.push(match map.tiles[map.xy_idx(src_x, src_y)] {
+                            TileType::DownStairs | TileType::UpStairs => TileType::Floor,
+                            tile => tile,
+                        });
+                    }
+                }
+
+                patterns.push(pattern);
+            }
+        }
+    }
+
+    let mut seen = FxHashSet::default();
+    patterns.retain(|pattern| seen.insert(pattern.clone()));
+    patterns
+}
+
+/// Work out the exits of every pattern, and which patterns can be placed next to each other.
+///
+/// Two chunks fit together on a side if they share at least one exit along it,
+/// or if neither of them has any exits on that side at all.
+pub fn patterns_to_constraints(patterns: Vec<Vec<TileType>>, chunk_size: i32) -> Vec<MapChunk> {
+    let mut constraints: Vec<MapChunk> = patterns
+        .into_iter()
+        .map(|pattern| {
+            let open = |x: i32, y: i32| pattern[(y * chunk_size + x) as usize] != TileType::Wall;
+            let exits = [
+                (0..chunk_size).map(|x| open(x, 0)).collect::<Vec<_>>(),
+                (0..chunk_size).map(|x| open(x, chunk_size - 1)).collect(),
+                (0..chunk_size).map(|y| open(0, y)).collect(),
+                (0..chunk_size).map(|y| open(chunk_size - 1, y)).collect(),
+            ];
+            MapChunk {
+                pattern,
+                exits,
+                compatible_with: Default::default(),
+            }
+        })
+        .collect();
+
+    for i in 0..constraints.len() {
+        for direction in Direction::ALL {
+            let our_exits = &constraints[i].exits[direction as usize];
+            let our_side_is_closed = !our_exits.iter().any(|exit| *exit);
+
+            let compatible = constraints
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| {
+                    let their_exits = &other.exits[direction.opposite() as usize];
+                    if our_side_is_closed {
+                        !their_exits.iter().any(|exit| *exit)
+                    } else {
+                        our_exits
+                            .iter()
+                            .zip(their_exits)
+                            .any(|(ours, theirs)| *ours && *theirs)
+                    }
+                })
+                .map(|(j, _)| j)
+                .collect();
+
+            constraints[i].compatible_with[direction as usize] = compatible;
+        }
+    }
+
+    constraints
+}
@@ -0,0 +1,150 @@
+mod constraints;
+mod solver;
+
+use rltk::RandomNumberGenerator;
+use rustc_hash::FxHashMap;
+use specs::prelude::*;
+
+use self::constraints::{build_patterns, patterns_to_constraints};
+use self::solver::Solver;
+use super::common::{generate_voronoi_spawn_regions, place_down_stairs_at_most_distant};
+use super::MapBuilder;
+use crate::{spawner, Map, Position, TileType};
+
+/// The width and height of the patterns cut out of the source map.
+const CHUNK_SIZE: i32 = 8;
+
+/// How many times to try solving before giving up and using the source map as-is.
+const MAX_SOLVE_ATTEMPTS: usize = 50;
+
+/// Solved maps with fewer reachable floor tiles than this are thrown away.
+const MIN_FLOOR_TILES: usize = 200;
+
+/// Builds levels using Wave Function Collapse.
+///
+/// Another builder generates a source map, which gets cut up into small
+/// patterns. The patterns are then shuffled back together into a new layout,
+/// only ever placing patterns next to each other if their edges line up.
+pub struct WaveformCollapseBuilder {
+    map: Map,
+    starting_position: Position,
+    /// Generates the map that the patterns are taken from.
+    source: Box<dyn MapBuilder>,
+    noise_areas: FxHashMap<i32, Vec<usize>>,
+}
+
+impl WaveformCollapseBuilder {
+    pub fn new(new_depth: i32, source: Box<dyn MapBuilder>) -> Self {
+        Self {
+            // Resized to match the source map once it's been built
+            map: Map {
+                depth: new_depth,
+                ..Map::default()
+            },
+            starting_position: Position::default(),
+            source,
+            noise_areas: FxHashMap::default(),
+        }
+    }
+
+    /// Wall off the edges of a freshly-solved map, pick a starting position, and
+    /// place the stairs.
+    ///
+    /// Returns `false` if the result is too small to be worth playing.
+    fn finish_solved_map(&mut self) -> bool {
+        let (width, height) = (self.map.width, self.map.height);
+        for x in 0..width {
+            for y in [0, height - 1] {
+                let idx = self.map.xy_idx(x, y);
+                self.map.tiles[idx] = TileType::Wall;
+            }
+        }
+        for y in 0..height {
+            for x in [0, width - 1] {
+                let idx = self.map.xy_idx(x, y);
+                self.map.tiles[idx] = TileType::Wall;
+            }
+        }
+
+        // Start on the floor tile closest to the middle of the map
+        let center = rltk::Point::new(width / 2, height / 2);
+        let Some(start_idx) = self
+            .map
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| **tile == TileType::Floor)
+            .map(|(idx, _)| idx)
+            .min_by_key(|idx| {
+                let (x, y) = self.map.idx_xy(*idx);
+                (x - center.x).abs() + (y - center.y).abs()
+            })
+        else {
+            return false;
+        };
+
+        place_down_stairs_at_most_distant(&mut self.map, start_idx);
+        let floor_tiles = self
+            .map
+            .tiles
+            .iter()
+            .filter(|tile| **tile == TileType::Floor)
+            .count();
+        if floor_tiles < MIN_FLOOR_TILES {
+            return false;
+        }
+
+        self.starting_position = Position::from(self.map.idx_xy(start_idx));
+        true
+    }
+}
+
+impl MapBuilder for WaveformCollapseBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.source.build_map(rng);
+        let source_map = self.source.get_map();
+        let constraints =
+            patterns_to_constraints(build_patterns(&source_map, CHUNK_SIZE), CHUNK_SIZE);
+
+        let mut solved = false;
+        for _ in 0..MAX_SOLVE_ATTEMPTS {
+            self.map = Map::new(source_map.depth, source_map.size());
+            let mut solver = Solver::new(&constraints, CHUNK_SIZE, &self.map);
+            while !solver.iteration(&mut self.map, rng) {}
+
+            if solver.possible && self.finish_solved_map() {
+                solved = true;
+                break;
+            }
+        }
+
+        if !solved {
+            rltk::console::log("Wave Function Collapse failed; using its source map instead");
+            self.map = source_map;
+            self.starting_position = self.source.get_starting_position();
+        }
+
+        // Don't spawn anything right on top of the player
+        let start_idx = self
+            .map
+            .xy_idx(self.starting_position.x, self.starting_position.y);
+        self.noise_areas = generate_voronoi_spawn_regions(&self.map, rng);
+        for area in self.noise_areas.values_mut() {
+            area.retain(|idx| *idx != start_idx);
+        }
+    }
+
+    fn spawn_entities(&mut self, ecs: &mut World) {
+        for area in self.noise_areas.values() {
+            spawner::spawn_region(ecs, area, self.map.depth);
+        }
+    }
+
+    fn get_map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_starting_position(&self) -> Position {
+        self.starting_position
+    }
+}
@@ -0,0 +1,124 @@
+use rltk::RandomNumberGenerator;
+
+use super::constraints::{Direction, MapChunk};
+use crate::Map;
+
+/// Fills a map with [`MapChunk`]s one at a time, always picking a chunk that is
+/// compatible with every neighbour that has already been placed.
+pub struct Solver<'a> {
+    constraints: &'a [MapChunk],
+    chunk_size: i32,
+    /// Which constraint has been placed in each chunk of the map, if any.
+    chunks: Vec<Option<usize>>,
+    chunks_x: i32,
+    chunks_y: i32,
+    /// Chunks of the map that haven't been filled in yet.
+    remaining: Vec<usize>,
+    /// Set to `false` if the solver painted itself into a corner.
+    pub possible: bool,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(constraints: &'a [MapChunk], chunk_size: i32, map: &Map) -> Self {
+        let chunks_x = map.width / chunk_size;
+        let chunks_y = map.height / chunk_size;
+        let num_chunks = (chunks_x * chunks_y) as usize;
+
+        Self {
+            constraints,
+            chunk_size,
+            chunks: vec![None; num_chunks],
+            chunks_x,
+            chunks_y,
+            remaining: (0..num_chunks).collect(),
+            possible: true,
+        }
+    }
+
+    fn chunk_idx(&self, x: i32, y: i32) -> usize {
+        (y * self.chunks_x + x) as usize
+    }
+
+    /// The chunk already placed next to `chunk` in `direction`, if any.
+    fn placed_neighbour(&self, chunk: usize, direction: Direction) -> Option<usize> {
+        let x = chunk as i32 % self.chunks_x;
+        let y = chunk as i32 / self.chunks_x;
+        let (nx, ny) = match direction {
+            Direction::North => (x, y - 1),
+            Direction::South => (x, y + 1),
+            Direction::West => (x - 1, y),
+            Direction::East => (x + 1, y),
+        };
+
+        if nx < 0 || nx >= self.chunks_x || ny < 0 || ny >= self.chunks_y {
+            None
+        } else {
+            self.chunks[self.chunk_idx(nx, ny)]
+        }
+    }
+
+    fn num_placed_neighbours(&self, chunk: usize) -> usize {
+        Direction::ALL
+            .into_iter()
+            .filter(|direction| self.placed_neighbour(chunk, *direction).is_some())
+            .count()
+    }
+
+    /// Fill in one more chunk of the map.
+    ///
+    /// Returns `true` once the solver is finished, either because every chunk has
+    /// been filled in, or because it turned out to be impossible to continue (in
+    /// which case [`Solver::possible`] is set to `false`).
+    pub fn iteration(&mut self, map: &mut Map, rng: &mut RandomNumberGenerator) -> bool {
+        if self.remaining.is_empty() {
+            return true;
+        }
+
+        // Fill in the most-constrained chunk next, or a random one if nothing has been
+        // placed yet.
+        let mut remaining = std::mem::take(&mut self.remaining);
+        remaining.sort_by_key(|chunk| std::cmp::Reverse(self.num_placed_neighbours(*chunk)));
+        self.remaining = remaining;
+        let remaining_idx = if self.num_placed_neighbours(self.remaining[0]) == 0 {
+            (rng.roll_dice(1, self.remaining.len() as i32) - 1) as usize
+        } else {
+            0
+        };
+        let chunk = self.remaining.remove(remaining_idx);
+
+        // Only keep options that every placed neighbour is happy with
+        let mut options: Vec<usize> = (0..self.constraints.len()).collect();
+        for direction in Direction::ALL {
+            if let Some(neighbour) = self.placed_neighbour(chunk, direction) {
+                let allowed =
+                    &self.constraints[neighbour].compatible_with[direction.opposite() as usize];
+                options.retain(|option| allowed.contains(option));
+            }
+        }
+
+        if options.is_empty() {
+            self.possible = false;
+            return true;
+        }
+
+        let picked = options[(rng.roll_dice(1, options.len() as i32) - 1) as usize];
+        self.chunks[chunk] = Some(picked);
+        self.stamp(map, chunk, picked);
+
+        false
+    }
+
+    /// Copy constraint `picked`'s pattern onto the map at `chunk`.
+    fn stamp(&self, map: &mut Map, chunk: usize, picked: usize) {
+        let left_x = (chunk as i32 % self.chunks_x) * self.chunk_size;
+        let top_y = (chunk as i32 / self.chunks_x) * self.chunk_size;
+        let pattern = &self.constraints[picked].pattern;
+
+        for y in 0..self.chunk_size {
+            for x in 0..self.chunk_size {
+                let idx = map.xy_idx(left_x + x, top_y + y);
+                map.tiles[idx] = pattern[(y * self.chunk_size + x) as usize];
+            }
+        }
+    }
+}
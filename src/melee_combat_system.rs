@@ -1,6 +1,19 @@
+use rltk::{RandomNumberGenerator, RGB};
 use specs::prelude::*;
 
-use crate::{CombatStats, GameLog, Name, SufferDamage, WantsToMelee};
+use crate::effects::{EffectQueue, EffectType, Targets, PARTICLE_LIFETIME_MS};
+use crate::l10n::{self, tr};
+use crate::{
+    BlockChance, CombatStats, CritRangeBonus, DefenseBonus, EquipmentSlot, Equipped, GameEvent,
+    GameEvents, GameLog, Map, MeleeAttack, MeleePowerBonus, Name, PlayerEntity, Position, Skill,
+    Skills, StatusEffects, WantsToMelee,
+};
+
+/// Every melee attack rolls a d20. Rolling this or higher is a critical hit.
+const CRITICAL_ROLL: i32 = 20;
+
+/// Rolling this or lower on the d20 is a fumble, and the attack goes nowhere.
+const FUMBLE_ROLL: i32 = 1;
 
 /// A system that handles tracking and applying melee damage to entities every ECS tick.
 pub struct MeleeCombatSystem;
@@ -9,35 +22,211 @@ impl<'a> System<'a> for MeleeCombatSystem {
     type SystemData = (
         Entities<'a>,
         WriteExpect<'a, GameLog>,
+        WriteExpect<'a, GameEvents>,
         WriteStorage<'a, WantsToMelee>,
         ReadStorage<'a, Name>,
         ReadStorage<'a, CombatStats>,
-        WriteStorage<'a, SufferDamage>,
+        WriteExpect<'a, EffectQueue>,
+        ReadStorage<'a, MeleePowerBonus>,
+        ReadStorage<'a, DefenseBonus>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, Skills>,
+        ReadStorage<'a, StatusEffects>,
+        ReadStorage<'a, BlockChance>,
+        ReadExpect<'a, PlayerEntity>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadStorage<'a, MeleeAttack>,
+        ReadStorage<'a, CritRangeBonus>,
+        ReadExpect<'a, Map>,
+        ReadStorage<'a, Position>,
     );
 
     fn run(
         &mut self,
-        (entities, mut log, mut wants_to_melee, names, combat_stats, mut inflict_damage): Self::SystemData,
+        (
+            entities,
+            mut log,
+            mut events,
+            mut wants_to_melee,
+            names,
+            combat_stats,
+            mut effects,
+            melee_power_bonuses,
+            defense_bonuses,
+            equipped,
+            skills,
+            status_effects,
+            block_chances,
+            player_entity,
+            mut rng,
+            melee_attacks,
+            crit_range_bonuses,
+            map,
+            positions,
+        ): Self::SystemData,
     ) {
-        for (_entity, wants_to_melee, name, stats) in
+        for (entity, wants_to_melee, name, stats) in
             (&entities, &wants_to_melee, &names, &combat_stats).join()
         {
             if stats.hp > 0 {
+                let offensive_bonus: i32 = (&melee_power_bonuses, &equipped)
+                    .join()
+                    .filter(|(_, worn)| worn.owner == entity)
+                    .map(|(bonus, _)| bonus.power)
+                    .sum::<i32>()
+                    + skills
+                        .get(entity)
+                        .map_or(0, |skills| skills.rank(Skill::Melee))
+                    + status_effects
+                        .get(entity)
+                        .map_or(0, StatusEffects::power_bonus);
+
                 let target_stats = combat_stats.get(wants_to_melee.target).unwrap();
                 if target_stats.hp > 0 {
                     let target_name = names.get(wants_to_melee.target).unwrap();
 
-                    let damage = i32::max(0, stats.power - target_stats.defense);
+                    // Only tell the player about fights they're in or can see
+                    let seen = [entity, wants_to_melee.target].iter().any(|fighter| {
+                        *fighter == **player_entity
+                            || positions
+                                .get(*fighter)
+                                .is_some_and(|pos| map.visible_tiles[map.xy_idx(pos.x, pos.y)])
+                    });
+
+                    let natural_roll = rng.roll_dice(1, 20);
+                    if natural_roll <= FUMBLE_ROLL {
+                        if seen {
+                            log.log(
+                                tr("melee-fumble")
+                                    .arg("attacker", l10n::name(&name.name))
+                                    .arg("target", l10n::name(&target_name.name)),
+                            );
+                        }
+                        continue;
+                    }
+                    let crit_range: i32 = (&crit_range_bonuses, &equipped)
+                        .join()
+                        .filter(|(_, worn)| worn.owner == entity)
+                        .map(|(bonus, _)| bonus.range)
+                        .sum();
+                    let critical = natural_roll >= CRITICAL_ROLL - crit_range;
+
+                    let block_chance: i32 = (&block_chances, &equipped)
+                        .join()
+                        .filter(|(_, worn)| worn.owner == wants_to_melee.target)
+                        .map(|(block, _)| block.percent)
+                        .sum();
+                    if block_chance > 0 && rng.roll_dice(1, 100) <= block_chance {
+                        let blocked = if wants_to_melee.target == **player_entity {
+                            tr("melee-player-block").arg("attacker", l10n::name(&name.name))
+                        } else {
+                            tr("melee-block")
+                                .arg("attacker", l10n::name(&name.name))
+                                .arg("target", l10n::name(&target_name.name))
+                        };
+                        if seen {
+                            log.entry()
+                                .colored_message(&blocked, RGB::named(rltk::LIGHT_BLUE))
+                                .log();
+                        }
+                        effects.add_effect(
+                            EffectType::Particle {
+                                glyph: rltk::to_cp437('◘'),
+                                fg: RGB::named(rltk::LIGHT_BLUE),
+                                lifetime_ms: PARTICLE_LIFETIME_MS,
+                            },
+                            Targets::Single {
+                                target: wants_to_melee.target,
+                            },
+                        );
+                        continue;
+                    }
+
+                    let defensive_bonus: i32 = (&defense_bonuses, &equipped)
+                        .join()
+                        .filter(|(_, worn)| worn.owner == wants_to_melee.target)
+                        .map(|(bonus, _)| bonus.defense)
+                        .sum::<i32>()
+                        + skills
+                            .get(wants_to_melee.target)
+                            .map_or(0, |skills| skills.rank(Skill::Defense))
+                        + status_effects
+                            .get(wants_to_melee.target)
+                            .map_or(0, StatusEffects::defense_bonus);
 
-                    if damage == 0 {
-                        log.log(format!("{name} is unable to hurt {target_name}"));
-                    } else {
-                        log.log(format!("{name} hits {target_name}, for {damage} hp."));
-                        SufferDamage::new_damage(
-                            &mut inflict_damage,
-                            wants_to_melee.target,
-                            damage,
+                    // Fight with whatever's in hand, or with fists and claws if nothing is
+                    let attack = (&melee_attacks, &equipped)
+                        .join()
+                        .find(|(_, worn)| worn.owner == entity && worn.slot == EquipmentSlot::Melee)
+                        .map(|(attack, _)| attack)
+                        .or_else(|| melee_attacks.get(entity));
+                    let roll = attack.map_or(0, |attack| attack.damage.roll(&mut rng));
+
+                    let mut damage = i32::max(
+                        0,
+                        (roll + stats.power + offensive_bonus)
+                            - (target_stats.defense + defensive_bonus),
+                    );
+                    if critical {
+                        damage *= 2;
+                    }
+
+                    if damage == 0 && seen {
+                        log.log(
+                            tr("melee-no-damage")
+                                .arg("attacker", l10n::name(&name.name))
+                                .arg("target", l10n::name(&target_name.name)),
+                        );
+                    } else if damage > 0 {
+                        let hit = if critical {
+                            tr("melee-critical-hit")
+                        } else {
+                            tr("melee-hit")
+                        }
+                        .colored_arg("attacker", l10n::name(&name.name), RGB::named(rltk::YELLOW))
+                        .colored_arg(
+                            "target",
+                            l10n::name(&target_name.name),
+                            RGB::named(rltk::YELLOW),
+                        )
+                        .colored_arg(
+                            "damage",
+                            tr("hit-points").arg("amount", damage),
+                            RGB::named(rltk::RED),
+                        );
+                        if critical {
+                            if seen {
+                                log.entry()
+                                    .colored_message(&hit, RGB::named(rltk::ORANGE))
+                                    .log();
+                            }
+                            effects.add_effect(
+                                EffectType::Particle {
+                                    glyph: rltk::to_cp437('☼'),
+                                    fg: RGB::named(rltk::GOLD),
+                                    lifetime_ms: PARTICLE_LIFETIME_MS,
+                                },
+                                Targets::Single {
+                                    target: wants_to_melee.target,
+                                },
+                            );
+                        } else if seen {
+                            log.entry().message(&hit).log();
+                        }
+                        effects.add_effect(
+                            EffectType::Damage {
+                                amount: damage,
+                                attacker: Some(entity),
+                            },
+                            Targets::Single {
+                                target: wants_to_melee.target,
+                            },
                         );
+                        events.push(GameEvent::MeleeHit {
+                            attacker: entity,
+                            target: wants_to_melee.target,
+                            amount: damage,
+                        });
                     }
                 }
             }
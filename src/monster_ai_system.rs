@@ -1,40 +1,101 @@
-use rltk::Point;
+use rltk::{DijkstraMap, Point, RandomNumberGenerator};
 use specs::prelude::*;
 
+use crate::effects::{EffectQueue, EffectType, Targets};
+use crate::factions::{self, faction_of, Reaction};
+use crate::l10n::{self, tr};
 use crate::{
-    Confusion, Map, Monster, PlayerEntity, PlayerPos, Position, RunState, Viewshed, WantsToMelee,
+    morale, resolve_trajectory, AiState, CachedPath, CombatStats, EntityMoved, Faction, GameEvent,
+    GameEvents, GameLog, HeardNoise, InBackpack, LastSeenPlayer, Map, Monster, Morale, Name,
+    PlayerEntity, Position, RangedAttack, Renderable, Reputation, RunState, SpecialAbilities,
+    StatusEffect, StatusEffects, Terrain, Viewshed, WantsToMelee,
 };
 
+/// How many spots a monster checks after losing sight of the player before it
+/// forgets about them.
+const SEARCH_TURNS: i32 = 5;
+
+/// How far from where it last looked a hunting monster checks next.
+const SEARCH_RADIUS: i32 = 3;
+
+/// How far a monster's destination can get from the goal of its [`CachedPath`]
+/// before the monster works out a new path.
+const REPATH_DISTANCE: f32 = 2.0;
+
+/// How far from the player the map of distances to them is worked out, for
+/// monsters chasing them or running away.
+const PLAYER_MAP_DEPTH: f32 = 100.0;
+
 /// A system that handles a [`Monster`]'s AI.
+///
+/// Fighting monsters go after the nearest thing they can see that their
+/// [`Faction`] is hostile to. That's usually the player, but rival factions will
+/// happily fight each other too.
 pub struct MonsterAI;
 
 impl<'a> System<'a> for MonsterAI {
     type SystemData = (
         WriteExpect<'a, Map>,
-        ReadExpect<'a, PlayerPos>,
         ReadExpect<'a, PlayerEntity>,
         ReadExpect<'a, RunState>,
         Entities<'a>,
         WriteStorage<'a, Viewshed>,
-        ReadStorage<'a, Monster>,
+        WriteStorage<'a, Monster>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, WantsToMelee>,
-        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, StatusEffects>,
+        ReadStorage<'a, RangedAttack>,
+        WriteExpect<'a, EffectQueue>,
+        ReadStorage<'a, Name>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, GameEvents>,
+        // Everything that tells a monster where to go, grouped to stay within the
+        // most storages a system can ask for
+        (
+            WriteStorage<'a, HeardNoise>,
+            WriteStorage<'a, LastSeenPlayer>,
+            WriteStorage<'a, CachedPath>,
+        ),
+        WriteStorage<'a, EntityMoved>,
+        WriteStorage<'a, AiState>,
+        ReadStorage<'a, Morale>,
+        WriteStorage<'a, Renderable>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, CombatStats>,
+        ReadExpect<'a, Reputation>,
+        ReadStorage<'a, SpecialAbilities>,
     );
 
     fn run(
         &mut self,
         (
             mut map,
-            player_pos,
             player_entity,
             runstate,
             entities,
             mut viewshed,
-            monster,
+            mut monster,
             mut position,
             mut wants_to_melee,
-            mut confused,
+            status_effects,
+            ranged_attacks,
+            mut effects,
+            names,
+            mut log,
+            mut events,
+            (mut heard_noises, mut last_seen, mut cached_paths),
+            mut entity_moved,
+            mut ai_states,
+            morale,
+            mut renderables,
+            mut rng,
+            backpack,
+            factions,
+            combat_stats,
+            reputation,
+            special_abilities,
         ): Self::SystemData,
     ) {
         // Only run when it's the monsters' turn!
@@ -42,57 +103,466 @@ impl<'a> System<'a> for MonsterAI {
             return;
         }
 
-        for (entity, mut viewshed, _monster, mut pos) in
+        let mut surrendered = Vec::new();
+        let mut escaped = Vec::new();
+
+        // Everything that can be fought, where it is, and whose side it's on
+        let mut combatants: Vec<(Entity, Point, Faction)> = (&entities, &position, &combat_stats)
+            .join()
+            .filter(|(entity, _, stats)| {
+                stats.hp > 0 && ai_states.get(*entity) != Some(&AiState::Surrendered)
+            })
+            .map(|(entity, pos, _)| {
+                let faction = faction_of(entity, **player_entity, &factions);
+                (entity, Point::new(pos.x, pos.y), faction)
+            })
+            .collect();
+
+        // Monsters chasing the player head down a shared map of how far every tile
+        // is from them, and fleeing monsters climb up it. That's much cheaper than
+        // every monster finding its own path. It's only worked out if it's needed.
+        let player_point = combatants
+            .iter()
+            .find(|(entity, ..)| *entity == **player_entity)
+            .map_or(Point::zero(), |(_, pos, _)| *pos);
+        let mut player_map: Option<DijkstraMap> = None;
+
+        for (entity, viewshed, _monster, pos) in
             (&entities, &mut viewshed, &monster, &mut position).join()
         {
             // Check if the monster can actually act right now (is it confused, for example?)
-            let mut can_act = true;
-
-            if let Some(i_am_confused) = confused.get_mut(entity) {
-                i_am_confused.turns -= 1;
-                if i_am_confused.turns < 1 {
-                    confused.remove(entity);
-                }
-                can_act = false;
+            let can_act = status_effects
+                .get(entity)
+                .and_then(StatusEffects::skips_turn)
+                .is_none();
+            // Monsters that used a special ability this turn have already acted
+            let used_ability = special_abilities
+                .get(entity)
+                .is_some_and(|abilities| abilities.used_this_turn);
+            if !can_act || used_ability {
+                continue;
             }
 
-            if can_act {
-                // If the monster is close enough, it attacks (and doesn't move).
-                let distance = rltk::DistanceAlg::Pythagoras
-                    .distance2d(Point::new(pos.x, pos.y), **player_pos);
-                if distance < 1.5 {
-                    wants_to_melee
-                        .insert(
-                            entity,
-                            WantsToMelee {
-                                target: **player_entity,
+            // Monsters that have lost their nerve stop fighting, and either run for the
+            // edge of the map or give up. Fear wears off, but lost morale doesn't.
+            let frightened = status_effects
+                .get(entity)
+                .is_some_and(|effects| effects.has(StatusEffect::Frightened));
+            let broken = frightened || morale.get(entity).is_some_and(Morale::is_broken);
+            // Badly-hurt monsters run from the player, but will still fight if cornered
+            let wounded = combat_stats.get(entity).is_some_and(|stats| {
+                let flee_below = morale.get(entity).map_or(0.0, |morale| morale.flee_below);
+                (stats.hp as f32) < stats.max_hp as f32 * flee_below
+            });
+            let old_state = ai_states.get(entity).copied().unwrap_or_default();
+            let state = match old_state {
+                AiState::Fighting | AiState::Lurking | AiState::Fleeing if broken => {
+                    if !frightened && rng.roll_dice(1, 3) == 1 {
+                        AiState::Surrendered
+                    } else {
+                        morale::rout_destination(&map, Point::new(pos.x, pos.y)).map_or(
+                            AiState::Fighting,
+                            |dest| AiState::Routing {
+                                x: dest.x,
+                                y: dest.y,
                             },
                         )
-                        .expect(
-                            "Monster is unable to insert next attack against player into storage",
-                        );
-                } else if viewshed.visible_tiles.contains(&*player_pos) {
-                    // If the monster can see the player, it starts moving towards the
-                    // player.
-                    let path = rltk::a_star_search(
-                        map.xy_idx(pos.x, pos.y),
-                        map.xy_idx(player_pos.x, player_pos.y),
-                        &*map,
-                    );
-
-                    if path.success && path.steps.len() > 1 {
-                        let mut idx = map.xy_idx(pos.x, pos.y);
+                    }
+                }
+                AiState::Fighting | AiState::Lurking if wounded => AiState::Fleeing,
+                AiState::Routing { .. } if !broken => AiState::Fighting,
+                AiState::Fleeing if !wounded => AiState::Fighting,
+                state => state,
+            };
+
+            if state != old_state {
+                ai_states
+                    .insert(entity, state)
+                    .expect("Unable to update monster's AI state");
+
+                let idx = map.xy_idx(pos.x, pos.y);
+                if let Some(name) = names.get(entity).filter(|_| map.visible_tiles[idx]) {
+                    let msg = match state {
+                        AiState::Fighting => Some(tr("monster-regains-nerve")),
+                        AiState::Routing { .. } => Some(tr("monster-routs")),
+                        AiState::Fleeing => Some(tr("monster-flees")),
+                        AiState::Surrendered => Some(tr("monster-surrenders")),
+                        AiState::Lurking => None,
+                    };
+                    if let Some(msg) = msg {
+                        log.log(msg.arg("monster", l10n::name(&name.name)));
+                    }
+                }
+            }
+
+            match state {
+                AiState::Surrendered => {
+                    surrendered.push(entity);
+                }
+
+                AiState::Routing { x, y } => {
+                    if pos.x == x && pos.y == y {
+                        let idx = map.xy_idx(pos.x, pos.y);
+                        if let Some(name) = names.get(entity).filter(|_| map.visible_tiles[idx]) {
+                            log.log(tr("monster-escapes").arg("monster", l10n::name(&name.name)));
+                        }
                         map.blocked.set(idx, false);
+                        escaped.push(entity);
+                    } else if step_along_path(
+                        &mut map,
+                        pos,
+                        Point::new(x, y),
+                        &mut cached_paths,
+                        entity,
+                        |map, pos| path_to(map, pos, Point::new(x, y)),
+                    ) {
+                        viewshed.dirty = true;
+                        entity_moved
+                            .insert(entity, EntityMoved)
+                            .expect("Unable to insert marker for monster movement");
+                    }
+                }
 
-                        pos.x = path.steps[1] as i32 % map.width;
-                        pos.y = path.steps[1] as i32 / map.width;
-                        idx = map.xy_idx(pos.x, pos.y);
-                        map.blocked.set(idx, true);
+                AiState::Fleeing => {
+                    // Head for whichever neighbouring tile is furthest from the player
+                    let idx = map.xy_idx(pos.x, pos.y);
+                    let player_map =
+                        player_map.get_or_insert_with(|| distance_map(&map, player_point));
+                    let escape = DijkstraMap::find_highest_exit(player_map, idx, &*map)
+                        .filter(|exit| player_map.map[*exit] > player_map.map[idx]);
 
+                    if let Some(exit) = escape {
+                        move_to(&mut map, pos, exit);
                         viewshed.dirty = true;
+                        entity_moved
+                            .insert(entity, EntityMoved)
+                            .expect("Unable to insert marker for monster movement");
+                    } else if rltk::DistanceAlg::Pythagoras
+                        .distance2d(Point::new(pos.x, pos.y), player_point)
+                        < 1.5
+                    {
+                        // Cornered!
+                        wants_to_melee
+                            .insert(
+                                entity,
+                                WantsToMelee {
+                                    target: **player_entity,
+                                },
+                            )
+                            .expect("Monster is unable to insert next attack into storage");
+                    }
+                }
+
+                AiState::Fighting | AiState::Lurking => {
+                    // Pick on the nearest thing in sight that it's hostile to
+                    let here = Point::new(pos.x, pos.y);
+                    let faction = faction_of(entity, **player_entity, &factions);
+                    let target = combatants
+                        .iter()
+                        .filter(|(other, other_pos, other_faction)| {
+                            *other != entity
+                                && viewshed.visible_tiles.contains(other_pos)
+                                && factions::reaction(faction, *other_faction, &reputation)
+                                    == Reaction::Attack
+                        })
+                        .map(|(other, other_pos, _)| {
+                            let distance =
+                                rltk::DistanceAlg::Pythagoras.distance2d(here, *other_pos);
+                            (*other, *other_pos, distance)
+                        })
+                        .min_by(|(.., a), (.., b)| a.total_cmp(b));
+
+                    // Lurking pack members keep their distance from the player until the
+                    // pack's ready, and only fight back if the player gets too close
+                    let lurking = state == AiState::Lurking
+                        && target.is_some_and(|(target, ..)| target == **player_entity);
+
+                    // If the monster is close enough, it attacks (and doesn't move).
+                    if let Some((target, ..)) = target.filter(|(.., distance)| *distance < 1.5) {
+                        wants_to_melee
+                            .insert(entity, WantsToMelee { target })
+                            .expect("Monster is unable to insert next attack into storage");
+                    } else if let Some((target, ranged_attack)) = target
+                        .filter(|_| !lurking)
+                        .and_then(|(target, target_pos, _)| {
+                            ranged_attacks
+                                .get(entity)
+                                .map(|ranged_attack| (target, target_pos, ranged_attack))
+                        })
+                        .filter(|(_, target_pos, ranged_attack)| {
+                            // Only shoot if nothing else is in the way
+                            resolve_trajectory(&map, here, *target_pos, ranged_attack.range).impact
+                                == *target_pos
+                        })
+                        .map(|(target, _, ranged_attack)| (target, ranged_attack))
+                    {
+                        effects.add_effect(
+                            EffectType::Damage {
+                                amount: ranged_attack.damage,
+                                attacker: Some(entity),
+                            },
+                            Targets::Single { target },
+                        );
+                        let idx = map.xy_idx(pos.x, pos.y);
+                        let msg = if target == **player_entity {
+                            Some(tr("ranged-hit-player"))
+                        } else {
+                            names.get(target).filter(|_| map.visible_tiles[idx]).map(
+                                |target_name| {
+                                    tr("ranged-hit").arg("target", l10n::name(&target_name.name))
+                                },
+                            )
+                        };
+                        if let (Some(name), Some(msg)) = (names.get(entity), msg) {
+                            let msg = msg
+                                .colored_arg(
+                                    "attacker",
+                                    l10n::name(&name.name),
+                                    rltk::RGB::named(rltk::YELLOW),
+                                )
+                                .colored_arg(
+                                    "damage",
+                                    tr("hit-points").arg("amount", ranged_attack.damage),
+                                    rltk::RGB::named(rltk::RED),
+                                );
+                            log.entry().message(&msg).log();
+                        }
+                        events.push(GameEvent::RangedHit {
+                            attacker: entity,
+                            target,
+                            amount: ranged_attack.damage,
+                        });
+                    } else {
+                        let destination = if let Some((target, target_pos, _)) = target {
+                            // If the monster can see something to fight, it forgets about any
+                            // noises and starts moving towards it. If that's the player, it
+                            // remembers where it saw them.
+                            heard_noises.remove(entity);
+                            if target == **player_entity {
+                                last_seen
+                                    .insert(
+                                        entity,
+                                        LastSeenPlayer {
+                                            x: target_pos.x,
+                                            y: target_pos.y,
+                                            search_turns: SEARCH_TURNS,
+                                        },
+                                    )
+                                    .expect("Unable to remember where the monster saw the player");
+                            }
+                            (!lurking).then_some(target_pos)
+                        } else if let Some(noise) = heard_noises.get(entity).copied() {
+                            // Otherwise, it goes to check out anything it's heard, until it gets there.
+                            let noise_pos = Point::new(noise.x, noise.y);
+                            if rltk::DistanceAlg::Pythagoras
+                                .distance2d(Point::new(pos.x, pos.y), noise_pos)
+                                < 1.5
+                            {
+                                heard_noises.remove(entity);
+                            }
+                            Some(noise_pos)
+                        } else if let Some(memory) = last_seen.get_mut(entity) {
+                            // Failing that, it hunts for the player where it last saw them,
+                            // then searches nearby spots until it gives up.
+                            let memory_pos = Point::new(memory.x, memory.y);
+                            if rltk::DistanceAlg::Pythagoras
+                                .distance2d(Point::new(pos.x, pos.y), memory_pos)
+                                < 1.5
+                            {
+                                memory.search_turns -= 1;
+                                let next_spot = search_spot(&map, &mut rng, memory_pos);
+                                match next_spot.filter(|_| memory.search_turns > 0) {
+                                    Some(spot) => {
+                                        memory.x = spot.x;
+                                        memory.y = spot.y;
+                                        Some(spot)
+                                    }
+                                    None => {
+                                        last_seen.remove(entity);
+                                        None
+                                    }
+                                }
+                            } else {
+                                Some(memory_pos)
+                            }
+                        } else {
+                            None
+                        };
+
+                        // Monsters follow the paths they've already worked out for as long
+                        // as they can. New paths to the player are walked down the shared
+                        // map of distances to them.
+                        let moved = destination.is_some_and(|destination| {
+                            step_along_path(
+                                &mut map,
+                                pos,
+                                destination,
+                                &mut cached_paths,
+                                entity,
+                                |map, pos| {
+                                    if destination == player_point {
+                                        let player_map = player_map
+                                            .get_or_insert_with(|| distance_map(map, player_point));
+                                        path_down(map, pos, player_map)
+                                    } else {
+                                        path_to(map, pos, destination)
+                                    }
+                                },
+                            )
+                        });
+                        if moved {
+                            viewshed.dirty = true;
+                            entity_moved
+                                .insert(entity, EntityMoved)
+                                .expect("Unable to insert marker for monster movement");
+                        } else {
+                            // Give up on noises and memories that can't be reached
+                            if heard_noises.remove(entity).is_none() {
+                                last_seen.remove(entity);
+                            }
+                        }
                     }
                 }
             }
+
+            // Keep track of where it ended up, for everything else picking a target
+            if let Some(combatant) = combatants.iter_mut().find(|(other, ..)| *other == entity) {
+                combatant.1 = Point::new(pos.x, pos.y);
+            }
+        }
+
+        // Surrendered monsters aren't monsters anymore, as far as everyone else is concerned
+        for entity in surrendered {
+            monster.remove(entity);
+            heard_noises.remove(entity);
+            last_seen.remove(entity);
+            cached_paths.remove(entity);
+            if let Some(render) = renderables.get_mut(entity) {
+                render.fg = rltk::RGB::named(rltk::GREY);
+            }
+        }
+
+        // Anything escaping monsters were carrying goes with them
+        for (item, in_pack) in (&entities, &backpack).join() {
+            if escaped.contains(&in_pack.owner) {
+                entities
+                    .delete(item)
+                    .expect("Unable to delete item carried off by escaping monster");
+            }
         }
+        for entity in escaped {
+            entities
+                .delete(entity)
+                .expect("Unable to delete monster that escaped the level");
+        }
+    }
+}
+
+/// Pick somewhere within [`SEARCH_RADIUS`] of `around` for a monster hunting the
+/// player to check next.
+fn search_spot(map: &Map, rng: &mut RandomNumberGenerator, around: Point) -> Option<Point> {
+    let spots: Vec<Point> = (around.y - SEARCH_RADIUS..=around.y + SEARCH_RADIUS)
+        .flat_map(|y| (around.x - SEARCH_RADIUS..=around.x + SEARCH_RADIUS).map(move |x| (x, y)))
+        .filter(|&(x, y)| x > 0 && x < map.width - 1 && y > 0 && y < map.height - 1)
+        .filter(|&(x, y)| !map.blocked[map.xy_idx(x, y)])
+        .map(Point::from)
+        .filter(|spot| *spot != around)
+        .collect();
+
+    (!spots.is_empty()).then(|| spots[rng.range(0, spots.len())])
+}
+
+/// Take one step towards `destination` along `entity`'s [`CachedPath`], keeping
+/// the map's blocked tiles up to date. If the monster doesn't have a path that
+/// still leads near there, or the way ahead is blocked, it works out a new one
+/// with `find_path` first. Returns `false` if there's no way to get closer.
+fn step_along_path(
+    map: &mut Map,
+    pos: &mut Position,
+    destination: Point,
+    cached_paths: &mut WriteStorage<CachedPath>,
+    entity: Entity,
+    find_path: impl FnOnce(&Map, &Position) -> Vec<usize>,
+) -> bool {
+    let stale = cached_paths.get(entity).is_none_or(|path| {
+        rltk::DistanceAlg::Pythagoras.distance2d(path.goal, destination) > REPATH_DISTANCE
+            || path.steps.last().is_none_or(|next| map.blocked[*next])
+    });
+    if stale {
+        let steps = find_path(map, pos);
+        cached_paths
+            .insert(
+                entity,
+                CachedPath {
+                    goal: destination,
+                    steps,
+                },
+            )
+            .expect("Unable to remember monster's path");
     }
+
+    let Some(next) = cached_paths
+        .get_mut(entity)
+        .and_then(|path| path.steps.pop())
+        .filter(|next| !map.blocked[*next])
+    else {
+        return false;
+    };
+
+    move_to(map, pos, next);
+    true
+}
+
+/// The shortest path from `pos` to `destination`, as [`CachedPath::steps`].
+fn path_to(map: &Map, pos: &Position, destination: Point) -> Vec<usize> {
+    let path = rltk::a_star_search(
+        map.xy_idx(pos.x, pos.y),
+        map.xy_idx(destination.x, destination.y),
+        map,
+    );
+    if !path.success {
+        return Vec::new();
+    }
+
+    path.steps.into_iter().skip(1).rev().collect()
+}
+
+/// A map of how far every tile within [`PLAYER_MAP_DEPTH`] is from the player at
+/// `player_point`. Other monsters don't count as in the way, so that a crowd in a
+/// corridor doesn't send everyone behind it the long way round.
+fn distance_map(map: &Map, player_point: Point) -> DijkstraMap {
+    let player_idx = map.xy_idx(player_point.x, player_point.y);
+    DijkstraMap::new(
+        map.width,
+        map.height,
+        &[player_idx],
+        &Terrain(map),
+        PLAYER_MAP_DEPTH,
+    )
+}
+
+/// The path from `pos` straight down `player_map` to the player, as
+/// [`CachedPath::steps`]. It goes straight through anyone in the way;
+/// [`step_along_path()`] waits for them to move.
+fn path_down(map: &Map, pos: &Position, player_map: &DijkstraMap) -> Vec<usize> {
+    let mut steps = Vec::new();
+    let mut idx = map.xy_idx(pos.x, pos.y);
+    while let Some(exit) = DijkstraMap::find_lowest_exit(player_map, idx, &Terrain(map))
+        .filter(|exit| player_map.map[*exit] < player_map.map[idx])
+    {
+        steps.push(exit);
+        idx = exit;
+    }
+
+    steps.reverse();
+    steps
+}
+
+/// Move from `pos` to the tile at `idx`, keeping the map's blocked tiles up to date.
+fn move_to(map: &mut Map, pos: &mut Position, idx: usize) {
+    let old_idx = map.xy_idx(pos.x, pos.y);
+    map.blocked.set(old_idx, false);
+
+    (pos.x, pos.y) = map.idx_xy(idx);
+    map.blocked.set(idx, true);
 }
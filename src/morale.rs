@@ -0,0 +1,57 @@
+//! Monster morale. Monsters that see their allies cut down lose their nerve, and
+//! once their [`Morale`] breaks they either run for it or give up entirely. The
+//! actual routing and surrendering is handled by [`crate::MonsterAI`].
+
+use rltk::Point;
+use specs::prelude::*;
+
+use crate::game_events::GameEventListener;
+use crate::{AiState, GameEvent, Map, Morale, TileType, Viewshed};
+
+/// Morale lost by each monster that sees another monster die.
+const PACK_DEATH_MORALE: i32 = 3;
+
+/// Morale lost by each monster that sees a [`crate::Leader`] die.
+const LEADER_DEATH_MORALE: i32 = 6;
+
+/// Wears down the [`Morale`] of every monster that saw one of its allies die.
+pub struct MoraleListener;
+
+impl GameEventListener for MoraleListener {
+    fn on_event(&mut self, ecs: &World, event: &GameEvent) {
+        let GameEvent::MonsterKilled { pos, leader, .. } = event else {
+            return;
+        };
+
+        let loss = if *leader {
+            LEADER_DEATH_MORALE
+        } else {
+            PACK_DEATH_MORALE
+        };
+
+        let mut morale = ecs.write_storage::<Morale>();
+        let viewsheds = ecs.read_storage::<Viewshed>();
+        let ai_states = ecs.read_storage::<AiState>();
+        for (morale, viewshed, state) in (&mut morale, &viewsheds, ai_states.maybe()).join() {
+            if state != Some(&AiState::Surrendered) && viewshed.visible_tiles.contains(pos) {
+                morale.value -= loss;
+            }
+        }
+    }
+}
+
+/// Pick somewhere for a monster at `from` to run to: the closest open tile to it
+/// out of those nearest the edge of the map.
+pub fn rout_destination(map: &Map, from: Point) -> Option<Point> {
+    (0..map.tiles.len())
+        .filter(|idx| map.tiles[*idx] != TileType::Wall)
+        .map(|idx| Point::from_tuple(map.idx_xy(idx)))
+        .min_by_key(|p| {
+            let to_edge =
+                p.x.min(p.y)
+                    .min(map.width - 1 - p.x)
+                    .min(map.height - 1 - p.y);
+            let to_monster = rltk::DistanceAlg::Manhattan.distance2d(*p, from) as i32;
+            (to_edge, to_monster)
+        })
+}
@@ -0,0 +1,131 @@
+//! Adaptive music. Every turn, the [`MusicDirector`] scores how intense things are
+//! from what the player can see and how hurt they are, and every frame it crossfades
+//! the soundtrack's [`MusicLayer`]s towards that.
+//!
+//! Actually playing sound is left up to a [`MusicPlayer`]. There's no audio backend
+//! yet, so the game runs with a [`SilentPlayer`] for now.
+
+use specs::prelude::*;
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::{AiState, CombatStats, Monster, PlayerEntity, Position, Viewshed};
+
+/// How much each visible hostile adds to the intensity score.
+const INTENSITY_PER_HOSTILE: f32 = 0.25;
+
+/// Below this fraction of their max hp, the player starts to hear their heartbeat.
+const HEARTBEAT_HEALTH: f32 = 0.5;
+
+/// How long a layer takes to fade all the way in or out, in milliseconds.
+const CROSSFADE_MS: f32 = 2000.0;
+
+/// One of the stems that the soundtrack is mixed from.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, strum::Display, strum::EnumCount, strum::EnumIter)]
+pub enum MusicLayer {
+    /// Calm music for wandering around.
+    Exploration,
+    /// Drums and brass for when there are hostiles about.
+    Combat,
+    /// A heartbeat that creeps in as the player's health runs low.
+    Heartbeat,
+}
+
+/// Plays the soundtrack's [`MusicLayer`]s at whatever volumes they're told to.
+pub trait MusicPlayer: Send + Sync {
+    /// Set how loud `layer` is playing, from `0.0` (silent) to `1.0` (full volume).
+    fn set_volume(&mut self, layer: MusicLayer, volume: f32);
+}
+
+/// A [`MusicPlayer`] that doesn't play anything.
+pub struct SilentPlayer;
+
+impl MusicPlayer for SilentPlayer {
+    fn set_volume(&mut self, _layer: MusicLayer, _volume: f32) {}
+}
+
+/// Decides how loud each [`MusicLayer`] should be, and fades them in and out.
+pub struct MusicDirector {
+    player: Box<dyn MusicPlayer>,
+    /// How tense things are, from `0.0` (nothing around) to `1.0` (surrounded).
+    intensity: f32,
+    /// How close the player is to death, from `0.0` (healthy enough) to `1.0`.
+    danger: f32,
+    volumes: [f32; MusicLayer::COUNT],
+}
+
+impl Default for MusicDirector {
+    fn default() -> Self {
+        Self::new(Box::new(SilentPlayer))
+    }
+}
+
+impl MusicDirector {
+    /// Direct the music played by `player`, starting off with calm exploration music.
+    pub fn new(player: Box<dyn MusicPlayer>) -> Self {
+        let mut volumes = [0.0; MusicLayer::COUNT];
+        volumes[MusicLayer::Exploration as usize] = 1.0;
+        Self {
+            player,
+            intensity: 0.0,
+            danger: 0.0,
+            volumes,
+        }
+    }
+
+    /// How loud `layer` should end up once it's done fading.
+    fn target_volume(&self, layer: MusicLayer) -> f32 {
+        match layer {
+            MusicLayer::Exploration => 1.0 - self.intensity,
+            MusicLayer::Combat => self.intensity,
+            MusicLayer::Heartbeat => self.danger,
+        }
+    }
+
+    /// Fade every layer `frame_time_ms` closer to its target volume, and pass the
+    /// new volumes on to the [`MusicPlayer`].
+    pub fn crossfade(&mut self, frame_time_ms: f32) {
+        let step = frame_time_ms / CROSSFADE_MS;
+        for layer in MusicLayer::iter() {
+            let target = self.target_volume(layer);
+            let volume = &mut self.volumes[layer as usize];
+            *volume = if *volume < target {
+                (*volume + step).min(target)
+            } else {
+                (*volume - step).max(target)
+            };
+            self.player.set_volume(layer, *volume);
+        }
+    }
+}
+
+/// Work out how intense things are for the player right now. Should be run once
+/// per turn.
+pub fn update_intensity(ecs: &World) {
+    let player = **ecs.fetch::<PlayerEntity>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let Some(player_viewshed) = viewsheds.get(player) else {
+        return;
+    };
+
+    let monsters = ecs.read_storage::<Monster>();
+    let ai_states = ecs.read_storage::<AiState>();
+    let positions = ecs.read_storage::<Position>();
+    let hostiles = (&monsters, &positions, ai_states.maybe())
+        .join()
+        .filter(|(_, _, state)| *state != Some(&AiState::Surrendered))
+        .filter(|(_, pos, _)| {
+            player_viewshed
+                .visible_tiles
+                .contains(&rltk::Point::new(pos.x, pos.y))
+        })
+        .count();
+
+    let health = combat_stats
+        .get(player)
+        .map_or(1.0, |stats| stats.hp as f32 / stats.max_hp.max(1) as f32);
+
+    let mut director = ecs.fetch_mut::<MusicDirector>();
+    director.intensity = (hostiles as f32 * INTENSITY_PER_HOSTILE).min(1.0);
+    director.danger = ((HEARTBEAT_HEALTH - health) / HEARTBEAT_HEALTH).clamp(0.0, 1.0);
+}
@@ -0,0 +1,44 @@
+use specs::prelude::*;
+
+use crate::{HeardNoise, Monster, Position, WantsToMakeNoise};
+
+/// Lets every [`Monster`] within earshot of a noise that something
+/// [`WantsToMakeNoise`] know about it, so that they can go and investigate.
+pub struct NoiseSystem;
+
+impl<'a> System<'a> for NoiseSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToMakeNoise>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, HeardNoise>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut wants_noise, monsters, positions, mut heard): Self::SystemData,
+    ) {
+        for noise in wants_noise.join() {
+            let origin = rltk::Point::new(noise.x, noise.y);
+
+            for (entity, _monster, pos) in (&entities, &monsters, &positions).join() {
+                let distance = rltk::DistanceAlg::Pythagoras
+                    .distance2d(origin, rltk::Point::new(pos.x, pos.y));
+                if distance <= noise.volume as f32 {
+                    heard
+                        .insert(
+                            entity,
+                            HeardNoise {
+                                x: noise.x,
+                                y: noise.y,
+                            },
+                        )
+                        .expect("Unable to insert noise heard by monster");
+                }
+            }
+        }
+
+        wants_noise.clear();
+    }
+}
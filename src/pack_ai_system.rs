@@ -0,0 +1,139 @@
+use rustc_hash::FxHashMap;
+use specs::prelude::*;
+
+use crate::l10n::tr;
+use crate::{
+    AiState, GameLog, GroupLeader, Map, Monster, Morale, PackMember, PlayerPos, Position, RunState,
+    Viewshed,
+};
+
+/// How many of a pack have to be able to see the player before it attacks. Packs
+/// smaller than this attack once all of them can see the player.
+const PACK_ATTACK_WATCHERS: usize = 3;
+
+/// A system that coordinates monsters hunting in packs (see [`GroupLeader`]).
+///
+/// A pack keeps its distance from the player, [`AiState::Lurking`], until enough
+/// of its members can see them, and then the whole pack attacks at once. Once
+/// the pack is broken, by losing its leader or half of its members, the rest
+/// lose their nerve.
+pub struct PackAI;
+
+impl<'a> System<'a> for PackAI {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, PlayerPos>,
+        ReadExpect<'a, RunState>,
+        Entities<'a>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, PackMember>,
+        WriteStorage<'a, GroupLeader>,
+        ReadStorage<'a, Viewshed>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, AiState>,
+        WriteStorage<'a, Morale>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            map,
+            player_pos,
+            runstate,
+            entities,
+            monsters,
+            pack_members,
+            mut leaders,
+            viewsheds,
+            positions,
+            mut ai_states,
+            mut morale,
+            mut log,
+        ): Self::SystemData,
+    ) {
+        // Only run when it's the monsters' turn!
+        if *runstate != RunState::MonsterTurn {
+            return;
+        }
+
+        // Gather up every pack, leaders first
+        let mut packs: FxHashMap<Entity, Vec<Entity>> = (&entities, &leaders)
+            .join()
+            .map(|(leader, _)| (leader, vec![leader]))
+            .collect();
+        let mut leaderless = Vec::new();
+        for (entity, member) in (&entities, &pack_members).join() {
+            match packs.get_mut(&member.leader) {
+                Some(pack) => pack.push(entity),
+                None => leaderless.push(entity),
+            }
+        }
+
+        let is_seen = |entity: Entity| {
+            positions
+                .get(entity)
+                .is_some_and(|pos| map.visible_tiles[map.xy_idx(pos.x, pos.y)])
+        };
+
+        for (leader, pack) in packs {
+            // Only members that are still up for a fight count
+            let fighters: Vec<Entity> = pack
+                .into_iter()
+                .filter(|member| {
+                    monsters.contains(*member)
+                        && matches!(
+                            ai_states.get(*member).copied().unwrap_or_default(),
+                            AiState::Fighting | AiState::Lurking
+                        )
+                })
+                .collect();
+            let Some(group) = leaders.get_mut(leader) else {
+                continue;
+            };
+
+            let broken =
+                !fighters.contains(&leader) || fighters.len() * 2 <= group.pack_size as usize;
+            if broken {
+                leaderless.extend(fighters);
+                continue;
+            }
+
+            if !group.attacking {
+                let watching = fighters
+                    .iter()
+                    .filter(|member| {
+                        viewsheds
+                            .get(**member)
+                            .is_some_and(|viewshed| viewshed.visible_tiles.contains(&*player_pos))
+                    })
+                    .count();
+                if watching >= PACK_ATTACK_WATCHERS.min(fighters.len()) {
+                    group.attacking = true;
+                    if fighters.iter().any(|member| is_seen(*member)) {
+                        log.log(tr("pack-attacks"));
+                    }
+                }
+            }
+
+            let state = if group.attacking {
+                AiState::Fighting
+            } else {
+                AiState::Lurking
+            };
+            for member in fighters {
+                ai_states
+                    .insert(member, state)
+                    .expect("Unable to update pack member's AI state");
+            }
+        }
+
+        // Without a pack to back them up, what's left of it loses its nerve
+        for member in leaderless {
+            let Some(morale) = morale.get_mut(member) else {
+                continue;
+            };
+            morale.value = morale.value.min(0);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! The colours the map and UI are drawn in. Rather than naming colours directly,
+//! [`crate::render`] and [`crate::gui`] ask the [`Palette`] resource for the colour
+//! of whatever they're drawing, so that players who have trouble telling some
+//! colours apart can switch to a palette that avoids them.
+
+use rltk::RGB;
+use serde::{Deserialize, Serialize};
+
+use crate::{Rarity, StatusEffect};
+
+/// Which of the built-in palettes is in use.
+#[derive(
+    PartialEq,
+    Eq,
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumIter,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PaletteKind {
+    /// The game's usual colours.
+    #[default]
+    Standard,
+    /// For red-green colour blindness, where greens look muddy. Swaps red against
+    /// green for orange against blue.
+    Deuteranopia,
+    /// For red-green colour blindness, where reds look dark. Like
+    /// [`Self::Deuteranopia`], but with brighter warnings.
+    Protanopia,
+    /// For blue-yellow colour blindness. Leans on reds and teals instead.
+    Tritanopia,
+    /// Brighter, purer colours on plain black, for low vision.
+    #[strum(to_string = "High contrast")]
+    HighContrast,
+}
+
+impl PaletteKind {
+    /// The next palette along, wrapping back around to the standard one.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Standard => Self::Deuteranopia,
+            Self::Deuteranopia => Self::Protanopia,
+            Self::Protanopia => Self::Tritanopia,
+            Self::Tritanopia => Self::HighContrast,
+            Self::HighContrast => Self::Standard,
+        }
+    }
+}
+
+/// The colour of everything on screen that isn't an entity's own.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Palette {
+    pub kind: PaletteKind,
+    /// Behind everything.
+    pub background: RGB,
+    /// Plain text, and the borders of boxes.
+    pub text: RGB,
+    /// Text that's less important, like the turn numbers in the log.
+    pub muted: RGB,
+    /// Behind tooltips.
+    pub panel: RGB,
+    /// Text in tooltips.
+    pub panel_text: RGB,
+    /// Titles, and anything else that should catch the eye.
+    pub title: RGB,
+    /// Whichever menu entry is picked, and the mouse cursor.
+    pub selected: RGB,
+    /// Things worth knowing about, like mana, spells, and notes.
+    pub info: RGB,
+    /// A fainter [`Self::info`], for highlighting tiles.
+    pub info_dim: RGB,
+    /// Things to think twice about, like dropping items or a blast's centre.
+    pub warning: RGB,
+    /// A fainter [`Self::warning`], for highlighting tiles.
+    pub warning_dim: RGB,
+    /// Health, and anything that can't be done.
+    pub danger: RGB,
+    /// Gold, and buying things.
+    pub gold: RGB,
+    /// The tiles a ranged item can reach.
+    pub range: RGB,
+    /// A boss's name and health bar.
+    pub boss: RGB,
+    /// The names of items that are [`Rarity::Uncommon`]...
+    pub uncommon: RGB,
+    /// ...[`Rarity::Rare`]...
+    pub rare: RGB,
+    /// ...and [`Rarity::Legendary`]. Common items are shown as plain text.
+    pub legendary: RGB,
+    pub confused: RGB,
+    pub poisoned: RGB,
+    pub slowed: RGB,
+    /// Monsters' health bars fade from this at full health...
+    pub health_full: RGB,
+    /// ...to this when they're about to die.
+    pub health_empty: RGB,
+    pub stairs: RGB,
+    pub shallow_water: RGB,
+    pub deep_water: RGB,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new(PaletteKind::default())
+    }
+}
+
+impl Palette {
+    /// The colours for `kind` of palette.
+    pub fn new(kind: PaletteKind) -> Self {
+        let rgb = RGB::from_f32;
+        let named = RGB::named;
+
+        // Colours that are told apart by everyone, from Wong's "Points of view:
+        // Color blindness" (2011)
+        let orange = rgb(0.9, 0.6, 0.0);
+        let sky_blue = rgb(0.35, 0.7, 0.9);
+        let bluish_green = rgb(0.0, 0.6, 0.5);
+        let yellow = rgb(0.95, 0.9, 0.25);
+        let blue = rgb(0.0, 0.45, 0.7);
+        let vermillion = rgb(0.8, 0.4, 0.0);
+
+        let standard = Self {
+            kind,
+            background: named(rltk::BLACK),
+            text: named(rltk::WHITE),
+            muted: named(rltk::GREY),
+            panel: named(rltk::DIM_GREY),
+            panel_text: named(rltk::LIGHT_GRAY),
+            title: named(rltk::YELLOW),
+            selected: named(rltk::MAGENTA),
+            info: named(rltk::CYAN),
+            info_dim: named(rltk::DARK_CYAN),
+            warning: named(rltk::ORANGE),
+            warning_dim: named(rltk::DARK_ORANGE),
+            danger: named(rltk::RED),
+            gold: named(rltk::GOLD),
+            range: named(rltk::BLUE),
+            boss: named(rltk::MAGENTA),
+            uncommon: named(rltk::LIME_GREEN),
+            rare: named(rltk::DODGER_BLUE),
+            legendary: named(rltk::ORANGE),
+            confused: named(rltk::PINK),
+            poisoned: named(rltk::GREEN),
+            slowed: named(rltk::LIGHT_BLUE),
+            health_full: rgb(0.0, 1.0, 0.0),
+            health_empty: rgb(1.0, 0.0, 0.0),
+            stairs: rgb(0.0, 1.0, 1.0),
+            shallow_water: rgb(0.3, 0.6, 1.0),
+            deep_water: rgb(0.1, 0.2, 0.9),
+        };
+        let red_green = Self {
+            title: yellow,
+            info: sky_blue,
+            info_dim: rgb(0.15, 0.35, 0.5),
+            warning: orange,
+            warning_dim: rgb(0.45, 0.3, 0.0),
+            danger: vermillion,
+            range: blue,
+            uncommon: bluish_green,
+            rare: sky_blue,
+            legendary: yellow,
+            poisoned: bluish_green,
+            slowed: sky_blue,
+            health_full: sky_blue,
+            health_empty: vermillion,
+            stairs: named(rltk::WHITE),
+            shallow_water: sky_blue,
+            deep_water: blue,
+            ..standard
+        };
+
+        match kind {
+            PaletteKind::Standard => standard,
+            PaletteKind::Deuteranopia => red_green,
+            PaletteKind::Protanopia => Self {
+                danger: rgb(1.0, 0.35, 0.0),
+                health_empty: rgb(1.0, 0.35, 0.0),
+                ..red_green
+            },
+            PaletteKind::Tritanopia => Self {
+                title: rgb(1.0, 0.5, 0.5),
+                selected: rgb(0.0, 0.75, 0.75),
+                info: rgb(0.55, 0.85, 1.0),
+                info_dim: rgb(0.2, 0.35, 0.45),
+                warning: rgb(1.0, 0.6, 0.8),
+                warning_dim: rgb(0.5, 0.3, 0.4),
+                danger: rgb(1.0, 0.15, 0.15),
+                gold: rgb(1.0, 0.8, 0.6),
+                boss: rgb(0.0, 0.75, 0.75),
+                uncommon: rgb(0.0, 0.7, 0.6),
+                rare: rgb(0.55, 0.85, 1.0),
+                legendary: rgb(1.0, 0.5, 0.5),
+                poisoned: rgb(0.0, 0.7, 0.6),
+                slowed: rgb(0.55, 0.85, 1.0),
+                health_full: rgb(0.0, 0.8, 0.8),
+                health_empty: rgb(1.0, 0.15, 0.15),
+                stairs: named(rltk::WHITE),
+                ..standard
+            },
+            PaletteKind::HighContrast => Self {
+                muted: named(rltk::LIGHT_GRAY),
+                panel: named(rltk::BLACK),
+                panel_text: named(rltk::WHITE),
+                title: rgb(1.0, 1.0, 0.0),
+                selected: rgb(1.0, 0.0, 1.0),
+                info: rgb(0.0, 1.0, 1.0),
+                info_dim: rgb(0.0, 0.5, 0.5),
+                warning: rgb(1.0, 0.6, 0.0),
+                warning_dim: rgb(0.6, 0.3, 0.0),
+                danger: rgb(1.0, 0.25, 0.25),
+                gold: rgb(1.0, 0.85, 0.0),
+                range: rgb(0.2, 0.2, 1.0),
+                boss: rgb(1.0, 0.0, 1.0),
+                uncommon: rgb(0.3, 1.0, 0.3),
+                rare: rgb(0.4, 0.7, 1.0),
+                legendary: rgb(1.0, 0.6, 0.0),
+                confused: rgb(1.0, 0.6, 1.0),
+                poisoned: rgb(0.3, 1.0, 0.3),
+                slowed: rgb(0.5, 0.8, 1.0),
+                stairs: named(rltk::WHITE),
+                shallow_water: rgb(0.4, 0.7, 1.0),
+                deep_water: rgb(0.2, 0.4, 1.0),
+                ..standard
+            },
+        }
+    }
+
+    /// The colour a [`StatusEffect`] is shown in.
+    pub fn status_effect(&self, effect: StatusEffect) -> RGB {
+        match effect {
+            StatusEffect::Confused => self.confused,
+            StatusEffect::Poisoned => self.poisoned,
+            StatusEffect::Burning => self.warning,
+            StatusEffect::Slowed | StatusEffect::Wading => self.slowed,
+            StatusEffect::Hasted => self.title,
+            StatusEffect::Frightened => self.text,
+            StatusEffect::Strengthened => self.danger,
+            StatusEffect::Fortified => self.info,
+        }
+    }
+
+    /// The colour the name of an item with `rarity` is shown in.
+    pub fn rarity(&self, rarity: Rarity) -> RGB {
+        match rarity {
+            Rarity::Common => self.text,
+            Rarity::Uncommon => self.uncommon,
+            Rarity::Rare => self.rare,
+            Rarity::Legendary => self.legendary,
+        }
+    }
+
+    /// The colour of a health bar that's `fraction` full.
+    pub fn health(&self, fraction: f32) -> RGB {
+        self.health_empty
+            .lerp(self.health_full, fraction.clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    /// How bright `color` looks, as defined by WCAG 2.
+    fn luminance(color: RGB) -> f32 {
+        let linear = |c: f32| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(color.r) + 0.7152 * linear(color.g) + 0.0722 * linear(color.b)
+    }
+
+    #[test]
+    fn text_is_readable_on_the_background() {
+        for kind in PaletteKind::iter() {
+            let palette = Palette::new(kind);
+            let background = luminance(palette.background);
+            let text_colors = [
+                ("text", palette.text),
+                ("muted", palette.muted),
+                ("title", palette.title),
+                ("selected", palette.selected),
+                ("info", palette.info),
+                ("warning", palette.warning),
+                ("danger", palette.danger),
+                ("gold", palette.gold),
+                ("boss", palette.boss),
+                ("uncommon", palette.uncommon),
+                ("rare", palette.rare),
+                ("legendary", palette.legendary),
+                ("confused", palette.confused),
+                ("poisoned", palette.poisoned),
+                ("slowed", palette.slowed),
+            ];
+            for (role, color) in text_colors {
+                let contrast = (luminance(color) + 0.05) / (background + 0.05);
+                assert!(
+                    contrast >= 4.5,
+                    "{kind} {role} only has a contrast ratio of {contrast:.2}",
+                );
+            }
+        }
+    }
+}
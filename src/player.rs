@@ -3,14 +3,30 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use rltk::{Rltk, VirtualKeyCode};
+use rltk::{BaseMap, Point, Rltk, VirtualKeyCode};
 use specs::prelude::*;
 
+use crate::camera::Camera;
+use crate::crime::Crime;
+use crate::l10n::{self, tr};
 use crate::{
-    CombatStats, GameLog, Item, Map, Monster, Player, Position, RunState, State, TileType,
-    Viewshed, WantsToMelee, WantsToPickupItem,
+    progression, resolve_trajectory, AiState, Bystander, CombatStats, Config, Container,
+    EntityMoved, GameEvent, GameEvents, GameLog, Guard, HungerClock, HungerState, InBackpack, Item,
+    LevelExit, LevelId, Mana, Map, Monster, Name, Perk, Perks, Player, Position, Purse, QuestGiver,
+    QuestJournal, Quips, Reputation, RunState, ServiceNpc, Stash, State, StatusEffect,
+    StatusEffects, Swimmer, TileType, Vendor, Viewshed, WantsToMakeNoise, WantsToMelee,
+    WantsToPickupItem,
 };
 
+/// How far the player can throw a rock, or anything else.
+pub const THROW_RANGE: i32 = 6;
+
+/// How far away monsters can hear a thrown rock landing.
+const ROCK_NOISE_VOLUME: i32 = 8;
+
+/// How far auto-explore looks for unexplored parts of the level, in steps.
+const EXPLORE_SEARCH_DEPTH: f32 = 400.0;
+
 /// The player's position. Just a newtype wrapper over a [`rltk::Point`].
 ///
 /// Allows for unambiguously storing the player position as a specs resource.
@@ -78,17 +94,53 @@ impl PartialEq<Entity> for PlayerEntity {
     }
 }
 
+/// A path of map tile indexes that the player is automatically walking along,
+/// one step per turn.
+#[derive(Clone, Debug, Default)]
+pub struct TravelPath {
+    pub steps: Vec<usize>,
+    /// Whether the player is auto-exploring, heading for whatever part of the level
+    /// is nearest and unexplored until something interrupts them.
+    pub exploring: bool,
+    /// Items that the player has already seen while exploring, so that only new
+    /// ones stop them.
+    pub spotted_items: Vec<Entity>,
+}
+
+/// Whether the player is resting, skipping turn after turn until they're fully
+/// healed or something disturbs them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Resting(pub bool);
+
 /// Try to move the player by a certain delta vector, if the ECS contains
 /// at least one entity that has both the [`Position`] and [`Player`] components.
 ///
-/// Will prevent the player from moving off-screen or through walls.
-pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
+/// Will prevent the player from moving off-screen or through walls. Bumping into a
+/// friendly NPC talks to them instead (unless the town has turned against the
+/// player), and bumping into the stash or a [`Container`] opens it. [`Bystander`]s
+/// offer their quest if they have one, and just chat if not. Attacking a peaceful
+/// [`Guard`] is a [`Crime::Assault`]. Stepping into water leaves the player
+/// [`StatusEffect::Wading`] for as long as the tile's movement cost says.
+pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) -> RunState {
     let mut positions = ecs.write_storage::<Position>();
     let mut players = ecs.write_storage::<Player>();
     let mut viewsheds = ecs.write_storage::<Viewshed>();
     let combat_stats = ecs.read_storage::<CombatStats>();
+    let service_npcs = ecs.read_storage::<ServiceNpc>();
+    let stashes = ecs.read_storage::<Stash>();
+    let containers = ecs.read_storage::<Container>();
+    let vendors = ecs.read_storage::<Vendor>();
+    let bystanders = ecs.read_storage::<Bystander>();
+    let quips = ecs.read_storage::<Quips>();
+    let quest_givers = ecs.read_storage::<QuestGiver>();
+    let ai_states = ecs.read_storage::<AiState>();
+    let guards = ecs.read_storage::<Guard>();
+    let monsters = ecs.read_storage::<Monster>();
+    let names = ecs.read_storage::<Name>();
+    let wanted = ecs.fetch::<Reputation>().is_wanted_in_town();
     let entities = ecs.entities();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+    let mut entity_moved = ecs.write_storage::<EntityMoved>();
     let map = ecs.fetch::<Map>();
 
     for (entity, _player, pos, viewshed) in
@@ -100,15 +152,91 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             || pos.y + delta_y < 1
             || pos.y + delta_y > map.height - 1
         {
-            return;
+            return RunState::PlayerTurn;
         }
 
         let destination_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
 
-        // Check if there's anything to attack in the tile we're trying to move into
+        // Check if there's anyone to talk to or attack in the tile we're trying to move into
         for potential_target in map.tile_content[destination_idx].iter() {
+            if stashes.contains(*potential_target) {
+                return RunState::ShowStash { withdrawing: false };
+            }
+
+            if containers.contains(*potential_target) {
+                return RunState::ShowContainer {
+                    container: *potential_target,
+                    storing: false,
+                };
+            }
+
+            if bystanders.contains(*potential_target) {
+                if wanted {
+                    if let Some(name) = names.get(*potential_target) {
+                        ecs.fetch_mut::<GameLog>()
+                            .log(tr("bystander-backs-away").arg("name", l10n::name(&name.name)));
+                    }
+                    return RunState::PlayerTurn;
+                }
+                let offers_quest = quest_givers
+                    .get(*potential_target)
+                    .is_some_and(|giver| !ecs.fetch::<QuestJournal>().has_accepted(&giver.quest));
+                if offers_quest {
+                    return RunState::OfferQuest {
+                        giver: *potential_target,
+                    };
+                }
+                let line = quips.get(*potential_target).map_or(0, |quips| {
+                    let mut rng = ecs.write_resource::<rltk::RandomNumberGenerator>();
+                    rng.range(0, quips.lines.len().max(1) as i32) as usize
+                });
+                return RunState::ShowDialogue {
+                    speaker: *potential_target,
+                    line,
+                };
+            }
+
+            let townsfolk =
+                vendors.contains(*potential_target) || service_npcs.contains(*potential_target);
+            if townsfolk && wanted {
+                if let Some(name) = names.get(*potential_target) {
+                    ecs.fetch_mut::<GameLog>()
+                        .log(tr("refuses-criminal").arg("name", l10n::name(&name.name)));
+                }
+                return RunState::PlayerTurn;
+            }
+
+            if vendors.contains(*potential_target) {
+                return RunState::ShowVendor {
+                    vendor: *potential_target,
+                    selling: false,
+                };
+            }
+
+            if service_npcs.contains(*potential_target) {
+                return RunState::TalkToNpc {
+                    npc: *potential_target,
+                };
+            }
+
+            if ai_states.get(*potential_target) == Some(&AiState::Surrendered) {
+                let (x, y) = map.idx_xy(destination_idx);
+                loot_surrendered(ecs, entity, *potential_target, Position { x, y });
+                return RunState::PlayerTurn;
+            }
+
             let target = combat_stats.get(*potential_target);
             if let Some(_target) = target {
+                if guards.contains(*potential_target) && !monsters.contains(*potential_target) {
+                    ecs.write_resource::<GameEvents>()
+                        .push(GameEvent::CrimeCommitted {
+                            criminal: entity,
+                            victim: *potential_target,
+                            crime: Crime::Assault,
+                            scene: rltk::Point::new(pos.x, pos.y),
+                        });
+                }
+
                 // Found a target! Attack it.
                 wants_to_melee
                     .insert(
@@ -118,27 +246,156 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
                         },
                     )
                     .expect("Player failed to add attack target");
-                return; // avoid moving post-attack
+                return RunState::PlayerTurn; // avoid moving post-attack
             }
         }
 
         // Move if not blocked
         if !map.blocked[destination_idx] {
+            if map.tiles[destination_idx] == TileType::DeepWater
+                && map.tiles[map.xy_idx(pos.x, pos.y)] != TileType::DeepWater
+                && !ecs.read_storage::<Swimmer>().contains(entity)
+            {
+                ecs.fetch_mut::<GameLog>().log(tr("deep-water-plunge"));
+            }
+
             pos.x = min(map.width - 1, max(0, pos.x + delta_x));
             pos.y = min(map.height - 1, max(0, pos.y + delta_y));
 
+            // Water takes more than one turn to get through
+            let cost = map.tiles[destination_idx].movement_cost() as i32;
+            if cost > 1 {
+                StatusEffects::inflict(
+                    &mut ecs.write_storage::<StatusEffects>(),
+                    entity,
+                    StatusEffect::Wading,
+                    cost,
+                );
+            }
+
             // need to update the viewshed if the player moved somewhere!
             viewshed.dirty = true;
+            entity_moved
+                .insert(entity, EntityMoved)
+                .expect("Unable to insert marker for player movement");
 
             // Update the player position resource
             let mut ppos = ecs.write_resource::<PlayerPos>();
             ppos.update(pos.x, pos.y);
         }
     }
+
+    RunState::PlayerTurn
+}
+
+/// Take everything a surrendered monster at `monster_pos` is carrying, instead of
+/// attacking it. Anything too heavy for the player to carry is left on the floor.
+fn loot_surrendered(ecs: &World, player: Entity, monster: Entity, monster_pos: Position) {
+    let items: Vec<Entity> = (&ecs.entities(), &ecs.read_storage::<InBackpack>())
+        .join()
+        .filter(|(_, in_pack)| in_pack.owner == monster)
+        .map(|(item, _)| item)
+        .collect();
+    for item in &items {
+        if crate::inventory_system::player_has_room_for(ecs, *item) {
+            ecs.write_storage::<InBackpack>()
+                .insert(*item, InBackpack { owner: player })
+                .expect("Unable to hand over surrendered monster's item");
+        } else {
+            // Positions are borrowed while the player moves, so it's put down afterwards
+            let lazy = ecs.fetch::<LazyUpdate>();
+            lazy.remove::<InBackpack>(*item);
+            lazy.insert(*item, monster_pos);
+        }
+    }
+
+    let mut purses = ecs.write_storage::<Purse>();
+    let gold = purses
+        .get_mut(monster)
+        .map_or(0, |purse| std::mem::take(&mut purse.gold));
+    if gold > 0 {
+        if let Some(purse) = purses.get_mut(player) {
+            purse.gold += gold;
+        }
+    }
+
+    let name = ecs.read_storage::<Name>().get(monster).map_or_else(
+        || tr("nameless-monster").to_string(),
+        |name| l10n::name(&name.name),
+    );
+    let mut log = ecs.fetch_mut::<GameLog>();
+    if items.is_empty() && gold == 0 {
+        log.log(tr("surrender-nothing").arg("name", name));
+    } else {
+        log.log(tr("surrender-everything").arg("name", name));
+    }
+}
+
+/// Whether the player's [`StatusEffects`] give them another go before the monsters
+/// get their turn.
+pub fn player_gets_extra_turn(ecs: &World) -> bool {
+    let player_entity = ecs.fetch::<PlayerEntity>();
+    ecs.read_storage::<StatusEffects>()
+        .get(**player_entity)
+        .is_some_and(StatusEffects::gets_extra_turn)
 }
 
 /// Handle player input.
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    // Pick an upgrade for any level-ups before doing anything else
+    if progression::has_unspent_upgrades(&gs.ecs) {
+        return RunState::LevelUp;
+    }
+
+    // Some status effects make the player lose their turn outright
+    let skipped_by = {
+        let player_entity = gs.ecs.fetch::<PlayerEntity>();
+        gs.ecs
+            .read_storage::<StatusEffects>()
+            .get(**player_entity)
+            .and_then(StatusEffects::skips_turn)
+    };
+    if let Some(effect) = skipped_by {
+        gs.ecs.fetch_mut::<GameLog>().log(match effect {
+            StatusEffect::Slowed => tr("too-slow-to-act"),
+            StatusEffect::Wading => tr("wading-through-water"),
+            _ => tr("too-confused-to-act"),
+        });
+        return RunState::PlayerTurn;
+    }
+
+    // Keep walking if the player is travelling somewhere
+    if let Some(runstate) = continue_travel(&mut gs.ecs, ctx) {
+        return runstate;
+    }
+
+    // Keep resting if the player is resting
+    if let Some(runstate) = continue_resting(&mut gs.ecs, ctx) {
+        return runstate;
+    }
+
+    // Click on somewhere the player's seen to travel there
+    if ctx.left_click {
+        let (mouse_x, mouse_y) = ctx.mouse_pos();
+        let clicked = Camera::new(&gs.ecs)
+            .screen_to_map(Point::new(mouse_x, mouse_y))
+            .filter(|pos| {
+                let map = gs.ecs.fetch::<Map>();
+                map.revealed_tiles[map.xy_idx(pos.x, pos.y)]
+                    || gs.ecs.fetch::<Config>().display.debug_map_view
+            });
+        if let Some(pos) = clicked {
+            travel_to(&mut gs.ecs, pos.x, pos.y);
+        }
+        return RunState::AwaitingInput;
+    }
+
+    // With vi-keys turned off, the letters that would move the player do nothing
+    let vi_keys = gs.ecs.fetch::<Config>().gameplay.vi_keys;
+    if !vi_keys && !ctx.shift && ctx.key.is_some_and(is_vi_key) {
+        return RunState::AwaitingInput;
+    }
+
     // Player movement
     match ctx.key {
         // Nothing happened
@@ -150,31 +407,55 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
         Some(key) => match key {
             // Movement in cardinal directions
             VirtualKeyCode::Left | VirtualKeyCode::H | VirtualKeyCode::Numpad4 => {
-                try_move_player(-1, 0, &mut gs.ecs)
+                return try_move_player(-1, 0, &mut gs.ecs);
             }
             VirtualKeyCode::Right | VirtualKeyCode::L | VirtualKeyCode::Numpad6 => {
-                try_move_player(1, 0, &mut gs.ecs)
+                return try_move_player(1, 0, &mut gs.ecs);
             }
             VirtualKeyCode::Up | VirtualKeyCode::K | VirtualKeyCode::Numpad8 => {
-                try_move_player(0, -1, &mut gs.ecs)
+                return try_move_player(0, -1, &mut gs.ecs);
             }
+            // Read the quest journal (before J is taken as movement)
+            VirtualKeyCode::J if ctx.shift => return RunState::ShowJournal,
+
             VirtualKeyCode::Down | VirtualKeyCode::J | VirtualKeyCode::Numpad2 => {
-                try_move_player(0, 1, &mut gs.ecs)
+                return try_move_player(0, 1, &mut gs.ecs);
             }
 
             // Movement in diagonal directions
-            VirtualKeyCode::Numpad9 | VirtualKeyCode::I => try_move_player(1, -1, &mut gs.ecs),
-            VirtualKeyCode::Numpad7 | VirtualKeyCode::U => try_move_player(-1, -1, &mut gs.ecs),
-            VirtualKeyCode::Numpad3 | VirtualKeyCode::M => try_move_player(1, 1, &mut gs.ecs),
-            VirtualKeyCode::Numpad1 | VirtualKeyCode::N => try_move_player(-1, 1, &mut gs.ecs),
+            VirtualKeyCode::Numpad9 | VirtualKeyCode::I => {
+                return try_move_player(1, -1, &mut gs.ecs)
+            }
+            VirtualKeyCode::Numpad7 | VirtualKeyCode::U => {
+                return try_move_player(-1, -1, &mut gs.ecs)
+            }
+            VirtualKeyCode::Numpad3 | VirtualKeyCode::M => {
+                return try_move_player(1, 1, &mut gs.ecs)
+            }
+            VirtualKeyCode::Numpad1 | VirtualKeyCode::N => {
+                return try_move_player(-1, 1, &mut gs.ecs)
+            }
 
             // Skip turn
             VirtualKeyCode::Numpad5 | VirtualKeyCode::Space => return skip_turn(&mut gs.ecs),
 
-            // Go down a level if on DownStairs
+            // Explore until something interesting happens
+            VirtualKeyCode::O => return start_exploring(&mut gs.ecs),
+
+            // Rest until healed
+            VirtualKeyCode::R => return start_resting(&mut gs.ecs),
+
+            // Go down a level if on stairs down (or another way down)
             VirtualKeyCode::Period => {
-                if try_next_level(&mut gs.ecs) {
-                    return RunState::NextLevel;
+                if let Some(destination) = find_exit(&gs.ecs, true) {
+                    return RunState::ChangeLevel { destination };
+                }
+            }
+
+            // Go back up a level if on stairs up
+            VirtualKeyCode::Comma => {
+                if let Some(destination) = find_exit(&gs.ecs, false) {
+                    return RunState::ChangeLevel { destination };
                 }
             }
 
@@ -182,6 +463,29 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
             VirtualKeyCode::G => get_item(&mut gs.ecs),
             VirtualKeyCode::B => return RunState::ShowInventory,
             VirtualKeyCode::D => return RunState::ShowDropItem,
+            VirtualKeyCode::A => return auto_equip_player(&mut gs.ecs),
+            VirtualKeyCode::T => return RunState::ShowThrowItem,
+            VirtualKeyCode::Z => return RunState::ShowSpellbook,
+
+            // Look around, and leave notes on the map
+            VirtualKeyCode::X | VirtualKeyCode::Semicolon => {
+                let player_pos = gs.ecs.fetch::<PlayerPos>();
+                return RunState::Examine {
+                    x: player_pos.x,
+                    y: player_pos.y,
+                };
+            }
+            VirtualKeyCode::Tab => return RunState::ShowNotes,
+
+            // Read back through old messages
+            VirtualKeyCode::P => return RunState::ShowLog { offset: 0 },
+
+            // Undo the last turn, in runs without permadeath
+            VirtualKeyCode::Back => return RunState::RewindTurn,
+
+            // Cheat
+            #[cfg(feature = "debug-console")]
+            VirtualKeyCode::Grave => return RunState::DebugConsole,
 
             // Save and quit
             VirtualKeyCode::Escape => return RunState::SaveGame,
@@ -213,7 +517,7 @@ fn get_item(ecs: &mut World) {
     }
 
     match target_item {
-        None => gamelog.log("There is nothing here to pick up."),
+        None => gamelog.log(tr("nothing-to-pick-up")),
         Some(item) => {
             let mut pickup = ecs.write_storage::<WantsToPickupItem>();
             pickup
@@ -229,46 +533,372 @@ fn get_item(ecs: &mut World) {
     }
 }
 
-/// Check if the player can descend a level. Returns true if successful.
-fn try_next_level(ecs: &mut World) -> bool {
+/// Equip any strict upgrades the player is carrying. Only takes a turn if
+/// something actually got equipped.
+fn auto_equip_player(ecs: &mut World) -> RunState {
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+
+    if crate::inventory_system::auto_equip(ecs, player_entity).is_empty() {
+        ecs.fetch_mut::<GameLog>().log(tr("already-best-gear"));
+        RunState::AwaitingInput
+    } else {
+        RunState::PlayerTurn
+    }
+}
+
+/// Throw a rock towards `target`, making a noise wherever it lands to distract
+/// any monsters nearby.
+pub fn throw_rock(ecs: &mut World, target: rltk::Point) {
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let player_pos = **ecs.fetch::<PlayerPos>();
+    let impact = resolve_trajectory(&ecs.fetch::<Map>(), player_pos, target, THROW_RANGE).impact;
+
+    ecs.write_storage::<WantsToMakeNoise>()
+        .insert(
+            player_entity,
+            WantsToMakeNoise {
+                x: impact.x,
+                y: impact.y,
+                volume: ROCK_NOISE_VOLUME,
+            },
+        )
+        .expect("Unable to insert intent to make noise for player");
+    ecs.fetch_mut::<GameLog>().log(tr("throw-rock"));
+}
+
+/// Where the exit that the player is standing on leads, if there's one going
+/// down (or up, if not `downwards`). Tells the player if there isn't.
+fn find_exit(ecs: &World, downwards: bool) -> Option<LevelId> {
     let player_pos = ecs.fetch::<PlayerPos>();
     let map = ecs.fetch::<Map>();
+    let exits = ecs.read_storage::<LevelExit>();
+    let here = map.level_id();
     let player_idx = map.xy_idx(player_pos.x, player_pos.y);
 
-    if map.tiles[player_idx] == TileType::DownStairs {
-        true
-    } else {
-        let mut gamelog = ecs.fetch_mut::<GameLog>();
-        gamelog.log("You try digging down a level, but your arms are too weak!");
-        false
+    let exit = map.tile_content[player_idx]
+        .iter()
+        .filter_map(|entity| exits.get(*entity))
+        .map(|exit| exit.destination)
+        .find(|destination| (destination.depth > here.depth) == downwards);
+    // Levels from before exits were entities only have their stairs to go by
+    let stairs = match map.tiles[player_idx] {
+        TileType::DownStairs if downwards => here.below(),
+        TileType::UpStairs if !downwards => here.above(),
+        _ => None,
+    };
+
+    let destination = exit.or(stairs);
+    if destination.is_none() {
+        ecs.fetch_mut::<GameLog>().log(if downwards {
+            tr("no-way-down")
+        } else {
+            tr("no-way-up")
+        });
     }
+    destination
 }
 
-/// Skip the player's turn, and let them heal if there are no monsters nearby.
-fn skip_turn(ecs: &mut World) -> RunState {
+/// Returns `true` if there are any monsters in the player's viewshed.
+fn hostile_in_view(ecs: &World) -> bool {
     let player_entity = ecs.fetch::<PlayerEntity>();
     let viewsheds = ecs.read_component::<Viewshed>();
     let monsters = ecs.read_component::<Monster>();
 
     let level_map = ecs.fetch::<Map>();
 
-    // If there are monster's in the player's viewshed, then they can't heal by waiting
-    let mut can_heal = true;
     let player_viewshed = viewsheds.get(**player_entity).unwrap();
+    player_viewshed.visible_tiles.iter().any(|tile| {
+        let idx = level_map.xy_idx(tile.x, tile.y);
+        level_map.tile_content[idx]
+            .iter()
+            .any(|entity| monsters.get(*entity).is_some())
+    })
+}
+
+/// Returns `true` if the player is too hungry to get anything out of resting.
+fn too_hungry(ecs: &World) -> bool {
+    ecs.read_storage::<HungerClock>()
+        .get(**ecs.fetch::<PlayerEntity>())
+        .is_some_and(|clock| matches!(clock.state, HungerState::Hungry | HungerState::Starving))
+}
+
+/// Skip the player's turn, and let them heal and recover mana if there are no
+/// monsters nearby and they aren't going hungry.
+fn skip_turn(ecs: &mut World) -> RunState {
+    // If there are monster's in the player's viewshed, then they can't heal by waiting
+    if !hostile_in_view(ecs) && !too_hungry(ecs) {
+        let player_entity = ecs.fetch::<PlayerEntity>();
+        let mut all_combat_stats = ecs.write_component::<CombatStats>();
+        let player_stats = all_combat_stats.get_mut(**player_entity).unwrap();
+        let perks = ecs.read_storage::<Perks>();
+        let has_perk = |perk| {
+            perks
+                .get(**player_entity)
+                .is_some_and(|perks| perks.perks.contains(&perk))
+        };
+
+        let healing = if has_perk(Perk::FastHealing) { 2 } else { 1 };
+        player_stats.hp = (player_stats.hp + healing).min(player_stats.max_hp);
+
+        let mut all_mana = ecs.write_component::<Mana>();
+        if let Some(player_mana) = all_mana.get_mut(**player_entity) {
+            let recovery = if has_perk(Perk::Meditation) { 2 } else { 1 };
+            player_mana.mana = (player_mana.mana + recovery).min(player_mana.max_mana);
+        }
+    }
+
+    RunState::PlayerTurn
+}
+
+/// Returns `true` if the player has all of their health and mana.
+fn fully_rested(ecs: &World) -> bool {
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let healed = ecs
+        .read_storage::<CombatStats>()
+        .get(player_entity)
+        .is_none_or(|stats| stats.hp >= stats.max_hp);
+    let recovered = ecs
+        .read_storage::<Mana>()
+        .get(player_entity)
+        .is_none_or(|mana| mana.mana >= mana.max_mana);
+    healed && recovered
+}
+
+/// Start resting until healed, one skipped turn at a time. See
+/// [`continue_resting()`].
+fn start_resting(ecs: &mut World) -> RunState {
+    if hostile_in_view(ecs) {
+        ecs.fetch_mut::<GameLog>().log(tr("rest-monsters-nearby"));
+    } else if too_hungry(ecs) {
+        ecs.fetch_mut::<GameLog>().log(tr("rest-too-hungry"));
+    } else if fully_rested(ecs) {
+        ecs.fetch_mut::<GameLog>().log(tr("rest-already-rested"));
+    } else {
+        ecs.fetch_mut::<GameLog>().log(tr("rest-start"));
+        ecs.insert(Resting(true));
+    }
+    RunState::AwaitingInput
+}
+
+/// Skip another turn if the player is resting.
+///
+/// Resting stops as soon as a key is pressed, a monster comes into view, the
+/// player gets hungry, or they're back to full health and mana. Returns `None` if the player isn't
+/// resting.
+fn continue_resting(ecs: &mut World, ctx: &Rltk) -> Option<RunState> {
+    if !ecs.fetch::<Resting>().0 {
+        return None;
+    }
+
+    let stop_reason = if ctx.key.is_some() {
+        Some(tr("rest-stop"))
+    } else if hostile_in_view(ecs) {
+        Some(tr("rest-stop-monster"))
+    } else if too_hungry(ecs) {
+        Some(tr("rest-stop-hungry"))
+    } else if fully_rested(ecs) {
+        Some(tr("rest-done"))
+    } else {
+        None
+    };
+
+    if let Some(reason) = stop_reason {
+        ecs.insert(Resting(false));
+        ecs.fetch_mut::<GameLog>().log(reason);
+        return Some(RunState::AwaitingInput);
+    }
+
+    Some(skip_turn(ecs))
+}
+
+/// Plan a path for the player to automatically walk to (`x`, `y`).
+///
+/// Returns `false` (and logs why) if there's no known way to get there.
+pub fn travel_to(ecs: &mut World, x: i32, y: i32) -> bool {
+    let player_pos = *ecs.fetch::<PlayerPos>();
+    let map = ecs.fetch::<Map>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    if player_pos.x == x && player_pos.y == y {
+        gamelog.log(tr("travel-already-there"));
+        return false;
+    }
+
+    let path = rltk::a_star_search(
+        map.xy_idx(player_pos.x, player_pos.y),
+        map.xy_idx(x, y),
+        &*map,
+    );
+    if !path.success {
+        gamelog.log(tr("travel-no-path"));
+        return false;
+    }
+
+    // The first step is where the player is currently standing.
+    let mut travel = ecs.fetch_mut::<TravelPath>();
+    travel.steps = path.steps.into_iter().skip(1).collect();
+    true
+}
+
+/// Start auto-exploring the level, one step per turn. See [`continue_travel()`].
+fn start_exploring(ecs: &mut World) -> RunState {
+    if hostile_in_view(ecs) {
+        ecs.fetch_mut::<GameLog>()
+            .log(tr("explore-monsters-nearby"));
+        return RunState::AwaitingInput;
+    }
+
+    // Items already in view have already been spotted
+    let mut spotted_items = Vec::new();
+    new_items_in_view(ecs, &mut spotted_items);
+
+    let mut travel = ecs.fetch_mut::<TravelPath>();
+    travel.steps.clear();
+    travel.exploring = true;
+    travel.spotted_items = spotted_items;
+    RunState::AwaitingInput
+}
+
+/// Adds any items in the player's viewshed that aren't in `spotted` yet to it,
+/// and returns how many there were.
+fn new_items_in_view(ecs: &World, spotted: &mut Vec<Entity>) -> usize {
+    let player_entity = ecs.fetch::<PlayerEntity>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let items = ecs.read_storage::<Item>();
+    let level_map = ecs.fetch::<Map>();
+
+    let Some(player_viewshed) = viewsheds.get(**player_entity) else {
+        return 0;
+    };
+    let mut found = 0;
     for tile in player_viewshed.visible_tiles.iter() {
         let idx = level_map.xy_idx(tile.x, tile.y);
         for entity in level_map.tile_content[idx].iter() {
-            if monsters.get(*entity).is_some() {
-                can_heal = false;
+            if items.contains(*entity) && !spotted.contains(entity) {
+                spotted.push(*entity);
+                found += 1;
             }
         }
     }
+    found
+}
 
-    if can_heal {
-        let mut all_combat_stats = ecs.write_component::<CombatStats>();
-        let player_stats = all_combat_stats.get_mut(**player_entity).unwrap();
-        player_stats.hp = (player_stats.hp + 1).min(player_stats.max_hp);
+/// The first step towards the nearest part of the level that the player hasn't
+/// explored, if there's any left that they can get to.
+///
+/// Explored floor that borders unexplored tiles is what the player heads for.
+/// The tile they're standing on doesn't count, since if it's still on the edge of
+/// the unknown, there's nothing more to be seen from it.
+fn next_unexplored_step(ecs: &World) -> Option<usize> {
+    let map = ecs.fetch::<Map>();
+    let player_pos = ecs.fetch::<PlayerPos>();
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+
+    let frontier: Vec<usize> = (0..map.tiles.len())
+        .filter(|&idx| {
+            idx != player_idx
+                && map.revealed_tiles[idx]
+                && map.tiles[idx] != TileType::Wall
+                && map
+                    .get_available_exits(idx)
+                    .iter()
+                    .any(|(exit, _)| !map.revealed_tiles[*exit])
+        })
+        .collect();
+    if frontier.is_empty() {
+        return None;
     }
 
-    RunState::PlayerTurn
+    let dijkstra = rltk::DijkstraMap::new(
+        map.width,
+        map.height,
+        &frontier,
+        &*map,
+        EXPLORE_SEARCH_DEPTH,
+    );
+    rltk::DijkstraMap::find_lowest_exit(&dijkstra, player_idx, &*map)
+}
+
+/// Take the next step along the player's [`TravelPath`], if they have one, or
+/// towards the nearest unexplored tile if they're exploring.
+///
+/// Travelling stops as soon as a key is pressed, a monster comes into view, or
+/// the way forward is blocked. Exploring also stops when a new item comes into
+/// view, or there's nowhere left to explore. Returns `None` if the player isn't
+/// travelling or exploring.
+fn continue_travel(ecs: &mut World, ctx: &Rltk) -> Option<RunState> {
+    let exploring = {
+        let travel = ecs.fetch::<TravelPath>();
+        if travel.steps.is_empty() && !travel.exploring {
+            return None;
+        }
+        travel.exploring
+    };
+    let doing = if exploring {
+        tr("doing-exploring")
+    } else {
+        tr("doing-travelling")
+    }
+    .to_string();
+
+    let new_items = exploring && {
+        let mut spotted = std::mem::take(&mut ecs.fetch_mut::<TravelPath>().spotted_items);
+        let found = new_items_in_view(ecs, &mut spotted);
+        ecs.fetch_mut::<TravelPath>().spotted_items = spotted;
+        found > 0
+    };
+    if exploring && ecs.fetch::<TravelPath>().steps.is_empty() {
+        if let Some(step) = next_unexplored_step(ecs) {
+            ecs.fetch_mut::<TravelPath>().steps.push(step);
+        }
+    }
+
+    let stop_reason = if ctx.key.is_some() || ctx.left_click {
+        Some(tr("travel-stop").arg("doing", doing))
+    } else if hostile_in_view(ecs) {
+        Some(tr("travel-stop-monster").arg("doing", doing))
+    } else if new_items {
+        Some(tr("travel-stop-item").arg("doing", doing))
+    } else if ecs.fetch::<TravelPath>().steps.is_empty() {
+        Some(tr("explore-done"))
+    } else {
+        let map = ecs.fetch::<Map>();
+        let next_step = ecs.fetch::<TravelPath>().steps[0];
+        map.blocked[next_step].then(|| tr("travel-stop-blocked").arg("doing", doing))
+    };
+
+    if let Some(reason) = stop_reason {
+        let mut travel = ecs.fetch_mut::<TravelPath>();
+        travel.steps.clear();
+        travel.exploring = false;
+        travel.spotted_items.clear();
+        ecs.fetch_mut::<GameLog>().log(reason);
+        return Some(RunState::AwaitingInput);
+    }
+
+    let next_step = ecs.fetch_mut::<TravelPath>().steps.remove(0);
+    let (delta_x, delta_y) = {
+        let player_pos = ecs.fetch::<PlayerPos>();
+        let (x, y) = ecs.fetch::<Map>().idx_xy(next_step);
+        (x - player_pos.x, y - player_pos.y)
+    };
+    try_move_player(delta_x, delta_y, ecs);
+
+    Some(RunState::PlayerTurn)
+}
+
+/// Returns `true` if `key` is one of the letters that moves the player around,
+/// vi-style, when [`crate::config::GameplayConfig::vi_keys`] is on.
+pub fn is_vi_key(key: VirtualKeyCode) -> bool {
+    matches!(
+        key,
+        VirtualKeyCode::H
+            | VirtualKeyCode::J
+            | VirtualKeyCode::K
+            | VirtualKeyCode::L
+            | VirtualKeyCode::U
+            | VirtualKeyCode::I
+            | VirtualKeyCode::N
+            | VirtualKeyCode::M
+    )
 }
@@ -0,0 +1,223 @@
+//! Levelling up, and the upgrades the player picks each time they do.
+//!
+//! Experience comes from killing monsters. Every level gained gives the player
+//! an upgrade to pick from the raws (see [`crate::raws::Upgrade`]), which they're
+//! asked to choose the next time they're able to act.
+
+use specs::prelude::*;
+
+use crate::game_events::GameEventListener;
+use crate::l10n::{self, tr};
+use crate::raws::{Raws, Upgrade, UpgradeEffect};
+use crate::screen_effects::ScreenEffects;
+use crate::{
+    spawner, Attribute, Attributes, CombatStats, Experience, GameEvent, GameLog, KnownSpells, Mana,
+    Name, Perks, PlayerEntity, Skill, Skills, Spell,
+};
+
+/// Max HP gained for each point of [`Attribute::Vitality`].
+const HP_PER_VITALITY: i32 = 5;
+
+/// Max mana gained for each point of [`Attribute::Intellect`].
+const MANA_PER_INTELLECT: i32 = 3;
+
+/// Gives the player experience for every monster they kill, and levels them up.
+pub struct ExperienceListener;
+
+impl GameEventListener for ExperienceListener {
+    fn on_event(&mut self, ecs: &World, event: &GameEvent) {
+        let player = **ecs.fetch::<PlayerEntity>();
+        if let GameEvent::MonsterKilled { xp, killer, .. } = event {
+            if *killer == Some(player) {
+                gain_experience(ecs, *xp);
+            }
+        }
+    }
+}
+
+/// Trains the player's [`Skills`] a little every time they're put to use: hitting
+/// things trains [`Skill::Melee`], getting hit trains [`Skill::Defense`], and
+/// casting spells trains [`Skill::Magic`].
+pub struct SkillPracticeListener;
+
+impl GameEventListener for SkillPracticeListener {
+    fn on_event(&mut self, ecs: &World, event: &GameEvent) {
+        let player = **ecs.fetch::<PlayerEntity>();
+        let skill = match event {
+            GameEvent::MeleeHit { attacker, .. } if *attacker == player => Skill::Melee,
+            GameEvent::MeleeHit { target, .. } if *target == player => Skill::Defense,
+            GameEvent::ItemUsed { user, item }
+                if *user == player && ecs.read_storage::<Spell>().contains(*item) =>
+            {
+                Skill::Magic
+            }
+            _ => return,
+        };
+
+        let mut skills = ecs.write_storage::<Skills>();
+        let Some(skills) = skills.get_mut(player) else {
+            return;
+        };
+        if skills.practice(skill) {
+            ecs.fetch_mut::<GameLog>()
+                .entry()
+                .colored_message(
+                    &tr("skill-improves")
+                        .arg("skill", l10n::name(&skill.to_string()))
+                        .arg("rank", skills.rank(skill)),
+                    rltk::RGB::named(rltk::LIGHT_GREEN),
+                )
+                .log();
+        }
+    }
+}
+
+/// Give the player `xp` experience, levelling them up as many times as it takes.
+pub fn gain_experience(ecs: &World, xp: i32) {
+    let player = **ecs.fetch::<PlayerEntity>();
+    let mut experience = ecs.write_storage::<Experience>();
+    let Some(experience) = experience.get_mut(player) else {
+        return;
+    };
+
+    experience.xp += xp;
+    while experience.xp >= experience.xp_to_next_level() {
+        experience.xp -= experience.xp_to_next_level();
+        experience.level += 1;
+        experience.unspent_upgrades += 1;
+        ecs.fetch_mut::<GameLog>()
+            .entry()
+            .colored_message(
+                &tr("level-up").arg("level", experience.level),
+                rltk::RGB::named(rltk::MAGENTA),
+            )
+            .log();
+        ecs.fetch_mut::<ScreenEffects>().level_up();
+    }
+}
+
+/// Whether the player has levelled up, and still has to pick an upgrade for it.
+pub fn has_unspent_upgrades(ecs: &World) -> bool {
+    let player = **ecs.fetch::<PlayerEntity>();
+    ecs.read_storage::<Experience>()
+        .get(player)
+        .is_some_and(|experience| experience.unspent_upgrades > 0)
+}
+
+/// List the upgrades that the player can currently pick from. Spells they already
+/// know and perks they already have aren't offered again.
+pub fn available_upgrades(ecs: &World) -> Vec<Upgrade> {
+    let player = **ecs.fetch::<PlayerEntity>();
+    let level = ecs
+        .read_storage::<Experience>()
+        .get(player)
+        .map_or(1, |experience| experience.level);
+
+    let names = ecs.read_storage::<Name>();
+    let known_spells: Vec<String> = ecs
+        .read_storage::<KnownSpells>()
+        .get(player)
+        .map(|known| {
+            known
+                .spells
+                .iter()
+                .filter_map(|spell| names.get(*spell).map(|name| name.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let perks = ecs.read_storage::<Perks>();
+    let perks = perks.get(player);
+
+    ecs.fetch::<Raws>()
+        .upgrades
+        .iter()
+        .filter(|upgrade| upgrade.min_level <= level)
+        .filter(|upgrade| match &upgrade.effect {
+            UpgradeEffect::LearnSpell(spell) => !known_spells.contains(spell),
+            UpgradeEffect::Perk(perk) => !perks.is_some_and(|perks| perks.perks.contains(perk)),
+            UpgradeEffect::Attribute(..) | UpgradeEffect::SkillRank(_) => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Give the player `upgrade`, using up one of their unspent level-ups.
+pub fn apply_upgrade(ecs: &mut World, upgrade: &Upgrade) {
+    let player = **ecs.fetch::<PlayerEntity>();
+
+    match &upgrade.effect {
+        UpgradeEffect::LearnSpell(name) => {
+            let spell = spawner::spawn_spell(ecs, name).unwrap_or_else(|| {
+                panic!("Upgrade {:?} teaches unknown spell {name:?}", upgrade.name)
+            });
+            let mut known_spells = ecs.write_storage::<KnownSpells>();
+            if let Some(known) = known_spells.get_mut(player) {
+                known.spells.push(spell);
+            }
+        }
+
+        UpgradeEffect::Attribute(attribute, points) => {
+            raise_attribute(ecs, player, *attribute, *points)
+        }
+
+        UpgradeEffect::SkillRank(skill) => {
+            if let Some(skills) = ecs.write_storage::<Skills>().get_mut(player) {
+                *skills.rank_mut(*skill) += 1;
+            }
+        }
+
+        UpgradeEffect::Perk(perk) => {
+            if let Some(perks) = ecs.write_storage::<Perks>().get_mut(player) {
+                perks.perks.push(*perk);
+            }
+        }
+    }
+
+    if let Some(experience) = ecs.write_storage::<Experience>().get_mut(player) {
+        experience.unspent_upgrades -= 1;
+    }
+    ecs.fetch_mut::<GameLog>().log(
+        tr("gain-upgrade")
+            .arg("upgrade", l10n::name(&upgrade.name))
+            .arg("description", l10n::name(&upgrade.description)),
+    );
+}
+
+/// Put `points` into `entity`'s `attribute`, and update its stats to match.
+fn raise_attribute(ecs: &mut World, entity: Entity, attribute: Attribute, points: i32) {
+    if let Some(attributes) = ecs.write_storage::<Attributes>().get_mut(entity) {
+        match attribute {
+            Attribute::Might => attributes.might += points,
+            Attribute::Toughness => attributes.toughness += points,
+            Attribute::Vitality => attributes.vitality += points,
+            Attribute::Intellect => attributes.intellect += points,
+        }
+    }
+
+    let mut combat_stats = ecs.write_storage::<CombatStats>();
+    let mut mana = ecs.write_storage::<Mana>();
+    match attribute {
+        Attribute::Might => {
+            if let Some(stats) = combat_stats.get_mut(entity) {
+                stats.power += points;
+            }
+        }
+        Attribute::Toughness => {
+            if let Some(stats) = combat_stats.get_mut(entity) {
+                stats.defense += points;
+            }
+        }
+        Attribute::Vitality => {
+            if let Some(stats) = combat_stats.get_mut(entity) {
+                stats.max_hp += points * HP_PER_VITALITY;
+                stats.hp += points * HP_PER_VITALITY;
+            }
+        }
+        Attribute::Intellect => {
+            if let Some(pool) = mana.get_mut(entity) {
+                pool.max_mana += points * MANA_PER_INTELLECT;
+                pool.mana += points * MANA_PER_INTELLECT;
+            }
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Quests that the townsfolk hand out. A [`QuestGiver`] offers the player their
+//! quest when bumped into, and once it's accepted it goes in the [`QuestJournal`].
+//! The [`QuestListener`] keeps track of progress from the monsters killed and
+//! items picked up along the way, and pays out the reward when a quest is done.
+
+use rltk::RGB;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::game_events::GameEventListener;
+use crate::l10n::{self, tr, Translation};
+use crate::{progression, Affixed, GameEvent, GameLog, Name, PlayerEntity, Purse, QuestGiver};
+
+/// What the player has to do to finish a [`Quest`].
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum QuestGoal {
+    /// Kill `count` monsters called `monster`.
+    Kill { monster: &'static str, count: i32 },
+    /// Find an item called `item` and pick it up.
+    Retrieve { item: &'static str },
+}
+
+/// A job that one of the townsfolk wants doing.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Quest {
+    pub name: &'static str,
+    /// The name of the townsperson who gives the quest out.
+    pub giver: &'static str,
+    /// How the giver asks the player to take the quest on.
+    pub pitch: &'static str,
+    pub goal: QuestGoal,
+    /// How much gold finishing the quest is worth.
+    pub gold: i32,
+    /// How much experience finishing the quest is worth.
+    pub xp: i32,
+}
+
+impl Quest {
+    /// How many steps there are to finishing the quest, e.g. monsters to kill.
+    pub fn steps(&self) -> i32 {
+        match self.goal {
+            QuestGoal::Kill { count, .. } => count,
+            QuestGoal::Retrieve { .. } => 1,
+        }
+    }
+
+    /// A short description of the goal, e.g. `Kill 3 Goblins`.
+    pub fn objective(&self) -> Translation {
+        match self.goal {
+            QuestGoal::Kill { monster, count: 1 } => {
+                tr("quest-kill-one").arg("monster", l10n::name(monster))
+            }
+            QuestGoal::Kill { monster, count } => tr("quest-kill")
+                .arg("count", count)
+                .arg("monster", l10n::name(monster)),
+            QuestGoal::Retrieve { item } => tr("quest-retrieve").arg("item", l10n::name(item)),
+        }
+    }
+}
+
+/// Every quest in the game.
+pub const QUESTS: &[Quest] = &[
+    Quest {
+        name: "Chicken Thieves",
+        giver: "Farmer",
+        pitch: "Goblins keep taking my chickens. Kill 3 for me?",
+        goal: QuestGoal::Kill {
+            monster: "Goblin",
+            count: 3,
+        },
+        gold: 30,
+        xp: 40,
+    },
+    Quest {
+        name: "Last Orders",
+        giver: "Drunk",
+        pitch: "Orcs drank the tavern dry! Teach 2 of them a lesson?",
+        goal: QuestGoal::Kill {
+            monster: "Orc",
+            count: 2,
+        },
+        gold: 40,
+        xp: 80,
+    },
+    Quest {
+        name: "Family Heirloom",
+        giver: "Townsperson",
+        pitch: "My father's longsword is lost down there. Find it?",
+        goal: QuestGoal::Retrieve { item: "Longsword" },
+        gold: 50,
+        xp: 60,
+    },
+];
+
+/// The quest called `name`, if there is one.
+pub fn quest(name: &str) -> Option<&'static Quest> {
+    QUESTS.iter().find(|quest| quest.name == name)
+}
+
+/// The quest that the townsperson called `giver` hands out, if they have one.
+pub fn quest_given_by(giver: &str) -> Option<&'static Quest> {
+    QUESTS.iter().find(|quest| quest.giver == giver)
+}
+
+/// How the player is getting on with a quest they've taken on.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The name of the [`Quest`].
+    pub quest: String,
+    /// How many of the quest's [`Quest::steps()`] are done.
+    pub progress: i32,
+}
+
+impl JournalEntry {
+    /// The quest that this is an entry for.
+    pub fn quest(&self) -> Option<&'static Quest> {
+        quest(&self.quest)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.quest()
+            .is_none_or(|quest| self.progress >= quest.steps())
+    }
+}
+
+/// Every quest the player has taken on during the run, in the order they were
+/// accepted.
+#[derive(PartialEq, Eq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct QuestJournal {
+    pub entries: Vec<JournalEntry>,
+}
+
+impl QuestJournal {
+    /// Whether the player has already taken on the quest called `name`.
+    pub fn has_accepted(&self, name: &str) -> bool {
+        self.entries.iter().any(|entry| entry.quest == name)
+    }
+}
+
+/// Take on the quest offered by `giver`. Does nothing if they don't have one, or
+/// the player already accepted it.
+pub fn accept_quest(ecs: &World, giver: Entity) {
+    let Some(quest) = ecs
+        .read_storage::<QuestGiver>()
+        .get(giver)
+        .and_then(|giver| quest(&giver.quest))
+    else {
+        return;
+    };
+
+    let mut journal = ecs.write_resource::<QuestJournal>();
+    if journal.has_accepted(quest.name) {
+        return;
+    }
+    journal.entries.push(JournalEntry {
+        quest: quest.name.to_string(),
+        progress: 0,
+    });
+
+    ecs.fetch_mut::<GameLog>()
+        .entry()
+        .colored_message(
+            &tr("quest-accepted")
+                .arg("quest", l10n::name(quest.name))
+                .arg("objective", quest.objective()),
+            RGB::named(rltk::CYAN),
+        )
+        .log();
+}
+
+/// Moves quests along as the player kills monsters and picks things up, and
+/// rewards them for every quest they finish.
+pub struct QuestListener;
+
+impl GameEventListener for QuestListener {
+    fn on_event(&mut self, ecs: &World, event: &GameEvent) {
+        let player = **ecs.fetch::<PlayerEntity>();
+        let picked_up = match event {
+            // Affixed items still count as whatever they were before
+            GameEvent::ItemPickedUp { collector, item } if *collector == player => ecs
+                .read_storage::<Affixed>()
+                .get(*item)
+                .map(|affixed| affixed.base.clone())
+                .or_else(|| {
+                    ecs.read_storage::<Name>()
+                        .get(*item)
+                        .map(|name| name.name.clone())
+                }),
+            _ => None,
+        };
+
+        let mut finished = Vec::new();
+        {
+            let mut journal = ecs.write_resource::<QuestJournal>();
+            for entry in journal.entries.iter_mut() {
+                let Some(quest) = entry.quest() else {
+                    continue;
+                };
+                if entry.is_complete() {
+                    continue;
+                }
+
+                let advanced = match (quest.goal, event) {
+                    (
+                        QuestGoal::Kill { monster, .. },
+                        GameEvent::MonsterKilled { name, killer, .. },
+                    ) => name == monster && *killer == Some(player),
+                    (QuestGoal::Retrieve { item }, _) => picked_up.as_deref() == Some(item),
+                    _ => false,
+                };
+                if advanced {
+                    entry.progress += 1;
+                    if entry.is_complete() {
+                        finished.push(quest);
+                    }
+                }
+            }
+        }
+
+        for quest in finished {
+            if let Some(purse) = ecs.write_storage::<Purse>().get_mut(player) {
+                purse.gold += quest.gold;
+            }
+            ecs.fetch_mut::<GameLog>()
+                .entry()
+                .colored_message(
+                    &tr("quest-complete")
+                        .arg("quest", l10n::name(quest.name))
+                        .arg("gold", tr("gold-amount").arg("amount", quest.gold))
+                        .arg("xp", quest.xp),
+                    RGB::named(rltk::GOLD),
+                )
+                .log();
+            progression::gain_experience(ecs, quest.xp);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Game data that's loaded from the `.ron` files in the `raws/` directory, rather
+//! than being hardcoded.
+//!
+//! The files are baked into the binary at compile time, so there's nothing extra
+//! to ship alongside the game.
+
+use serde::Deserialize;
+
+use crate::spawner::{self, Spawn};
+use crate::{Attribute, Perk, Skill};
+
+const UPGRADES_RON: &str = include_str!("../raws/upgrades.ron");
+const AFFIXES_RON: &str = include_str!("../raws/affixes.ron");
+const SPAWN_TABLES_RON: &str = include_str!("../raws/spawn_tables.ron");
+
+#[derive(Debug, thiserror::Error)]
+pub enum RawsError {
+    #[error("Failed to parse raws file {file}")]
+    Parse {
+        file: &'static str,
+        source: ron::error::SpannedError,
+    },
+    #[error("Spawn table {table} in {file} has an entry for {name:?}, which can't be spawned")]
+    UnknownSpawn {
+        file: &'static str,
+        table: &'static str,
+        name: String,
+    },
+}
+
+/// All of the game data loaded from the raws.
+#[derive(Debug, Clone)]
+pub struct Raws {
+    /// Everything the player can pick from when they level up.
+    pub upgrades: Vec<Upgrade>,
+    /// Every magic affix that can be rolled onto equipment.
+    pub affixes: Vec<Affix>,
+    /// What turns up where, and how likely it is at each depth.
+    pub spawn_tables: SpawnTables,
+}
+
+impl Raws {
+    /// Parse all of the raws files.
+    pub fn load() -> Result<Self, RawsError> {
+        let upgrades = ron::from_str(UPGRADES_RON).map_err(|source| RawsError::Parse {
+            file: "upgrades.ron",
+            source,
+        })?;
+        let affixes = ron::from_str(AFFIXES_RON).map_err(|source| RawsError::Parse {
+            file: "affixes.ron",
+            source,
+        })?;
+        let spawn_tables: SpawnTables =
+            ron::from_str(SPAWN_TABLES_RON).map_err(|source| RawsError::Parse {
+                file: "spawn_tables.ron",
+                source,
+            })?;
+        spawn_tables.validate()?;
+
+        Ok(Self {
+            upgrades,
+            affixes,
+            spawn_tables,
+        })
+    }
+}
+
+/// An improvement that the player can choose when they level up.
+#[derive(PartialEq, Eq, Clone, Debug, Deserialize)]
+pub struct Upgrade {
+    pub name: String,
+    pub description: String,
+    pub effect: UpgradeEffect,
+    /// The upgrade isn't offered until the player is at least this level.
+    #[serde(default)]
+    pub min_level: i32,
+}
+
+/// What picking an [`Upgrade`] actually does.
+#[derive(PartialEq, Eq, Clone, Debug, Deserialize)]
+pub enum UpgradeEffect {
+    /// Learn the spell with this name.
+    LearnSpell(String),
+    /// Put some points into an attribute.
+    Attribute(Attribute, i32),
+    /// Train a skill up by one rank.
+    SkillRank(Skill),
+    /// Pick up a passive perk.
+    Perk(Perk),
+}
+
+/// A bit of magic that can be rolled onto a piece of equipment when it's spawned,
+/// adding to its name and its stats.
+#[derive(PartialEq, Eq, Clone, Debug, Deserialize)]
+pub struct Affix {
+    pub name: String,
+    pub position: AffixPosition,
+    pub items: AffixItems,
+    /// How likely the affix is to be rolled, compared to the others.
+    pub weight: i32,
+    /// The affix isn't rolled on levels shallower than this.
+    #[serde(default)]
+    pub min_depth: i32,
+    pub modifiers: Vec<AffixModifier>,
+    /// How much more gold the affix makes an item worth.
+    pub value: i32,
+}
+
+/// Which side of an item's name an [`Affix`] goes on.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Deserialize)]
+pub enum AffixPosition {
+    Prefix,
+    Suffix,
+}
+
+/// What an [`Affix`] can be rolled onto.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Deserialize)]
+pub enum AffixItems {
+    Weapons,
+    Armor,
+    Any,
+}
+
+/// Something that an [`Affix`] adds to an item.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Deserialize)]
+pub enum AffixModifier {
+    Power(i32),
+    Defense(i32),
+    Damage(i32),
+    CritRange(i32),
+    BlockChance(i32),
+    Light(i32),
+}
+
+/// The spawn tables that levels, chests, and vendors are filled from.
+#[derive(PartialEq, Eq, Clone, Debug, Deserialize)]
+pub struct SpawnTables {
+    /// Monsters, items, and traps spawned into the rooms and regions of a level.
+    pub rooms: Vec<SpawnTableEntry>,
+    /// Whatever might be found inside a chest.
+    pub chest_loot: Vec<SpawnTableEntry>,
+    /// What vendors in town restock with, at the deepest depth the player has reached.
+    pub vendor_stock: Vec<SpawnTableEntry>,
+}
+
+/// One entry in a spawn table: something that can be spawned, and how likely it
+/// is compared to the rest of the table at any given depth.
+#[derive(PartialEq, Eq, Clone, Debug, Deserialize)]
+pub struct SpawnTableEntry {
+    /// What to spawn. An entry called `"None"` spawns nothing at all.
+    pub name: String,
+    #[serde(default)]
+    pub weight: i32,
+    /// How much is added to the weight for every level down...
+    #[serde(default)]
+    pub per_depth: i32,
+    /// ...divided by this, so that the weight can grow slower than a point per level.
+    #[serde(default = "one")]
+    pub depth_divisor: i32,
+}
+
+impl SpawnTables {
+    /// Make sure that every entry in every table is something that can be spawned.
+    fn validate(&self) -> Result<(), RawsError> {
+        let tables = [
+            ("rooms", &self.rooms),
+            ("chest_loot", &self.chest_loot),
+            ("vendor_stock", &self.vendor_stock),
+        ];
+        for (table, entries) in tables {
+            let unknown = entries
+                .iter()
+                .filter_map(|entry| Spawn::from(entry.name.as_str()).name().map(str::to_string))
+                .find(|name| !spawner::can_spawn(name));
+            if let Some(name) = unknown {
+                return Err(RawsError::UnknownSpawn {
+                    file: "spawn_tables.ron",
+                    table,
+                    name,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+const fn one() -> i32 {
+    1
+}
+
+impl SpawnTableEntry {
+    /// How likely the entry is to be rolled at `depth`. Zero or less means never.
+    pub const fn weight_at(&self, depth: i32) -> i32 {
+        self.weight + self.per_depth * depth / self.depth_divisor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raws_parse() {
+        Raws::load().unwrap();
+    }
+
+    #[test]
+    fn unknown_spawns_are_rejected() {
+        let entry = |name: &str| SpawnTableEntry {
+            name: name.to_string(),
+            weight: 1,
+            per_depth: 0,
+            depth_divisor: 1,
+        };
+        let mut tables = SpawnTables {
+            rooms: vec![entry("None"), entry("Goblin")],
+            chest_loot: vec![],
+            vendor_stock: vec![],
+        };
+        assert!(tables.validate().is_ok());
+
+        tables.vendor_stock.push(entry("Goblin King"));
+        assert!(matches!(
+            tables.validate(),
+            Err(RawsError::UnknownSpawn {
+                table: "vendor_stock",
+                ..
+            })
+        ));
+    }
+}
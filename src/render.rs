@@ -1,69 +1,224 @@
-use rltk::{Rltk, RGB};
+use rltk::{Point, Rltk, RGB};
 use specs::prelude::*;
 
-use crate::{Map, Position, Renderable, TileType, DEBUG_MAP_VIEW};
+use crate::camera::Camera;
+use crate::{
+    CombatStats, Config, FloatingText, Hidden, Map, MapTheme, Monster, Palette, ParticleLifetime,
+    Position, Renderable, TileType,
+};
 
-/// Draw a game map on screen. Only draws tiles visible within the player's viewshed.
+/// Whether to draw a tiny health bar under monsters that have been hurt.
+#[derive(Clone, Copy, Debug)]
+pub struct MonsterHealthBars {
+    pub enabled: bool,
+}
+
+impl Default for MonsterHealthBars {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Draw the part of the map that's in view of the [`Camera`]. Only draws tiles the
+/// player has seen, and greys out the ones that aren't currently in their viewshed.
 pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
+    let palette = ecs.fetch::<Palette>();
+    let debug_map_view = ecs.fetch::<Config>().display.debug_map_view;
+    let camera = Camera::new(ecs);
 
-    let mut y = 0;
-    let mut x = 0;
-
-    for (idx, tile) in map.tiles.iter().enumerate() {
-        // Render a tile depending on the tile type
-        if map.revealed_tiles[idx] || DEBUG_MAP_VIEW {
-            let glyph;
-            let mut fg;
-
-            match tile {
-                TileType::Floor => {
-                    glyph = rltk::to_cp437('.');
-                    fg = RGB::from_f32(0.0, 0.5, 0.5);
-                }
-                TileType::Wall => {
-                    glyph = wall_glyph(&map, x, y);
-                    fg = RGB::from_f32(0.0, 1.0, 0.0);
-                }
-                TileType::DownStairs => {
-                    glyph = rltk::to_cp437('>');
-                    fg = RGB::from_f32(0.0, 1.0, 1.0);
-                }
-            }
+    for (Point { x, y }, screen) in camera.tiles() {
+        let idx = map.xy_idx(x, y);
 
-            // If the tile isn't _currently_ visible to the player, grey it out
+        // Render a tile depending on the tile type, and the level's theme
+        if map.revealed_tiles[idx] || debug_map_view {
+            let (glyph, mut fg) = tile_glyph(&map, &palette, x, y);
+
+            // If the tile isn't _currently_ visible to the player, grey it out.
+            // Otherwise, on dark levels, tint it by whatever's lighting it up.
             if !map.visible_tiles[idx] {
                 fg = fg.to_greyscale();
+            } else if map.dark {
+                fg = lit_color(fg, map.light.get(idx).copied());
             }
 
-            ctx.set(x, y, fg, RGB::from_f32(0.0, 0.0, 0.0), glyph);
-        }
-
-        // Next coord
-        x += 1;
-        if x > map.width - 1 {
-            x = 0;
-            y += 1;
+            ctx.set(screen.x, screen.y, fg, palette.background, glyph);
         }
     }
 }
 
-/// Render any entity that has [`Position`] and [`Renderable`].
+/// How `fg` looks under `light`, on a dark level. Tiles that aren't lit at all
+/// are only just visible.
+fn lit_color(fg: RGB, light: Option<RGB>) -> RGB {
+    const AMBIENT: f32 = 0.25;
+    let light = light.unwrap_or_else(|| RGB::from_f32(0.0, 0.0, 0.0));
+    RGB::from_f32(
+        fg.r * (AMBIENT + light.r).min(1.0),
+        fg.g * (AMBIENT + light.g).min(1.0),
+        fg.b * (AMBIENT + light.b).min(1.0),
+    )
+}
+
+/// The glyph and colour to draw the tile at (`x`, `y`) with, in the map's
+/// [`MapTheme`]. Tiles that matter wherever they are, like stairs and water, are
+/// coloured by the [`Palette`] instead.
+fn tile_glyph(map: &Map, palette: &Palette, x: i32, y: i32) -> (rltk::FontCharType, RGB) {
+    let tile = map.tiles[map.xy_idx(x, y)];
+    let (glyph, (r, g, b)) = match (tile, map.theme) {
+        (TileType::DownStairs, _) => return (rltk::to_cp437('>'), palette.stairs),
+        (TileType::UpStairs, _) => return (rltk::to_cp437('<'), palette.stairs),
+        (TileType::ShallowWater, _) => return (rltk::to_cp437('~'), palette.shallow_water),
+        (TileType::DeepWater, _) => return (rltk::to_cp437('≈'), palette.deep_water),
+
+        (TileType::Floor, MapTheme::Dungeon) => (rltk::to_cp437('.'), (0.0, 0.5, 0.5)),
+        (TileType::Wall, MapTheme::Dungeon) => (wall_glyph(map, x, y), (0.0, 1.0, 0.0)),
+
+        (TileType::Floor, MapTheme::Forest) => (rltk::to_cp437('"'), (0.2, 0.5, 0.1)),
+        (TileType::Wall, MapTheme::Forest) => (rltk::to_cp437('♣'), (0.0, 0.7, 0.2)),
+
+        (TileType::Floor, MapTheme::LimestoneCavern) => (rltk::to_cp437('░'), (0.3, 0.3, 0.4)),
+        (TileType::Wall, MapTheme::LimestoneCavern) => (rltk::to_cp437('▓'), (0.7, 0.7, 0.8)),
+
+        (TileType::Floor, MapTheme::DwarfFortress) => (rltk::to_cp437('.'), (0.6, 0.5, 0.3)),
+        (TileType::Wall, MapTheme::DwarfFortress) => (wall_glyph(map, x, y), (0.6, 0.6, 0.6)),
+
+        (TileType::Floor, MapTheme::Hell) => (rltk::to_cp437('.'), (0.6, 0.1, 0.0)),
+        (TileType::Wall, MapTheme::Hell) => (wall_glyph(map, x, y), (1.0, 0.3, 0.0)),
+    };
+    (glyph, RGB::from_f32(r, g, b))
+}
+
+/// Render any entity that has [`Position`] and [`Renderable`], and isn't [`Hidden`].
 pub fn draw_entities(ecs: &World, ctx: &mut Rltk) {
     let positions = ecs.read_storage::<Position>();
     let renderables = ecs.read_storage::<Renderable>();
+    let hidden = ecs.read_storage::<Hidden>();
     let map = ecs.fetch::<Map>();
+    let debug_map_view = ecs.fetch::<Config>().display.debug_map_view;
+    let camera = Camera::new(ecs);
 
-    let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
+    let mut data = (&positions, &renderables, !&hidden)
+        .join()
+        .map(|(pos, render, _)| (pos, render))
+        .collect::<Vec<_>>();
 
     // Sort entities by render order, so we render lower entities underneath higher entities.
-    data.sort_unstable_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
+    data.sort_unstable_by_key(|&(_, render)| std::cmp::Reverse(render.render_order));
 
     for (pos, render) in data {
         // Only render the entity if the player can currently see it!
         let idx = map.xy_idx(pos.x, pos.y);
-        if map.visible_tiles[idx] || DEBUG_MAP_VIEW {
-            ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
+        if map.visible_tiles[idx] || debug_map_view {
+            if let Some(screen) = camera.map_to_screen(Point::new(pos.x, pos.y)) {
+                ctx.set(screen.x, screen.y, render.fg, render.bg, render.glyph);
+            }
+        }
+    }
+}
+
+/// The whole of the current map as text, one line per row, with every entity
+/// that isn't [`Hidden`] drawn on top. Whether the player has seen any of it
+/// doesn't matter, so walls are all drawn as plain `#`s rather than joined up
+/// with their revealed neighbours.
+pub fn map_to_text(ecs: &World) -> String {
+    let map = ecs.fetch::<Map>();
+    let palette = ecs.fetch::<Palette>();
+    let mut glyphs: Vec<rltk::FontCharType> = (0..map.height)
+        .flat_map(|y| (0..map.width).map(move |x| (x, y)))
+        .map(|(x, y)| match map.tiles[map.xy_idx(x, y)] {
+            TileType::Wall => rltk::to_cp437('#'),
+            _ => tile_glyph(&map, &palette, x, y).0,
+        })
+        .collect();
+
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let mut data = (&positions, &renderables, !&hidden)
+        .join()
+        .map(|(pos, render, _)| (pos, render))
+        .collect::<Vec<_>>();
+    data.sort_unstable_by_key(|&(_, render)| std::cmp::Reverse(render.render_order));
+    for (pos, render) in data {
+        glyphs[map.xy_idx(pos.x, pos.y)] = render.glyph;
+    }
+
+    let mut text = String::with_capacity(glyphs.len() + map.height as usize);
+    for row in glyphs.chunks(map.width as usize) {
+        text.extend(row.iter().map(|&glyph| rltk::to_char(glyph as u8)));
+        text.push('\n');
+    }
+    text
+}
+
+/// Draw a tiny health bar under every wounded monster the player can see, as long
+/// as [`MonsterHealthBars`] are on and there's nothing on the tile it would cover.
+pub fn draw_health_bars(ecs: &World, ctx: &mut Rltk) {
+    if !ecs.fetch::<MonsterHealthBars>().enabled {
+        return;
+    }
+
+    let positions = ecs.read_storage::<Position>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let monsters = ecs.read_storage::<Monster>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let map = ecs.fetch::<Map>();
+    let palette = ecs.fetch::<Palette>();
+    let debug_map_view = ecs.fetch::<Config>().display.debug_map_view;
+    let camera = Camera::new(ecs);
+
+    for (pos, stats, _, _) in (&positions, &combat_stats, &monsters, !&hidden).join() {
+        if stats.hp >= stats.max_hp
+            || !(map.visible_tiles[map.xy_idx(pos.x, pos.y)] || debug_map_view)
+        {
+            continue;
+        }
+        let below = Point::new(pos.x, pos.y + 1);
+        if below.y >= map.height || !map.tile_content[map.xy_idx(below.x, below.y)].is_empty() {
+            continue;
+        }
+        let Some(screen) = camera.map_to_screen(below) else {
+            continue;
+        };
+
+        // Fades from green to red (in the standard palette) as the monster gets
+        // closer to death
+        let fg = palette.health(stats.hp.max(0) as f32 / stats.max_hp as f32);
+        ctx.set(
+            screen.x,
+            screen.y,
+            fg,
+            palette.background,
+            rltk::to_cp437('▬'),
+        );
+    }
+}
+
+/// Draw every [`FloatingText`] particle, rising from just above where it was
+/// spawned as it ages.
+pub fn draw_floating_text(ecs: &World, ctx: &mut Rltk) {
+    let positions = ecs.read_storage::<Position>();
+    let texts = ecs.read_storage::<FloatingText>();
+    let lifetimes = ecs.read_storage::<ParticleLifetime>();
+    let map = ecs.fetch::<Map>();
+    let palette = ecs.fetch::<Palette>();
+    let debug_map_view = ecs.fetch::<Config>().display.debug_map_view;
+    let camera = Camera::new(ecs);
+
+    for (pos, text, lifetime) in (&positions, &texts, &lifetimes).join() {
+        if !(map.visible_tiles[map.xy_idx(pos.x, pos.y)] || debug_map_view) {
+            continue;
+        }
+        let Some(screen) = camera.map_to_screen(Point::new(pos.x, pos.y)) else {
+            continue;
+        };
+
+        let age = 1.0 - lifetime.lifetime_ms / text.duration_ms;
+        let rise = 1 + (age * 2.0) as i32;
+        let x = screen.x - text.text.len() as i32 / 2;
+        let y = screen.y - rise;
+        if y >= 0 {
+            ctx.print_color(x, y, text.fg, palette.background, &text.text);
         }
     }
 }
@@ -122,6 +277,20 @@ fn wall_glyph(map: &Map, x: i32, y: i32) -> rltk::FontCharType {
 }
 
 fn is_revealed_and_wall(map: &Map, x: i32, y: i32) -> bool {
+    if x < 0 || y < 0 || x >= map.width || y >= map.height {
+        return false;
+    }
     let idx = map.xy_idx(x, y);
     map.tiles[idx] == TileType::Wall && map.revealed_tiles[idx]
 }
+
+/// Highlight every tile the player has left a note on.
+pub fn draw_map_notes(ecs: &World, ctx: &mut Rltk) {
+    let map = ecs.fetch::<Map>();
+    let palette = ecs.fetch::<Palette>();
+    let camera = Camera::new(ecs);
+
+    for note in map.notes.iter() {
+        camera.set_bg(ctx, Point::new(note.x, note.y), palette.info_dim);
+    }
+}
@@ -0,0 +1,252 @@
+//! Recording runs, and playing them back. Every run writes the player's input to
+//! `replays/last_run.ron` as it goes, frame by frame, after the options (and seed)
+//! that the run was started with. Starting the game with `--replay <file>` starts
+//! the same run again and feeds it the recorded input instead of the player's, which
+//! makes reported bugs easy to reproduce. Once the recording runs out, the player
+//! takes over from wherever it left off. Ghosts come from whatever bones are lying
+//! around locally, so a run that met one won't play back the same anywhere else.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use rltk::{Rltk, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::l10n::tr;
+use crate::{GameLog, RunOptions};
+
+/// Where replays are written to.
+#[cfg(not(target_arch = "wasm32"))]
+const REPLAYS_DIR: &str = "./replays";
+
+/// The replay of the most recently started run.
+const LAST_RUN_PATH: &str = "./replays/last_run.ron";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Unable to create the replays directory `{path}`")]
+    CreateReplaysDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Unable to write the replay to `{path}`")]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to serialize recorded input")]
+    Serialization { source: ron::Error },
+
+    #[error("Unable to read the replay at `{path}`")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Line {line} of the replay at `{path}` is corrupted")]
+    Corrupted {
+        path: PathBuf,
+        line: usize,
+        source: ron::error::SpannedError,
+    },
+
+    #[error("The replay at `{path}` is empty")]
+    Empty { path: PathBuf },
+}
+
+/// The input that the player gave on one frame of a run.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedInput {
+    /// How many frames into the run the input was given.
+    pub frame: u64,
+    /// The turn the game log was on at the time. Only there to help find your
+    /// way around a replay file.
+    pub turn: u32,
+    pub key: Option<VirtualKeyCode>,
+    pub shift: bool,
+    /// Where the mouse was, in pixels.
+    pub mouse_pos: (i32, i32),
+    pub left_click: bool,
+}
+
+/// A run read back from a replay file.
+#[derive(Debug, Clone)]
+pub struct ReplayFile {
+    /// The options (and seed) that the run was started with.
+    pub options: RunOptions,
+    pub inputs: VecDeque<RecordedInput>,
+}
+
+impl ReplayFile {
+    /// Read the replay at `path`: the run's options on the first line, and then
+    /// one recorded input per line.
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let file = File::open(path).map_err(|e| ReplayError::Read {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+
+        let mut lines = Vec::new();
+        for line in BufReader::new(file).lines() {
+            lines.push(line.map_err(|e| ReplayError::Read {
+                path: path.to_path_buf(),
+                source: e,
+            })?);
+        }
+        let corrupted = |line: usize| {
+            move |e| ReplayError::Corrupted {
+                path: path.to_path_buf(),
+                line: line + 1,
+                source: e,
+            }
+        };
+
+        let Some((options, inputs)) = lines.split_first() else {
+            return Err(ReplayError::Empty {
+                path: path.to_path_buf(),
+            });
+        };
+        let options = ron::from_str(options).map_err(corrupted(0))?;
+        let inputs = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, line)| ron::from_str(line).map_err(corrupted(i + 1)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { options, inputs })
+    }
+}
+
+/// Whether the current run's input is being written to a replay file.
+#[derive(Debug, Default)]
+enum Recording {
+    /// Not recording, e.g. because the run was loaded from a save.
+    #[default]
+    Off,
+    /// A run with these options has started, but the replay file won't be created
+    /// until it gets its first frame.
+    Pending(RunOptions),
+    /// Never happens on `wasm32`, where there's nowhere to write replays to.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    Writing(File),
+}
+
+/// Records the current run's input, and plays back a recorded run's input.
+#[derive(Debug, Default)]
+pub struct Replay {
+    /// How many frames the current run has been going for.
+    frame: u64,
+    recording: Recording,
+    /// Recorded input still to be played back, in order.
+    playback: VecDeque<RecordedInput>,
+    last_mouse_pos: (i32, i32),
+}
+
+impl Replay {
+    /// Start recording a new run that was started with `options`.
+    pub fn start_run(&mut self, options: RunOptions) {
+        self.frame = 0;
+        self.recording = Recording::Pending(options);
+    }
+
+    /// Stop recording, and stop any playback. Has to be done whenever a run is
+    /// loaded, since a replay can only start from the beginning of a run.
+    pub fn stop(&mut self) {
+        self.recording = Recording::Off;
+        self.playback.clear();
+    }
+
+    /// Play `inputs` back in place of the player's input, starting from the
+    /// current frame.
+    pub fn play(&mut self, inputs: VecDeque<RecordedInput>) {
+        self.playback = inputs;
+    }
+}
+
+/// Swap this frame's input for the recorded input if a replay is playing, and
+/// then record whatever the input ends up being. Should be called on every frame
+/// of a run, before anything looks at the input.
+pub fn process_input(ecs: &World, ctx: &mut Rltk) -> Result<(), ReplayError> {
+    let mut replay = ecs.fetch_mut::<Replay>();
+    let frame = replay.frame;
+    replay.frame += 1;
+
+    if !replay.playback.is_empty() {
+        let recorded = replay
+            .playback
+            .front()
+            .filter(|input| input.frame == frame)
+            .copied();
+        ctx.key = None;
+        ctx.shift = false;
+        ctx.left_click = false;
+        ctx.mouse_pos = replay.last_mouse_pos;
+        if let Some(input) = recorded {
+            replay.playback.pop_front();
+            ctx.key = input.key;
+            ctx.shift = input.shift;
+            ctx.left_click = input.left_click;
+            ctx.mouse_pos = input.mouse_pos;
+        }
+
+        if replay.playback.is_empty() {
+            ecs.fetch_mut::<GameLog>().log(tr("replay-over"));
+        }
+    }
+
+    if let Recording::Pending(options) = replay.recording {
+        replay.recording = create_replay_file(&options)?;
+    }
+
+    let changed = ctx.key.is_some() || ctx.left_click || ctx.mouse_pos != replay.last_mouse_pos;
+    replay.last_mouse_pos = ctx.mouse_pos;
+    if let (true, Recording::Writing(file)) = (changed, &mut replay.recording) {
+        let input = RecordedInput {
+            frame,
+            turn: ecs.fetch::<GameLog>().turn,
+            key: ctx.key,
+            shift: ctx.shift,
+            mouse_pos: ctx.mouse_pos,
+            left_click: ctx.left_click,
+        };
+        let line = ron::to_string(&input).map_err(|e| ReplayError::Serialization { source: e })?;
+        writeln!(file, "{line}").map_err(|e| ReplayError::Write {
+            path: PathBuf::from(LAST_RUN_PATH),
+            source: e,
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Start `replays/last_run.ron` over for a run started with `options`.
+#[cfg(not(target_arch = "wasm32"))]
+fn create_replay_file(options: &RunOptions) -> Result<Recording, ReplayError> {
+    std::fs::create_dir_all(REPLAYS_DIR).map_err(|e| ReplayError::CreateReplaysDir {
+        path: PathBuf::from(REPLAYS_DIR),
+        source: e,
+    })?;
+    let write_error = |e| ReplayError::Write {
+        path: PathBuf::from(LAST_RUN_PATH),
+        source: e,
+    };
+
+    let mut file = File::create(LAST_RUN_PATH).map_err(write_error)?;
+    let header = ron::to_string(options).map_err(|e| ReplayError::Serialization { source: e })?;
+    writeln!(file, "{header}").map_err(write_error)?;
+
+    Ok(Recording::Writing(file))
+}
+
+/// On `wasm32`, there's nowhere to write replays to. So runs just aren't recorded.
+#[cfg(target_arch = "wasm32")]
+#[inline]
+fn create_replay_file(_options: &RunOptions) -> Result<Recording, ReplayError> {
+    Ok(Recording::Off)
+}
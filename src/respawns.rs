@@ -0,0 +1,100 @@
+//! Monsters that wander onto the level over time, so that clearing out a level
+//! doesn't make it safe forever.
+//!
+//! The [`RespawnTimer`] counts down monster turns, and whenever it runs out a new
+//! monster turns up somewhere out of the player's sight: near the edge of the map,
+//! or in one of the level's rooms. Every level only has so many monsters to give
+//! (see [`Map::respawns_left`]), and deeper levels have more.
+
+use rltk::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::{spawner, Map, PlayerPos, TileType};
+
+/// The fewest turns between two monsters wandering in.
+const MIN_TURNS_BETWEEN_RESPAWNS: i32 = 80;
+
+/// The most turns between two monsters wandering in.
+const MAX_TURNS_BETWEEN_RESPAWNS: i32 = 160;
+
+/// Monsters never wander in this close to the player, even out of sight.
+const RESPAWN_SAFE_DISTANCE: f32 = 12.0;
+
+/// How close to the edge of the map counts as the edge.
+const EDGE_DEPTH: i32 = 4;
+
+/// How many monsters can wander onto a level at `depth`, over the whole run.
+pub const fn respawn_budget(depth: i32) -> i32 {
+    2 + depth
+}
+
+/// Counts down the turns until the next monster wanders in.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RespawnTimer {
+    turns_until_next: i32,
+}
+
+impl Default for RespawnTimer {
+    fn default() -> Self {
+        Self {
+            turns_until_next: MAX_TURNS_BETWEEN_RESPAWNS,
+        }
+    }
+}
+
+/// Tick the [`RespawnTimer`] down by a turn, and bring in a new monster if it's
+/// time and the level has any left to give. Should be run once per monster turn.
+pub fn run_respawns(ecs: &mut World) {
+    // Nothing wanders into town
+    {
+        let map = ecs.fetch::<Map>();
+        if map.depth <= 1 || map.respawns_left <= 0 {
+            return;
+        }
+    }
+
+    let spot = {
+        let mut timer = ecs.write_resource::<RespawnTimer>();
+        timer.turns_until_next -= 1;
+        if timer.turns_until_next > 0 {
+            return;
+        }
+
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        timer.turns_until_next = rng.range(MIN_TURNS_BETWEEN_RESPAWNS, MAX_TURNS_BETWEEN_RESPAWNS);
+
+        let map = ecs.fetch::<Map>();
+        let spots = respawn_spots(&map, **ecs.fetch::<PlayerPos>());
+        (!spots.is_empty()).then(|| spots[rng.range(0, spots.len())])
+    };
+    let Some(idx) = spot else {
+        return;
+    };
+
+    let depth = ecs.fetch::<Map>().depth;
+    if spawner::spawn_wandering_monster(ecs, idx, depth).is_some() {
+        ecs.write_resource::<Map>().respawns_left -= 1;
+    }
+}
+
+/// Every free floor tile that a monster could wander in on: out of the player's
+/// sight and well away from them, and either near the edge of the map or in a room.
+fn respawn_spots(map: &Map, player_pos: rltk::Point) -> Vec<usize> {
+    (0..map.tiles.len())
+        .filter(|&idx| {
+            map.tiles[idx] == TileType::Floor && !map.blocked[idx] && !map.visible_tiles[idx]
+        })
+        .filter(|&idx| {
+            let (x, y) = map.idx_xy(idx);
+            let distance =
+                rltk::DistanceAlg::Pythagoras.distance2d(rltk::Point::new(x, y), player_pos);
+            let near_edge = x.min(y).min(map.width - 1 - x).min(map.height - 1 - y) < EDGE_DEPTH;
+            let in_room = map
+                .rooms
+                .iter()
+                .any(|room| x > room.x1 && x <= room.x2 && y > room.y1 && y <= room.y2);
+            distance >= RESPAWN_SAFE_DISTANCE && (near_edge || in_room)
+        })
+        .collect()
+}
@@ -0,0 +1,78 @@
+//! Vendors in town that get new wares in while the player is off in the dungeon.
+//!
+//! Whenever the player arrives in town, every vendor that [`Restocks`] tops up its
+//! stock once for every [`RESTOCK_TURNS`] turns since it last did, picking from
+//! what's to be found as deep as the player has been. Vendors only have so much
+//! room, so old stock (including anything the player sold them) gets sold on to
+//! somebody else to make space.
+
+use rltk::{RandomNumberGenerator, RGB};
+use specs::prelude::*;
+
+use crate::l10n::{self, tr};
+use crate::{spawner, GameLog, InBackpack, Name, Position, Restocks, RunStats};
+
+/// How many turns it takes a vendor to get new wares in.
+pub const RESTOCK_TURNS: u32 = 500;
+
+/// How many new items a vendor gets in each time it restocks.
+const ITEMS_PER_RESTOCK: usize = 3;
+
+/// The most items a vendor will keep in stock after restocking.
+const MAX_STOCK: usize = 16;
+
+/// Restock every vendor on the current level that's due for it, and let the player
+/// know who has. Should be run whenever the player arrives in town.
+pub fn restock_vendors(ecs: &mut World) {
+    let turn = ecs.fetch::<GameLog>().turn;
+    let depth = ecs.fetch::<RunStats>().deepest_level;
+    let due: Vec<(Entity, u32)> = (
+        &ecs.entities(),
+        &ecs.read_storage::<Restocks>(),
+        &ecs.read_storage::<Position>(),
+    )
+        .join()
+        .map(|(vendor, restocks, _)| {
+            (
+                vendor,
+                turn.saturating_sub(restocks.last_restock) / RESTOCK_TURNS,
+            )
+        })
+        .filter(|(_, restocks)| *restocks > 0)
+        .collect();
+
+    for (vendor, restocks) in due {
+        // Anything more than a full shop's worth would only be sold on again
+        let count = (restocks as usize * ITEMS_PER_RESTOCK).min(MAX_STOCK);
+        spawner::restock_vendor(ecs, vendor, depth, count);
+        sell_off_excess_stock(ecs, vendor);
+
+        if let Some(restocking) = ecs.write_storage::<Restocks>().get_mut(vendor) {
+            restocking.last_restock += restocks * RESTOCK_TURNS;
+        }
+        let vendor_name = ecs
+            .read_storage::<Name>()
+            .get(vendor)
+            .map(|name| l10n::name(&name.name))
+            .unwrap_or_default();
+        ecs.fetch_mut::<GameLog>()
+            .log(tr("vendor-restocked").colored_arg("vendor", vendor_name, RGB::named(rltk::GOLD)));
+    }
+}
+
+/// Get rid of random items from `vendor`'s stock until it's down to [`MAX_STOCK`].
+fn sell_off_excess_stock(ecs: &mut World, vendor: Entity) {
+    let mut stock: Vec<Entity> = (&ecs.entities(), &ecs.read_storage::<InBackpack>())
+        .join()
+        .filter(|(_, pack_item)| pack_item.owner == vendor)
+        .map(|(item, _)| item)
+        .collect();
+
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+    while stock.len() > MAX_STOCK {
+        let item = stock.swap_remove(rng.range(0, stock.len()));
+        ecs.entities()
+            .delete(item)
+            .expect("Unable to delete vendor's old stock");
+    }
+}
@@ -0,0 +1,76 @@
+//! Undoing accidental moves. In casual runs (ones without permadeath), the game
+//! takes a snapshot of itself at the start of every turn using the same
+//! serialization as save files, and the player can rewind a few turns back.
+
+use std::collections::VecDeque;
+
+use specs::prelude::*;
+
+use crate::l10n::tr;
+use crate::saveload_system::{self, LoadGameError, SaveGameError};
+use crate::{GameLog, MapIndexingSystem, RunOptions};
+
+/// How many turns back the player can rewind.
+const REWIND_TURNS: usize = 5;
+
+/// Snapshots of the last few turns, oldest first. The newest one is the turn that's
+/// currently being played.
+#[derive(Debug, Default)]
+pub struct RewindBuffer {
+    snapshots: VecDeque<Vec<u8>>,
+}
+
+/// Remember how the game looks at the start of this turn, so that it can be
+/// rewound to later. Does nothing in runs with permadeath.
+pub fn record_turn(ecs: &mut World) -> Result<(), SaveGameError> {
+    if ecs.fetch::<RunOptions>().permadeath {
+        return Ok(());
+    }
+
+    let snapshot = saveload_system::snapshot(ecs)?;
+    let mut buffer = ecs.write_resource::<RewindBuffer>();
+    buffer.snapshots.push_back(snapshot);
+    while buffer.snapshots.len() > REWIND_TURNS + 1 {
+        buffer.snapshots.pop_front();
+    }
+
+    Ok(())
+}
+
+/// Put the game back the way it was at the start of the previous turn, if there's
+/// a snapshot of it and the run allows it.
+pub fn rewind_turn(ecs: &mut World) -> Result<(), LoadGameError> {
+    if ecs.fetch::<RunOptions>().permadeath {
+        ecs.fetch_mut::<GameLog>().log(tr("rewind-permadeath"));
+        return Ok(());
+    }
+
+    let previous = {
+        let mut buffer = ecs.write_resource::<RewindBuffer>();
+        if buffer.snapshots.len() < 2 {
+            None
+        } else {
+            buffer.snapshots.pop_back();
+            buffer.snapshots.back().cloned()
+        }
+    };
+    let Some(previous) = previous else {
+        ecs.fetch_mut::<GameLog>().log(tr("rewind-too-far"));
+        return Ok(());
+    };
+
+    saveload_system::restore_snapshot(ecs, &previous)?;
+    // Tile contents aren't saved, so fill them back in before the player moves
+    MapIndexingSystem.run_now(ecs);
+    ecs.maintain();
+
+    ecs.fetch_mut::<GameLog>().log(tr("rewind-turn"));
+    Ok(())
+}
+
+/// Forget every recorded turn, e.g. when a different run is loaded, and start
+/// recording again from the current one.
+pub fn reset(ecs: &mut World) -> Result<(), SaveGameError> {
+    ecs.insert(RewindBuffer::default());
+    record_turn(ecs)
+}
@@ -2,77 +2,95 @@ use rltk::RandomNumberGenerator;
 
 /// An entry in a [`RngTable`].
 #[derive(Debug, Clone)]
-pub(crate) struct RngTableEntry {
-    name: String,
+pub(crate) struct RngTableEntry<T> {
+    payload: T,
     weight: i32,
 }
 
-impl RngTableEntry {
-    pub fn new<S: ToString>(name: S, weight: i32) -> Self {
+impl<T> RngTableEntry<T> {
+    pub fn new(payload: impl Into<T>, weight: i32) -> Self {
         Self {
-            name: name.to_string(),
+            payload: payload.into(),
             weight,
         }
     }
 }
 
-impl<S: ToString> From<(S, i32)> for RngTableEntry {
-    fn from((name, weight): (S, i32)) -> Self {
-        Self::new(name, weight)
+impl<T, P: Into<T>> From<(P, i32)> for RngTableEntry<T> {
+    fn from((payload, weight): (P, i32)) -> Self {
+        Self::new(payload, weight)
     }
 }
 
 /// A "spawn table" for defining the relative probabilities of random events occurring.
-#[derive(Default)]
-pub(crate) struct RngTable {
-    entries: Vec<RngTableEntry>,
+/// Each entry carries a payload of type `T`, which is what rolling the table gives back.
+#[derive(Debug, Clone)]
+pub(crate) struct RngTable<T = String> {
+    entries: Vec<RngTableEntry<T>>,
     total_weight: i32,
 }
 
-impl RngTable {
+impl<T> Default for RngTable<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            total_weight: 0,
+        }
+    }
+}
+
+impl<T> RngTable<T> {
     pub fn new() -> Self {
         Self::default()
     }
 
-    /// Add a new entry to the table.
-    pub fn add<S: ToString>(mut self, name: S, weight: i32) -> Self {
-        self.add_entry(RngTableEntry::new(name, weight));
+    /// Add a new entry to the table. Entries with a weight of zero or less can
+    /// never be rolled.
+    pub fn add(mut self, payload: impl Into<T>, weight: i32) -> Self {
+        self.add_entry(RngTableEntry::new(payload, weight));
         self
     }
 
-    fn add_entry(&mut self, entry: RngTableEntry) {
+    /// Replace the weight of every entry with whatever `reweigh` makes of its
+    /// payload and weight.
+    pub fn map_weights(self, reweigh: impl Fn(&T, i32) -> i32) -> Self {
+        let mut this = Self::new();
+        for entry in self.entries {
+            let weight = reweigh(&entry.payload, entry.weight);
+            this.add_entry(RngTableEntry { weight, ..entry });
+        }
+        this
+    }
+
+    fn add_entry(&mut self, mut entry: RngTableEntry<T>) {
+        entry.weight = entry.weight.max(0);
         self.total_weight += entry.weight;
         self.entries.push(entry);
     }
 
-    /// Roll the table for some result. The returned string will be an entry
+    /// Roll the table for some result. The returned payload will be one
     /// previously added with [`RngTable::add()`].
     ///
-    /// If no entries have been added, `None` will be returned.
-    /// `None` will also be returned if every roll for every table entry fails.
-    pub fn roll<'a>(&'a self, rng: &mut RandomNumberGenerator) -> Option<&'a str> {
+    /// If no entries with any weight have been added, `None` will be returned.
+    pub fn roll(&self, rng: &mut RandomNumberGenerator) -> Option<&T> {
         if self.total_weight == 0 {
             return None;
         }
 
         let mut roll = rng.roll_dice(1, self.total_weight) - 1;
-        let mut index = 0;
-
-        while roll > 0 {
-            if roll < self.entries[index].weight {
-                return Some(&self.entries[index].name);
+        for entry in self.entries.iter() {
+            if roll < entry.weight {
+                return Some(&entry.payload);
             }
-
-            roll -= self.entries[index].weight;
-            index += 1;
+            roll -= entry.weight;
         }
 
         None
     }
 }
 
-impl From<&[RngTableEntry]> for RngTable {
-    fn from(entries: &[RngTableEntry]) -> Self {
+impl<T: Clone> From<&[RngTableEntry<T>]> for RngTable<T> {
+    fn from(entries: &[RngTableEntry<T>]) -> Self {
         let mut this = Self::new();
         for entry in entries.iter() {
             this.add_entry(entry.clone());
@@ -80,3 +98,19 @@ impl From<&[RngTableEntry]> for RngTable {
         this
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolls_every_entry_with_weight() {
+        let table: RngTable<i32> = RngTable::new().add(1, 1).add(2, 0).add(3, -5).add(4, 1);
+        let mut rng = RandomNumberGenerator::seeded(7);
+        let mut rolled = [false; 5];
+        for _ in 0..100 {
+            rolled[*table.roll(&mut rng).unwrap() as usize] = true;
+        }
+        assert_eq!(rolled, [false, true, false, false, true]);
+    }
+}
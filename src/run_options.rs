@@ -0,0 +1,191 @@
+//! Options that change how a run plays out. They're picked in the options menu
+//! before the run starts, saved along with it, and locked until the next run.
+
+use std::hash::Hasher;
+use std::ops::{Deref, DerefMut};
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::game_events::{GameEvent, GameEventListener};
+use crate::l10n::{tr, Translation};
+use crate::{GameLog, RunStats};
+
+/// Seeds are picked from below this, so that they're short enough to read out.
+pub const MAX_SEED: u64 = 1_000_000_000;
+
+/// How hard the dungeon's inhabitants are.
+#[derive(
+    PartialEq,
+    Eq,
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumIter,
+)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// The difficulty after this one, wrapping back around to the easiest.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Easy => Self::Normal,
+            Self::Normal => Self::Hard,
+            Self::Hard => Self::Easy,
+        }
+    }
+
+    /// How many extra monsters and items are spawned into each room.
+    pub fn extra_spawns(self) -> i32 {
+        match self {
+            Self::Easy => -1,
+            Self::Normal => 0,
+            Self::Hard => 1,
+        }
+    }
+
+    /// Scale a monster's hit points to suit the difficulty.
+    pub fn scale_monster_hp(self, hp: i32) -> i32 {
+        match self {
+            Self::Easy => hp * 3 / 4,
+            Self::Normal => hp,
+            Self::Hard => hp * 5 / 4,
+        }
+    }
+}
+
+/// The options the current run was started with.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RunOptions {
+    pub difficulty: Difficulty,
+    /// Whether loading a save deletes it, so that death is final.
+    pub permadeath: bool,
+    /// Whether to log gameplay tips as the player goes deeper.
+    pub hints: bool,
+    /// What the run's random number generator was seeded with.
+    pub seed: u64,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::default(),
+            permadeath: true,
+            hints: true,
+            seed: 0,
+        }
+    }
+}
+
+impl RunOptions {
+    /// A short description of the options, e.g. `Normal, permadeath, seed 1234`.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![self.difficulty.to_string()];
+        if self.permadeath {
+            parts.push("permadeath".to_string());
+        }
+        if self.hints {
+            parts.push("hints".to_string());
+        }
+        parts.push(format!("seed {}", self.seed));
+        parts.join(", ")
+    }
+}
+
+/// The options that the next new run will start with. Unlike [`RunOptions`], these
+/// can be changed at any time from the options menu.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct NewRunOptions {
+    pub options: RunOptions,
+    /// The seed that every new run starts from, to replay the same dungeon. New
+    /// runs get a random seed if this is `None`.
+    pub fixed_seed: Option<u64>,
+}
+
+impl NewRunOptions {
+    /// The seed for the next new run: the fixed one if there is one, or a random
+    /// one if not.
+    pub fn next_seed(&self) -> u64 {
+        self.fixed_seed
+            .unwrap_or_else(|| rltk::RandomNumberGenerator::new().range(0, MAX_SEED))
+    }
+}
+
+impl Deref for NewRunOptions {
+    type Target = RunOptions;
+
+    fn deref(&self) -> &Self::Target {
+        &self.options
+    }
+}
+
+impl DerefMut for NewRunOptions {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.options
+    }
+}
+
+/// Turn whatever the player typed in as a seed into one. Numbers are used as-is,
+/// and anything else (like a date, for a daily challenge) is hashed into a number.
+/// Blank text means a random seed.
+pub fn parse_seed(text: &str) -> Option<u64> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    text.parse().ok().or_else(|| {
+        let mut hasher = rustc_hash::FxHasher::default();
+        hasher.write(text.as_bytes());
+        Some(hasher.finish() % MAX_SEED)
+    })
+}
+
+/// The tip logged when the player first reaches `depth`, if there is one and
+/// [`RunOptions::hints`] are on.
+fn hint(depth: i32) -> Option<Translation> {
+    let hint = match depth {
+        1 => tr("hint-townsfolk"),
+        2 => tr("hint-backpack"),
+        3 => tr("hint-look-around"),
+        4 => tr("hint-surrender"),
+        _ => return None,
+    };
+    Some(hint)
+}
+
+/// Logs a hint the first time the player reaches a depth that has one. Has to be
+/// subscribed before [`crate::game_events::RunStatsListener`], so that it can tell
+/// new depths from ones the player is returning to.
+pub struct HintListener;
+
+impl GameEventListener for HintListener {
+    fn on_event(&mut self, ecs: &World, event: &GameEvent) {
+        if let GameEvent::LevelEntered { depth } = event {
+            if *depth > ecs.fetch::<RunStats>().deepest_level {
+                log_hint(ecs, *depth);
+            }
+        }
+    }
+}
+
+/// Log the hint for `depth`, if there is one and hints are on.
+pub fn log_hint(ecs: &World, depth: i32) {
+    if !ecs.fetch::<RunOptions>().hints {
+        return;
+    }
+    if let Some(hint) = hint(depth) {
+        ecs.write_resource::<GameLog>()
+            .entry()
+            .colored_message(&hint, rltk::RGB::named(rltk::CYAN))
+            .log();
+    }
+}
@@ -1,13 +1,83 @@
+use std::borrow::Cow;
 use std::convert::Infallible;
-use std::fs::File;
-use std::path::Path;
+use std::io::Write;
 
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use specs::saveload::{
     DeserializeComponents, MarkedBuilder, SerializeComponents, SimpleMarker, SimpleMarkerAllocator,
 };
 
-use crate::{components::*, PlayerEntity, PlayerPos};
+use crate::{components::*, storage, LevelId, Map, PlayerEntity, PlayerPos, RunOptions};
+
+/// The version of the save format that this build writes. Bump it whenever the
+/// serialized components or resources change, and teach [`migrate()`] how to
+/// upgrade saves from the version before.
+///
+/// 1. Saves from before the format was versioned.
+/// 2. Added [`SaveHeader::version`].
+/// 3. Added [`SaveHeader::format`].
+/// 4. Added [`Description`].
+/// 5. Added [`Bystander`] and [`Quips`].
+/// 6. Added [`QuestGiver`] and [`ExtraSerializationHelper`].
+/// 7. Added [`LevelExit`].
+/// 8. Added [`Swimmer`].
+/// 9. Added [`LightSource`].
+/// 10. Added [`Faction`].
+/// 11. Added [`GroupLeader`] and [`PackMember`].
+/// 12. Added [`Boss`] and [`SpecialAbilities`].
+/// 13. Added [`Weight`].
+/// 14. Added [`Container`] and [`WantsToTransferItem`].
+/// 15. Added [`Shatters`] and [`WantsToThrowItem`].
+/// 16. Added [`ProvidesMapping`].
+/// 17. Added [`ProvidesTownPortal`] and [`Portal`].
+/// 18. Added [`HungerClock`] and [`ProvidesFood`].
+/// 19. Added [`BlockChance`].
+/// 20. Added [`MeleeAttack`].
+/// 21. Added [`CritRangeBonus`].
+/// 22. Added [`Rarity`].
+/// 23. Added [`Affixed`].
+/// 24. Added [`Enchants`].
+/// 25. Added [`Restocks`].
+/// 26. Added [`Enchanted`].
+pub(crate) const SAVE_VERSION: u32 = 26;
+
+/// How the body of a save (everything after the header) is encoded.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize, strum::Display)]
+pub(crate) enum SaveFormat {
+    /// Plain RON text. Easy to read, but big and slow for late-game worlds.
+    #[default]
+    Ron,
+    /// Bincode, compressed with DEFLATE. Only written and read by builds with the
+    /// `compressed-saves` feature.
+    Compressed,
+}
+
+/// The format that this build writes saves in.
+const SAVE_FORMAT: SaveFormat = if cfg!(feature = "compressed-saves") {
+    SaveFormat::Compressed
+} else {
+    SaveFormat::Ron
+};
+
+/// Where the player's manual saves go, one file per slot.
+const SAVES_DIR: &str = "./saves";
+
+/// How many games can be saved at once.
+pub(crate) const SAVE_SLOTS: usize = 3;
+
+/// Where the game autosaves to while a session is running, in case it crashes.
+const AUTOSAVE_PATH: &str = "./autosave.ron";
+
+/// What in-memory snapshots are called in errors, in place of a file path.
+const SNAPSHOT_PATH: &str = "<snapshot>";
+
+/// Where characters that died leave their bones, one file per level.
+const BONES_DIR: &str = "./bones";
+
+/// Exists for as long as a session is running. If it's still around on startup,
+/// the game didn't shut down cleanly.
+const LOCK_PATH: &str = "./session.lock";
 
 #[derive(Debug, thiserror::Error)]
 pub(crate) enum SaveGameError {
@@ -16,8 +86,8 @@ pub(crate) enum SaveGameError {
         source: Box<dyn std::error::Error + Send + Sync>,
     },
 
-    #[error("Unable to create and/or open `{path}` for writing")]
-    FileCreation {
+    #[error("Unable to write the save to `{path}`")]
+    Write {
         path: std::path::PathBuf,
         source: std::io::Error,
     },
@@ -26,6 +96,86 @@ pub(crate) enum SaveGameError {
     SerializerInit {
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+
+    #[error("Unable to write the save's header to `{path}`")]
+    WriteHeader {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+
+    #[cfg(feature = "compressed-saves")]
+    #[error("Unable to finish writing the save to `{path}`")]
+    WriteBody {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// What's written on the first line of every save, so that it can be described
+/// without loading the whole game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SaveHeader {
+    /// The [`SAVE_VERSION`] of the build that wrote the save.
+    #[serde(default = "unversioned")]
+    pub version: u32,
+    /// How the rest of the save is encoded.
+    #[serde(default)]
+    pub format: SaveFormat,
+    /// The options the saved run was started with.
+    pub run_options: RunOptions,
+    /// The player character's name.
+    pub name: String,
+    /// How deep the player was when they saved.
+    pub depth: i32,
+    /// When the game was saved, in seconds since the Unix epoch.
+    pub saved_at: u64,
+}
+
+/// The version of saves from before the header recorded one.
+const fn unversioned() -> u32 {
+    1
+}
+
+impl SaveHeader {
+    /// Describe the game that's being saved in `ecs`.
+    fn describe(ecs: &World) -> Self {
+        let player = **ecs.fetch::<PlayerEntity>();
+        Self {
+            version: SAVE_VERSION,
+            format: SAVE_FORMAT,
+            run_options: *ecs.fetch::<RunOptions>(),
+            name: ecs
+                .read_storage::<Name>()
+                .get(player)
+                .map(|name| name.to_string())
+                .unwrap_or_default(),
+            depth: ecs.fetch::<Map>().depth,
+            saved_at: storage::unix_time(),
+        }
+    }
+
+    /// Whether this build knows how to load the save. Saves written by newer
+    /// builds can't be loaded. Compressed saves can only be loaded by builds with
+    /// the `compressed-saves` feature, and can't be migrated, so they also have to
+    /// be from the current version.
+    pub fn is_compatible(&self) -> bool {
+        match self.format {
+            SaveFormat::Ron => self.version <= SAVE_VERSION,
+            SaveFormat::Compressed => {
+                cfg!(feature = "compressed-saves") && self.version == SAVE_VERSION
+            }
+        }
+    }
+}
+
+/// Where the bones of the last character to die on `level` are kept.
+fn bones_path(level: LevelId) -> String {
+    format!("{BONES_DIR}/{:?}_{}.ron", level.branch, level.depth)
+}
+
+/// Where the game in save slot `slot` (counting from zero) is kept.
+fn slot_path(slot: usize) -> String {
+    format!("{SAVES_DIR}/slot_{}.ron", slot + 1)
 }
 
 macro_rules! serialize_individually {
@@ -48,65 +198,269 @@ macro_rules! serialize_individually {
     };
 }
 
-/// On `wasm32`, saving isn't implemented yet. So this function is a no-op.
-#[cfg(target_arch = "wasm32")]
-#[inline]
-pub(crate) fn save_game(_ecs: &mut specs::World) -> Result<(), SaveGameError> {
-    // no-op
-    Ok(())
+/// Save the game to `saves/slot_N.ron`, overwriting whatever was in save slot
+/// `slot` (counting from zero).
+pub(crate) fn save_game(ecs: &mut specs::World, slot: usize) -> Result<(), SaveGameError> {
+    save_to(ecs, &slot_path(slot))
 }
 
-/// Save the game to `$PWD/savegame.ron`.
-///
-/// Does nothing on `wasm32`.
-#[cfg(not(target_arch = "wasm32"))]
-pub(crate) fn save_game(ecs: &mut specs::World) -> Result<(), SaveGameError> {
-    // Temporarily add a copy of the Map to the ECS world so that it gets serialized with
+/// Save the game to `autosave.ron`, so that it can be recovered if the game
+/// crashes.
+pub(crate) fn autosave(ecs: &mut specs::World) -> Result<(), SaveGameError> {
+    save_to(ecs, AUTOSAVE_PATH)
+}
+
+/// Save the game to `path` in [`storage`]. The whole save is put together in
+/// memory first, so that a save that fails partway doesn't clobber the last one.
+fn save_to(ecs: &mut specs::World, path: &str) -> Result<(), SaveGameError> {
+    let mut save = Vec::new();
+    write_world(ecs, &mut save, path)?;
+    storage::write(path, &save).map_err(|e| SaveGameError::Write {
+        path: std::path::PathBuf::from(path),
+        source: e,
+    })
+}
+
+/// Take an in-memory snapshot of the game, in the same format as a save file. See
+/// [`restore_snapshot()`].
+pub(crate) fn snapshot(ecs: &mut specs::World) -> Result<Vec<u8>, SaveGameError> {
+    let mut snapshot = Vec::new();
+    write_world(ecs, &mut snapshot, SNAPSHOT_PATH)?;
+    Ok(snapshot)
+}
+
+/// Serialize the whole game into `writer`. `path` is only used to describe where
+/// it's going in errors.
+fn write_world<W: Write>(
+    ecs: &mut specs::World,
+    mut writer: W,
+    path: &str,
+) -> Result<(), SaveGameError> {
+    // Temporarily add copies of the Map, RunStats, MasterDungeonMap, MasterItemNames,
+    // LevelEventScheduler, and Reputation to the ECS world so that they get serialized with
     // everything else.
     let map_copy = ecs.get_mut::<crate::map::Map>().unwrap().clone();
+    let run_stats_copy = (*ecs.fetch::<crate::RunStats>()).clone();
+    let dungeon_master_copy = (*ecs.fetch::<crate::MasterDungeonMap>()).clone();
+    let item_names_copy = (*ecs.fetch::<crate::MasterItemNames>()).clone();
+    let level_events_copy = *ecs.fetch::<crate::LevelEventScheduler>();
+    let reputation_copy = *ecs.fetch::<crate::Reputation>();
+    let quest_journal_copy = (*ecs.fetch::<crate::QuestJournal>()).clone();
+    let respawns_copy = *ecs.fetch::<crate::respawns::RespawnTimer>();
+    let extra_save_helper = ecs
+        .create_entity()
+        .with(ExtraSerializationHelper {
+            quest_journal: quest_journal_copy,
+            respawns: respawns_copy,
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build();
     let save_helper = ecs
         .create_entity()
-        .with(SerializationHelper { map: map_copy })
+        .with(SerializationHelper {
+            map: map_copy,
+            run_stats: run_stats_copy,
+            dungeon_master: dungeon_master_copy,
+            item_names: item_names_copy,
+            level_events: level_events_copy,
+            reputation: reputation_copy,
+        })
         .marked::<SimpleMarker<Serializable>>()
         .build();
 
     // Actually serialize (need a scope for borrow checker)
     {
-        let data = (
-            ecs.entities(),
-            ecs.read_storage::<SimpleMarker<Serializable>>(),
-        );
-
-        let writer = File::create("./savegame.ron").map_err(|e| SaveGameError::FileCreation {
-            path: std::path::PathBuf::from("./savegame.ron"),
+        // The header goes on the first line by itself, so that the main menu can
+        // describe the save without loading the whole thing
+        let header = ron::to_string(&SaveHeader::describe(ecs)).map_err(|e| {
+            SaveGameError::Serialization {
+                source: Box::new(e),
+            }
+        })?;
+        writeln!(writer, "{header}").map_err(|e| SaveGameError::WriteHeader {
+            path: std::path::PathBuf::from(path),
             source: e,
         })?;
-        let mut serializer =
-            ron::Serializer::new(writer, None).map_err(|e| SaveGameError::SerializerInit {
-                source: Box::new(e),
-            })?;
 
-        serialize_individually!(
-            ecs, serializer, data;
-            [
-                Position, Renderable, Player, Viewshed, Monster, Name, BlocksTile, CombatStats,
-                SufferDamage, WantsToMelee, Item, Consumable, Ranged, InflictsDamage, AreaOfEffect,
-                Confusion, ProvidesHealing, InBackpack, WantsToPickupItem, WantsToUseItem,
-                WantsToDropItem, SerializationHelper
-            ]
-        )?;
+        match SAVE_FORMAT {
+            SaveFormat::Ron => {
+                let mut serializer = ron::Serializer::new(writer, None).map_err(|e| {
+                    SaveGameError::SerializerInit {
+                        source: Box::new(e),
+                    }
+                })?;
+                serialize_components(ecs, &mut serializer)?;
+            }
+
+            #[cfg(feature = "compressed-saves")]
+            SaveFormat::Compressed => {
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(writer, flate2::Compression::default());
+                let mut serializer =
+                    bincode::Serializer::new(&mut encoder, bincode::DefaultOptions::new());
+                serialize_components(ecs, &mut serializer)?;
+                encoder.finish().map_err(|e| SaveGameError::WriteBody {
+                    path: std::path::PathBuf::from(path),
+                    source: e,
+                })?;
+            }
+
+            #[cfg(not(feature = "compressed-saves"))]
+            SaveFormat::Compressed => {
+                unreachable!(
+                    "Compressed saves are only written with the `compressed-saves` feature"
+                )
+            }
+        }
     }
 
     // Remove the temporary map copy.
     ecs.delete_entity(save_helper)
         .expect("Unable to delete temporary copy of map from ECS world (this should never happen)");
+    ecs.delete_entity(extra_save_helper).expect(
+        "Unable to delete temporary copy of resources from ECS world (this should never happen)",
+    );
+
+    Ok(())
+}
+
+/// Serialize every marked entity's components with `serializer`, in whichever
+/// format it writes.
+fn serialize_components<S, E>(ecs: &World, serializer: &mut S) -> Result<(), SaveGameError>
+where
+    for<'a> &'a mut S: serde::Serializer<Ok = (), Error = E>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let data = (
+        ecs.entities(),
+        ecs.read_storage::<SimpleMarker<Serializable>>(),
+    );
+    serialize_individually!(
+        ecs, *serializer, data;
+        [
+            Position, Renderable, Player, Viewshed, Monster, Name, BlocksTile, CombatStats,
+            WantsToMelee, Item, Consumable, Ranged, InflictsDamage, AreaOfEffect,
+            InflictsStatusEffect, StatusEffects, ProvidesHealing, InBackpack, WantsToPickupItem,
+            WantsToUseItem,
+            WantsToDropItem, Equippable, Equipped, MeleePowerBonus, DefenseBonus, HomeLocation,
+            Hoarder, RangedAttack, HeardNoise, MakesNoise, WantsToMakeNoise,
+            Purse, ServiceNpc, Hidden, EntryTrigger, SingleActivation, EntityMoved,
+            Stash, InStash, OtherLevelPosition, WinsGame, Mana, Spell, KnownSpells,
+            Experience, Attributes, Skills, Perks, Morale, Leader, AiState, Gold, Value, Vendor,
+            ParticleLifetime, LastSeenPlayer, Guard,
+            SerializationHelper,
+            // Components added since version 3 go after the helper, so that older
+            // saves can be migrated by tacking empty storages onto the end
+            Description, Bystander, Quips, QuestGiver, ExtraSerializationHelper, LevelExit,
+            Swimmer, LightSource, Faction, GroupLeader, PackMember, Boss, SpecialAbilities, Weight, Container,
+            WantsToTransferItem, Shatters, WantsToThrowItem, ProvidesMapping,
+            ProvidesTownPortal, Portal, HungerClock, ProvidesFood, BlockChance,
+            MeleeAttack, CritRangeBonus, Rarity, Affixed, Enchants, Restocks, Enchanted,
+        ]
+    )?;
+
+    Ok(())
+}
+
+/// What's in every save slot, and whether there's a session to recover, as of
+/// the last [`SaveIndex::scan()`]. The menus read this every frame instead of
+/// going through [`storage`] each time.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SaveIndex {
+    /// What's in every save slot, in order. Empty slots (and saves too damaged to
+    /// read) are `None`.
+    pub slots: Vec<Option<SaveHeader>>,
+    /// The options that a session left over in `autosave.ron` was started with, if
+    /// it can be recovered. See [`SaveIndex::can_recover_session()`].
+    pub recoverable: Option<RunOptions>,
+}
+
+impl SaveIndex {
+    /// Look through storage for every save.
+    pub fn scan() -> Self {
+        let slots: Vec<Option<SaveHeader>> = (0..SAVE_SLOTS)
+            .map(|slot| read_header(&slot_path(slot)))
+            .collect();
+
+        // An autosave can be recovered if this build can load it, and the session
+        // it's from didn't end cleanly: either its lockfile is still around, or
+        // it's newer than the last manual save
+        let last_saved = slots.iter().flatten().map(|header| header.saved_at).max();
+        let recoverable = read_header(AUTOSAVE_PATH)
+            .filter(SaveHeader::is_compatible)
+            .filter(|autosave| {
+                storage::exists(LOCK_PATH)
+                    || last_saved.is_none_or(|saved| autosave.saved_at > saved)
+            })
+            .map(|autosave| autosave.run_options);
+
+        Self { slots, recoverable }
+    }
+
+    /// Returns true if there's a game saved in any of the save slots.
+    pub fn any_saved(&self) -> bool {
+        self.slots.iter().any(Option::is_some)
+    }
+
+    /// Returns true if there's an autosave left over from a session that didn't
+    /// end cleanly, and that this build can load.
+    pub fn can_recover_session(&self) -> bool {
+        self.recoverable.is_some()
+    }
+}
+
+/// Read just the [`SaveHeader`] off of the first line of the save at `path`.
+fn read_header(path: &str) -> Option<SaveHeader> {
+    let save = storage::read(path).ok()?;
+    let header = save.split(|&byte| byte == b'\n').next()?;
+    ron::de::from_bytes(header).ok()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    #[error("Unable to autosave at the start of the session")]
+    Autosave {
+        #[from]
+        source: SaveGameError,
+    },
+
+    #[error("Unable to update the session lockfile `{path}`")]
+    Lockfile {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Could not delete autosave at `{path}`")]
+    DeleteAutosave {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Mark a session as running, and autosave it straight away so that there's
+/// always something to recover if it crashes.
+pub(crate) fn begin_session(ecs: &mut World) -> Result<(), SessionError> {
+    storage::write(LOCK_PATH, &[]).map_err(|e| SessionError::Lockfile {
+        path: std::path::PathBuf::from(LOCK_PATH),
+        source: e,
+    })?;
+    autosave(ecs)?;
 
     Ok(())
 }
 
-/// Returns true if the file `savegame.ron` exists in the current working directory.
-pub(crate) fn does_save_exist() -> bool {
-    Path::new("./savegame.ron").exists()
+/// Mark the running session as having ended cleanly, and throw away its autosave.
+pub(crate) fn end_session() -> Result<(), SessionError> {
+    storage::remove(LOCK_PATH).map_err(|e| SessionError::Lockfile {
+        path: std::path::PathBuf::from(LOCK_PATH),
+        source: e,
+    })?;
+    storage::remove(AUTOSAVE_PATH).map_err(|e| SessionError::DeleteAutosave {
+        path: std::path::PathBuf::from(AUTOSAVE_PATH),
+        source: e,
+    })?;
+
+    Ok(())
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -129,6 +483,26 @@ pub enum LoadGameError {
 
     #[error("Could not find game map in `{savegame_path}`. The game save may be corrupted.")]
     NoMapFound { savegame_path: std::path::PathBuf },
+
+    #[error("Could not find the header in `{savegame_path}`. The game save may be corrupted.")]
+    NoHeaderFound { savegame_path: std::path::PathBuf },
+
+    #[error(
+        "`{savegame_path}` is an incompatible save ({format} format version {version}), and \
+        can't be loaded by this version of the game."
+    )]
+    IncompatibleSave {
+        savegame_path: std::path::PathBuf,
+        version: u32,
+        format: SaveFormat,
+    },
+
+    #[cfg(feature = "compressed-saves")]
+    #[error("Unable to decompress the save at `{path}`")]
+    Decompression {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
 }
 
 macro_rules! deserialize_individually {
@@ -155,7 +529,63 @@ macro_rules! deserialize_individually {
     };
 }
 
-pub(crate) fn load_game(ecs: &mut World) -> Result<(), LoadGameError> {
+/// Load the game in save slot `slot` (counting from zero).
+pub(crate) fn load_game(ecs: &mut World, slot: usize) -> Result<(), LoadGameError> {
+    load_from(ecs, &slot_path(slot))
+}
+
+/// Load the game from `autosave.ron`, recovering a session that crashed.
+pub(crate) fn recover_session(ecs: &mut World) -> Result<(), LoadGameError> {
+    load_from(ecs, AUTOSAVE_PATH)
+}
+
+fn load_from(ecs: &mut World, path: &str) -> Result<(), LoadGameError> {
+    let data = storage::read(path).map_err(|e| LoadGameError::OpenFile {
+        path: std::path::PathBuf::from(path),
+        source: e,
+    })?;
+    read_world(ecs, &data, path)
+}
+
+/// Put the game back the way it was when `snapshot` was taken with [`snapshot()`].
+pub(crate) fn restore_snapshot(ecs: &mut World, snapshot: &[u8]) -> Result<(), LoadGameError> {
+    read_world(ecs, snapshot, SNAPSHOT_PATH)
+}
+
+/// Replace everything in the game with what's serialized in `data`. `path` is only
+/// used to describe where it came from in errors.
+fn read_world(ecs: &mut World, data: &[u8], path: &str) -> Result<(), LoadGameError> {
+    // Check that the save can be loaded before throwing away the current game
+    let Some(header_end) = data.iter().position(|&byte| byte == b'\n') else {
+        return Err(LoadGameError::NoHeaderFound {
+            savegame_path: std::path::PathBuf::from(path),
+        });
+    };
+    let (header, data) = (&data[..header_end], &data[header_end + 1..]);
+    let header: SaveHeader =
+        ron::de::from_bytes(header).map_err(|e| LoadGameError::Deserialization {
+            source: Box::new(e),
+        })?;
+    if !header.is_compatible() {
+        return Err(LoadGameError::IncompatibleSave {
+            savegame_path: std::path::PathBuf::from(path),
+            version: header.version,
+            format: header.format,
+        });
+    }
+    let data = match header.format {
+        SaveFormat::Ron => {
+            let data = std::str::from_utf8(data).map_err(|e| LoadGameError::Deserialization {
+                source: Box::new(e),
+            })?;
+            migrate(header.version, data, path)?
+                .into_owned()
+                .into_bytes()
+        }
+        SaveFormat::Compressed => inflate(data, path)?,
+    };
+    check_body(header.format, &data, path)?;
+
     // Delete every single entity
     {
         let to_delete = ecs.entities().join().collect::<Vec<_>>();
@@ -165,31 +595,9 @@ pub(crate) fn load_game(ecs: &mut World) -> Result<(), LoadGameError> {
         }
     }
 
-    // Read the savegame file and deserialize it into the ECS
-    let data = std::fs::read_to_string("./savegame.ron").map_err(|e| LoadGameError::OpenFile {
-        path: std::path::PathBuf::from("./savegame.ron"),
-        source: e,
-    })?;
-    let mut de =
-        ron::Deserializer::from_str(&data).map_err(|e| LoadGameError::DeserializerInit {
-            source: Box::new(e),
-        })?;
-
-    {
-        deserialize_individually!(
-            ecs,
-            de,
-            &ecs.entities(),
-            &mut ecs.write_storage::<SimpleMarker<Serializable>>(),
-            &mut ecs.write_resource::<SimpleMarkerAllocator<Serializable>>();
-            [
-                Position, Renderable, Player, Viewshed, Monster, Name, BlocksTile, CombatStats,
-                SufferDamage, WantsToMelee, Item, Consumable, Ranged, InflictsDamage, AreaOfEffect,
-                Confusion, ProvidesHealing, InBackpack, WantsToPickupItem, WantsToUseItem,
-                WantsToDropItem, SerializationHelper
-            ]
-        )?;
-    }
+    // Deserialize the save data into the ECS
+    ecs.insert(header.run_options);
+    deserialize_body(ecs, header.format, &data)?;
 
     // Find the map and player to add them to the ECS as resources
     let mut serialization_helper_entity: Option<Entity> = None;
@@ -210,7 +618,15 @@ pub(crate) fn load_game(ecs: &mut World) -> Result<(), LoadGameError> {
             // The per-tile entity content vector isn't serialized/deserialized.
             // This will be rebuilt every tick anyways, so just allocate an
             // empty vector in the newly-loaded map.
-            level_map.tile_content = vec![Vec::new(); super::map::MAPSIZE];
+            level_map.tile_content = vec![Vec::new(); level_map.tiles.len()];
+
+            *ecs.write_resource::<crate::RunStats>() = serialization_helper.run_stats.clone();
+            *ecs.write_resource::<crate::MasterDungeonMap>() =
+                serialization_helper.dungeon_master.clone();
+            *ecs.write_resource::<crate::MasterItemNames>() =
+                serialization_helper.item_names.clone();
+            *ecs.write_resource::<crate::LevelEventScheduler>() = serialization_helper.level_events;
+            *ecs.write_resource::<crate::Reputation>() = serialization_helper.reputation;
 
             // Queue the temporary SerializationHelper entity for deletion.
             serialization_helper_entity = Some(entity);
@@ -225,19 +641,211 @@ pub(crate) fn load_game(ecs: &mut World) -> Result<(), LoadGameError> {
             *player_resource = PlayerEntity(entity);
         }
     }
+    // Resources that came after the SerializationHelper are in an extra helper,
+    // unless the save predates them
+    let extra_helper = {
+        let entities = ecs.entities();
+        let extra_helpers = ecs.read_storage::<ExtraSerializationHelper>();
+        (&entities, &extra_helpers)
+            .join()
+            .map(|(entity, helper)| (entity, helper.clone()))
+            .next()
+    };
+    let extras = match extra_helper {
+        Some((ent, extras)) => {
+            ecs.delete_entity(ent)
+                .expect("Unable to delete temporary resource helper entity from ECS");
+            extras
+        }
+        None => ExtraSerializationHelper::default(),
+    };
+    ecs.insert(extras.quest_journal);
+    ecs.insert(extras.respawns);
+
     // Delete the serialization helper entity. If we never found one,
     // then return an error (instead of trying to regenerate the world or something)
     if let Some(ent) = serialization_helper_entity {
         ecs.delete_entity(ent).expect("Somehow unable to delete temporary Map serialization helper entity from ECS even though we found it in the ECS (this should never ever happen)");
     } else {
         return Err(LoadGameError::NoMapFound {
-            savegame_path: std::path::PathBuf::from("./savegame.ron"),
+            savegame_path: std::path::PathBuf::from(path),
         });
     }
 
     Ok(())
 }
 
+/// Make sure that the whole (migrated) body of a save loads, by deserializing it
+/// into a world of its own. Loading it straight into the game's world could fail
+/// halfway, after everything that was already there had been thrown away.
+fn check_body(format: SaveFormat, data: &[u8], path: &str) -> Result<(), LoadGameError> {
+    let mut scratch = World::new();
+    register_all_components(&mut scratch);
+    scratch.insert(SimpleMarkerAllocator::<Serializable>::new());
+    deserialize_body(&mut scratch, format, data)?;
+
+    if scratch.read_storage::<SerializationHelper>().is_empty() {
+        return Err(LoadGameError::NoMapFound {
+            savegame_path: std::path::PathBuf::from(path),
+        });
+    }
+    Ok(())
+}
+
+/// Deserialize the body of a save, in `format`, into the ECS.
+fn deserialize_body(ecs: &mut World, format: SaveFormat, data: &[u8]) -> Result<(), LoadGameError> {
+    match format {
+        SaveFormat::Ron => {
+            let mut de = ron::Deserializer::from_bytes(data).map_err(|e| {
+                LoadGameError::DeserializerInit {
+                    source: Box::new(e),
+                }
+            })?;
+            deserialize_components(ecs, &mut de)
+        }
+
+        #[cfg(feature = "compressed-saves")]
+        SaveFormat::Compressed => {
+            let mut de = bincode::Deserializer::from_slice(data, bincode::DefaultOptions::new());
+            deserialize_components(ecs, &mut de)
+        }
+
+        #[cfg(not(feature = "compressed-saves"))]
+        SaveFormat::Compressed => {
+            unreachable!("Compressed saves are rejected as incompatible without the `compressed-saves` feature")
+        }
+    }
+}
+
+/// Deserialize every component saved in `de` into the ECS, in whichever format it
+/// reads.
+fn deserialize_components<'de, D, E>(ecs: &mut World, de: &mut D) -> Result<(), LoadGameError>
+where
+    for<'a> &'a mut D: serde::Deserializer<'de, Error = E>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    deserialize_individually!(
+        ecs,
+        *de,
+        &ecs.entities(),
+        &mut ecs.write_storage::<SimpleMarker<Serializable>>(),
+        &mut ecs.write_resource::<SimpleMarkerAllocator<Serializable>>();
+        [
+            Position, Renderable, Player, Viewshed, Monster, Name, BlocksTile, CombatStats,
+            WantsToMelee, Item, Consumable, Ranged, InflictsDamage, AreaOfEffect,
+            InflictsStatusEffect, StatusEffects, ProvidesHealing, InBackpack, WantsToPickupItem,
+            WantsToUseItem,
+            WantsToDropItem, Equippable, Equipped, MeleePowerBonus, DefenseBonus, HomeLocation,
+            Hoarder, RangedAttack, HeardNoise, MakesNoise, WantsToMakeNoise,
+            Purse, ServiceNpc, Hidden, EntryTrigger, SingleActivation, EntityMoved,
+            Stash, InStash, OtherLevelPosition, WinsGame, Mana, Spell, KnownSpells,
+            Experience, Attributes, Skills, Perks, Morale, Leader, AiState, Gold, Value, Vendor,
+            ParticleLifetime, LastSeenPlayer, Guard,
+            SerializationHelper,
+            // Components added since version 3 go after the helper, so that older
+            // saves can be migrated by tacking empty storages onto the end
+            Description, Bystander, Quips, QuestGiver, ExtraSerializationHelper, LevelExit,
+            Swimmer, LightSource, Faction, GroupLeader, PackMember, Boss, SpecialAbilities, Weight, Container,
+            WantsToTransferItem, Shatters, WantsToThrowItem, ProvidesMapping,
+            ProvidesTownPortal, Portal, HungerClock, ProvidesFood, BlockChance,
+            MeleeAttack, CritRangeBonus, Rarity, Affixed, Enchants, Restocks, Enchanted,
+        ]
+    )?;
+
+    Ok(())
+}
+
+/// Decompress the body of a compressed save.
+#[cfg(feature = "compressed-saves")]
+fn inflate(data: &[u8], path: &str) -> Result<Vec<u8>, LoadGameError> {
+    use std::io::Read;
+
+    let mut inflated = Vec::new();
+    flate2::read::DeflateDecoder::new(data)
+        .read_to_end(&mut inflated)
+        .map_err(|e| LoadGameError::Decompression {
+            path: std::path::PathBuf::from(path),
+            source: e,
+        })?;
+    Ok(inflated)
+}
+
+/// Compressed saves are rejected as incompatible before they get this far without
+/// the `compressed-saves` feature.
+#[cfg(not(feature = "compressed-saves"))]
+fn inflate(_data: &[u8], _path: &str) -> Result<Vec<u8>, LoadGameError> {
+    unreachable!(
+        "Compressed saves are rejected as incompatible without the `compressed-saves` feature"
+    )
+}
+
+/// Upgrade the body of a save written with format `version` to the current
+/// [`SAVE_VERSION`], one version at a time.
+fn migrate<'a>(version: u32, data: &'a str, path: &str) -> Result<Cow<'a, str>, LoadGameError> {
+    let mut data = Cow::Borrowed(data);
+    for from in version..SAVE_VERSION {
+        data = match from {
+            // Versions 2 and 3 only added fields to the header, which have defaults
+            1 | 2 => data,
+            // Nothing in older saves has a description
+            3 => Cow::Owned(format!("{data}[]")),
+            // Nor does it have any bystanders in it
+            4 => Cow::Owned(format!("{data}[][]")),
+            // Nor any quests. Without an extra helper, its resources are left at their defaults.
+            5 => Cow::Owned(format!("{data}[][]")),
+            // Older levels only have stairs, which are found by their tiles instead
+            6 => Cow::Owned(format!("{data}[]")),
+            // Nor was there any water to swim in
+            7 => Cow::Owned(format!("{data}[]")),
+            // Nor any light, since every level was lit
+            8 => Cow::Owned(format!("{data}[]")),
+            // Factions are assumed from what an entity is, when it doesn't have one
+            9 => Cow::Owned(format!("{data}[]")),
+            // Monsters from older saves all fend for themselves
+            10 => Cow::Owned(format!("{data}[][]")),
+            // And there weren't any bosses yet
+            11 => Cow::Owned(format!("{data}[][]")),
+            // Items in older saves don't weigh anything
+            12 => Cow::Owned(format!("{data}[]")),
+            // Nor were there any chests to loot
+            13 => Cow::Owned(format!("{data}[][]")),
+            // Only rocks could be thrown, so potions never had to shatter
+            14 => Cow::Owned(format!("{data}[][]")),
+            // Nor was there any magic mapping
+            15 => Cow::Owned(format!("{data}[]")),
+            // Nor any portals back to town
+            16 => Cow::Owned(format!("{data}[][]")),
+            // Players from older saves never get hungry, so there's nothing to eat either
+            17 => Cow::Owned(format!("{data}[][]")),
+            // Shields in older saves only ever added to defense
+            18 => Cow::Owned(format!("{data}[]")),
+            // Without any dice to roll, older saves fight with their flat power alone
+            19 => Cow::Owned(format!("{data}[]")),
+            // Nothing crits any more easily than anything else
+            20 => Cow::Owned(format!("{data}[]")),
+            // Everything in older saves is common
+            21 => Cow::Owned(format!("{data}[]")),
+            // Nor was anything enchanted with affixes
+            22 => Cow::Owned(format!("{data}[]")),
+            // Nor were there any scrolls of enchanting
+            23 => Cow::Owned(format!("{data}[]")),
+            // Merchants in older saves only ever have what they started with
+            24 => Cow::Owned(format!("{data}[]")),
+            // Items enchanted in older saves are as risky to enchant as new ones
+            25 => Cow::Owned(format!("{data}[]")),
+            _ => {
+                return Err(LoadGameError::IncompatibleSave {
+                    savegame_path: std::path::PathBuf::from(path),
+                    version,
+                    format: SaveFormat::Ron,
+                })
+            }
+        };
+    }
+
+    Ok(data)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum DeleteSaveError {
     #[error("Could not delete saved game at `{path}`")]
@@ -247,16 +855,215 @@ pub enum DeleteSaveError {
     },
 }
 
-/// Delete `savegame.ron` in the current working directory
-pub(crate) fn delete_save() -> Result<(), DeleteSaveError> {
-    let path = Path::new("savegame.ron");
+/// Delete the game in save slot `slot` (counting from zero).
+pub(crate) fn delete_save(slot: usize) -> Result<(), DeleteSaveError> {
+    let path = slot_path(slot);
+    storage::remove(&path).map_err(|e| DeleteSaveError::CannotRemove {
+        source: e,
+        path: std::path::PathBuf::from(path),
+    })
+}
 
-    if path.exists() {
-        std::fs::remove_file(path).map_err(|e| DeleteSaveError::CannotRemove {
-            source: e,
-            path: std::path::PathBuf::from(path),
+/// What's left of a character that died: enough to bring them back as a ghost
+/// when a later run reaches the level they died on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Bones {
+    /// The dead character's name.
+    pub name: String,
+    /// The level they died on.
+    pub level: LevelId,
+    /// Where on the level they died.
+    pub x: i32,
+    pub y: i32,
+    pub max_hp: i32,
+    pub power: i32,
+    pub defense: i32,
+    /// The names of everything they were carrying, and whether they had it
+    /// equipped.
+    pub items: Vec<(String, bool)>,
+}
+
+impl Bones {
+    /// Gather up the bones of the player in `ecs`, where they fell.
+    fn describe(ecs: &World) -> Self {
+        let player = **ecs.fetch::<PlayerEntity>();
+        let map = ecs.fetch::<Map>();
+        let PlayerPos(pos) = *ecs.fetch::<PlayerPos>();
+        let stats = ecs
+            .read_storage::<CombatStats>()
+            .get(player)
+            .cloned()
+            .unwrap_or_default();
+
+        let names = ecs.read_storage::<Name>();
+        let items = ecs.read_storage::<Item>();
+        let affixed = ecs.read_storage::<Affixed>();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let equipped = ecs.read_storage::<Equipped>();
+        // Affixed items are left behind as whatever they were before their magic
+        let carried = (
+            &names,
+            &items,
+            affixed.maybe(),
+            backpack.maybe(),
+            equipped.maybe(),
+        )
+            .join()
+            .filter_map(|(name, _, affixed, in_pack, worn)| {
+                let owner = in_pack.map(|p| p.owner).or(worn.map(|e| e.owner))?;
+                let name = affixed.map_or_else(|| name.to_string(), |a| a.base.clone());
+                (owner == player).then_some((name, worn.is_some()))
+            })
+            .collect();
+
+        Self {
+            name: names
+                .get(player)
+                .map(|name| name.to_string())
+                .unwrap_or_default(),
+            level: LevelId {
+                branch: map.branch,
+                depth: map.depth,
+            },
+            x: pos.x,
+            y: pos.y,
+            max_hp: stats.max_hp,
+            power: stats.power,
+            defense: stats.defense,
+            items: carried,
+        }
+    }
+}
+
+/// Leave the bones of the player, who just died, on the level they died on. They
+/// replace the bones of anyone who died there before.
+pub(crate) fn save_bones(ecs: &World) -> Result<(), SaveGameError> {
+    let bones = Bones::describe(ecs);
+    let path = bones_path(bones.level);
+    let data =
+        ron::ser::to_string_pretty(&bones, ron::ser::PrettyConfig::default()).map_err(|e| {
+            SaveGameError::Serialization {
+                source: Box::new(e),
+            }
         })?;
+    storage::write(&path, data.as_bytes()).map_err(|e| SaveGameError::Write {
+        path: std::path::PathBuf::from(path),
+        source: e,
+    })
+}
+
+/// The bones left on `level` by a character that died there, if there are any
+/// that can still be read.
+pub(crate) fn load_bones(level: LevelId) -> Option<Bones> {
+    let data = storage::read(&bones_path(level)).ok()?;
+    ron::de::from_bytes(&data).ok()
+}
+
+/// Throw away the bones on `level`, so that they're only ever found once.
+pub(crate) fn delete_bones(level: LevelId) -> Result<(), DeleteSaveError> {
+    let path = bones_path(level);
+    storage::remove(&path).map_err(|e| DeleteSaveError::CannotRemove {
+        source: e,
+        path: std::path::PathBuf::from(path),
+    })
+}
+
+#[cfg(all(test, not(feature = "compressed-saves")))]
+mod tests {
+    use super::*;
+    use crate::State;
+
+    /// How many component storages were in saves from before the first
+    /// migration.
+    const V1_STORAGES: usize = 58;
+
+    /// Split the body of a RON save into each storage it's made up of.
+    fn storages(body: &str) -> Vec<&str> {
+        let mut storages = Vec::new();
+        let (mut depth, mut start, mut in_string, mut escaped) = (0, 0, false, false);
+        for (i, c) in body.char_indices() {
+            if in_string {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' => in_string = true,
+                '[' if depth == 0 => {
+                    start = i;
+                    depth = 1;
+                }
+                '[' => depth += 1,
+                ']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        storages.push(&body[start..=i]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        storages
     }
 
-    Ok(())
+    /// Start a new run, and save it.
+    fn saved_run() -> (State, String) {
+        let mut gs = State::new().unwrap();
+        gs.start_new_run();
+        let save = String::from_utf8(snapshot(&mut gs.ecs).unwrap()).unwrap();
+        (gs, save)
+    }
+
+    #[test]
+    fn saves_load_back() {
+        let (mut gs, save) = saved_run();
+        let depth = gs.ecs.fetch::<Map>().depth;
+        let player_name = SaveHeader::describe(&gs.ecs).name;
+
+        restore_snapshot(&mut gs.ecs, save.as_bytes()).unwrap();
+        assert_eq!(gs.ecs.fetch::<Map>().depth, depth);
+        assert_eq!(SaveHeader::describe(&gs.ecs).name, player_name);
+    }
+
+    #[test]
+    fn v1_saves_are_migrated() {
+        let (mut gs, save) = saved_run();
+        let (header, body) = save.split_once('\n').unwrap();
+        assert!(header.contains(&format!("version:{SAVE_VERSION},")));
+
+        // Saves from before the header was versioned only had the first storages
+        let header = header
+            .replace(&format!("version:{SAVE_VERSION},"), "")
+            .replace("format:Ron,", "");
+        let body = storages(body)[..V1_STORAGES].concat();
+        let v1_save = format!("{header}\n{body}");
+
+        let v1_header: SaveHeader = ron::from_str(&header).unwrap();
+        assert_eq!(v1_header.version, 1);
+        assert_eq!(
+            storages(&migrate(v1_header.version, &body, "v1").unwrap()).len(),
+            storages(save.split_once('\n').unwrap().1).len()
+        );
+
+        let depth = gs.ecs.fetch::<Map>().depth;
+        restore_snapshot(&mut gs.ecs, v1_save.as_bytes()).unwrap();
+        assert_eq!(gs.ecs.fetch::<Map>().depth, depth);
+        let player = **gs.ecs.fetch::<PlayerEntity>();
+        assert!(gs.ecs.read_storage::<Player>().contains(player));
+    }
+
+    #[test]
+    fn damaged_saves_leave_the_game_alone() {
+        let (mut gs, save) = saved_run();
+        let player = **gs.ecs.fetch::<PlayerEntity>();
+
+        let damaged = &save[..save.len() / 2];
+        assert!(restore_snapshot(&mut gs.ecs, damaged.as_bytes()).is_err());
+        assert!(gs.ecs.is_alive(player));
+        assert!(gs.ecs.read_storage::<Player>().contains(player));
+    }
 }
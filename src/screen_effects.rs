@@ -0,0 +1,139 @@
+//! Full-screen feedback drawn over everything else: the screen shakes and flashes
+//! red around the edges when the player takes a heavy hit, and glows briefly when
+//! they level up.
+//!
+//! Shaking can be turned down or off entirely with the [`ScreenShake`] option, for
+//! players who are sensitive to motion.
+
+use rltk::{Rltk, RGB};
+use specs::prelude::*;
+
+use crate::camera::{VIEW_HEIGHT, VIEW_WIDTH};
+
+/// How long the screen shakes after a heavy hit, in milliseconds.
+const SHAKE_MS: f32 = 250.0;
+
+/// How long the red flash after a heavy hit lasts, in milliseconds.
+const DAMAGE_FLASH_MS: f32 = 300.0;
+
+/// How long the glow after levelling up lasts, in milliseconds.
+const LEVEL_UP_FLASH_MS: f32 = 500.0;
+
+/// How many tiles in from the edge of the map's window a flash reaches.
+const VIGNETTE_WIDTH: i32 = 5;
+
+/// How hard the screen shakes. Turning it off stops the screen from moving at all.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, strum::Display)]
+pub enum ScreenShake {
+    Off,
+    #[default]
+    Gentle,
+    Strong,
+}
+
+impl ScreenShake {
+    /// The next strength up, wrapping back around to off.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Gentle,
+            Self::Gentle => Self::Strong,
+            Self::Strong => Self::Off,
+        }
+    }
+
+    /// How far the screen moves when shaking, in fractions of a tile.
+    fn amplitude(self) -> f32 {
+        match self {
+            Self::Off => 0.0,
+            Self::Gentle => 0.1,
+            Self::Strong => 0.3,
+        }
+    }
+}
+
+/// A coloured glow around the edges of the screen that fades out over time.
+#[derive(Debug, Clone, Copy)]
+struct Flash {
+    color: RGB,
+    /// How bright the flash starts out, from `0.0` to `1.0`.
+    strength: f32,
+    duration_ms: f32,
+    remaining_ms: f32,
+}
+
+/// Screen effects that are currently playing out.
+#[derive(Debug, Default)]
+pub struct ScreenEffects {
+    shake_ms: f32,
+    flash: Option<Flash>,
+}
+
+impl ScreenEffects {
+    /// Shake the screen and flash it red, for when the player takes a heavy hit.
+    pub fn heavy_hit(&mut self) {
+        self.shake_ms = SHAKE_MS;
+        self.flash(RGB::named(rltk::RED), 0.6, DAMAGE_FLASH_MS);
+    }
+
+    /// Give the screen a subtle glow, for when the player levels up.
+    pub fn level_up(&mut self) {
+        self.flash(RGB::named(rltk::GOLD), 0.25, LEVEL_UP_FLASH_MS);
+    }
+
+    fn flash(&mut self, color: RGB, strength: f32, duration_ms: f32) {
+        self.flash = Some(Flash {
+            color,
+            strength,
+            duration_ms,
+            remaining_ms: duration_ms,
+        });
+    }
+}
+
+/// Play out any screen effects on top of what's already been drawn this frame,
+/// and age them by `ctx.frame_time_ms`.
+pub fn draw_screen_effects(ecs: &World, ctx: &mut Rltk) {
+    let shake = *ecs.fetch::<ScreenShake>();
+    let mut effects = ecs.fetch_mut::<ScreenEffects>();
+    let frame_time_ms = ctx.frame_time_ms;
+
+    // Jiggle the whole console around while the shake lasts
+    if effects.shake_ms > 0.0 {
+        effects.shake_ms -= frame_time_ms;
+        let phase = effects.shake_ms * 0.15;
+        let amplitude = shake.amplitude() * (effects.shake_ms / SHAKE_MS).max(0.0);
+        ctx.set_offset(phase.sin() * amplitude, (phase * 1.3).cos() * amplitude);
+    } else {
+        ctx.set_offset(0.0, 0.0);
+    }
+
+    let Some(flash) = effects.flash.as_mut() else {
+        return;
+    };
+    flash.remaining_ms -= frame_time_ms;
+    if flash.remaining_ms <= 0.0 {
+        effects.flash = None;
+        return;
+    }
+
+    // Tint the edges of the map's window, brightest at the very edge and fading inwards
+    let fade = flash.strength * flash.remaining_ms / flash.duration_ms;
+    for y in 0..VIEW_HEIGHT {
+        for x in 0..VIEW_WIDTH {
+            let from_edge = x.min(y).min(VIEW_WIDTH - 1 - x).min(VIEW_HEIGHT - 1 - y);
+            if from_edge >= VIGNETTE_WIDTH {
+                continue;
+            }
+            let alpha = fade * (1.0 - from_edge as f32 / VIGNETTE_WIDTH as f32);
+            ctx.set_bg(
+                x,
+                y,
+                RGB::from_f32(
+                    flash.color.r * alpha,
+                    flash.color.g * alpha,
+                    flash.color.b * alpha,
+                ),
+            );
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Services that friendly NPCs in town sell to the player for gold.
+
+use specs::prelude::*;
+
+use crate::l10n::{self, tr, Translation};
+use crate::{
+    CombatStats, GameLog, InBackpack, MasterItemNames, Name, PlayerEntity, Purse, Service,
+    StatusEffects,
+};
+
+/// How much gold the healer charges for each hit point restored.
+const GOLD_PER_HP: i32 = 2;
+
+/// How much gold the healer charges to cure the player's ailments.
+const CURE_PRICE: i32 = 15;
+
+/// How much gold the sage charges to identify a kind of item.
+const IDENTIFY_PRICE: i32 = 20;
+
+/// Something a [`crate::ServiceNpc`] is offering to do for the player.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ServiceOffer {
+    pub description: String,
+    pub price: i32,
+    kind: OfferKind,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+enum OfferKind {
+    Heal,
+    Cure,
+    /// Identify every item with this (real) name.
+    Identify(String),
+}
+
+/// List everything that an NPC providing `service` can currently do for the player.
+pub fn service_offers(ecs: &World, service: Service) -> Vec<ServiceOffer> {
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let mut offers = Vec::new();
+
+    match service {
+        Service::Healer => {
+            if let Some(stats) = ecs.read_storage::<CombatStats>().get(player_entity) {
+                let missing_hp = stats.max_hp - stats.hp;
+                if missing_hp > 0 {
+                    offers.push(ServiceOffer {
+                        description: tr("service-heal")
+                            .arg("amount", tr("hit-points").arg("amount", missing_hp))
+                            .to_string(),
+                        price: missing_hp * GOLD_PER_HP,
+                        kind: OfferKind::Heal,
+                    });
+                }
+            }
+
+            let ailing = ecs
+                .read_storage::<StatusEffects>()
+                .get(player_entity)
+                .is_some_and(|effects| effects.active.iter().any(|a| a.effect.is_harmful()));
+            if ailing {
+                offers.push(ServiceOffer {
+                    description: tr("service-cure").to_string(),
+                    price: CURE_PRICE,
+                    kind: OfferKind::Cure,
+                });
+            }
+        }
+
+        Service::Sage => {
+            let item_names = ecs.fetch::<MasterItemNames>();
+            let names = ecs.read_storage::<Name>();
+            let backpack = ecs.read_storage::<InBackpack>();
+            for (name, _) in (&names, &backpack)
+                .join()
+                .filter(|(_, pack_item)| pack_item.owner == player_entity)
+            {
+                let kind = OfferKind::Identify(name.name.clone());
+                if !item_names.is_identified(&name.name)
+                    && !offers.iter().any(|offer| offer.kind == kind)
+                {
+                    offers.push(ServiceOffer {
+                        description: tr("service-identify")
+                            .arg("item", item_names.display_name(&name.name))
+                            .to_string(),
+                        price: IDENTIFY_PRICE,
+                        kind,
+                    });
+                }
+            }
+        }
+    }
+
+    offers
+}
+
+/// What an NPC providing `service` says when they have nothing to offer.
+pub fn nothing_to_offer_message(service: Service) -> Translation {
+    match service {
+        Service::Healer => tr("healer-nothing-to-offer"),
+        Service::Sage => tr("sage-nothing-to-offer"),
+    }
+}
+
+/// Have the player pay for and receive `offer`. Logs a message and does nothing
+/// if they can't afford it.
+pub fn buy_service(ecs: &mut World, offer: &ServiceOffer) {
+    let player_entity = **ecs.fetch::<PlayerEntity>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    {
+        let mut purses = ecs.write_storage::<Purse>();
+        let Some(purse) = purses
+            .get_mut(player_entity)
+            .filter(|p| p.gold >= offer.price)
+        else {
+            gamelog.log(tr("cannot-afford-service"));
+            return;
+        };
+        purse.gold -= offer.price;
+    }
+
+    match &offer.kind {
+        OfferKind::Heal => {
+            if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(player_entity) {
+                stats.hp = stats.max_hp;
+            }
+            gamelog
+                .log(tr("healer-heals").arg("price", tr("gold-amount").arg("amount", offer.price)));
+        }
+
+        OfferKind::Cure => {
+            if let Some(effects) = ecs.write_storage::<StatusEffects>().get_mut(player_entity) {
+                effects.active.retain(|active| !active.effect.is_harmful());
+            }
+            gamelog
+                .log(tr("healer-cures").arg("price", tr("gold-amount").arg("amount", offer.price)));
+        }
+
+        OfferKind::Identify(name) => {
+            let mut item_names = ecs.fetch_mut::<MasterItemNames>();
+            let unidentified = item_names.display_name(name);
+            item_names.identify(name);
+            gamelog.log(
+                tr("sage-identifies")
+                    .arg("price", tr("gold-amount").arg("amount", offer.price))
+                    .arg("unidentified", unidentified)
+                    .arg("item", l10n::name(name)),
+            );
+        }
+    }
+}
@@ -1,33 +1,65 @@
-use std::collections::hash_map;
-
 use rltk::{RandomNumberGenerator, RGB};
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use specs::prelude::*;
 use specs::saveload::{MarkedBuilder, SimpleMarker};
 
+use crate::item_factory;
+use crate::quests;
+use crate::raws::{Raws, SpawnTableEntry};
 use crate::rng_table::RngTable;
+use crate::saveload_system::Bones;
 use crate::{
-    AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, InflictsDamage, Item, Monster,
-    Name, Player, PlayerEntity, Position, ProvidesHealing, Ranged, Rect, Renderable, Serializable,
-    Viewshed, MAPWIDTH,
+    Ability, AiState, AreaOfEffect, Attributes, BlockChance, BlocksTile, Boss, Branch, Bystander,
+    CombatStats, Config, Consumable, Container, CritRangeBonus, DefenseBonus, Description, Dice,
+    EnchantTarget, Enchants, EntryTrigger, EquipmentSlot, Equippable, Equipped, Experience,
+    Faction, Gold, GroupLeader, Guard, Hidden, Hoarder, HomeLocation, HungerClock, HungerState,
+    InBackpack, InflictsDamage, InflictsStatusEffect, Item, KnownSpells, Leader, LevelExit,
+    LevelId, LightSource, MakesNoise, Mana, Map, MapTheme, MeleeAttack, MeleePowerBonus, Monster,
+    Morale, Name, PackMember, Perks, Player, PlayerEntity, Portal, Position, ProvidesFood,
+    ProvidesHealing, ProvidesMapping, ProvidesTownPortal, Purse, QuestGiver, Quips, Ranged,
+    RangedAttack, Rarity, Rect, Renderable, Restocks, RunOptions, Serializable, Service,
+    ServiceNpc, Shatters, SingleActivation, Skills, SpecialAbilities, SpecialAbility, Spell, Stash,
+    StatusEffect, Swimmer, TileType, Value, Vendor, Viewshed, Weight, WinsGame,
 };
 
-const SPAWN_DIE: i32 = 7;
-const MAX_SPAWN_TRIES_PER_ROOM: usize = 20;
+/// The deepest level of the dungeon, where the Amulet of Yendor waits instead of
+/// another staircase down.
+pub const FINAL_DEPTH: i32 = 10;
 
 /// Spawns the player and returns their [`PlayerEntity`] reference.
 pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> PlayerEntity {
+    let spells = ["Magic Missile", "Fireball"]
+        .into_iter()
+        .filter_map(|name| spawn_spell(ecs, name))
+        .collect();
+    let starting_hp = ecs.fetch::<Config>().gameplay.starting_hp;
+
     let ent = ecs
         .create_entity()
         .with(Player)
+        .with(Faction::Player)
         .with(Name::from("Player"))
         .with(CombatStats {
-            max_hp: 30,
-            hp: 30,
+            max_hp: starting_hp,
+            hp: starting_hp,
             defense: 2,
-            power: 5,
+            power: 3,
         })
+        .with(MeleeAttack {
+            damage: Dice::new(1, 4, 0),
+        })
+        .with(Mana {
+            max_mana: 12,
+            mana: 12,
+        })
+        .with(KnownSpells { spells })
+        .with(Experience::default())
+        .with(Attributes::default())
+        .with(Skills::default())
+        .with(Perks::default())
+        .with(HungerClock::new(HungerState::WellFed))
         .with(Position::from((player_x, player_y)))
+        .with(Purse { gold: 50 })
         .with(Renderable {
             glyph: rltk::to_cp437('@'),
             fg: RGB::named(rltk::YELLOW),
@@ -40,79 +72,1036 @@ pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> PlayerEntity {
         })
         .marked::<SimpleMarker<Serializable>>()
         .build();
+
+    // Some levels are pitch black without one
+    give_items(ecs, ent, &["Torch"]);
+
     PlayerEntity(ent)
 }
 
-fn room_entity_spawn_table(map_depth: i32) -> RngTable {
-    RngTable::new()
-        .add("Goblin", 10)
-        .add("Orc", 1 + map_depth)
-        .add("Health Potion", 7)
-        .add("Fireball Scroll", 2 + map_depth)
-        .add("Confusion Scroll", 2 + map_depth)
-        .add("Magic Missile Scroll", 4)
+/// Creates the spell called `name`, if there is one. Spells don't have a position,
+/// and only exist in some entity's [`KnownSpells`].
+pub fn spawn_spell(ecs: &mut World, name: &str) -> Option<Entity> {
+    let spell = match name {
+        "Magic Missile" => ecs
+            .create_entity()
+            .with(Spell { mana_cost: 2 })
+            .with(Ranged { range: 6 })
+            .with(InflictsDamage { damage: 8 }),
+        "Fireball" => ecs
+            .create_entity()
+            .with(Spell { mana_cost: 6 })
+            .with(Ranged { range: 6 })
+            .with(InflictsDamage { damage: 20 })
+            .with(AreaOfEffect { radius: 3 })
+            .with(InflictsStatusEffect {
+                effect: StatusEffect::Burning,
+                turns: 3,
+            }),
+        "Confuse" => ecs
+            .create_entity()
+            .with(Spell { mana_cost: 3 })
+            .with(Ranged { range: 6 })
+            .with(InflictsStatusEffect {
+                effect: StatusEffect::Confused,
+                turns: 4,
+            }),
+        "Haste" => ecs
+            .create_entity()
+            .with(Spell { mana_cost: 5 })
+            .with(InflictsStatusEffect {
+                effect: StatusEffect::Hasted,
+                turns: 6,
+            }),
+        _ => return None,
+    };
+
+    Some(
+        spell
+            .with(Name::from(name))
+            .marked::<SimpleMarker<Serializable>>()
+            .build(),
+    )
+}
+
+/// Spawns the friendly NPCs that live in town (including a merchant), along with
+/// the player's stash, on open floor near where the player starts.
+pub fn spawn_town_npcs(ecs: &mut World, start: Position) {
+    let npcs = [
+        (Service::Healer, "Healer", RGB::named(rltk::GREEN)),
+        (Service::Sage, "Sage", RGB::named(rltk::VIOLET)),
+    ];
+
+    // Find the free floor tiles closest to the start, without crowding the player
+    let spots: Vec<Position> = {
+        let map = ecs.fetch::<Map>();
+        let positions = ecs.read_storage::<Position>();
+        let occupied: Vec<usize> = positions
+            .join()
+            .map(|pos| map.xy_idx(pos.x, pos.y))
+            .collect();
+
+        let start = rltk::Point::new(start.x, start.y);
+        let mut spots: Vec<(usize, f32)> = (0..map.tiles.len())
+            .filter(|idx| map.tiles[*idx] == TileType::Floor && !occupied.contains(idx))
+            .map(|idx| {
+                let point = rltk::Point::from_tuple(map.idx_xy(idx));
+                (idx, rltk::DistanceAlg::Pythagoras.distance2d(start, point))
+            })
+            .filter(|(_, distance)| *distance >= 2.0)
+            .collect();
+        spots.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        spots
+            .into_iter()
+            .map(|(idx, _)| Position::from(map.idx_xy(idx)))
+            .collect()
+    };
+
+    let mut spots = spots.into_iter();
+
+    if let Some(pos) = spots.next() {
+        ecs.create_entity()
+            .with(Stash)
+            .with(Name::from("Stash"))
+            .maybe_with(description("Stash"))
+            .with(BlocksTile)
+            .with(pos)
+            .with(Renderable {
+                glyph: rltk::to_cp437('■'),
+                fg: RGB::named(rltk::BROWN1),
+                render_order: 1,
+                ..Default::default()
+            })
+            .marked::<SimpleMarker<Serializable>>()
+            .build();
+    }
+
+    if let Some(pos) = spots.next() {
+        let merchant = ecs
+            .create_entity()
+            .with(Vendor)
+            .with(Restocks::default())
+            .with(Faction::Townsfolk)
+            .with(Name::from("Merchant"))
+            .maybe_with(description("Merchant"))
+            .with(BlocksTile)
+            .with(pos)
+            .with(Renderable {
+                glyph: rltk::to_cp437('@'),
+                fg: RGB::named(rltk::GOLD),
+                render_order: 1,
+                ..Default::default()
+            })
+            .marked::<SimpleMarker<Serializable>>()
+            .build();
+        give_items(ecs, merchant, MERCHANT_STOCK);
+    }
+
+    for ((service, name, fg), pos) in npcs.into_iter().zip(spots.by_ref()) {
+        ecs.create_entity()
+            .with(ServiceNpc { service })
+            .with(Faction::Townsfolk)
+            .with(Name::from(name))
+            .maybe_with(description(name))
+            .with(BlocksTile)
+            .with(pos)
+            .with(Renderable {
+                glyph: rltk::to_cp437('@'),
+                fg,
+                render_order: 1,
+                ..Default::default()
+            })
+            .marked::<SimpleMarker<Serializable>>()
+            .build();
+    }
+
+    // The town watch, led by their captain
+    for (j, pos) in spots.by_ref().take(TOWN_GUARDS).enumerate() {
+        let guard = spawn_guard(
+            ecs,
+            pos,
+            if j == 0 {
+                "Guard Captain"
+            } else {
+                "Town Guard"
+            },
+        );
+        if j == 0 {
+            ecs.write_storage::<Leader>()
+                .insert(guard, Leader)
+                .expect("Unable to make guard captain a leader");
+        }
+    }
+
+    for ((name, fg, quips), pos) in TOWN_BYSTANDERS.iter().zip(spots) {
+        ecs.create_entity()
+            .with(Bystander)
+            .with(Faction::Townsfolk)
+            .with(Name::from(*name))
+            .maybe_with(description(name))
+            .with(Quips::from(*quips))
+            .maybe_with(quests::quest_given_by(name).map(|quest| QuestGiver {
+                quest: quest.name.to_string(),
+            }))
+            .with(HomeLocation { x: pos.x, y: pos.y })
+            .with(BlocksTile)
+            .with(pos)
+            .with(Renderable {
+                glyph: rltk::to_cp437('@'),
+                fg: RGB::named(*fg),
+                render_order: 1,
+                ..Default::default()
+            })
+            .marked::<SimpleMarker<Serializable>>()
+            .build();
+    }
+}
+
+/// A kind of [`Bystander`]: their name, colour, and [`Quips`].
+type BystanderKind = (&'static str, (u8, u8, u8), &'static [&'static str]);
+
+/// The [`Bystander`]s milling about town, with what they have to say for themselves.
+const TOWN_BYSTANDERS: &[BystanderKind] = &[
+    (
+        "Townsperson",
+        rltk::WHEAT,
+        &[
+            "Another one off to the dungeon? Try to come back in one piece.",
+            "The well water's been tasting funny since they dug that pit.",
+            "Mind the guards. They don't take kindly to trouble.",
+        ],
+    ),
+    (
+        "Farmer",
+        rltk::KHAKI,
+        &[
+            "Goblins took three of my chickens last week.",
+            "If you find my good shovel down there, I'll want it back.",
+        ],
+    ),
+    (
+        "Drunk",
+        rltk::PINK,
+        &[
+            "*hic* The amulet? Saw it myself, I did. Down at the bottom.",
+            "Buy us a drink, friend?",
+            "The walls keep moving when I'm not looking.",
+        ],
+    ),
+    (
+        "Child",
+        rltk::LIGHT_PINK,
+        &[
+            "Are you a real adventurer?",
+            "Mum says I'm not allowed past the stairs.",
+        ],
+    ),
+];
+
+/// How many [`Guard`]s keep watch over the town.
+const TOWN_GUARDS: usize = 3;
+
+/// A peaceful member of the town watch at `pos`. See [`Guard`].
+fn spawn_guard(ecs: &mut World, pos: Position, name: &str) -> Entity {
+    ecs.create_entity()
+        .with(Guard)
+        .with(Faction::Townsfolk)
+        .with(Name::from(name))
+        .maybe_with(description(name))
+        .with(BlocksTile)
+        .with(Morale::default())
+        .with(AiState::default())
+        .with(CombatStats {
+            max_hp: 30,
+            hp: 30,
+            defense: 3,
+            power: 4,
+        })
+        .with(MeleeAttack {
+            damage: Dice::new(1, 6, 0),
+        })
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg: RGB::named(rltk::LIGHT_BLUE),
+            render_order: 1,
+            ..Default::default()
+        })
+        .with(Viewshed {
+            range: 8,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+/// Everything the merchant in town starts out selling.
+const MERCHANT_STOCK: &[&str] = &[
+    "Health Potion",
+    "Health Potion",
+    "Magic Missile Scroll",
+    "Noisemaker",
+    "Torch",
+    "Dagger",
+    "Shield",
+    "Leather Armor",
+];
+
+/// Put one of each item in `items` into `owner`'s backpack, e.g. for a vendor to sell.
+fn give_items(ecs: &mut World, owner: Entity, items: &[&str]) {
+    for name in items {
+        let item = spawn_named(ecs, 0, 0, name).unwrap_or_else(|| panic!("Unknown item {name:?}"));
+        ecs.write_storage::<Position>().remove(item);
+        ecs.write_storage::<InBackpack>()
+            .insert(item, InBackpack { owner })
+            .expect("Unable to give item");
+    }
+}
+
+/// Give `vendor` `count` new items to sell, from what's to be found around
+/// `depth`.
+pub fn restock_vendor(ecs: &mut World, vendor: Entity, depth: i32, count: usize) {
+    let items: Vec<String> = {
+        let table = spawn_table(&ecs.fetch::<Raws>().spawn_tables.vendor_stock, depth)
+            .map_weights(|spawn, weight| rarity_bias(spawn, weight, depth));
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        (0..count)
+            .filter_map(|_| table.roll(&mut rng)?.name().map(str::to_string))
+            .collect()
+    };
+    let items: Vec<&str> = items.iter().map(String::as_str).collect();
+    give_items(ecs, vendor, &items);
+}
+
+/// Drop a pile of `amount` gold at (`x`, `y`).
+pub fn spawn_gold(ecs: &mut World, x: i32, y: i32, amount: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Gold { amount })
+        .with(Name::from(format!("{amount} gold")))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('$'),
+            fg: RGB::named(rltk::GOLD),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+/// Something that a spawn table can turn up.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Spawn {
+    /// Leave the spot empty.
+    Nothing,
+    /// Spawn the thing with this name, as in [`spawn_named()`].
+    Named(String),
+}
+
+impl Spawn {
+    /// The name of whatever's to be spawned, if anything.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Nothing => None,
+            Self::Named(name) => Some(name),
+        }
+    }
+}
+
+impl From<&str> for Spawn {
+    /// Spawn table entries called `"None"` spawn [`Spawn::Nothing`].
+    fn from(name: &str) -> Self {
+        match name {
+            "None" => Self::Nothing,
+            _ => Self::Named(name.to_string()),
+        }
+    }
+}
+
+/// Build a spawn table from the `entries` in the raws, weighted for `depth`.
+fn spawn_table(entries: &[SpawnTableEntry], depth: i32) -> RngTable<Spawn> {
+    entries.iter().fold(RngTable::new(), |table, entry| {
+        table.add(entry.name.as_str(), entry.weight_at(depth))
+    })
+}
+
+/// What can be spawned on a level at `map_depth`, and how likely each thing is.
+/// The level's `theme` makes some things more likely.
+fn room_entity_spawn_table(raws: &Raws, map_depth: i32, theme: MapTheme) -> RngTable<Spawn> {
+    let table = spawn_table(&raws.spawn_tables.rooms, map_depth);
+    theme_spawn_bias(theme)
+        .iter()
+        .fold(table, |table, (name, weight)| table.add(*name, *weight))
+        .map_weights(|spawn, weight| rarity_bias(spawn, weight, map_depth))
+}
+
+/// What might be found inside a [`Container`] on a level at `map_depth`, and how
+/// likely each thing is.
+fn chest_loot_table(raws: &Raws, map_depth: i32) -> RngTable<Spawn> {
+    spawn_table(&raws.spawn_tables.chest_loot, map_depth)
+        .map_weights(|spawn, weight| rarity_bias(spawn, weight, map_depth))
+}
+
+/// Shift the `weight` of an item in a spawn table towards rarer items the deeper
+/// `map_depth` is. Anything that isn't an item is left alone.
+fn rarity_bias(spawn: &Spawn, weight: i32, map_depth: i32) -> i32 {
+    spawn
+        .name()
+        .and_then(rarity)
+        .map_or(weight, |rarity| weight * rarity.spawn_bias(map_depth) / 100)
+}
+
+/// Extra weight given to things in the spawn table on levels with `theme`.
+fn theme_spawn_bias(theme: MapTheme) -> &'static [(&'static str, i32)] {
+    match theme {
+        MapTheme::Dungeon => &[],
+        MapTheme::Forest => &[
+            ("Goblin", 5),
+            ("Goblin Archer", 4),
+            ("Giant Rat", 4),
+            ("Health Potion", 2),
+        ],
+        MapTheme::LimestoneCavern => &[
+            ("Kobold Hoarder", 4),
+            ("Bear Trap", 2),
+            ("Noisemaker", 2),
+            ("Brazier", 4),
+        ],
+        MapTheme::DwarfFortress => &[
+            ("Orc", 4),
+            ("Brazier", 6),
+            ("Longsword", 2),
+            ("Tower Shield", 2),
+            ("Chain Mail", 2),
+            ("Iron Helm", 2),
+            ("Poison Dart Trap", 3),
+        ],
+        MapTheme::Hell => &[("Orc", 6), ("Fireball Scroll", 4), ("Fear Scroll", 2)],
+    }
+}
+
+/// Roll how many monsters and items to spawn into a room or region.
+///
+/// This follows the roll of 1d(spawn_die) - floor(spawn_die / 2), plus 1 for each level
+/// past the first floor. The size of the die comes from the [`Config`].
+fn roll_spawn_count(rng: &mut RandomNumberGenerator, spawn_die: i32, map_depth: i32) -> i32 {
+    rng.roll_dice(1, spawn_die + (spawn_die as f32 / 2.0).floor() as i32) + (map_depth - 1)
+        - (spawn_die as f32 / 2.0).floor() as i32
 }
 
 /// Fills a room with monsters, items, and other stuff.
 pub fn spawn_room(ecs: &mut World, room: &Rect, map_depth: i32) {
-    let spawn_table = room_entity_spawn_table(map_depth);
-    let mut spawn_points: FxHashMap<usize, Option<String>> = FxHashMap::default();
+    let area: Vec<usize> = {
+        let map = ecs.fetch::<Map>();
+        (room.y1 + 1..=room.y2)
+            .flat_map(|y| (room.x1 + 1..=room.x2).map(move |x| (x, y)))
+            .map(|(x, y)| map.xy_idx(x, y))
+            .collect()
+    };
+    spawn_region(ecs, &area, map_depth);
+}
 
-    // Figure out how many monsters and items to spawn, and where to put them
+/// Fills an arbitrarily-shaped region of floor tiles with monsters, items, and
+/// other stuff. The region is given as a list of map tile indexes.
+///
+/// Rather than being scattered uniformly, things are placed around the region's
+/// [`PointOfInterest`]s: loot tends to sit against the walls, traps in doorways,
+/// and monsters guard the loot, doorways, and stairs.
+pub fn spawn_region(ecs: &mut World, area: &[usize], map_depth: i32) {
+    let spawn_table =
+        room_entity_spawn_table(&ecs.fetch::<Raws>(), map_depth, ecs.fetch::<Map>().theme);
+    let mut spawn_points: FxHashMap<usize, String> = FxHashMap::default();
+
+    // Figure out what to spawn, and where to put it
     {
+        let map = ecs.fetch::<Map>();
         let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let points_of_interest = find_points_of_interest(&map, area);
+
+        let difficulty = ecs.fetch::<RunOptions>().difficulty;
+        let spawn_die = ecs.fetch::<Config>().gameplay.spawn_die;
+        let num_spawns = i32::min(
+            area.len() as i32,
+            roll_spawn_count(&mut rng, spawn_die, map_depth) + difficulty.extra_spawns(),
+        );
+        let mut rolls: Vec<String> = (0..num_spawns)
+            .filter_map(|_| spawn_table.roll(&mut rng)?.name().map(str::to_string))
+            .collect();
+        // Place loot before the monsters that guard it
+        rolls.sort_by_key(|name| spawn_anchor(name) == SpawnAnchor::Guard);
+
+        // Nothing gets dropped into the water
+        let mut free: Vec<usize> = area
+            .iter()
+            .copied()
+            .filter(|idx| !map.tiles[*idx].is_water())
+            .collect();
+        let mut loot = Vec::new();
+        for name in rolls {
+            if free.is_empty() {
+                break;
+            }
+
+            let anchors: Vec<usize> = match spawn_anchor(&name) {
+                SpawnAnchor::Edge => points_of_interest
+                    .iter()
+                    .filter(|(_, poi)| *poi == PointOfInterest::Edge)
+                    .map(|(idx, _)| *idx)
+                    .collect(),
+                SpawnAnchor::Doorway => points_of_interest
+                    .iter()
+                    .filter(|(_, poi)| *poi == PointOfInterest::Doorway)
+                    .map(|(idx, _)| *idx)
+                    .collect(),
+                SpawnAnchor::Guard => points_of_interest
+                    .iter()
+                    .filter(|(_, poi)| *poi != PointOfInterest::Edge)
+                    .map(|(idx, _)| *idx)
+                    .chain(loot.iter().copied())
+                    .collect(),
+            };
 
-        // This gives a room a spawn count following the roll of 1d(SPAWN_DIE) - floor(SPAWN_DIE / 2),
-        // plus 1 for each level past the first floor.
-        let num_spawns = rng.roll_dice(1, SPAWN_DIE + (SPAWN_DIE as f32 / 2.0).floor() as i32)
-            + (map_depth - 1)
-            - (SPAWN_DIE as f32 / 2.0).floor() as i32;
-
-        for _ in 0..num_spawns {
-            let mut added = false;
-            let mut tries = 0;
-            while !added && tries < MAX_SPAWN_TRIES_PER_ROOM {
-                let x = (room.x1 + 1 + rng.roll_dice(1, i32::abs(room.width() - 1))) as usize;
-                let y = (room.y1 + 1 + rng.roll_dice(1, i32::abs(room.height() - 1))) as usize;
-                let idx = (y * MAPWIDTH) + x;
-
-                if let hash_map::Entry::Vacant(e) = spawn_points.entry(idx) {
-                    e.insert(spawn_table.roll(&mut rng).map(|s| s.to_string()));
-                    added = true;
-                } else {
-                    tries += 1;
-                }
+            let idx = pick_anchored_tile(&map, &mut rng, &free, &anchors);
+            free.retain(|free_idx| *free_idx != idx);
+            if spawn_anchor(&name) != SpawnAnchor::Guard {
+                loot.push(idx);
             }
+            spawn_points.insert(idx, name);
         }
     }
 
     // Actually spawn the entities
-    for (map_idx, roll_result) in spawn_points.iter() {
-        let x = (*map_idx % MAPWIDTH) as i32;
-        let y = (*map_idx / MAPWIDTH) as i32;
-
-        if let Some(roll_result) = roll_result {
-            match roll_result.as_ref() {
-                "Goblin" => spawn_goblin(ecs, x, y),
-                "Orc" => spawn_orc(ecs, x, y),
-                "Health Potion" => spawn_health_potion(ecs, x, y),
-                "Fireball Scroll" => spawn_fireball_scroll(ecs, x, y),
-                "Confusion Scroll" => spawn_confusion_scroll(ecs, x, y),
-                "Magic Missile Scroll" => spawn_magic_missile_scroll(ecs, x, y),
-                s => unreachable!("Should be impossible to roll entity {s:?} that isn't in the spawn table, but here we are!"),
-            };
+    let mut goblins = Vec::new();
+    for (map_idx, name) in spawn_points.iter() {
+        let Some(entity) = spawn_entity(ecs, *map_idx, name) else {
+            continue;
+        };
+        if PACK_MONSTERS.contains(&name.as_str()) {
+            goblins.push(entity);
+        }
+    }
+
+    // Goblins that start out together hunt together
+    form_pack(ecs, &goblins);
+}
+
+/// Monsters that form a pack with others like them spawned in the same region.
+const PACK_MONSTERS: &[&str] = &["Goblin", "Goblin Archer"];
+
+/// Band `members` together into a pack, led by the first of them. It takes at
+/// least two to make a pack.
+fn form_pack(ecs: &mut World, members: &[Entity]) {
+    let [leader, rest @ ..] = members else {
+        return;
+    };
+    if rest.is_empty() {
+        return;
+    }
+
+    ecs.write_storage::<GroupLeader>()
+        .insert(
+            *leader,
+            GroupLeader {
+                pack_size: members.len() as i32,
+                attacking: false,
+            },
+        )
+        .expect("Unable to make pack leader");
+    let mut pack_members = ecs.write_storage::<PackMember>();
+    for member in rest {
+        pack_members
+            .insert(*member, PackMember { leader: *leader })
+            .expect("Unable to add monster to pack");
+    }
+}
+
+/// Somewhere in a room or region that's worth building an encounter around.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum PointOfInterest {
+    /// A staircase.
+    Stairs,
+    /// A tile where a corridor or neighbouring area leads in.
+    Doorway,
+    /// A tile up against a wall.
+    Edge,
+}
+
+/// Where a spawned thing would like to be placed.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum SpawnAnchor {
+    /// Tucked up against a wall or in a corner.
+    Edge,
+    /// Where something is likely to walk through.
+    Doorway,
+    /// Near something worth guarding.
+    Guard,
+}
+
+/// How far from its anchor something may be placed.
+const ANCHOR_RADIUS: i32 = 2;
+
+/// The chance, out of 4, that something is placed near an anchor rather than
+/// anywhere in its region.
+const ANCHOR_CHANCE: i32 = 3;
+
+/// How many times to roll the spawn table looking for a wandering monster before
+/// giving up.
+const MAX_WANDERING_MONSTER_ROLLS: usize = 20;
+
+fn spawn_anchor(name: &str) -> SpawnAnchor {
+    match name {
+        "Goblin" | "Orc" | "Giant Rat" | "Kobold Hoarder" | "Goblin Archer" => SpawnAnchor::Guard,
+        "Bear Trap" | "Poison Dart Trap" => SpawnAnchor::Doorway,
+        _ => SpawnAnchor::Edge,
+    }
+}
+
+/// Find the stairs, doorways, and edges in `area`.
+fn find_points_of_interest(map: &Map, area: &[usize]) -> Vec<(usize, PointOfInterest)> {
+    let in_area: FxHashSet<usize> = area.iter().copied().collect();
+
+    area.iter()
+        .filter_map(|&idx| {
+            if matches!(map.tiles[idx], TileType::DownStairs | TileType::UpStairs) {
+                return Some((idx, PointOfInterest::Stairs));
+            }
+
+            let (x, y) = map.idx_xy(idx);
+            let neighbours: Vec<usize> = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .into_iter()
+                .map(|(dx, dy)| (x + dx, y + dy))
+                .filter(|&(nx, ny)| nx >= 0 && nx < map.width && ny >= 0 && ny < map.height)
+                .map(|(nx, ny)| map.xy_idx(nx, ny))
+                .collect();
+
+            if neighbours
+                .iter()
+                .any(|n| map.tiles[*n] != TileType::Wall && !in_area.contains(n))
+            {
+                Some((idx, PointOfInterest::Doorway))
+            } else if neighbours.iter().any(|n| map.tiles[*n] == TileType::Wall) {
+                Some((idx, PointOfInterest::Edge))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Pick one of the `free` tiles, usually within [`ANCHOR_RADIUS`] of one of the
+/// `anchors`.
+fn pick_anchored_tile(
+    map: &Map,
+    rng: &mut RandomNumberGenerator,
+    free: &[usize],
+    anchors: &[usize],
+) -> usize {
+    if !anchors.is_empty() && rng.roll_dice(1, 4) <= ANCHOR_CHANCE {
+        let anchor = anchors[rng.range(0, anchors.len())];
+        let (ax, ay) = map.idx_xy(anchor);
+        let nearby: Vec<usize> = free
+            .iter()
+            .copied()
+            .filter(|&idx| {
+                let (x, y) = map.idx_xy(idx);
+                (x - ax).abs() <= ANCHOR_RADIUS && (y - ay).abs() <= ANCHOR_RADIUS
+            })
+            .collect();
+        if !nearby.is_empty() {
+            return nearby[rng.range(0, nearby.len())];
+        }
+    }
+
+    free[rng.range(0, free.len())]
+}
+
+/// Spawn a monster fit for `map_depth` at a map tile index, if the spawn table
+/// turns one up. Used for monsters that arrive on a level after it's been built.
+pub fn spawn_wandering_monster(ecs: &mut World, map_idx: usize, map_depth: i32) -> Option<Entity> {
+    let spawn_table =
+        room_entity_spawn_table(&ecs.fetch::<Raws>(), map_depth, ecs.fetch::<Map>().theme);
+    let name = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        (0..MAX_WANDERING_MONSTER_ROLLS)
+            .filter_map(|_| spawn_table.roll(&mut rng)?.name())
+            .find(|name| spawn_anchor(name) == SpawnAnchor::Guard)?
+            .to_string()
+    };
+
+    let (x, y) = ecs.fetch::<Map>().idx_xy(map_idx);
+    spawn_named(ecs, x, y, &name)
+}
+
+/// Spawn a named entity from a spawn table at a map tile index, if there is such
+/// a thing. Every name in the raws' spawn tables is checked for when they're loaded.
+pub fn spawn_entity(ecs: &mut World, map_idx: usize, name: &str) -> Option<Entity> {
+    let (x, y) = ecs.fetch::<Map>().idx_xy(map_idx);
+    spawn_named(ecs, x, y, name)
+}
+
+/// Whether there's anything called `name` that [`spawn_named()`] can spawn.
+pub fn can_spawn(name: &str) -> bool {
+    spawn_fn(name).is_some()
+}
+
+/// The function that spawns whatever's called `name`, if there is such a thing.
+fn spawn_fn(name: &str) -> Option<fn(&mut World, i32, i32) -> Entity> {
+    let spawn: fn(&mut World, i32, i32) -> Entity = match name {
+        "Goblin" => spawn_goblin,
+        "Orc" => spawn_orc,
+        "Kobold Hoarder" => spawn_kobold_hoarder,
+        "Goblin Archer" => spawn_goblin_archer,
+        "Giant Rat" => spawn_giant_rat,
+        "Goblin Warchief" => spawn_goblin_warchief,
+        "Orc Warlord" => spawn_orc_warlord,
+        "Pit Fiend" => spawn_pit_fiend,
+        "Health Potion" => spawn_health_potion,
+        "Fireball Scroll" => spawn_fireball_scroll,
+        "Confusion Scroll" => spawn_confusion_scroll,
+        "Magic Missile Scroll" => spawn_magic_missile_scroll,
+        "Noisemaker" => spawn_noisemaker,
+        "Dagger" => spawn_dagger,
+        "Shield" => spawn_shield,
+        "Longsword" => spawn_longsword,
+        "Tower Shield" => spawn_tower_shield,
+        "Leather Armor" => spawn_leather_armor,
+        "Chain Mail" => spawn_chain_mail,
+        "Leather Cap" => spawn_leather_cap,
+        "Iron Helm" => spawn_iron_helm,
+        "Leather Boots" => spawn_leather_boots,
+        "Iron Boots" => spawn_iron_boots,
+        "Bear Trap" => spawn_bear_trap,
+        "Poison Dart Trap" => spawn_poison_dart_trap,
+        "Haste Potion" => spawn_haste_potion,
+        "Strength Potion" => spawn_strength_potion,
+        "Ration" => spawn_ration,
+        "Bear Jerky" => spawn_bear_jerky,
+        "Mushroom" => spawn_mushroom,
+        "Fortitude Potion" => spawn_fortitude_potion,
+        "Slowness Scroll" => spawn_slowness_scroll,
+        "Fear Scroll" => spawn_fear_scroll,
+        "Magic Mapping Scroll" => spawn_magic_mapping_scroll,
+        "Town Portal Scroll" => spawn_town_portal_scroll,
+        "Enchant Weapon Scroll" => {
+            |ecs, x, y| spawn_enchant_scroll(ecs, x, y, EnchantTarget::Weapon)
+        }
+        "Enchant Armor Scroll" => |ecs, x, y| spawn_enchant_scroll(ecs, x, y, EnchantTarget::Armor),
+        "Torch" => spawn_torch,
+        "Brazier" => spawn_brazier,
+        "Chest" => spawn_chest,
+        "Artifact" => item_factory::spawn_artifact,
+        _ => return None,
+    };
+    Some(spawn)
+}
+
+/// Spawn the entity called `name` at (`x`, `y`), if there is such a thing.
+pub fn spawn_named(ecs: &mut World, x: i32, y: i32, name: &str) -> Option<Entity> {
+    let entity = spawn_fn(name)?(ecs, x, y);
+
+    if let Some(description) = description(name) {
+        ecs.write_storage::<Description>()
+            .insert(entity, description)
+            .expect("Unable to insert description");
+    }
+    if let Some(rarity) = rarity(name) {
+        ecs.write_storage::<Rarity>()
+            .insert(entity, rarity)
+            .expect("Unable to insert rarity");
+    }
+    // Artifacts are magic enough already
+    if name != "Artifact" {
+        item_factory::roll_affixes(ecs, entity);
+    }
+
+    Some(entity)
+}
+
+/// How rare the item called `name` is, or `None` if it isn't an item.
+fn rarity(name: &str) -> Option<Rarity> {
+    let rarity = match name {
+        "Health Potion"
+        | "Magic Missile Scroll"
+        | "Noisemaker"
+        | "Dagger"
+        | "Shield"
+        | "Leather Armor"
+        | "Leather Cap"
+        | "Leather Boots"
+        | "Ration"
+        | "Bear Jerky"
+        | "Mushroom"
+        | "Torch" => Rarity::Common,
+        "Fireball Scroll"
+        | "Confusion Scroll"
+        | "Slowness Scroll"
+        | "Fear Scroll"
+        | "Magic Mapping Scroll"
+        | "Town Portal Scroll"
+        | "Haste Potion"
+        | "Longsword"
+        | "Chain Mail"
+        | "Iron Helm"
+        | "Iron Boots" => Rarity::Uncommon,
+        "Tower Shield"
+        | "Strength Potion"
+        | "Fortitude Potion"
+        | "Enchant Weapon Scroll"
+        | "Enchant Armor Scroll" => Rarity::Rare,
+        "Artifact" | "Amulet of Yendor" => Rarity::Legendary,
+        _ => return None,
+    };
+    Some(rarity)
+}
+
+/// The flavour text shown in the tooltip of whatever's called `name`, if it has any.
+fn description(name: &str) -> Option<Description> {
+    let text = match name {
+        "Goblin" => "A scrawny, snivelling thing. Braver in a crowd.",
+        "Orc" => "Big, green, and mean. Goblins follow wherever one leads.",
+        "Kobold Hoarder" => "It can't resist anything shiny, and won't share.",
+        "Goblin Archer" => "Prefers to fight from a safe distance.",
+        "Giant Rat" => "Bites anything that moves, goblins included.",
+        "Goblin Warchief" => "Rules the forest goblins, and never fights alone for long.",
+        "Orc Warlord" => "Its blows shake the halls. Angrier the more it bleeds.",
+        "Pit Fiend" => "Something from below the dungeon, wreathed in smoke and spite.",
+        "Town Guard" | "Guard Captain" => "Keeps the peace in town. Don't give them a reason.",
+        "Merchant" => "Will buy almost anything, for a lot less than they sell it for.",
+        "Healer" => "Patches up wounds, for a price.",
+        "Sage" => "Knows a potion from a poison, for a price.",
+        "Townsperson" => "Goes about their business, and keeps half an eye on yours.",
+        "Farmer" => "Smells of hay, and worries about the harvest.",
+        "Drunk" => "Has been propping up the town since before you arrived.",
+        "Child" => "Far too curious for their own good.",
+        "Stash" => "A sturdy chest. Whatever's left in it stays safe between trips.",
+        "Artifact" => "There's nothing else quite like it, anywhere.",
+        "Health Potion" => "A rich red tonic that knits flesh back together.",
+        "Haste Potion" => "Fizzes and crackles in the bottle.",
+        "Ration" => "Hard bread and harder cheese. Filling, at least.",
+        "Bear Jerky" => "Tough, salty, and light enough to carry a lot of.",
+        "Mushroom" => "Probably edible. Probably.",
+        "Strength Potion" => "Thick and bitter. Swords feel lighter for a while after.",
+        "Fortitude Potion" => "Leaves your skin as tough as old leather, for a while.",
+        "Fireball Scroll" => "Warm to the touch. Best read from a distance.",
+        "Magic Missile Scroll" => "Sends a bolt of force at the nearest foe.",
+        "Confusion Scroll" => "The letters won't stay still long enough to read.",
+        "Slowness Scroll" => "Reading it feels like wading through treacle.",
+        "Fear Scroll" => "Its words make even monsters' blood run cold.",
+        "Magic Mapping Scroll" => "The ink shifts into the shape of the halls around you.",
+        "Town Portal Scroll" => "Opens the way home, and keeps it open for the trip back.",
+        "Enchant Weapon Scroll" => "Sharpens whatever blade it's read over. Usually.",
+        "Enchant Armor Scroll" => "Hardens whatever armor it's read over. Usually.",
+        "Noisemaker" => "Rattles loudly enough to draw attention from afar.",
+        "Torch" => "Lights the way, whether it's carried or left on the floor.",
+        "Chest" => "Whatever's inside is yours for the taking.",
+        "Brazier" => "Coals that have been smouldering for longer than anyone remembers.",
+        "Dagger" => "Short, sharp, and better than bare hands.",
+        "Shield" => "A battered wooden shield.",
+        "Longsword" => "A well-balanced blade with a long reach.",
+        "Tower Shield" => "Heavy enough to hide behind.",
+        "Leather Armor" => "Stiff, scuffed, and better than nothing.",
+        "Chain Mail" => "Thousands of little rings, and every one of them heavy.",
+        "Leather Cap" => "Keeps the rain off, and the odd club.",
+        "Iron Helm" => "Dented, but it's not your head that got dented.",
+        "Leather Boots" => "Well worn in by somebody else.",
+        "Iron Boots" => "Clank with every step.",
+        "Bear Trap" => "Steel jaws, waiting to snap shut.",
+        "Poison Dart Trap" => "A pressure plate, and tiny holes in the walls.",
+        "Portal" => {
+            "A shimmering doorway, held open by magic. Use the stairs keys to step through."
         }
+        "Entrance to the Caves" => "A dark opening into the rock. Press > to go in.",
+        "Amulet of Yendor" => "The reason you came down here. Take it back to the surface.",
+        _ => return None,
+    };
+    Some(Description::from(text))
+}
+
+/// How much fight the monster called `name` has in it. Goblins run as soon as a
+/// fight turns against them, but orcs fight to the death.
+fn monster_morale(name: &str) -> Morale {
+    let flee_below = match name {
+        "Goblin" | "Goblin Archer" => 0.5,
+        "Kobold Hoarder" => 0.4,
+        "Orc" => 0.0,
+        _ => Morale::default().flee_below,
+    };
+    Morale {
+        flee_below,
+        ..Morale::default()
     }
 }
 
 fn spawn_orc(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
-    spawn_monster(ecs, x, y, rltk::to_cp437('o'), "Orc")
+    // Goblins look up to orcs, and lose heart when one falls
+    let orc = spawn_monster(ecs, x, y, rltk::to_cp437('o'), "Orc");
+    ecs.write_storage::<MeleeAttack>()
+        .insert(
+            orc,
+            MeleeAttack {
+                damage: Dice::new(1, 6, 0),
+            },
+        )
+        .expect("Unable to insert orc's melee attack");
+    ecs.write_storage::<Leader>()
+        .insert(orc, Leader)
+        .expect("Unable to make orc a leader");
+    orc
 }
 
 fn spawn_goblin(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
     spawn_monster(ecs, x, y, rltk::to_cp437('g'), "Goblin")
 }
 
+fn spawn_goblin_archer(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    let archer = spawn_monster(ecs, x, y, rltk::to_cp437('a'), "Goblin Archer");
+    ecs.write_storage::<RangedAttack>()
+        .insert(
+            archer,
+            RangedAttack {
+                range: 6,
+                damage: 3,
+            },
+        )
+        .expect("Unable to insert goblin archer's ranged attack");
+    archer
+}
+
+/// Kobold hoarders make their nest wherever they're spawned, and slowly fill it
+/// with any loot they find lying around the level. They're at home in the damp,
+/// and swim well.
+fn spawn_kobold_hoarder(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    let kobold = spawn_monster(ecs, x, y, rltk::to_cp437('k'), "Kobold Hoarder");
+    ecs.write_storage::<Swimmer>()
+        .insert(kobold, Swimmer)
+        .expect("Unable to make kobold a swimmer");
+    ecs.write_storage::<HomeLocation>()
+        .insert(kobold, HomeLocation { x, y })
+        .expect("Unable to insert kobold's home location");
+    ecs.write_storage::<Hoarder>()
+        .insert(kobold, Hoarder { capacity: 2 })
+        .expect("Unable to insert kobold's hoarder component");
+    kobold
+}
+
+/// Giant rats are weak, and only out for themselves. They'll fight goblins as
+/// readily as the player.
+fn spawn_giant_rat(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    let rat = spawn_monster(ecs, x, y, rltk::to_cp437('r'), "Giant Rat");
+    ecs.write_storage::<Faction>()
+        .insert(rat, Faction::Wildlife)
+        .expect("Unable to make giant rat wildlife");
+    if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(rat) {
+        stats.max_hp /= 2;
+        stats.hp = stats.max_hp;
+        stats.power -= 1;
+    }
+    rat
+}
+
+fn spawn_goblin_warchief(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    let abilities = SpecialAbilities {
+        abilities: vec![SpecialAbility::new(
+            Ability::Summon {
+                monster: "Goblin".to_string(),
+                count: 2,
+            },
+            10,
+        )],
+        enrage_power: 2,
+        ..Default::default()
+    };
+    spawn_boss_monster(
+        ecs,
+        x,
+        y,
+        rltk::to_cp437('G'),
+        "Goblin Warchief",
+        3,
+        abilities,
+    )
+}
+
+fn spawn_orc_warlord(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    let abilities = SpecialAbilities {
+        abilities: vec![
+            SpecialAbility::new(
+                Ability::Slam {
+                    radius: 1,
+                    damage: 6,
+                },
+                4,
+            ),
+            SpecialAbility::new(
+                Ability::Summon {
+                    monster: "Goblin Archer".to_string(),
+                    count: 2,
+                },
+                12,
+            ),
+        ],
+        enrage_power: 4,
+        ..Default::default()
+    };
+    spawn_boss_monster(ecs, x, y, rltk::to_cp437('O'), "Orc Warlord", 4, abilities)
+}
+
+fn spawn_pit_fiend(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    let abilities = SpecialAbilities {
+        abilities: vec![
+            SpecialAbility::new(
+                Ability::Slam {
+                    radius: 2,
+                    damage: 8,
+                },
+                5,
+            ),
+            SpecialAbility::new(
+                Ability::Summon {
+                    monster: "Orc".to_string(),
+                    count: 2,
+                },
+                15,
+            ),
+        ],
+        enrage_power: 5,
+        ..Default::default()
+    };
+    spawn_boss_monster(ecs, x, y, rltk::to_cp437('&'), "Pit Fiend", 5, abilities)
+}
+
+/// Spawn a [`Boss`]: a monster with `hp_multiplier` times the usual health, that
+/// hits harder the more of it there is, and has `abilities` to use. Bosses never
+/// lose their nerve.
+fn spawn_boss_monster(
+    ecs: &mut World,
+    x: i32,
+    y: i32,
+    glyph: rltk::FontCharType,
+    name: &str,
+    hp_multiplier: i32,
+    abilities: SpecialAbilities,
+) -> specs::Entity {
+    let boss = spawn_monster(ecs, x, y, glyph, name);
+    if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(boss) {
+        stats.max_hp *= hp_multiplier;
+        stats.hp = stats.max_hp;
+        stats.power += hp_multiplier;
+        stats.defense += 1;
+    }
+    if let Some(renderable) = ecs.write_storage::<Renderable>().get_mut(boss) {
+        renderable.fg = RGB::named(rltk::MAGENTA);
+    }
+    ecs.write_storage::<Morale>().remove(boss);
+    ecs.write_storage::<Boss>()
+        .insert(boss, Boss)
+        .expect("Unable to make monster a boss");
+    ecs.write_storage::<SpecialAbilities>()
+        .insert(boss, abilities)
+        .expect("Unable to insert boss's special abilities");
+    boss
+}
+
 fn spawn_monster<S: ToString>(
     ecs: &mut World,
     x: i32,
@@ -120,15 +1109,28 @@ fn spawn_monster<S: ToString>(
     glyph: rltk::FontCharType,
     name: S,
 ) -> specs::Entity {
+    let gold = ecs
+        .write_resource::<RandomNumberGenerator>()
+        .roll_dice(2, 6);
+    let hp = ecs.fetch::<RunOptions>().difficulty.scale_monster_hp(16);
+    let name = name.to_string();
+
     ecs.create_entity()
         .with(Monster)
-        .with(Name::from(name.to_string()))
+        .with(Faction::Goblinoids)
+        .with(Purse { gold })
+        .with(monster_morale(&name))
+        .with(AiState::default())
+        .with(Name::from(name))
         .with(BlocksTile)
         .with(CombatStats {
-            max_hp: 16,
-            hp: 16,
+            max_hp: hp,
+            hp,
             defense: 1,
-            power: 4,
+            power: 2,
+        })
+        .with(MeleeAttack {
+            damage: Dice::new(1, 4, 0),
         })
         .with(Position::from((x, y)))
         .with(Renderable {
@@ -148,8 +1150,11 @@ fn spawn_monster<S: ToString>(
 fn spawn_health_potion(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
     ecs.create_entity()
         .with(Item)
+        .with(Value { gold: 15 })
         .with(Consumable)
+        .with(Shatters)
         .with(ProvidesHealing { heal_amount: 8 })
+        .with(Weight { pounds: 1 })
         .with(Name::from("Health Potion"))
         .with(Position::from((x, y)))
         .with(Renderable {
@@ -162,17 +1167,17 @@ fn spawn_health_potion(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
         .build()
 }
 
-fn spawn_fireball_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+fn spawn_ration(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
     ecs.create_entity()
         .with(Item)
+        .with(Value { gold: 8 })
         .with(Consumable)
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 20 })
-        .with(AreaOfEffect { radius: 3 })
-        .with(Name::from("Fireball Scroll"))
+        .with(ProvidesFood)
+        .with(Weight { pounds: 2 })
+        .with(Name::from("Ration"))
         .with(Position::from((x, y)))
         .with(Renderable {
-            glyph: rltk::to_cp437(')'),
+            glyph: rltk::to_cp437('%'),
             fg: RGB::named(rltk::ORANGE),
             render_order: 2,
             ..Default::default()
@@ -181,17 +1186,18 @@ fn spawn_fireball_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
         .build()
 }
 
-fn spawn_magic_missile_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+fn spawn_bear_jerky(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
     ecs.create_entity()
         .with(Item)
+        .with(Value { gold: 5 })
         .with(Consumable)
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 8 })
-        .with(Name::from("Magic Missile Scroll"))
+        .with(ProvidesFood)
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Bear Jerky"))
         .with(Position::from((x, y)))
         .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::CYAN),
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::BURLYWOOD),
             render_order: 2,
             ..Default::default()
         })
@@ -199,20 +1205,823 @@ fn spawn_magic_missile_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity
         .build()
 }
 
-fn spawn_confusion_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+/// One in every [`BAD_MUSHROOM_CHANCE`] mushrooms leaves whoever eats it confused.
+const BAD_MUSHROOM_CHANCE: i32 = 4;
+
+fn spawn_mushroom(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    let bad = ecs
+        .write_resource::<RandomNumberGenerator>()
+        .roll_dice(1, BAD_MUSHROOM_CHANCE)
+        == 1;
+    let mut mushroom = ecs
+        .create_entity()
+        .with(Item)
+        .with(Value { gold: 2 })
+        .with(Consumable)
+        .with(ProvidesFood)
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Mushroom"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('♣'),
+            fg: RGB::named(rltk::WHEAT),
+            render_order: 2,
+            ..Default::default()
+        });
+    if bad {
+        mushroom = mushroom.with(InflictsStatusEffect {
+            effect: StatusEffect::Confused,
+            turns: 5,
+        });
+    }
+    mushroom.marked::<SimpleMarker<Serializable>>().build()
+}
+
+fn spawn_strength_potion(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 30 })
+        .with(Consumable)
+        .with(Shatters)
+        .with(InflictsStatusEffect {
+            effect: StatusEffect::Strengthened,
+            turns: 20,
+        })
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Strength Potion"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('¡'),
+            fg: RGB::named(rltk::ORANGE),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_fortitude_potion(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 30 })
+        .with(Consumable)
+        .with(Shatters)
+        .with(InflictsStatusEffect {
+            effect: StatusEffect::Fortified,
+            turns: 20,
+        })
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Fortitude Potion"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('¡'),
+            fg: RGB::named(rltk::SLATE_GRAY),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_haste_potion(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 25 })
+        .with(Consumable)
+        .with(Shatters)
+        .with(InflictsStatusEffect {
+            effect: StatusEffect::Hasted,
+            turns: 10,
+        })
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Haste Potion"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('¡'),
+            fg: RGB::named(rltk::GREEN),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_fireball_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
     ecs.create_entity()
         .with(Item)
+        .with(Value { gold: 30 })
         .with(Consumable)
         .with(Ranged { range: 6 })
-        .with(Confusion { turns: 4 })
-        .with(Name::from("Confusion Scroll"))
+        .with(InflictsDamage { damage: 20 })
+        .with(AreaOfEffect { radius: 3 })
+        .with(InflictsStatusEffect {
+            effect: StatusEffect::Burning,
+            turns: 3,
+        })
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Fireball Scroll"))
         .with(Position::from((x, y)))
         .with(Renderable {
             glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::PINK),
+            fg: RGB::named(rltk::ORANGE),
             render_order: 2,
             ..Default::default()
         })
         .marked::<SimpleMarker<Serializable>>()
         .build()
 }
+
+fn spawn_magic_missile_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 20 })
+        .with(Consumable)
+        .with(Ranged { range: 6 })
+        .with(InflictsDamage { damage: 8 })
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Magic Missile Scroll"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::CYAN),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_confusion_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 20 })
+        .with(Consumable)
+        .with(Ranged { range: 6 })
+        .with(InflictsStatusEffect {
+            effect: StatusEffect::Confused,
+            turns: 4,
+        })
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Confusion Scroll"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::PINK),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_slowness_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 20 })
+        .with(Consumable)
+        .with(Ranged { range: 6 })
+        .with(InflictsStatusEffect {
+            effect: StatusEffect::Slowed,
+            turns: 8,
+        })
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Slowness Scroll"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::LIGHT_BLUE),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_fear_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 20 })
+        .with(Consumable)
+        .with(Ranged { range: 6 })
+        .with(AreaOfEffect { radius: 2 })
+        .with(InflictsStatusEffect {
+            effect: StatusEffect::Frightened,
+            turns: 6,
+        })
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Fear Scroll"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::WHITE),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 40 })
+        .with(Consumable)
+        .with(ProvidesMapping)
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Magic Mapping Scroll"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::GOLD),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_town_portal_scroll(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 50 })
+        .with(Consumable)
+        .with(ProvidesTownPortal)
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Town Portal Scroll"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::MEDIUM_PURPLE),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_enchant_scroll(ecs: &mut World, x: i32, y: i32, target: EnchantTarget) -> specs::Entity {
+    let name = match target {
+        EnchantTarget::Weapon => "Enchant Weapon Scroll",
+        EnchantTarget::Armor => "Enchant Armor Scroll",
+    };
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 120 })
+        .with(Consumable)
+        .with(Enchants { target })
+        .with(Weight { pounds: 1 })
+        .with(Name::from(name))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::LIGHT_BLUE),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_noisemaker(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 5 })
+        .with(Consumable)
+        .with(Ranged { range: 8 })
+        .with(MakesNoise { volume: 16 })
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Noisemaker"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('*'),
+            fg: RGB::named(rltk::YELLOW),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_dagger(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    // Easy to slip between the ribs
+    let dagger = spawn_melee_weapon(ecs, x, y, "Dagger", Dice::new(1, 6, 0), 2, 20);
+    ecs.write_storage::<CritRangeBonus>()
+        .insert(dagger, CritRangeBonus { range: 2 })
+        .expect("Unable to insert dagger's crit range bonus");
+    dagger
+}
+
+fn spawn_longsword(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    spawn_melee_weapon(ecs, x, y, "Longsword", Dice::new(1, 8, 1), 4, 45)
+}
+
+/// A weapon that rolls `damage` when it hits, and adds `power` on top. Better
+/// weapons are shown in yellow.
+fn spawn_melee_weapon<S: ToString>(
+    ecs: &mut World,
+    x: i32,
+    y: i32,
+    name: S,
+    damage: Dice,
+    power: i32,
+    value: i32,
+) -> specs::Entity {
+    let fg = if power > 2 {
+        RGB::named(rltk::YELLOW)
+    } else {
+        RGB::named(rltk::CYAN)
+    };
+
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: value })
+        // Hitting harder takes more metal
+        .with(Weight { pounds: power })
+        .with(Equippable {
+            slot: EquipmentSlot::Melee,
+        })
+        .with(MeleeAttack { damage })
+        .with(MeleePowerBonus { power })
+        .with(Name::from(name.to_string()))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('/'),
+            fg,
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_shield(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    let shield = spawn_armor(ecs, x, y, "Shield", EquipmentSlot::Shield, 1, 20);
+    ecs.write_storage::<BlockChance>()
+        .insert(shield, BlockChance { percent: 10 })
+        .expect("Unable to insert shield's block chance");
+    shield
+}
+
+fn spawn_tower_shield(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    let shield = spawn_armor(ecs, x, y, "Tower Shield", EquipmentSlot::Shield, 3, 45);
+    ecs.write_storage::<BlockChance>()
+        .insert(shield, BlockChance { percent: 25 })
+        .expect("Unable to insert shield's block chance");
+    shield
+}
+
+fn spawn_leather_armor(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    spawn_armor(ecs, x, y, "Leather Armor", EquipmentSlot::Torso, 1, 20)
+}
+
+fn spawn_chain_mail(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    spawn_armor(ecs, x, y, "Chain Mail", EquipmentSlot::Torso, 2, 50)
+}
+
+fn spawn_leather_cap(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    spawn_armor(ecs, x, y, "Leather Cap", EquipmentSlot::Head, 1, 15)
+}
+
+fn spawn_iron_helm(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    spawn_armor(ecs, x, y, "Iron Helm", EquipmentSlot::Head, 2, 35)
+}
+
+fn spawn_leather_boots(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    spawn_armor(ecs, x, y, "Leather Boots", EquipmentSlot::Feet, 1, 15)
+}
+
+fn spawn_iron_boots(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    spawn_armor(ecs, x, y, "Iron Boots", EquipmentSlot::Feet, 2, 35)
+}
+
+/// Anything worn for its [`DefenseBonus`], whether that's a shield or a piece of
+/// armor. Sturdier pieces are shown in yellow, like the better weapons.
+fn spawn_armor<S: ToString>(
+    ecs: &mut World,
+    x: i32,
+    y: i32,
+    name: S,
+    slot: EquipmentSlot,
+    defense: i32,
+    value: i32,
+) -> specs::Entity {
+    let (glyph, pounds) = match slot {
+        EquipmentSlot::Shield => ('(', defense * 5),
+        EquipmentSlot::Torso => ('[', defense * 8),
+        _ => ('[', defense * 2),
+    };
+    let fg = if defense > 1 {
+        RGB::named(rltk::YELLOW)
+    } else {
+        RGB::named(rltk::CYAN)
+    };
+
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: value })
+        .with(Weight { pounds })
+        .with(Equippable { slot })
+        .with(DefenseBonus { defense })
+        .with(Name::from(name.to_string()))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437(glyph),
+            fg,
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_torch(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Item)
+        .with(Value { gold: 5 })
+        .with(LightSource {
+            range: 6,
+            color: RGB::from_f32(1.0, 0.8, 0.5),
+        })
+        .with(Weight { pounds: 2 })
+        .with(Name::from("Torch"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('/'),
+            fg: RGB::named(rltk::ORANGE),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+/// A bowl of burning coals that lights up its surroundings for good. Too hot to
+/// carry around.
+fn spawn_brazier(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(LightSource {
+            range: 8,
+            color: RGB::from_f32(1.0, 0.5, 0.2),
+        })
+        .with(Name::from("Brazier"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('☼'),
+            fg: RGB::named(rltk::ORANGE_RED),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+/// A chest with a few items from the [`chest_loot_table()`] inside, for the
+/// player to take or to leave things in.
+fn spawn_chest(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    let chest = ecs
+        .create_entity()
+        .with(Container)
+        .with(Name::from("Chest"))
+        .with(BlocksTile)
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('■'),
+            fg: RGB::named(rltk::CHOCOLATE),
+            render_order: 1,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build();
+
+    let loot: Vec<String> = {
+        let table = chest_loot_table(&ecs.fetch::<Raws>(), ecs.fetch::<Map>().depth);
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let count = rng.roll_dice(1, 3);
+        (0..count)
+            .filter_map(|_| table.roll(&mut rng)?.name().map(str::to_string))
+            .collect()
+    };
+    let loot: Vec<&str> = loot.iter().map(String::as_str).collect();
+    give_items(ecs, chest, &loot);
+
+    chest
+}
+
+fn spawn_bear_trap(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Name::from("Bear Trap"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('^'),
+            fg: RGB::named(rltk::RED),
+            render_order: 2,
+            ..Default::default()
+        })
+        .with(Hidden)
+        .with(EntryTrigger)
+        .with(InflictsDamage { damage: 6 })
+        .with(SingleActivation)
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+fn spawn_poison_dart_trap(ecs: &mut World, x: i32, y: i32) -> specs::Entity {
+    ecs.create_entity()
+        .with(Name::from("Poison Dart Trap"))
+        .with(Position::from((x, y)))
+        .with(Renderable {
+            glyph: rltk::to_cp437('^'),
+            fg: RGB::named(rltk::GREEN),
+            render_order: 2,
+            ..Default::default()
+        })
+        .with(Hidden)
+        .with(EntryTrigger)
+        .with(InflictsDamage { damage: 2 })
+        .with(InflictsStatusEffect {
+            effect: StatusEffect::Poisoned,
+            turns: 5,
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+/// Spawn the Amulet of Yendor at a map tile index. Win the game by using it, or by
+/// carrying it back up to the surface.
+pub fn spawn_amulet(ecs: &mut World, map_idx: usize) -> specs::Entity {
+    let pos = Position::from(ecs.fetch::<Map>().idx_xy(map_idx));
+    ecs.create_entity()
+        .with(Item)
+        .with(WinsGame)
+        .with(Weight { pounds: 1 })
+        .with(Name::from("Amulet of Yendor"))
+        .maybe_with(description("Amulet of Yendor"))
+        .maybe_with(rarity("Amulet of Yendor"))
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('♀'),
+            fg: RGB::named(rltk::GOLD),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+/// Make the stairs at a map tile index lead to `destination`. The stairs
+/// themselves are drawn as part of the map, so the exit isn't.
+pub fn spawn_stairs(ecs: &mut World, map_idx: usize, destination: LevelId) -> specs::Entity {
+    let pos = Position::from(ecs.fetch::<Map>().idx_xy(map_idx));
+    ecs.create_entity()
+        .with(LevelExit { destination })
+        .with(pos)
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+/// A [`Portal`] at `pos`, leading to `destination`.
+pub fn spawn_portal(ecs: &mut World, pos: Position, destination: LevelId) -> specs::Entity {
+    ecs.create_entity()
+        .with(Portal)
+        .with(LevelExit { destination })
+        .with(Name::from("Portal"))
+        .maybe_with(description("Portal"))
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('Ω'),
+            fg: RGB::named(rltk::MEDIUM_PURPLE),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build()
+}
+
+/// Open up the way into `branch` somewhere on the current level that can be
+/// reached from `start`, but not too close to it.
+pub fn spawn_branch_entrance(ecs: &mut World, branch: Branch, start: Position) {
+    const MIN_DISTANCE: f32 = 10.0;
+
+    let Some(entrance) = branch.entrance() else {
+        return;
+    };
+    let destination = LevelId {
+        branch,
+        depth: entrance.depth + 1,
+    };
+
+    let spot = {
+        let map = ecs.fetch::<Map>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let reachable = rltk::DijkstraMap::new(
+            map.width,
+            map.height,
+            &[map.xy_idx(start.x, start.y)],
+            &*map,
+            1000.0,
+        );
+        let spots: Vec<usize> = (0..map.tiles.len())
+            .filter(|idx| {
+                map.tiles[*idx] == TileType::Floor
+                    && reachable.map[*idx] >= MIN_DISTANCE
+                    && reachable.map[*idx] < f32::MAX
+            })
+            .collect();
+        (!spots.is_empty()).then(|| spots[rng.range(0, spots.len())])
+    };
+    let Some(idx) = spot else {
+        return;
+    };
+
+    let pos = Position::from(ecs.fetch::<Map>().idx_xy(idx));
+    let name = format!("Entrance to the {branch}");
+    ecs.create_entity()
+        .with(LevelExit { destination })
+        .maybe_with(description(&name))
+        .with(Name::from(name.as_str()))
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('∩'),
+            fg: RGB::named(rltk::SANDY_BROWN),
+            render_order: 2,
+            ..Default::default()
+        })
+        .marked::<SimpleMarker<Serializable>>()
+        .build();
+}
+
+/// The boss that guards `level`, if it has one. There's one every few levels of
+/// the main dungeon.
+const fn level_boss(level: LevelId) -> Option<&'static str> {
+    match (level.branch, level.depth) {
+        (Branch::Main, 3) => Some("Goblin Warchief"),
+        (Branch::Main, 6) => Some("Orc Warlord"),
+        (Branch::Main, 9) => Some("Pit Fiend"),
+        _ => None,
+    }
+}
+
+/// Spawn the boss of `level`, if it has one, as far from `start` as can be reached.
+pub fn spawn_boss(ecs: &mut World, level: LevelId, start: Position) {
+    let Some(name) = level_boss(level) else {
+        return;
+    };
+
+    let spot = {
+        let map = ecs.fetch::<Map>();
+        let positions = ecs.read_storage::<Position>();
+        let blockers = ecs.read_storage::<BlocksTile>();
+        let occupied: FxHashSet<usize> = (&positions, &blockers)
+            .join()
+            .map(|(pos, _)| map.xy_idx(pos.x, pos.y))
+            .collect();
+        let reachable = rltk::DijkstraMap::new(
+            map.width,
+            map.height,
+            &[map.xy_idx(start.x, start.y)],
+            &*map,
+            1000.0,
+        );
+        (0..map.tiles.len())
+            .filter(|idx| {
+                map.tiles[*idx] == TileType::Floor
+                    && !occupied.contains(idx)
+                    && reachable.map[*idx] < f32::MAX
+            })
+            .max_by(|a, b| reachable.map[*a].total_cmp(&reachable.map[*b]))
+    };
+    if let Some(idx) = spot {
+        spawn_entity(ecs, idx, name);
+    }
+}
+
+/// How likely a character that died on a level is to come back and haunt it, as
+/// one in this many.
+pub const GHOST_ODDS: i32 = 3;
+
+/// Raise the ghost of the character whose `bones` were left on the current level,
+/// where they died if that spot can be reached from `start`, or anywhere else that
+/// can be. The ghost is only half the fighter they were in life, and loses half of
+/// what they were carrying on the way back, but still wields anything they had
+/// equipped. Returns `None` if there's nowhere to put them.
+pub(crate) fn spawn_ghost(ecs: &mut World, bones: &Bones, start: Position) -> Option<Entity> {
+    let spot = {
+        let map = ecs.fetch::<Map>();
+        let positions = ecs.read_storage::<Position>();
+        let blockers = ecs.read_storage::<BlocksTile>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let occupied: FxHashSet<usize> = (&positions, &blockers)
+            .join()
+            .map(|(pos, _)| map.xy_idx(pos.x, pos.y))
+            .collect();
+        let reachable = rltk::DijkstraMap::new(
+            map.width,
+            map.height,
+            &[map.xy_idx(start.x, start.y)],
+            &*map,
+            1000.0,
+        );
+        let spots: Vec<usize> = (0..map.tiles.len())
+            .filter(|idx| {
+                map.tiles[*idx] == TileType::Floor
+                    && !occupied.contains(idx)
+                    && reachable.map[*idx] > 0.0
+                    && reachable.map[*idx] < f32::MAX
+            })
+            .collect();
+        let where_they_fell = map.xy_idx(bones.x, bones.y);
+        if spots.contains(&where_they_fell) {
+            Some(where_they_fell)
+        } else {
+            (!spots.is_empty()).then(|| spots[rng.range(0, spots.len())])
+        }
+    }?;
+
+    let (x, y) = ecs.fetch::<Map>().idx_xy(spot);
+    let ghost = spawn_monster(
+        ecs,
+        x,
+        y,
+        rltk::to_cp437('@'),
+        format!("Ghost of {}", bones.name),
+    );
+    if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(ghost) {
+        stats.max_hp = (bones.max_hp / 2).max(1);
+        stats.hp = stats.max_hp;
+        stats.power = bones.power;
+        stats.defense = bones.defense;
+    }
+    if let Some(renderable) = ecs.write_storage::<Renderable>().get_mut(ghost) {
+        renderable.fg = RGB::named(rltk::LIGHT_SLATE);
+    }
+    // The dead have nothing left to lose
+    ecs.write_storage::<Morale>().remove(ghost);
+    ecs.write_storage::<Description>()
+        .insert(
+            ghost,
+            Description::from("Someone who died here before you, and isn't done yet."),
+        )
+        .expect("Unable to insert ghost's description");
+
+    for (name, was_equipped) in &bones.items {
+        if ecs.write_resource::<RandomNumberGenerator>().range(0, 2) == 0 {
+            continue;
+        }
+        let Some(item) = spawn_named(ecs, 0, 0, name) else {
+            continue;
+        };
+        ecs.write_storage::<Position>().remove(item);
+        let slot = ecs.read_storage::<Equippable>().get(item).map(|e| e.slot);
+        match slot {
+            Some(slot) if *was_equipped => {
+                ecs.write_storage::<Equipped>()
+                    .insert(item, Equipped { owner: ghost, slot })
+                    .expect("Unable to equip ghost's item");
+            }
+            _ => {
+                ecs.write_storage::<InBackpack>()
+                    .insert(item, InBackpack { owner: ghost })
+                    .expect("Unable to give ghost its item");
+            }
+        }
+    }
+
+    Some(ghost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::State;
+
+    #[test]
+    fn spawn_tables_only_name_spawnable_things() {
+        let mut gs = State::new().unwrap();
+        gs.start_new_run();
+
+        let tables = gs.ecs.fetch::<Raws>().spawn_tables.clone();
+        let themed = [
+            MapTheme::Dungeon,
+            MapTheme::Forest,
+            MapTheme::LimestoneCavern,
+            MapTheme::DwarfFortress,
+            MapTheme::Hell,
+        ]
+        .into_iter()
+        .flat_map(|theme| theme_spawn_bias(theme).iter().map(|(name, _)| *name));
+        let names = tables
+            .rooms
+            .iter()
+            .chain(tables.chest_loot.iter())
+            .chain(tables.vendor_stock.iter())
+            .filter_map(|entry| Spawn::from(entry.name.as_str()).name().map(str::to_string))
+            .chain(themed.map(str::to_string));
+
+        for name in names {
+            assert!(
+                spawn_named(&mut gs.ecs, 0, 0, &name).is_some(),
+                "{name:?} is in a spawn table, but can't be spawned"
+            );
+        }
+    }
+}
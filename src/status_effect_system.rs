@@ -0,0 +1,89 @@
+use specs::prelude::*;
+
+use crate::effects::{EffectQueue, EffectType, Targets};
+use crate::l10n::{self, tr};
+use crate::{GameEvent, GameEvents, GameLog, PlayerEntity, RunState, StatusEffect, StatusEffects};
+
+/// A system that applies every entity's [`StatusEffects`] once per turn, and
+/// ticks down how long they've got left.
+pub struct StatusEffectSystem;
+
+impl<'a> System<'a> for StatusEffectSystem {
+    type SystemData = (
+        ReadExpect<'a, PlayerEntity>,
+        ReadExpect<'a, RunState>,
+        Entities<'a>,
+        WriteStorage<'a, StatusEffects>,
+        WriteExpect<'a, EffectQueue>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, GameEvents>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            player_entity,
+            runstate,
+            entities,
+            mut status_effects,
+            mut effect_queue,
+            mut log,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        // The player can get more than one go a turn, but the monsters only get one,
+        // so tick on theirs
+        if *runstate != RunState::MonsterTurn {
+            return;
+        }
+
+        let mut recovered = Vec::new();
+
+        for (entity, effects) in (&entities, &mut status_effects).join() {
+            for active in effects.active.iter_mut() {
+                let damage = active.effect.damage_per_turn();
+                if damage > 0 {
+                    effect_queue.add_effect(
+                        EffectType::Damage {
+                            amount: damage,
+                            attacker: None,
+                        },
+                        Targets::Single { target: entity },
+                    );
+                    events.push(GameEvent::StatusDamage {
+                        target: entity,
+                        effect: active.effect,
+                        amount: damage,
+                    });
+                    if entity == **player_entity {
+                        log.log(
+                            tr("status-damage")
+                                .arg("effect", l10n::status_effect(active.effect))
+                                .arg("damage", tr("hit-points").arg("amount", damage)),
+                        );
+                    }
+                }
+
+                active.turns -= 1;
+                // Wading only lasts a step, so its end isn't worth announcing
+                if active.turns < 1
+                    && entity == **player_entity
+                    && active.effect != StatusEffect::Wading
+                {
+                    log.log(
+                        tr("status-wears-off").arg("effect", l10n::status_effect(active.effect)),
+                    );
+                }
+            }
+
+            effects.active.retain(|active| active.turns > 0);
+            if effects.active.is_empty() {
+                recovered.push(entity);
+            }
+        }
+
+        for entity in recovered {
+            status_effects.remove(entity);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Where the game keeps its saves and settings. Natively, that's plain files
+//! relative to the working directory. On the web there's no filesystem, so the
+//! same paths are used as keys into the browser's `localStorage` instead.
+
+use std::io;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use self::native::*;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use self::web::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::io;
+    use std::path::Path;
+
+    /// Read everything stored at `path`.
+    pub(crate) fn read(path: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    /// Store `data` at `path`, replacing whatever was there, and creating any
+    /// directories it needs to go in.
+    pub(crate) fn write(path: &str, data: &[u8]) -> io::Result<()> {
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, data)
+    }
+
+    /// Whether anything is stored at `path`.
+    pub(crate) fn exists(path: &str) -> bool {
+        Path::new(path).exists()
+    }
+
+    /// Delete whatever is stored at `path`, if there's anything there.
+    pub(crate) fn remove(path: &str) -> io::Result<()> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    /// The current time, in seconds since the Unix epoch.
+    pub(crate) fn unix_time() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use std::io;
+
+    use js_sys::wasm_bindgen::JsValue;
+
+    fn local_storage() -> io::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::Unsupported, "localStorage isn't available")
+            })
+    }
+
+    fn js_error(e: JsValue) -> io::Error {
+        io::Error::other(format!("{e:?}"))
+    }
+
+    /// Read everything stored at `path`.
+    pub(crate) fn read(path: &str) -> io::Result<Vec<u8>> {
+        let text = local_storage()?
+            .get_item(path)
+            .map_err(js_error)?
+            .ok_or(io::ErrorKind::NotFound)?;
+        super::decode(&text)
+    }
+
+    /// Store `data` at `path`, replacing whatever was there.
+    pub(crate) fn write(path: &str, data: &[u8]) -> io::Result<()> {
+        local_storage()?
+            .set_item(path, &super::encode(data))
+            .map_err(js_error)
+    }
+
+    /// Whether anything is stored at `path`.
+    pub(crate) fn exists(path: &str) -> bool {
+        local_storage().is_ok_and(|storage| storage.get_item(path).is_ok_and(|item| item.is_some()))
+    }
+
+    /// Delete whatever is stored at `path`, if there's anything there.
+    pub(crate) fn remove(path: &str) -> io::Result<()> {
+        local_storage()?.remove_item(path).map_err(js_error)
+    }
+
+    /// The current time, in seconds since the Unix epoch.
+    pub(crate) fn unix_time() -> u64 {
+        (js_sys::Date::now() / 1000.0) as u64
+    }
+}
+
+/// `localStorage` can only hold strings, so bytes are stored one per character.
+/// RON saves come out as the same text they'd be in a file, give or take anything
+/// outside of ASCII.
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+fn encode(data: &[u8]) -> String {
+    data.iter().copied().map(char::from).collect()
+}
+
+/// Undo [`encode()`].
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+fn decode(text: &str) -> io::Result<Vec<u8>> {
+    text.chars()
+        .map(|c| u8::try_from(c).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{decode, encode};
+
+    proptest! {
+        #[test]
+        fn encoded_bytes_decode_to_themselves(data in prop::collection::vec(any::<u8>(), 0..512)) {
+            prop_assert_eq!(decode(&encode(&data)).unwrap(), data);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! The one place where every system that runs each tick is listed, along with
+//! what has to run before what.
+
+use specs::prelude::*;
+
+use crate::{
+    BossAI, BystanderAI, DrowningSystem, GoldCollectionSystem, HoarderAI, HungerSystem,
+    ItemCollectionSystem, ItemDropSystem, ItemThrowSystem, ItemTransferSystem, ItemUseSystem,
+    LightingSystem, MapIndexingSystem, MeleeCombatSystem, MonsterAI, NoiseSystem, PackAI,
+    StatusEffectSystem, TriggerSystem, VisibilitySystem,
+};
+
+/// Build the dispatcher that runs every system once per tick.
+///
+/// Systems that don't touch any of the same data are free to run alongside each
+/// other on the thread pool. Where one system relies on what another does (the
+/// map needs lighting before anyone can see, monsters act on what they can see,
+/// and so on) the dependency is spelled out here. Systems that merely share some
+/// data, without caring which goes first, are left for specs to keep apart.
+pub fn build_dispatcher() -> Dispatcher<'static, 'static> {
+    DispatcherBuilder::new()
+        .with(LightingSystem, "lighting", &[])
+        .with(VisibilitySystem, "visibility", &["lighting"])
+        .with(HungerSystem, "hunger", &[])
+        // Monster AI acts on what everyone can see. Packs decide what their members
+        // do first, and bosses that used an ability this turn don't get to move as
+        // well.
+        .with(PackAI, "packs", &["visibility"])
+        .with(BossAI, "bosses", &["visibility", "packs"])
+        .with(MonsterAI, "monsters", &["visibility", "packs", "bosses"])
+        .with(HoarderAI, "hoarders", &["visibility", "monsters"])
+        .with(BystanderAI, "bystanders", &[])
+        // Everything that happens because of where everyone's ended up
+        .with(
+            MapIndexingSystem,
+            "map_indexing",
+            &["monsters", "hoarders", "bystanders"],
+        )
+        .with(GoldCollectionSystem, "gold", &["map_indexing"])
+        // Triggers clear out who moved this tick, so gold has to look first
+        .with(TriggerSystem, "triggers", &["map_indexing", "gold"])
+        .with(MeleeCombatSystem, "melee", &["monsters"])
+        // Status effects wear off and deep water drowns at the end of the monsters'
+        // turn, once everyone has acted (or been stopped from acting) and moved
+        .with(
+            StatusEffectSystem,
+            "status_effects",
+            &["bosses", "monsters", "hoarders", "bystanders", "melee"],
+        )
+        .with(
+            DrowningSystem,
+            "drowning",
+            &["bosses", "monsters", "hoarders", "bystanders"],
+        )
+        // Whatever the player (or a hoarder) wanted to do with items
+        .with(ItemCollectionSystem, "pickup", &["hoarders"])
+        .with(ItemTransferSystem, "transfer", &[])
+        .with(ItemDropSystem, "drop", &["hoarders"])
+        .with(ItemThrowSystem, "throw", &[])
+        .with(ItemUseSystem, "use_items", &[])
+        .with(NoiseSystem, "noise", &["use_items"])
+        .build()
+}
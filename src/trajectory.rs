@@ -0,0 +1,60 @@
+use rltk::Point;
+
+use crate::{Map, TileType};
+
+/// The path taken by a projectile, like an arrow or a magic missile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trajectory {
+    /// Every tile the projectile passed through, not counting where it started.
+    pub path: Vec<Point>,
+    /// Where the projectile came to a stop.
+    pub impact: Point,
+}
+
+/// Work out where a projectile fired from `from` towards `to` ends up.
+///
+/// The projectile flies along a straight (Bresenham) line, and stops at `to`,
+/// once it has gone `range` tiles, just before the first wall in the way, or on
+/// the first tile with something blocking in it - whichever comes first. The
+/// target doesn't need to be visible, so shots can be fired blindly down a
+/// corridor at whatever might be lurking there.
+pub fn resolve_trajectory(map: &Map, from: Point, to: Point, range: i32) -> Trajectory {
+    let mut trajectory = Trajectory {
+        path: Vec::new(),
+        impact: from,
+    };
+
+    for point in rltk::line2d_bresenham(from, to).into_iter().skip(1) {
+        if rltk::DistanceAlg::Pythagoras.distance2d(from, point) > range as f32
+            || point.x < 0
+            || point.x >= map.width
+            || point.y < 0
+            || point.y >= map.height
+        {
+            break;
+        }
+
+        let idx = map.xy_idx(point.x, point.y);
+        if map.tiles[idx] == TileType::Wall {
+            break;
+        }
+
+        trajectory.path.push(point);
+        trajectory.impact = point;
+
+        if map.blocked[idx] {
+            break;
+        }
+    }
+
+    trajectory
+}
+
+/// Every tile caught in a blast of `radius` centred on `center`. The blast
+/// doesn't go through walls, or reach the edges of the map.
+pub fn blast_area(map: &Map, center: Point, radius: i32) -> Vec<Point> {
+    rltk::field_of_view(center, radius, map)
+        .into_iter()
+        .filter(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1)
+        .collect()
+}
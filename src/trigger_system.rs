@@ -0,0 +1,42 @@
+use specs::prelude::*;
+
+use crate::effects::{EffectQueue, EffectType, Targets};
+use crate::{EntityMoved, EntryTrigger, Map, Position};
+
+/// A system that sets off any [`EntryTrigger`]s that an entity stepped onto this tick.
+///
+/// What each trap actually does is up to [`crate::effects`]: triggered traps are
+/// revealed, and [`crate::SingleActivation`] traps are used up.
+pub struct TriggerSystem;
+
+impl<'a> System<'a> for TriggerSystem {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        WriteStorage<'a, EntityMoved>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, EntryTrigger>,
+        Entities<'a>,
+        WriteExpect<'a, EffectQueue>,
+    );
+
+    fn run(
+        &mut self,
+        (map, mut entity_moved, position, entry_triggers, entities, mut effects): Self::SystemData,
+    ) {
+        for (entity, _moved, pos) in (&entities, &entity_moved, &position).join() {
+            let idx = map.xy_idx(pos.x, pos.y);
+            for trap in map.tile_content[idx].iter().copied() {
+                if trap == entity || !entry_triggers.contains(trap) {
+                    continue;
+                }
+
+                effects.add_effect(
+                    EffectType::TriggerFire { trigger: trap },
+                    Targets::Single { target: entity },
+                );
+            }
+        }
+
+        entity_moved.clear();
+    }
+}
@@ -0,0 +1,107 @@
+//! Layouts for the HUD drawn around the map, selectable from the options menu.
+
+use rltk::Point;
+
+use crate::camera::{VIEW_HEIGHT, VIEW_WIDTH};
+use crate::Rect;
+
+/// The different ways the HUD can be arranged around the map.
+#[derive(
+    PartialEq, Eq, Copy, Clone, Debug, Default, strum::Display, strum::AsRefStr, strum::EnumIter,
+)]
+pub enum HudLayout {
+    /// The log sits in a box underneath the map.
+    #[default]
+    #[strum(to_string = "Classic")]
+    Classic,
+    /// Like [`Self::Classic`], but with room for a lot more of the log.
+    #[strum(to_string = "Large log")]
+    LargeLog,
+    /// The log runs down a tall panel to the right of the map.
+    #[strum(to_string = "Log on the right")]
+    LogRight,
+    /// Just a status line and the latest message, under the map.
+    #[strum(to_string = "Compact")]
+    Compact,
+}
+
+/// Where everything in the HUD goes, for some [`HudLayout`].
+#[derive(PartialEq, Copy, Clone)]
+pub struct UiLayout {
+    /// How many tiles wide the console needs to be.
+    pub console_width: u32,
+    /// How many tiles tall the console needs to be.
+    pub console_height: u32,
+    /// A box to draw around the log and status readouts, if any.
+    pub panel: Option<Rect>,
+    /// Where log messages are printed, newest first, one per row.
+    pub log: Rect,
+    /// Where the depth and health readouts start.
+    pub status: Point,
+    /// How wide the health bar is.
+    pub health_bar_width: i32,
+    /// Where the player's gold is shown.
+    pub gold: Point,
+    /// Where the player's mana is shown.
+    pub mana: Point,
+    /// Where the player's active status effects are listed.
+    pub effects: Point,
+}
+
+impl HudLayout {
+    /// Work out where everything goes for this layout.
+    pub const fn layout(self) -> UiLayout {
+        const MAP_W: i32 = VIEW_WIDTH;
+        const MAP_H: i32 = VIEW_HEIGHT;
+
+        match self {
+            Self::Classic => UiLayout {
+                console_width: 80,
+                console_height: 50,
+                panel: Some(Rect::new(0, MAP_H, 79, 6)),
+                log: Rect::new(2, MAP_H + 1, 76, 5),
+                status: Point::constant(2, MAP_H),
+                health_bar_width: 51,
+                gold: Point::constant(2, MAP_H + 6),
+                mana: Point::constant(14, MAP_H + 6),
+                effects: Point::constant(28, MAP_H + 6),
+            },
+
+            Self::LargeLog => UiLayout {
+                console_width: 80,
+                console_height: 60,
+                panel: Some(Rect::new(0, MAP_H, 79, 16)),
+                log: Rect::new(2, MAP_H + 1, 76, 15),
+                status: Point::constant(2, MAP_H),
+                health_bar_width: 51,
+                gold: Point::constant(2, MAP_H + 16),
+                mana: Point::constant(14, MAP_H + 16),
+                effects: Point::constant(28, MAP_H + 16),
+            },
+
+            Self::LogRight => UiLayout {
+                console_width: MAP_W as u32 + 40,
+                console_height: MAP_H as u32,
+                panel: Some(Rect::new(MAP_W, 0, 39, MAP_H - 1)),
+                log: Rect::new(MAP_W + 2, 2, 36, MAP_H - 3),
+                status: Point::constant(MAP_W + 2, 0),
+                health_bar_width: 12,
+                gold: Point::constant(MAP_W + 2, MAP_H - 1),
+                mana: Point::constant(MAP_W + 14, MAP_H - 1),
+                effects: Point::constant(MAP_W + 2, 1),
+            },
+
+            Self::Compact => UiLayout {
+                console_width: 80,
+                console_height: 45,
+                panel: None,
+                log: Rect::new(0, MAP_H + 1, 56, 1),
+                status: Point::constant(0, MAP_H),
+                health_bar_width: 28,
+                gold: Point::constant(68, MAP_H),
+                mana: Point::constant(55, MAP_H),
+                effects: Point::constant(56, MAP_H + 1),
+            },
+        }
+    }
+}
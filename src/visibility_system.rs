@@ -1,12 +1,20 @@
-use rltk::{field_of_view, Algorithm2D, Point};
+use rltk::{field_of_view, Algorithm2D, Point, RandomNumberGenerator};
 use specs::prelude::*;
 
-use crate::{Map, Player, Position, Viewshed};
+use crate::l10n::{self, tr};
+use crate::{GameLog, Hidden, Map, Name, Player, Position, Viewshed};
 
 /// A system that updates the visible tiles for any entity with a [`Viewshed`]
 /// and a [`Position`].
+///
+/// Whenever the player looks around, they also get a chance to spot any
+/// [`Hidden`] entities they can see. On dark levels, the player can only see tiles
+/// that are lit up (see [`crate::LightingSystem`]). Monsters see in the dark.
 pub struct VisibilitySystem;
 
+/// The player spots a hidden entity they can see on a roll of 1 on this die.
+const SPOT_HIDDEN_DIE: i32 = 24;
+
 impl<'a> System<'a> for VisibilitySystem {
     type SystemData = (
         WriteExpect<'a, Map>,
@@ -14,9 +22,16 @@ impl<'a> System<'a> for VisibilitySystem {
         WriteStorage<'a, Viewshed>,
         WriteStorage<'a, Position>,
         ReadStorage<'a, Player>,
+        WriteStorage<'a, Hidden>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadStorage<'a, Name>,
+        WriteExpect<'a, GameLog>,
     );
 
-    fn run(&mut self, (mut map, entities, mut viewshed, pos, player): Self::SystemData) {
+    fn run(
+        &mut self,
+        (mut map, entities, mut viewshed, pos, player, mut hidden, mut rng, names, mut log): Self::SystemData,
+    ) {
         for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
             if viewshed.dirty {
                 viewshed.dirty = false;
@@ -28,6 +43,16 @@ impl<'a> System<'a> for VisibilitySystem {
 
                 // If this is the player, reveal what they can see!
                 if let Some(_p) = player.get(ent) {
+                    // In the dark, they can only see what's lit up, and what's
+                    // right in front of their nose
+                    if map.dark {
+                        let center = Point::new(pos.x, pos.y);
+                        viewshed.visible_tiles.retain(|p| {
+                            map.is_lit(map.xy_idx(p.x, p.y))
+                                || rltk::DistanceAlg::Pythagoras.distance2d(center, *p) < 1.5
+                        });
+                    }
+
                     // Grey out all tiles that were visible to the player the last time the
                     // viewshed was updated.
                     for mut t in map.visible_tiles.iter_mut() {
@@ -40,6 +65,16 @@ impl<'a> System<'a> for VisibilitySystem {
                         let idx = map.xy_idx(vis.x, vis.y);
                         map.revealed_tiles.set(idx, true);
                         map.visible_tiles.set(idx, true);
+
+                        // Chance to spot anything hidden here
+                        for e in map.tile_content[idx].iter() {
+                            if hidden.contains(*e) && rng.roll_dice(1, SPOT_HIDDEN_DIE) == 1 {
+                                if let Some(name) = names.get(*e) {
+                                    log.log(tr("spot-hidden").arg("thing", l10n::name(&name.name)));
+                                }
+                                hidden.remove(*e);
+                            }
+                        }
                     }
                 }
             }